@@ -0,0 +1,43 @@
+// `IntType` only differs from the literal type under some features
+#![allow(clippy::useless_conversion)]
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_lisp::{compiler::compile, default_env, interpreter::eval, lisp, model::Value};
+use std::{cell::RefCell, rc::Rc};
+
+fn predicate() -> Value {
+    lisp! {
+        (and (> n 10) (< n 1000) (or (== (/ n 7) 3) (!= n 500)))
+    }
+}
+
+fn compiled_vs_interpreted(c: &mut Criterion) {
+    let mut group = c.benchmark_group("predicate_loop");
+    let env = Rc::new(RefCell::new(default_env()));
+    let expr = predicate();
+
+    group.bench_function("interpreted", |b| {
+        b.iter(|| {
+            for n in 0..100 {
+                env.borrow_mut().define("n".into(), Value::Int(n.into()));
+                black_box(eval(env.clone(), &expr).unwrap());
+            }
+        })
+    });
+
+    let compiled = compile(&expr);
+
+    group.bench_function("compiled", |b| {
+        b.iter(|| {
+            for n in 0..100 {
+                env.borrow_mut().define("n".into(), Value::Int(n.into()));
+                black_box(compiled.eval(env.clone()).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, compiled_vs_interpreted);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+mod harness;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use harness::run_lisp_bench;
+
+fn call_heavy(c: &mut Criterion) {
+    c.bench_function(
+        "call_heavy",
+        run_lisp_bench(
+            "
+            (defun fib (n)
+              (if (< n 2)
+                n
+                (+ (fib (- n 1)) (fib (- n 2)))))
+            (fib 16)",
+        ),
+    );
+}
+
+criterion_group!(benches, call_heavy);
+criterion_main!(benches);
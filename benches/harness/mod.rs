@@ -0,0 +1,20 @@
+//! Shared by the benchmarks which run Lisp programs
+
+use criterion::{black_box, BatchSize, Bencher};
+use rust_lisp::{default_env, interpreter::eval_block, parser::parse};
+use std::{cell::RefCell, rc::Rc};
+
+/// A benchmark routine which evaluates `src` in a fresh default environment on
+/// each iteration. Parsing and creating the environment happen outside of the
+/// measurement, so only the evaluation is timed.
+pub fn run_lisp_bench(src: &str) -> impl FnMut(&mut Bencher) {
+    let exprs = parse(src).map(|expr| expr.unwrap()).collect::<Vec<_>>();
+
+    move |b| {
+        b.iter_batched(
+            || Rc::new(RefCell::new(default_env())),
+            |env| black_box(eval_block(env, exprs.iter().cloned()).unwrap()),
+            BatchSize::SmallInput,
+        )
+    }
+}
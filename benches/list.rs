@@ -0,0 +1,125 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rust_lisp::{
+    default_env,
+    interpreter::{call_function, eval_block},
+    model::{IntType, List, Value},
+    parser::parse,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Counts allocations, so the benchmarks can report how many each run makes
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const LENGTH: usize = 100_000;
+
+fn values() -> Vec<Value> {
+    (0..LENGTH).map(|n| Value::from(n.to_string())).collect()
+}
+
+fn build_list(c: &mut Criterion) {
+    c.bench_function("list_from_vec", |b| {
+        b.iter_batched(
+            values,
+            |v| black_box(List::from_vec(v)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("list_from_iter", |b| {
+        b.iter_batched(
+            values,
+            |v| black_box(v.into_iter().collect::<List>()),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+/// Lots of short-lived lists, built by non-tail recursion and by the list
+/// builtins
+const CHURN: &str = "
+(defun build (n)
+  (if (== n 0)
+    (list)
+    (cons n (build (- n 1)))))
+
+(defun churn (n)
+  (if (> n 0)
+    (begin
+      (reverse (map (lambda (x) (* x 2)) (build 200)))
+      (filter (lambda (x) (> x 10)) (range 0 50))
+      (churn (- n 1)))))
+
+(churn 200)";
+
+fn list_churn(c: &mut Criterion) {
+    let env = Rc::new(RefCell::new(default_env()));
+    let exprs = parse(CHURN).map(|expr| expr.unwrap()).collect::<Vec<_>>();
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    eval_block(env.clone(), exprs.iter().cloned()).unwrap();
+    println!(
+        "list_churn: {} allocations per run",
+        ALLOCATIONS.load(Ordering::Relaxed) - before
+    );
+
+    c.bench_function("list_churn", |b| {
+        b.iter(|| black_box(eval_block(env.clone(), exprs.iter().cloned()).unwrap()))
+    });
+}
+
+/// The in-place list operations against the ones that copy, on a big list
+fn in_place(c: &mut Criterion) {
+    let env = Rc::new(RefCell::new(default_env()));
+    let numbers = || -> Value {
+        let mut numbers = (0..LENGTH as IntType).collect::<Vec<_>>();
+        numbers.reverse();
+        List::from_vec(numbers.into_iter().map(Value::from).collect()).into()
+    };
+
+    for name in [
+        "sort",
+        "sort_in_place",
+        "reverse",
+        "reverse_in_place",
+        "remove",
+        "delete_in_place",
+    ] {
+        let func = env.borrow().get(&name.into()).unwrap();
+        let args = move |list| match name {
+            "remove" | "delete_in_place" => vec![Value::from(0 as IntType), list],
+            _ => vec![list],
+        };
+
+        c.bench_function(name, |b| {
+            b.iter_batched(
+                || args(numbers()),
+                |args| black_box(call_function(env.clone(), &func, args).unwrap()),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+criterion_group!(benches, build_list, list_churn, in_place);
+criterion_main!(benches);
@@ -0,0 +1,136 @@
+//! Representative Lisp workloads, as a shared yardstick for performance work.
+//! The results from one machine are recorded in the README.
+
+mod harness;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use harness::run_lisp_bench;
+use rust_lisp::parser::parse;
+
+const FIB: &str = "
+(defun fib (n)
+  (if (< n 2)
+    n
+    (+ (fib (- n 1)) (fib (- n 2)))))
+
+(fib 25)";
+
+const COUNT: &str = "
+(defun count_to (n i)
+  (if (== i n)
+    i
+    (count_to n (+ i 1))))
+
+(count_to 1000000 0)";
+
+const MULTI_EXPRESSION_BODY: &str = "
+(defun count_up (n total)
+  (set total (+ total 1))
+  (set n (- n 1))
+  (if (== n 0)
+    total
+    (count_up n total)))
+
+(count_up 100000 0)";
+
+const LISTS: &str = "
+(defun reverse_onto (lst tail)
+  (if (is_null lst)
+    tail
+    (reverse_onto (cdr lst) (cons (car lst) tail))))
+
+(defun append (a b)
+  (reverse_onto (reverse a) b))
+
+(define numbers (range 0 100000))
+(length (append (reverse numbers) numbers))";
+
+const STRINGS: &str = "
+(defun repeat (s n)
+  (if (== n 0)
+    s
+    (repeat (+ s \"abc\" n) (- n 1))))
+
+(defun repeat_sb (sb n)
+  (if (== n 0)
+    (sb_to_string sb)
+    (repeat_sb (sb_append sb \"abc\" n) (- n 1))))
+
+(list (repeat \"\" 10000) (repeat_sb (string_builder) 10000))";
+
+/// `(let ((x0 0)) (let ((x1 (+ x0 1))) ... x999))`
+fn nested_lets(depth: usize) -> String {
+    let mut src = String::new();
+
+    for i in 0..depth {
+        if i == 0 {
+            src.push_str("(let ((x0 0)) ");
+        } else {
+            src.push_str(&format!("(let ((x{} (+ x{} 1))) ", i, i - 1));
+        }
+    }
+
+    src.push_str(&format!("x{}", depth - 1));
+    src.push_str(&")".repeat(depth));
+
+    src
+}
+
+/// Ordinary-looking definitions, repeated until there's at least `size` bytes
+fn generated_source(size: usize) -> String {
+    let chunk = "
+;; a comment, like real code has
+(defun process_item_0 (item &key (scale 2.5))
+  (let ((name \"some string literal\")
+        (values '(1 2 3 four five)))
+    (if (> item 10)
+      (map (lambda (x) (* x scale)) values)
+      (list name item -42 3.25))))
+";
+
+    let mut src = String::with_capacity(size + chunk.len());
+    let mut n = 0;
+
+    while src.len() < size {
+        src.push_str(&chunk.replace("process_item_0", &format!("process_item_{}", n)));
+        n += 1;
+    }
+
+    src
+}
+
+fn evaluation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("eval");
+    // these take a while per iteration
+    group.sample_size(10);
+
+    group.bench_function("fib_25", run_lisp_bench(FIB));
+    group.bench_function("count_to_1e6", run_lisp_bench(COUNT));
+    group.bench_function("body_3_exprs_1e5", run_lisp_bench(MULTI_EXPRESSION_BODY));
+    group.bench_function("lists_100k", run_lisp_bench(LISTS));
+    group.bench_function("nested_let_1000", run_lisp_bench(&nested_lets(1000)));
+    group.bench_function("string_concat_10k", run_lisp_bench(STRINGS));
+
+    group.finish();
+}
+
+fn parsing(c: &mut Criterion) {
+    let src = generated_source(1024 * 1024);
+
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(src.len() as u64));
+
+    group.bench_function("1mb", |b| {
+        b.iter(|| {
+            for expr in parse(black_box(&src)) {
+                black_box(expr.unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, evaluation, parsing);
+criterion_main!(benches);
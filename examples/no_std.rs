@@ -0,0 +1,23 @@
+//! Using the interpreter without the standard library (only `alloc`). This is
+//! built as a library, since an actual `no_std` binary needs a panic handler
+//! and an allocator, which depend on the target:
+//!
+//! ```sh
+//! cargo build --example no_std --no-default-features
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use rust_lisp::{default_env, interpreter::eval, lisp, model::RuntimeError, model::Value};
+
+/// Evaluates `(+ 1 2)`
+pub fn one_plus_two() -> Result<Value, RuntimeError> {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    eval(env, &lisp! { (+ 1 2) })
+}
@@ -0,0 +1,13 @@
+//! The map and set types used by the interpreter (including for
+//! `Value::HashMap`): the standard library's, or without the `std` feature,
+//! `hashbrown`'s
+
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(feature = "std")] {
+        pub use std::collections::{HashMap, HashSet};
+    } else {
+        pub use hashbrown::{HashMap, HashSet};
+    }
+}
@@ -0,0 +1,341 @@
+//! Compiling expressions ahead of time, for code that gets evaluated many
+//! times over (a predicate called in a loop, etc).
+//!
+//! `eval()` walks the expression and matches on special forms every time it
+//! runs. `compile()` does that walk once and produces a tree of closures, so
+//! that each `CompiledExpr::eval()` only has to do the evaluation itself. The
+//! results (including errors, and when they happen) are the same as calling
+//! `eval()` on the original expression.
+//!
+//! Only the expression itself is compiled; the bodies of any functions it
+//! defines or calls are still interpreted as usual. Forms which are rarely
+//! hot (`quote`, `lambda`, `defun`, etc) are handed to `eval()` as-is.
+
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+
+use crate::{
+    interpreter::{
+        call_function, check_let_names, eval, expand_macro, internal_defines, is_truthy, lookup,
+        push_arg, special_form, splice_arg, SpecialForm,
+    },
+    model::{Env, List, RuntimeError, Symbol, Value},
+    utils::{require_arg, require_typed_arg},
+};
+
+type Thunk = Box<dyn Fn(&Rc<RefCell<Env>>) -> Result<Value, RuntimeError>>;
+
+/// An expression that has been pre-processed by `compile()`
+pub struct CompiledExpr(Thunk);
+
+impl CompiledExpr {
+    /// Evaluate the expression in the context of a given environment
+    pub fn eval(&self, env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
+        (self.0)(&env)
+    }
+}
+
+impl core::fmt::Debug for CompiledExpr {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("<compiled_expression>")
+    }
+}
+
+/// Pre-process an expression so that it can be evaluated repeatedly without
+/// re-walking it. Problems with the expression's structure (like a malformed
+/// `let`) aren't reported here, but when the compiled expression is evaluated
+/// and reaches them, the same as with `eval()`.
+pub fn compile(expression: &Value) -> CompiledExpr {
+    CompiledExpr(compile_thunk(expression))
+}
+
+fn compile_thunk(expression: &Value) -> Thunk {
+    match expression {
+        Value::Symbol(symbol) => {
+            let symbol = symbol.clone();
+
+            Box::new(move |env| lookup(env, &symbol))
+        }
+
+        // `()` evaluates to itself, as in the interpreter
+        Value::List(list) if *list == List::NIL => Box::new(|_| Ok(Value::NIL)),
+
+        Value::List(list) => match list.car() {
+            Ok(Value::Symbol(keyword)) => {
+                let compiled = match special_form(list) {
+                    Some(form @ (SpecialForm::Define | SpecialForm::Set)) => {
+                        compile_define(form, list)
+                    }
+                    Some(SpecialForm::Let) => compile_let(list),
+                    Some(SpecialForm::Begin) => compile_block(list.cdr()),
+                    Some(SpecialForm::Cond) => compile_cond(list),
+                    Some(SpecialForm::If) => compile_if(list),
+                    Some(form @ (SpecialForm::And | SpecialForm::Or)) => {
+                        compile_and_or(form == SpecialForm::Or, list)
+                    }
+                    // any other special form is left to the interpreter
+                    Some(_) => interpreted(expression),
+                    None => compile_call(list),
+                };
+
+                unless_registered(keyword, expression, compiled)
+            }
+            _ => compile_call(list),
+        },
+
+        // plain value
+        _ => {
+            let value = expression.clone();
+
+            Box::new(move |_| Ok(value.clone()))
+        }
+    }
+}
+
+/// Special forms can be registered after an expression is compiled, so
+/// whether its head is one has to be checked when it's evaluated
+fn unless_registered(keyword: Symbol, expression: &Value, compiled: Thunk) -> Thunk {
+    let expression = expression.clone();
+
+    Box::new(move |env| {
+        let is_registered = {
+            let env = env.borrow();
+            let forms = env.context().special_forms.borrow();
+
+            !forms.is_empty() && forms.contains_key(&keyword)
+        };
+
+        if is_registered {
+            eval(env.clone(), &expression)
+        } else {
+            compiled(env)
+        }
+    })
+}
+
+fn interpreted(expression: &Value) -> Thunk {
+    let expression = expression.clone();
+
+    Box::new(move |env| eval(env.clone(), &expression))
+}
+
+fn fail(err: RuntimeError) -> Thunk {
+    Box::new(move |_| Err(err.clone()))
+}
+
+fn compile_define(form: SpecialForm, list: &List) -> Thunk {
+    let keyword = form.keyword();
+    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+    let symbol = match require_typed_arg::<&Symbol>(keyword, args, 0) {
+        Ok(symbol) => symbol.clone(),
+        Err(err) => return fail(err),
+    };
+    let value_expr = match require_arg(keyword, args, 1) {
+        Ok(expr) => compile_thunk(expr),
+        Err(err) => return fail(err),
+    };
+
+    if form == SpecialForm::Define {
+        Box::new(move |env| {
+            env.borrow().check_definition(keyword, &symbol)?;
+
+            let value = value_expr(env)?.into_single();
+            let value = value.named(&symbol);
+            env.borrow_mut().define(symbol.clone(), value.clone());
+            Ok(value)
+        })
+    } else {
+        Box::new(move |env| {
+            env.borrow().check_set(keyword, &symbol)?;
+
+            let value = value_expr(env)?.into_single();
+            env.borrow_mut().set(symbol.clone(), value.clone())?;
+            Ok(value)
+        })
+    }
+}
+
+fn compile_let(list: &List) -> Thunk {
+    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+    let declarations = match require_typed_arg::<&List>("let", args, 0)
+        .and_then(|declarations| check_let_names(declarations).map(|_| declarations))
+    {
+        Ok(declarations) => declarations
+            .into_iter()
+            .map(|decl| compile_let_declaration(&decl))
+            .collect::<Vec<_>>(),
+        Err(err) => return fail(err),
+    };
+    let internal_defines = internal_defines(&list.cdr().cdr());
+    let body = compile_block(list.cdr().cdr());
+
+    Box::new(move |env| {
+        let let_env = Env::extend_local(env.clone()).into_shared();
+
+        for decl in &declarations {
+            let (symbol, expr) = decl.as_ref().map_err(Clone::clone)?;
+
+            let result = expr(&let_env)?;
+            let_env
+                .borrow_mut()
+                .define(symbol.clone(), result.into_single());
+        }
+
+        for name in &internal_defines {
+            let_env.borrow_mut().declare(name.clone());
+        }
+
+        body(&let_env)
+    })
+}
+
+fn compile_let_declaration(decl: &Value) -> Result<(Symbol, Thunk), RuntimeError> {
+    let decl_cons: &List = decl
+        .try_into()
+        .map_err(|_| RuntimeError::new(format!("Expected declaration clause, found {}", decl)))?;
+    let symbol = &decl_cons.car()?;
+    let symbol: &Symbol = symbol.try_into().map_err(|_| {
+        RuntimeError::new(format!(
+            "Expected symbol for let declaration, found {}",
+            symbol
+        ))
+    })?;
+    let expr = &decl_cons.cdr().car()?;
+
+    Ok((symbol.clone(), compile_thunk(expr)))
+}
+
+fn compile_block(body: List) -> Thunk {
+    let mut exprs = body
+        .into_iter()
+        .map(|expr| compile_thunk(&expr))
+        .collect::<Vec<_>>();
+
+    let last = match exprs.pop() {
+        Some(last) => last,
+        None => return Box::new(|_| Ok(Value::NIL)),
+    };
+
+    Box::new(move |env| {
+        for expr in &exprs {
+            expr(env)?;
+        }
+
+        last(env)
+    })
+}
+
+fn compile_cond(list: &List) -> Thunk {
+    let clauses = list
+        .cdr()
+        .into_iter()
+        .map(|clause| {
+            let clause: &List = (&clause).try_into().map_err(|_| {
+                RuntimeError::new(format!("Expected conditional clause, found {}", clause))
+            })?;
+
+            let condition = compile_thunk(&clause.car()?);
+            let body = compile_block(clause.cdr());
+
+            Ok((condition, body))
+        })
+        .collect::<Vec<Result<(Thunk, Thunk), RuntimeError>>>();
+
+    Box::new(move |env| {
+        for clause in &clauses {
+            let (condition, body) = clause.as_ref().map_err(Clone::clone)?;
+
+            if is_truthy(env, &condition(env)?) {
+                return body(env);
+            }
+        }
+
+        Ok(Value::NIL)
+    })
+}
+
+fn compile_if(list: &List) -> Thunk {
+    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+    let condition = match require_arg("if", args, 0) {
+        Ok(expr) => compile_thunk(expr),
+        Err(err) => return fail(err),
+    };
+    let then_expr = match require_arg("if", args, 1) {
+        Ok(expr) => compile_thunk(expr),
+        Err(err) => return fail(err),
+    };
+    let else_expr = require_arg("if", args, 2).ok().map(compile_thunk);
+
+    Box::new(move |env| {
+        if is_truthy(env, &condition(env)?) {
+            then_expr(env)
+        } else {
+            else_expr
+                .as_ref()
+                .map(|expr| expr(env))
+                .unwrap_or(Ok(Value::NIL))
+        }
+    })
+}
+
+fn compile_and_or(is_or: bool, list: &List) -> Thunk {
+    let args = list
+        .cdr()
+        .into_iter()
+        .map(|arg| compile_thunk(&arg))
+        .collect::<Vec<_>>();
+
+    Box::new(move |env| {
+        let mut last_result: Option<Value> = None;
+        for arg in &args {
+            let result = arg(env)?;
+
+            if is_or == is_truthy(env, &result) {
+                return Ok(result);
+            }
+
+            last_result = Some(result);
+        }
+
+        Ok(if let Some(last_result) = last_result {
+            last_result
+        } else {
+            // there were zero arguments
+            (!is_or).into()
+        })
+    })
+}
+
+fn compile_call(list: &List) -> Thunk {
+    let func = compile_thunk(&list.car().unwrap_or(Value::NIL));
+    let raw_args = list.into_iter().skip(1).collect::<Vec<Value>>();
+    let args = raw_args
+        .iter()
+        .map(|arg_expr| match splice_arg(arg_expr) {
+            Some(spliced) => (compile_thunk(&spliced), true),
+            None => (compile_thunk(arg_expr), false),
+        })
+        .collect::<Vec<_>>();
+
+    Box::new(move |env| {
+        let func_or_macro = func(env)?;
+
+        if matches!(func_or_macro, Value::Macro(_)) {
+            // macros get their arguments unevaluated, and their expansion
+            // can only be known at runtime, so that's interpreted
+            let expanded = expand_macro(env, &func_or_macro, raw_args.clone())?;
+
+            eval(env.clone(), &expanded)
+        } else {
+            let mut arg_values = Vec::new();
+            for (arg, spliced) in &args {
+                push_arg(&mut arg_values, arg(env)?, *spliced)?;
+            }
+
+            call_function(env.clone(), &func_or_macro, arg_values)
+        }
+    })
+}
@@ -0,0 +1,315 @@
+//! Conversions between Rust data and lisp values, for passing structured data
+//! (config, etc) in and out of the interpreter.
+//!
+//! Structs are represented as association lists, with one `(name value)`
+//! entry per field and the field names as symbols. They can implement
+//! [`IntoLisp`] and [`FromLisp`] using [`AlistBuilder`] and [`AlistReader`]:
+//!
+//! ```
+//! use rust_lisp::convert::{AlistBuilder, AlistReader, FromLisp, IntoLisp};
+//! use rust_lisp::model::{IntType, RuntimeError, Value};
+//!
+//! struct Server {
+//!     host: String,
+//!     port: IntType,
+//! }
+//!
+//! impl IntoLisp for Server {
+//!     fn into_lisp(self) -> Value {
+//!         AlistBuilder::new()
+//!             .field("host", self.host)
+//!             .field("port", self.port)
+//!             .build()
+//!     }
+//! }
+//!
+//! impl FromLisp for Server {
+//!     fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+//!         let mut fields = AlistReader::new("Server", value)?;
+//!         let host = fields.field("host");
+//!         let port = fields.field("port");
+//!         fields.finish()?;
+//!
+//!         Ok(Server {
+//!             host: host.unwrap(),
+//!             port: port.unwrap(),
+//!         })
+//!     }
+//! }
+//! ```
+
+use crate::model::{ErrorKind, FloatType, IntType, List, RuntimeError, Symbol, Value};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+/// A Rust type which can be turned into a lisp value
+pub trait IntoLisp {
+    fn into_lisp(self) -> Value;
+}
+
+/// A Rust type which can be read back out of a lisp value
+pub trait FromLisp: Sized {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError>;
+}
+
+impl IntoLisp for Value {
+    fn into_lisp(self) -> Value {
+        self
+    }
+}
+
+impl FromLisp for Value {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoLisp for IntType {
+    fn into_lisp(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl FromLisp for IntType {
+    // `IntType` isn't `Copy` with the `bigint` feature
+    #[allow(clippy::clone_on_copy)]
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Int(i) => Ok(i.clone()),
+            _ => Err(wrong_type("an integer", value)),
+        }
+    }
+}
+
+impl IntoLisp for FloatType {
+    fn into_lisp(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl FromLisp for FloatType {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Float(f) => Ok(*f),
+            _ => Err(wrong_type("a float", value)),
+        }
+    }
+}
+
+impl IntoLisp for bool {
+    fn into_lisp(self) -> Value {
+        Value::from(self)
+    }
+}
+
+impl FromLisp for bool {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::True => Ok(true),
+            Value::False => Ok(false),
+            _ => Err(wrong_type("T or F", value)),
+        }
+    }
+}
+
+impl IntoLisp for String {
+    fn into_lisp(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoLisp for &str {
+    fn into_lisp(self) -> Value {
+        Value::String(self.to_owned())
+    }
+}
+
+impl FromLisp for String {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(wrong_type("a string", value)),
+        }
+    }
+}
+
+impl IntoLisp for char {
+    fn into_lisp(self) -> Value {
+        Value::Char(self)
+    }
+}
+
+impl FromLisp for char {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Char(ch) => Ok(*ch),
+            _ => Err(wrong_type("a char", value)),
+        }
+    }
+}
+
+impl<T: IntoLisp> IntoLisp for Vec<T> {
+    fn into_lisp(self) -> Value {
+        Value::List(self.into_iter().map(IntoLisp::into_lisp).collect())
+    }
+}
+
+impl<T: FromLisp> FromLisp for Vec<T> {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::List(list) => list
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    T::from_lisp(&item).map_err(|err| {
+                        RuntimeError::with_kind(err.kind, format!("item {}: {}", index, err.msg))
+                    })
+                })
+                .collect(),
+            _ => Err(wrong_type("a list", value)),
+        }
+    }
+}
+
+/// `None` is represented as `NIL`. Note that this means `Some` of a value
+/// which is itself represented as `NIL` (like an empty `Vec`) reads back as
+/// `None`.
+impl<T: IntoLisp> IntoLisp for Option<T> {
+    fn into_lisp(self) -> Value {
+        match self {
+            Some(value) => value.into_lisp(),
+            None => Value::NIL,
+        }
+    }
+}
+
+impl<T: FromLisp> FromLisp for Option<T> {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        if *value == Value::NIL {
+            Ok(None)
+        } else {
+            T::from_lisp(value).map(Some)
+        }
+    }
+}
+
+fn wrong_type(expected: &str, value: &Value) -> RuntimeError {
+    RuntimeError::with_kind(
+        ErrorKind::TypeError,
+        format!("expected {}, got {}", expected, value),
+    )
+}
+
+/// Builds the association list for a struct, one field at a time
+#[derive(Debug, Default)]
+pub struct AlistBuilder {
+    entries: Vec<Value>,
+}
+
+impl AlistBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn field<T: IntoLisp>(mut self, name: &str, value: T) -> Self {
+        self.entries.push(Value::List(
+            [Value::Symbol(Symbol::from(name)), value.into_lisp()]
+                .iter()
+                .collect(),
+        ));
+        self
+    }
+
+    pub fn build(self) -> Value {
+        Value::List(List::from_vec(self.entries))
+    }
+}
+
+/// Reads a struct's fields back out of an association list. Problems with
+/// individual fields don't fail immediately; they're collected so that
+/// `finish()` can report all of them at once.
+#[derive(Debug)]
+pub struct AlistReader {
+    type_name: String,
+    entries: Vec<(Symbol, Value)>,
+    errors: Vec<String>,
+}
+
+impl AlistReader {
+    /// Fails if `value` isn't an association list. `type_name` is only used
+    /// for error messages.
+    pub fn new(type_name: &str, value: &Value) -> Result<Self, RuntimeError> {
+        let list = match value {
+            Value::List(list) => list,
+            _ => {
+                return Err(RuntimeError::new(format!(
+                    "Couldn't read {}: expected an association list, got {}",
+                    type_name, value
+                )))
+            }
+        };
+
+        let entries = list
+            .into_iter()
+            .map(|entry| match &entry {
+                Value::List(pair) => match (pair.car(), pair.cdr().car()) {
+                    (Ok(Value::Symbol(name)), Ok(value)) if pair.cdr().cdr() == List::NIL => {
+                        Ok((name, value))
+                    }
+                    _ => Err(entry),
+                },
+                _ => Err(entry),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|entry| {
+                RuntimeError::new(format!(
+                    "Couldn't read {}: expected a (name value) entry, got {}",
+                    type_name, entry
+                ))
+            })?;
+
+        Ok(Self {
+            type_name: type_name.to_owned(),
+            entries,
+            errors: Vec::new(),
+        })
+    }
+
+    /// Read a field, or return `None` and record the error if it's missing
+    /// or has the wrong type
+    pub fn field<T: FromLisp>(&mut self, name: &str) -> Option<T> {
+        let found = self
+            .entries
+            .iter()
+            .find(|(symbol, _)| &*symbol.0 == name)
+            .map(|(_, value)| value);
+
+        match found {
+            Some(value) => match T::from_lisp(value) {
+                Ok(value) => Some(value),
+                Err(err) => {
+                    self.errors.push(format!("field \"{}\": {}", name, err.msg));
+                    None
+                }
+            },
+            None => {
+                self.errors.push(format!("missing field \"{}\"", name));
+                None
+            }
+        }
+    }
+
+    /// Returns an error listing every field that couldn't be read. If this
+    /// succeeds, every call to `field()` has returned `Some`.
+    pub fn finish(self) -> Result<(), RuntimeError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RuntimeError::new(format!(
+                "Couldn't read {}: {}",
+                self.type_name,
+                self.errors.join("; ")
+            )))
+        }
+    }
+}
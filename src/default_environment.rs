@@ -1,33 +1,150 @@
+use crate::collections::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use crate::model::TIMED_OUT;
 use crate::{
-    interpreter::eval,
-    lisp,
-    model::{Env, HashMapRc, IntType, List, RuntimeError, Symbol, Value},
-    utils::{require_arg, require_typed_arg},
+    interpreter::{call_function, eval, is_truthy, make_generator, resume_generator},
+    model::{
+        checked_div, cons_all, float_to_scientific, int_from_i128, int_to_i128,
+        int_to_string_radix, int_to_usize, int_type_to_float_type, mismatched_types, ops_equal,
+        parse_number_radix, sorted_members, ArithmeticError, Combinator, Context, Env, ErrorKind,
+        Function, Generator, HashMapRc, IntType, Lambda, List, LogLevel, Promise, RuntimeError,
+        SetRc, Symbol, Value, STEP_LIMIT_EXCEEDED,
+    },
+    prelude::PRELUDE,
+    utils::{keyword_args, require_arg, require_typed_arg},
 };
-use cfg_if::cfg_if;
-use std::{cell::RefCell, collections::HashMap, convert::TryInto, rc::Rc};
-cfg_if! {
-    if #[cfg(feature = "bigint")] {
-        use num_traits::ToPrimitive;
-    }
-}
+use alloc::{borrow::ToOwned, rc::Rc, string::String, vec::Vec};
+use core::{cell::RefCell, cmp::Ordering, convert::TryInto, fmt::Write};
 
 /// Initialize an instance of `Env` with several core Lisp functions implemented
 /// in Rust. **Without this, you will only have access to the functions you
-/// implement yourself.**
+/// implement yourself.** Without the `std` feature, the functions which do
+/// I/O (`print`) are left out. The filesystem functions are only included with
+/// the `io` feature, and the regular expression functions with `regex`.
+///
+/// Some functions (like `cadr` and `identity`) are written in Lisp instead, in
+/// a prelude which is evaluated into an environment that the returned one
+/// extends (see `Env::eval_prelude()`).
+// sets and hash maps are keyed by `Value`, which is hashed by identity where
+// it's mutable
+#[allow(clippy::mutable_key_type)]
 pub fn default_env() -> Env {
     let mut env = Env::new();
 
+    #[cfg(feature = "std")]
     env.define(
         Symbol::from("print"),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let expr = require_arg("print", &args, 0)?;
 
-            println!("{}", &expr);
+            let env = env.borrow();
+            let context = env.context();
+
+            writeln!(context.output.borrow_mut(), "{}", context.display(expr))
+                .map_err(|_| RuntimeError::new("\"print\" failed to write its output"))?;
+            Ok(expr.clone())
+        }),
+    );
+
+    // like `print`, but shared structure is labelled, so that it reads back
+    // the same (see `Value::labelled()`)
+    #[cfg(feature = "std")]
+    env.define(
+        Symbol::from("write"),
+        Value::NativeFunc(|env, args| {
+            let expr = require_arg("write", &args, 0)?;
+
+            let env = env.borrow();
+            let context = env.context();
+
+            writeln!(context.output.borrow_mut(), "{}", context.write(expr))
+                .map_err(|_| RuntimeError::new("\"write\" failed to write its output"))?;
             Ok(expr.clone())
         }),
     );
 
+    #[cfg(feature = "io")]
+    crate::io_builtins::define_io_builtins(&mut env);
+
+    #[cfg(feature = "process")]
+    crate::process_builtins::define_process_builtins(&mut env);
+
+    #[cfg(feature = "regex")]
+    crate::regex_builtins::define_regex_builtins(&mut env);
+
+    env.define(
+        Symbol::from("warn"),
+        Value::NativeFunc(|env, args| {
+            let msg = match require_arg("warn", &args, 0)? {
+                Value::String(msg) => msg.clone(),
+                other => format!("{}", other),
+            };
+
+            env.borrow().context().warn(msg);
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    // fails with a message built like `format`'s
+    env.define(
+        Symbol::from("error"),
+        Value::NativeFunc(|env, args| {
+            let template = require_typed_arg::<&String>("error", &args, 0)?;
+
+            Err(RuntimeError::new(format_string(
+                "error",
+                env.borrow().context(),
+                template,
+                &args[1..],
+            )?))
+        }),
+    );
+
+    env.define(
+        Symbol::from("log_debug"),
+        Value::NativeFunc(|env, args| log("log_debug", LogLevel::Debug, env, args)),
+    );
+
+    env.define(
+        Symbol::from("log_info"),
+        Value::NativeFunc(|env, args| log("log_info", LogLevel::Info, env, args)),
+    );
+
+    env.define(
+        Symbol::from("log_warn"),
+        Value::NativeFunc(|env, args| log("log_warn", LogLevel::Warn, env, args)),
+    );
+
+    env.define(
+        Symbol::from("log_error"),
+        Value::NativeFunc(|env, args| log("log_error", LogLevel::Error, env, args)),
+    );
+
+    env.define(
+        Symbol::from("log_level"),
+        Value::NativeFunc(|env, _args| {
+            let level = env.borrow().context().log_level.get();
+
+            Ok(Value::Symbol(Symbol::from(level.name())))
+        }),
+    );
+
+    env.define(
+        Symbol::from("set_log_level"),
+        Value::NativeFunc(|env, args| {
+            let name = require_typed_arg::<&Symbol>("set_log_level", &args, 0)?;
+            let level = LogLevel::from_name(&name.0).ok_or_else(|| RuntimeError::new(format!(
+                    "Function \"set_log_level\" requires a level of debug, info, warn or error; got {}",
+                    name
+                )))?;
+
+            env.borrow().context().log_level.set(level);
+
+            Ok(Value::Symbol(name.clone()))
+        }),
+    );
+
     env.define(
         Symbol::from("is_null"),
         Value::NativeFunc(|_env, args| {
@@ -75,6 +192,33 @@ pub fn default_env() -> Env {
         }),
     );
 
+    env.define(
+        Symbol::from("is_char"),
+        Value::NativeFunc(|_env, args| {
+            let val = require_arg("is_char", &args, 0)?;
+
+            Ok(Value::from(matches!(val, Value::Char(_))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("is_bytes"),
+        Value::NativeFunc(|_env, args| {
+            let val = require_arg("is_bytes", &args, 0)?;
+
+            Ok(Value::from(matches!(val, Value::Bytes(_))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("is_set"),
+        Value::NativeFunc(|_env, args| {
+            let val = require_arg("is_set", &args, 0)?;
+
+            Ok(Value::from(matches!(val, Value::Set(_))))
+        }),
+    );
+
     env.define(
         Symbol::from("is_procedure"),
         Value::NativeFunc(|_env, args| {
@@ -82,7 +226,9 @@ pub fn default_env() -> Env {
 
             Ok(match val {
                 Value::Lambda(_) => Value::True,
-                Value::NativeFunc(_) => Value::True,
+                Value::NativeFunc(_) | Value::NativeClosure(_) | Value::Combinator(_) => {
+                    Value::True
+                }
                 _ => Value::NIL,
             })
         }),
@@ -114,23 +260,60 @@ pub fn default_env() -> Env {
         Value::NativeFunc(|_env, args| {
             let list = require_typed_arg::<&List>("cdr", &args, 0)?;
 
-            Ok(Value::List(list.cdr()))
+            Ok(list.cdr_value())
         }),
     );
 
     env.define(
         Symbol::from("cons"),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let car = require_arg("cons", &args, 0)?;
             let cdr = require_typed_arg::<&List>("cons", &args, 1)?;
+            env.borrow().context().allocate_conses(1)?;
 
             Ok(Value::List(cdr.cons(car.clone())))
         }),
     );
 
+    env.define(
+        Symbol::from("set_car"),
+        Value::NativeFunc(|_env, args| {
+            let list = require_typed_arg::<&List>("set_car", &args, 0)?;
+            let car = require_arg("set_car", &args, 1)?;
+
+            list.set_car(car.clone())?;
+
+            Ok(Value::List(list.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("set_cdr"),
+        Value::NativeFunc(|_env, args| {
+            let list = require_typed_arg::<&List>("set_cdr", &args, 0)?;
+            let cdr = require_typed_arg::<&List>("set_cdr", &args, 1)?;
+
+            list.set_cdr(cdr)?;
+
+            Ok(Value::List(list.clone()))
+        }),
+    );
+
     env.define(
         Symbol::from("list"),
-        Value::NativeFunc(|_env, args| Ok(Value::List(args.iter().collect::<List>()))),
+        Value::NativeFunc(|env, args| new_list(&env, List::from_vec(args))),
+    );
+
+    // also known as `cons*`; if the last argument isn't a list, the result is
+    // a dotted list like `(1 2 . 3)`
+    env.define(
+        Symbol::from("list_star"),
+        Value::NativeFunc(|env, args| list_star(env, "list_star", &args)),
+    );
+
+    env.define(
+        Symbol::from("cons_star"),
+        Value::NativeFunc(|env, args| list_star(env, "cons_star", &args)),
     );
 
     env.define(
@@ -139,37 +322,120 @@ pub fn default_env() -> Env {
             let index = require_typed_arg::<IntType>("nth", &args, 0)?;
             let list = require_typed_arg::<&List>("nth", &args, 1)?;
 
-            let index = TryInto::<usize>::try_into(index).map_err(|_| RuntimeError {
-                msg: "Failed converting to `usize`".to_owned(),
-            })?;
+            let index = TryInto::<usize>::try_into(index)
+                .map_err(|_| RuntimeError::new("Failed converting to `usize`"))?;
 
-            Ok(list.into_iter().nth(index).unwrap_or(Value::NIL))
+            Ok(list.get(index).unwrap_or(Value::NIL))
         }),
     );
 
     env.define(
-        Symbol::from("sort"),
+        Symbol::from("member"),
+        Value::NativeFunc(|_env, args| {
+            let item = require_arg("member", &args, 0)?;
+            let list = require_typed_arg::<&List>("member", &args, 1)?;
+
+            Ok(Value::List(tail_from(list, |other| other == item)))
+        }),
+    );
+
+    env.define(
+        Symbol::from("memq"),
         Value::NativeFunc(|_env, args| {
+            let item = require_arg("memq", &args, 0)?;
+            let list = require_typed_arg::<&List>("memq", &args, 1)?;
+
+            Ok(Value::List(tail_from(list, |other| {
+                other.is_identical(item)
+            })))
+        }),
+    );
+
+    env.define(
+        Symbol::from("remove"),
+        Value::NativeFunc(|env, args| {
+            let item = require_arg("remove", &args, 0)?;
+            let list = require_typed_arg::<&List>("remove", &args, 1)?;
+
+            new_list(
+                &env,
+                list.into_iter().filter(|other| other != item).collect(),
+            )
+        }),
+    );
+
+    env.define(
+        Symbol::from("sort"),
+        Value::NativeFunc(|env, args| {
             let list = require_typed_arg::<&List>("sort", &args, 0)?;
 
             let mut v: Vec<Value> = list.into_iter().collect();
 
             v.sort();
 
-            Ok(Value::List(v.into_iter().collect()))
+            new_list(&env, List::from_vec(v))
         }),
     );
 
     env.define(
         Symbol::from("reverse"),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let list = require_typed_arg::<&List>("reverse", &args, 0)?;
 
             let mut v: Vec<Value> = list.into_iter().collect();
 
             v.reverse();
 
-            Ok(Value::List(v.into_iter().collect()))
+            new_list(&env, List::from_vec(v))
+        }),
+    );
+
+    // the in-place versions of `sort`, `reverse`, `append` and `remove`, which
+    // change the list's cons cells instead of copying them (so every list
+    // sharing them sees the change)
+    env.define(
+        Symbol::from("sort_in_place"),
+        Value::NativeFunc(|_env, args| {
+            let list = require_typed_arg::<&List>("sort_in_place", &args, 0)?;
+
+            list.sort_in_place()?;
+
+            Ok(Value::List(list.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("reverse_in_place"),
+        Value::NativeFunc(|_env, args| {
+            let list = require_typed_arg::<&List>("reverse_in_place", &args, 0)?;
+
+            list.reverse_in_place()?;
+
+            Ok(Value::List(list.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("append_in_place"),
+        Value::NativeFunc(|_env, args| {
+            let mut result = List::NIL;
+
+            for index in (0..args.len()).rev() {
+                let list = require_typed_arg::<&List>("append_in_place", &args, index)?;
+                result = list.append_in_place(&result)?;
+            }
+
+            Ok(Value::List(result))
+        }),
+    );
+
+    env.define(
+        Symbol::from("delete_in_place"),
+        Value::NativeFunc(|_env, args| {
+            let item = require_arg("delete_in_place", &args, 0)?;
+            let list = require_typed_arg::<&List>("delete_in_place", &args, 1)?;
+
+            Ok(Value::List(list.delete_in_place(item)?))
         }),
     );
 
@@ -180,13 +446,33 @@ pub fn default_env() -> Env {
             let list = require_typed_arg::<&List>("map", &args, 1)?;
 
             list.into_iter()
-                .map(|val| {
-                    let expr = lisp! { ({func.clone()} (quote {val})) };
-
-                    eval(env.clone(), &expr)
-                })
+                .map(|val| call_function(env.clone(), func, vec![val]).map(Value::into_single))
                 .collect::<Result<List, RuntimeError>>()
-                .map(Value::List)
+                .and_then(|list| new_list(&env, list))
+        }),
+    );
+
+    // maps a function which returns lists, and appends the results together
+    env.define(
+        Symbol::from("mapcat"),
+        Value::NativeFunc(|env, args| {
+            let func = require_arg("mapcat", &args, 0)?;
+            let list = require_typed_arg::<&List>("mapcat", &args, 1)?;
+
+            let mut items = Vec::new();
+            for val in list {
+                match call_function(env.clone(), func, vec![val])?.into_single() {
+                    Value::List(result) => items.extend(&result),
+                    other => {
+                        return Err(RuntimeError::new(format!(
+                            "\"mapcat\" requires the function to return lists; got {}",
+                            other
+                        )))
+                    }
+                }
+            }
+
+            new_list(&env, items.into_iter().collect())
         }),
     );
 
@@ -199,11 +485,9 @@ pub fn default_env() -> Env {
 
             list.into_iter()
                 .filter_map(|val: Value| -> Option<Result<Value, RuntimeError>> {
-                    let expr = lisp! { ({func.clone()} (quote {val.clone()})) };
-
-                    match eval(env.clone(), &expr) {
+                    match call_function(env.clone(), func, vec![val.clone()]) {
                         Ok(matches) => {
-                            if matches.into() {
+                            if is_truthy(&env, &matches) {
                                 Some(Ok(val))
                             } else {
                                 None
@@ -213,259 +497,1485 @@ pub fn default_env() -> Env {
                     }
                 })
                 .collect::<Result<List, RuntimeError>>()
-                .map(Value::List)
+                .and_then(|list| new_list(&env, list))
         }),
     );
 
     env.define(
-        Symbol::from("length"),
-        Value::NativeFunc(|_env, args| {
-            let list = require_typed_arg::<&List>("length", &args, 0)?;
+        Symbol::from("for_each"),
+        Value::NativeFunc(|env, args| {
+            let func = require_arg("for_each", &args, 0)?;
+            let list = require_typed_arg::<&List>("for_each", &args, 1)?;
 
-            cfg_if! {
-                if #[cfg(feature = "bigint")] {
-                    Ok(Value::Int(list.into_iter().len().into()))
-                } else {
-                    Ok(Value::Int(list.into_iter().len() as IntType))
-                }
+            for val in list {
+                call_function(env.clone(), func, vec![val])?;
             }
+
+            Ok(Value::NIL)
         }),
     );
 
     env.define(
-        Symbol::from("range"),
-        Value::NativeFunc(|_env, args| {
-            let start = require_typed_arg::<IntType>("range", &args, 0)?;
-            let end = require_typed_arg::<IntType>("range", &args, 1)?;
-
-            let mut current = start;
-
-            Ok(Value::List(
-                std::iter::from_fn(move || {
-                    if current == end {
-                        None
-                    } else {
-                        let res = Some(current.clone());
+        Symbol::from("every"),
+        Value::NativeFunc(|env, args| {
+            let func = require_arg("every", &args, 0)?;
 
-                        current += 1;
+            for items in items_in_step("every", &args, 1)? {
+                if !is_truthy(&env, &call_function(env.clone(), func, items)?) {
+                    return Ok(Value::False);
+                }
+            }
 
-                        res
-                    }
-                })
-                .map(Value::from)
-                .collect(),
-            ))
+            Ok(Value::True)
         }),
     );
 
     env.define(
-        Symbol::from("hash"),
-        Value::NativeFunc(|_env, args| {
-            let chunks = args.chunks(2);
-
-            let mut hash = HashMap::new();
+        Symbol::from("some"),
+        Value::NativeFunc(|env, args| {
+            let func = require_arg("some", &args, 0)?;
 
-            for pair in chunks {
-                let key = pair.get(0).unwrap();
-                let value = pair.get(1);
+            for items in items_in_step("some", &args, 1)? {
+                let result = call_function(env.clone(), func, items)?.into_single();
 
-                if let Some(value) = value {
-                    hash.insert(key.clone(), value.clone());
-                } else {
-                    return Err(RuntimeError {
-                        msg: format!("Must pass an even number of arguments to 'hash', because they're used as key/value pairs; found extra argument {}", key)
-                    });
+                if is_truthy(&env, &result) {
+                    return Ok(result);
                 }
             }
 
-            Ok(Value::HashMap(Rc::new(RefCell::new(hash))))
+            Ok(Value::False)
         }),
     );
 
+    // also known as `count_if`
+    for name in ["count", "count_if"] {
+        env.define(
+            Symbol::from(name),
+            Value::NativeFunc(|env, args| {
+                let func = require_arg("count", &args, 0)?;
+                let mut count = 0;
+
+                for items in items_in_step("count", &args, 1)? {
+                    if is_truthy(&env, &call_function(env.clone(), func, items)?) {
+                        count += 1;
+                    }
+                }
+
+                int_from_len("count", count)
+            }),
+        );
+    }
+
+    // the item whose key (from calling the function on it) is the smallest
+    // or largest, or the first of them if there's a tie. An empty list has no
+    // such item, so it's an error.
     env.define(
-        Symbol::from("hash_get"),
-        Value::NativeFunc(|_env, args| {
-            let hash = require_typed_arg::<&HashMapRc>("hash_get", &args, 0)?;
-            let key = require_arg("hash_get", &args, 1)?;
+        Symbol::from("min_by"),
+        Value::NativeFunc(|env, args| extreme_by(env, "min_by", &args, Ordering::Less)),
+    );
 
-            Ok(hash
-                .borrow()
-                .get(key)
-                .map(|v| v.clone())
-                .unwrap_or(Value::NIL))
-        }),
+    env.define(
+        Symbol::from("max_by"),
+        Value::NativeFunc(|env, args| extreme_by(env, "max_by", &args, Ordering::Greater)),
     );
 
+    // the items grouped by their key, as a list of `(key item ...)` lists,
+    // with the groups in the order their keys first came up, and the items in
+    // each in the order they were in
     env.define(
-        Symbol::from("hash_set"),
-        Value::NativeFunc(|_env, args| {
-            let hash = require_typed_arg::<&HashMapRc>("hash_set", &args, 0)?;
-            let key = require_arg("hash_set", &args, 1)?;
-            let value = require_arg("hash_set", &args, 2)?;
+        Symbol::from("group_by"),
+        Value::NativeFunc(|env, args| {
+            let func = require_arg("group_by", &args, 0)?;
+            let list = require_typed_arg::<&List>("group_by", &args, 1)?;
 
-            hash.borrow_mut().insert(key.clone(), value.clone());
+            let mut indices: HashMap<Value, usize> = HashMap::new();
+            let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
 
-            Ok(Value::HashMap(hash.clone()))
-        }),
-    );
+            for item in list {
+                let key = call_function(env.clone(), func, vec![item.clone()])?.into_single();
 
-    env.define(
-        Symbol::from("+"),
-        Value::NativeFunc(|_env, args| {
-            let first_arg = require_arg("+", &args, 1)?;
-
-            let mut total = match first_arg {
-                Value::Int(_) => Ok(Value::Int(0.into())),
-                Value::Float(_) => Ok(Value::Float(0.0)),
-                Value::String(_) => Ok(Value::String("".into())),
-                _ => Err(RuntimeError {
-                    msg: format!(
-                        "Function \"+\" requires arguments to be numbers or strings; found {}",
-                        first_arg
-                    ),
-                }),
-            }?;
-
-            for arg in args {
-                total = (&total + &arg).map_err(|_| RuntimeError {
-                    msg: format!(
-                        "Function \"+\" requires arguments to be numbers or strings; found {}",
-                        arg
-                    ),
-                })?;
+                match indices.get(&key) {
+                    Some(&index) => groups[index].1.push(item),
+                    None => {
+                        indices.insert(key.clone(), groups.len());
+                        groups.push((key, vec![item]));
+                    }
+                }
             }
 
-            Ok(total)
+            let groups = groups
+                .into_iter()
+                .map(|(key, items)| new_list(&env, List::from_vec(items).cons(key)))
+                .collect::<Result<List, RuntimeError>>()?;
+
+            new_list(&env, groups)
         }),
     );
 
+    // the items which match, and the ones which don't, as a list of the two
+    // lists
     env.define(
-        Symbol::from("-"),
-        Value::NativeFunc(|_env, args| {
-            let a = require_arg("-", &args, 0)?;
-            let b = require_arg("-", &args, 1)?;
+        Symbol::from("partition"),
+        Value::NativeFunc(|env, args| {
+            let func = require_arg("partition", &args, 0)?;
+            let list = require_typed_arg::<&List>("partition", &args, 1)?;
 
-            (a - b).map_err(|_| RuntimeError {
-                msg: String::from("Function \"-\" requires arguments to be numbers"),
-            })
+            let mut matching = Vec::new();
+            let mut rest = Vec::new();
+
+            for item in list {
+                if is_truthy(&env, &call_function(env.clone(), func, vec![item.clone()])?) {
+                    matching.push(item);
+                } else {
+                    rest.push(item);
+                }
+            }
+
+            let matching = new_list(&env, List::from_vec(matching))?;
+            let rest = new_list(&env, List::from_vec(rest))?;
+
+            new_list(&env, List::from_vec(vec![matching, rest]))
         }),
     );
 
+    // each item with its index, as `(index item)`
     env.define(
-        Symbol::from("*"),
-        Value::NativeFunc(|_env, args| {
-            let mut product = Value::Int(1.into());
+        Symbol::from("enumerate"),
+        Value::NativeFunc(|env, args| {
+            let list = require_typed_arg::<&List>("enumerate", &args, 0)?;
 
-            for arg in args {
-                product = (&product * &arg).map_err(|_| RuntimeError {
-                    msg: format!(
-                        "Function \"*\" requires arguments to be numbers; found {}",
-                        arg
-                    ),
-                })?;
-            }
+            list.into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    let index = int_from_len("enumerate", index)?;
 
-            Ok(product)
+                    new_list(&env, List::from_vec(vec![index, item]))
+                })
+                .collect::<Result<List, RuntimeError>>()
+                .and_then(|list| new_list(&env, list))
         }),
     );
 
     env.define(
-        Symbol::from("/"),
-        Value::NativeFunc(|_env, args| {
-            let a = require_arg("/", &args, 0)?;
-            let b = require_arg("/", &args, 1)?;
-
-            (a / b).map_err(|_| RuntimeError {
-                msg: String::from("Function \"/\" requires arguments to be numbers"),
-            })
+        Symbol::from("zip"),
+        Value::NativeFunc(|env, args| {
+            items_in_step("zip", &args, 0)?
+                .map(|items| new_list(&env, List::from_vec(items)))
+                .collect::<Result<List, RuntimeError>>()
+                .and_then(|list| new_list(&env, list))
         }),
     );
 
     env.define(
-        Symbol::from("truncate"),
-        Value::NativeFunc(|_env, args| {
-            let a = require_arg("truncate", &args, 0)?;
-            let b = require_arg("truncate", &args, 1)?;
+        Symbol::from("unzip"),
+        Value::NativeFunc(|env, args| {
+            let list = require_typed_arg::<&List>("unzip", &args, 0)?;
+            let mut columns: Option<Vec<Vec<Value>>> = None;
+
+            for (index, row) in list.into_iter().enumerate() {
+                let row = match &row {
+                    Value::List(row) => row.into_iter().collect::<Vec<Value>>(),
+                    other => {
+                        return Err(RuntimeError::new(format!("\"unzip\" requires a list of lists; got {}", other)))
+                    }
+                };
 
-            if let (Ok(a), Ok(b)) = (
+                let columns = columns.get_or_insert_with(|| vec![Vec::new(); row.len()]);
+
+                if row.len() != columns.len() {
+                    return Err(RuntimeError::new(format!(
+                            "\"unzip\" requires lists of the same length, but list {} has {} items instead of {}",
+                            index + 1,
+                            row.len(),
+                            columns.len()
+                        )));
+                }
+
+                for (column, item) in columns.iter_mut().zip(row) {
+                    column.push(item);
+                }
+            }
+
+            columns
+                .unwrap_or_default()
+                .into_iter()
+                .map(|column| new_list(&env, List::from_vec(column)))
+                .collect::<Result<List, RuntimeError>>()
+                .and_then(|list| new_list(&env, list))
+        }),
+    );
+
+    env.define(
+        Symbol::from("length"),
+        Value::NativeFunc(|_env, args| {
+            let list = require_typed_arg::<&List>("length", &args, 0)?;
+
+            int_from_len("length", list.len())
+        }),
+    );
+
+    env.define(
+        Symbol::from("range"),
+        Value::NativeFunc(|env, args| {
+            let start = require_typed_arg::<IntType>("range", &args, 0)?;
+            let end = require_typed_arg::<IntType>("range", &args, 1)?;
+
+            // a range that goes backwards is empty
+            if end < start {
+                return Ok(Value::NIL);
+            }
+
+            // counted before the list is made, however long it would be
+            env.borrow().context().allocate_list(|| {
+                int_to_i128(&start)
+                    .zip(int_to_i128(&end))
+                    .and_then(|(start, end)| end.checked_sub(start))
+                    .and_then(|len| usize::try_from(len).ok())
+                    .unwrap_or(usize::MAX)
+            })?;
+
+            let mut current = start;
+
+            Ok(Value::List(
+                core::iter::from_fn(move || {
+                    if current == end {
+                        None
+                    } else {
+                        let next = &current + 1;
+
+                        Some(core::mem::replace(&mut current, next))
+                    }
+                })
+                .map(Value::from)
+                .collect(),
+            ))
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash"),
+        Value::NativeFunc(|env, args| {
+            check_len(&env, args.len() / 2)?;
+            let chunks = args.chunks(2);
+
+            let mut hash = HashMap::new();
+
+            for pair in chunks {
+                let key = pair.first().unwrap();
+                let value = pair.get(1);
+
+                if let Some(value) = value {
+                    hash.insert(key.clone(), value.clone());
+                } else {
+                    return Err(RuntimeError::new(format!("Must pass an even number of arguments to 'hash', because they're used as key/value pairs; found extra argument {}", key)));
+                }
+            }
+
+            Ok(Value::HashMap(Rc::new(RefCell::new(hash))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_get"),
+        Value::NativeFunc(|_env, args| {
+            let hash = require_typed_arg::<&HashMapRc>("hash_get", &args, 0)?;
+            let key = require_arg("hash_get", &args, 1)?;
+
+            Ok(hash.borrow().get(key).cloned().unwrap_or(Value::NIL))
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_set"),
+        Value::NativeFunc(|env, args| {
+            let hash = require_typed_arg::<&HashMapRc>("hash_set", &args, 0)?;
+            let key = require_arg("hash_set", &args, 1)?;
+            let value = require_arg("hash_set", &args, 2)?;
+
+            hash.borrow_mut().insert(key.clone(), value.clone());
+            check_len(&env, hash.borrow().len())?;
+
+            Ok(Value::HashMap(hash.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("list_set"),
+        Value::NativeFunc(|env, args| {
+            let list = require_typed_arg::<&List>("list_set", &args, 0)?;
+            let index = require_typed_arg::<IntType>("list_set", &args, 1)?;
+            let update = require_arg("list_set", &args, 2)?;
+
+            let mut items = list.into_iter().collect::<Vec<Value>>();
+
+            let index = int_to_usize(&index)
+                .filter(|index| *index < items.len())
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "\"list_set\" index {} is out of range for a list of length {}",
+                        index,
+                        items.len()
+                    ))
+                })?;
+
+            items[index] = apply_update(env.clone(), update, items[index].clone())?;
+
+            new_list(&env, List::from_vec(items))
+        }),
+    );
+
+    env.define(
+        Symbol::from("alist_update"),
+        Value::NativeFunc(|env, args| {
+            let alist = require_typed_arg::<&List>("alist_update", &args, 0)?;
+            let key = require_arg("alist_update", &args, 1)?;
+            let update = require_arg("alist_update", &args, 2)?;
+
+            let mut entries = alist.into_iter().collect::<Vec<Value>>();
+
+            let mut found = None;
+            for (index, entry) in entries.iter().enumerate() {
+                let pair = match entry {
+                    Value::List(pair)
+                        if pair.cdr() != List::NIL && pair.cdr().cdr() == List::NIL =>
+                    {
+                        pair
+                    }
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                            "\"alist_update\" expected a (key value) entry, got {}",
+                            entry
+                        )))
+                    }
+                };
+
+                if pair.car()? == *key {
+                    found = Some((index, pair.cdr().car()?));
+                    break;
+                }
+            }
+
+            let (index, old) = found.ok_or_else(|| {
+                RuntimeError::new(format!(
+                    "\"alist_update\" found no entry for the key {}",
+                    key
+                ))
+            })?;
+
+            let new = apply_update(env.clone(), update, old)?;
+            entries[index] = Value::List([key.clone(), new].iter().collect());
+
+            new_list(&env, List::from_vec(entries))
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_update"),
+        Value::NativeFunc(|env, args| {
+            let hash = require_typed_arg::<&HashMapRc>("hash_update", &args, 0)?;
+            let key = require_arg("hash_update", &args, 1)?;
+            let update = require_arg("hash_update", &args, 2)?;
+
+            // not borrowed while `update` is called, in case it uses the hash
+            let old = hash.borrow().get(key).cloned();
+            let old = old.ok_or_else(|| {
+                RuntimeError::new(format!(
+                    "\"hash_update\" found no entry for the key {}",
+                    key
+                ))
+            })?;
+
+            let new = apply_update(env, update, old)?;
+            hash.borrow_mut().insert(key.clone(), new);
+
+            Ok(Value::HashMap(hash.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_count"),
+        Value::NativeFunc(|_env, args| {
+            let hash = require_typed_arg::<&HashMapRc>("hash_count", &args, 0)?;
+            let len = hash.borrow().len();
+
+            int_from_len("hash_count", len)
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_map"),
+        Value::NativeFunc(|env, args| {
+            let func = require_function("hash_map", &args, 0)?;
+            let hash = require_typed_arg::<&HashMapRc>("hash_map", &args, 1)?;
+
+            let entries = hash_entries(hash);
+            check_len(&env, entries.len())?;
+
+            let mut mapped = HashMap::new();
+            for (key, value) in entries {
+                let new = call_function(env.clone(), func, vec![key.clone(), value])?.into_single();
+                mapped.insert(key, new);
+            }
+
+            Ok(Value::HashMap(Rc::new(RefCell::new(mapped))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_for_each"),
+        Value::NativeFunc(|env, args| {
+            let func = require_function("hash_for_each", &args, 0)?;
+            let hash = require_typed_arg::<&HashMapRc>("hash_for_each", &args, 1)?;
+
+            for (key, value) in hash_entries(hash) {
+                call_function(env.clone(), func, vec![key, value])?;
+            }
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_merge"),
+        Value::NativeFunc(|env, args| {
+            let mut merged = HashMap::new();
+
+            for index in 0..args.len() {
+                let hash = require_typed_arg::<&HashMapRc>("hash_merge", &args, index)?;
+                merged.extend(hash_entries(hash));
+                check_len(&env, merged.len())?;
+            }
+
+            Ok(Value::HashMap(Rc::new(RefCell::new(merged))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("hash_assoc"),
+        Value::NativeFunc(|env, args| {
+            let hash = require_typed_arg::<&HashMapRc>("hash_assoc", &args, 0)?;
+            let key = require_arg("hash_assoc", &args, 1)?;
+            let value = require_arg("hash_assoc", &args, 2)?;
+
+            let mut updated = hash.borrow().clone();
+            updated.insert(key.clone(), value.clone());
+            check_len(&env, updated.len())?;
+
+            Ok(Value::HashMap(Rc::new(RefCell::new(updated))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("make_set"),
+        Value::NativeFunc(|env, args| {
+            check_len(&env, args.len())?;
+            let members = args
+                .iter()
+                .map(|member| require_set_member("make_set", member))
+                .collect::<Result<HashSet<Value>, RuntimeError>>()?;
+
+            Ok(Value::Set(Rc::new(RefCell::new(members))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("list_to_set"),
+        Value::NativeFunc(|env, args| {
+            let list = require_typed_arg::<&List>("list_to_set", &args, 0)?;
+            env.borrow()
+                .context()
+                .check_collection_len(list.checked_len()?)?;
+
+            let members = list
+                .into_iter()
+                .map(|member| require_set_member("list_to_set", &member))
+                .collect::<Result<HashSet<Value>, RuntimeError>>()?;
+
+            Ok(Value::Set(Rc::new(RefCell::new(members))))
+        }),
+    );
+
+    // sorted, so that the same set always gives the same list
+    env.define(
+        Symbol::from("set_to_list"),
+        Value::NativeFunc(|env, args| {
+            let set = require_typed_arg::<&SetRc>("set_to_list", &args, 0)?;
+
+            new_list(&env, sorted_members(&set.borrow()).into_iter().collect())
+        }),
+    );
+
+    env.define(
+        Symbol::from("set_add"),
+        Value::NativeFunc(|env, args| {
+            let set = require_typed_arg::<&SetRc>("set_add", &args, 0)?;
+
+            let members = args[1..]
+                .iter()
+                .map(|member| require_set_member("set_add", member))
+                .collect::<Result<Vec<Value>, RuntimeError>>()?;
+            set.borrow_mut().extend(members);
+            check_len(&env, set.borrow().len())?;
+
+            Ok(Value::Set(set.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("set_remove"),
+        Value::NativeFunc(|_env, args| {
+            let set = require_typed_arg::<&SetRc>("set_remove", &args, 0)?;
+
+            let mut members = set.borrow_mut();
+            for member in &args[1..] {
+                members.remove(member);
+            }
+            drop(members);
+
+            Ok(Value::Set(set.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("set_contains"),
+        Value::NativeFunc(|_env, args| {
+            let set = require_typed_arg::<&SetRc>("set_contains", &args, 0)?;
+            let member = require_arg("set_contains", &args, 1)?;
+
+            Ok(Value::from(set.borrow().contains(member)))
+        }),
+    );
+
+    env.define(
+        Symbol::from("set_union"),
+        Value::NativeFunc(|env, args| {
+            let mut union = HashSet::new();
+
+            for index in 0..args.len() {
+                let set = require_typed_arg::<&SetRc>("set_union", &args, index)?;
+                union.extend(set.borrow().iter().cloned());
+                check_len(&env, union.len())?;
+            }
+
+            Ok(Value::Set(Rc::new(RefCell::new(union))))
+        }),
+    );
+
+    // the members of the first set that are in all of the others
+    env.define(
+        Symbol::from("set_intersection"),
+        Value::NativeFunc(|_env, args| {
+            let (first, others) = require_sets("set_intersection", &args)?;
+
+            let intersection = first
+                .borrow()
+                .iter()
+                .filter(|member| others.iter().all(|set| set.borrow().contains(*member)))
+                .cloned()
+                .collect();
+
+            Ok(Value::Set(Rc::new(RefCell::new(intersection))))
+        }),
+    );
+
+    // the members of the first set that aren't in any of the others
+    env.define(
+        Symbol::from("set_difference"),
+        Value::NativeFunc(|_env, args| {
+            let (first, others) = require_sets("set_difference", &args)?;
+
+            let difference = first
+                .borrow()
+                .iter()
+                .filter(|member| !others.iter().any(|set| set.borrow().contains(*member)))
+                .cloned()
+                .collect();
+
+            Ok(Value::Set(Rc::new(RefCell::new(difference))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("put"),
+        Value::NativeFunc(|env, args| {
+            let symbol = require_typed_arg::<&Symbol>("put", &args, 0)?;
+            let key = require_typed_arg::<&Symbol>("put", &args, 1)?;
+            let value = require_arg("put", &args, 2)?;
+
+            env.borrow()
+                .context()
+                .plists
+                .borrow_mut()
+                .entry(symbol.clone())
+                .or_default()
+                .insert(key.clone(), value.clone());
+
+            Ok(value.clone())
+        }),
+    );
+
+    env.define(
+        Symbol::from("get"),
+        Value::NativeFunc(|env, args| {
+            let symbol = require_typed_arg::<&Symbol>("get", &args, 0)?;
+            let key = require_typed_arg::<&Symbol>("get", &args, 1)?;
+
+            Ok(env
+                .borrow()
+                .context()
+                .plists
+                .borrow()
+                .get(symbol)
+                .and_then(|plist| plist.get(key))
+                .cloned()
+                .unwrap_or(Value::NIL))
+        }),
+    );
+
+    env.define(
+        Symbol::from("remprop"),
+        Value::NativeFunc(|env, args| {
+            let symbol = require_typed_arg::<&Symbol>("remprop", &args, 0)?;
+            let key = require_typed_arg::<&Symbol>("remprop", &args, 1)?;
+
+            let env = env.borrow();
+            let mut plists = env.context().plists.borrow_mut();
+
+            let removed = plists.get_mut(symbol).and_then(|plist| plist.remove(key));
+
+            if plists.get(symbol).is_some_and(|plist| plist.is_empty()) {
+                plists.remove(symbol);
+            }
+
+            Ok(removed.unwrap_or(Value::NIL))
+        }),
+    );
+
+    env.define(
+        Symbol::from("generator"),
+        Value::NativeFunc(|_env, args| {
+            let lambda = require_typed_arg::<&Lambda>("generator", &args, 0)?;
+            let generator = make_generator(lambda, args[1..].to_vec())?;
+
+            Ok(Value::Generator(Rc::new(RefCell::new(generator))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("next"),
+        Value::NativeFunc(|_env, args| {
+            let generator = require_typed_arg::<&Rc<RefCell<Generator>>>("next", &args, 0)?;
+
+            // the second value tells a yielded NIL from an exhausted generator
+            Ok(Value::Values(match resume_generator(generator)? {
+                Some(value) => [value, Value::True].into(),
+                None => [Value::NIL, Value::False].into(),
+            }))
+        }),
+    );
+
+    env.define(
+        Symbol::from("is_exhausted"),
+        Value::NativeFunc(|_env, args| {
+            let generator = require_typed_arg::<&Rc<RefCell<Generator>>>("is_exhausted", &args, 0)?;
+
+            Ok(Value::from(generator.borrow().is_exhausted()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("gen_to_list"),
+        Value::NativeFunc(|env, args| {
+            let generator = require_typed_arg::<&Rc<RefCell<Generator>>>("gen_to_list", &args, 0)?;
+
+            core::iter::from_fn(|| resume_generator(generator).transpose())
+                .collect::<Result<List, RuntimeError>>()
+                .and_then(|list| new_list(&env, list))
+        }),
+    );
+
+    env.define(
+        Symbol::from("force"),
+        Value::NativeFunc(|_env, args| {
+            let promise = match require_arg("force", &args, 0)? {
+                Value::Promise(promise) => promise,
+                other => return Ok(other.clone()),
+            };
+
+            let (expr, env) = match &*promise.borrow() {
+                Promise::Delayed { expr, env } => (expr.clone(), env.clone()),
+                Promise::Forced(res) => return res.clone(),
+            };
+
+            let res = eval(env, &expr);
+
+            // if the promise was forced again while evaluating, keep the
+            // first result
+            let mut promise = promise.borrow_mut();
+            if let Promise::Delayed { .. } = *promise {
+                *promise = Promise::Forced(res);
+            }
+
+            match &*promise {
+                Promise::Forced(res) => res.clone(),
+                Promise::Delayed { .. } => unreachable!(),
+            }
+        }),
+    );
+
+    env.define(
+        Symbol::from("+"),
+        Value::NativeFunc(|env, args| {
+            if let Some(result) = foreign_arithmetic(&env, "+", &args, |a, b| a + b) {
+                return result;
+            }
+
+            let identity = match args.first() {
+                None | Some(Value::Int(_)) => Value::Int(IntType::from(0i8)),
+                Some(Value::Float(_)) => Value::Float(0.0),
+                // built in one buffer, instead of copying the result so far
+                // for each argument
+                Some(Value::String(_)) => return new_string(&env, concat_strings("+", &args)?),
+                Some(first_arg) => {
+                    return Err(RuntimeError::with_kind(
+                        ErrorKind::TypeError,
+                        format!(
+                            "Function \"+\" requires arguments to be numbers or strings; found {}",
+                            first_arg
+                        ),
+                    ))
+                }
+            };
+
+            fold_arithmetic("+", identity, &args, |a, b| a + b)
+        }),
+    );
+
+    env.define(
+        Symbol::from("string_append"),
+        Value::NativeFunc(|env, args| new_string(&env, concat_strings("string_append", &args)?)),
+    );
+
+    env.define(
+        Symbol::from("format"),
+        Value::NativeFunc(|env, args| {
+            let template = require_typed_arg::<&String>("format", &args, 0)?;
+
+            let formatted = format_string("format", env.borrow().context(), template, &args[1..])?;
+
+            new_string(&env, formatted)
+        }),
+    );
+
+    env.define(
+        Symbol::from("string_builder"),
+        Value::NativeFunc(|_env, _args| {
+            Ok(Value::StringBuilder(Rc::new(RefCell::new(String::new()))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("sb_append"),
+        Value::NativeFunc(|env, args| {
+            let builder = require_typed_arg::<&Rc<RefCell<String>>>("sb_append", &args, 0)?;
+
+            let mut buffer = builder.borrow_mut();
+            let len_before = buffer.len();
+            for piece in &args[1..] {
+                append_piece("sb_append", &mut buffer, piece)?;
+            }
+            let appended = buffer.len() - len_before;
+            drop(buffer);
+
+            // only what's appended is new
+            env.borrow().context().allocate_string(appended)?;
+
+            Ok(Value::StringBuilder(builder.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("sb_to_string"),
+        Value::NativeFunc(|env, args| {
+            let builder = require_typed_arg::<&Rc<RefCell<String>>>("sb_to_string", &args, 0)?;
+            let string = builder.borrow().clone();
+
+            new_string(&env, string)
+        }),
+    );
+
+    // strings are indexed by char, not by byte
+    env.define(
+        Symbol::from("string_ref"),
+        Value::NativeFunc(|_env, args| {
+            let string = require_typed_arg::<&String>("string_ref", &args, 0)?;
+            let index = require_typed_arg::<IntType>("string_ref", &args, 1)?;
+
+            int_to_usize(&index)
+                .and_then(|index| string.chars().nth(index))
+                .map(Value::Char)
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "\"string_ref\" index {} is out of range for a string of length {}",
+                        index,
+                        string.chars().count()
+                    ))
+                })
+        }),
+    );
+
+    env.define(
+        Symbol::from("string_to_list"),
+        Value::NativeFunc(|env, args| {
+            let string = require_typed_arg::<&String>("string_to_list", &args, 0)?;
+
+            new_list(&env, string.chars().map(Value::Char).collect())
+        }),
+    );
+
+    env.define(
+        Symbol::from("list_to_string"),
+        Value::NativeFunc(|env, args| {
+            let chars = require_typed_arg::<&List>("list_to_string", &args, 0)?;
+
+            chars
+                .into_iter()
+                .map(|item| match item {
+                    Value::Char(ch) => Some(ch),
+                    _ => None,
+                })
+                .collect::<Option<String>>()
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "\"list_to_string\" requires a list to only contain chars; got {}",
+                        chars
+                    ))
+                })
+                .and_then(|string| new_string(&env, string))
+        }),
+    );
+
+    env.define(
+        Symbol::from("string_for_each"),
+        Value::NativeFunc(|env, args| {
+            let func = require_arg("string_for_each", &args, 0)?;
+            let string = require_typed_arg::<&String>("string_for_each", &args, 1)?;
+
+            for ch in string.chars() {
+                call_function(env.clone(), func, vec![Value::Char(ch)])?;
+            }
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    env.define(
+        Symbol::from("vector"),
+        Value::NativeFunc(|env, args| {
+            check_len(&env, args.len())?;
+
+            Ok(Value::from(args))
+        }),
+    );
+
+    env.define(
+        Symbol::from("is_vector"),
+        Value::NativeFunc(|_env, args| {
+            let val = require_arg("is_vector", &args, 0)?;
+
+            Ok(Value::from(matches!(val, Value::Vector(_))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("vector_length"),
+        Value::NativeFunc(|_env, args| {
+            let items = require_typed_arg::<&Rc<RefCell<Vec<Value>>>>("vector_length", &args, 0)?;
+
+            int_from_len("vector_length", items.borrow().len())
+        }),
+    );
+
+    env.define(
+        Symbol::from("vector_ref"),
+        Value::NativeFunc(|_env, args| {
+            let items = require_typed_arg::<&Rc<RefCell<Vec<Value>>>>("vector_ref", &args, 0)?;
+            let items = items.borrow();
+            let index = require_index("vector_ref", &args, 1, items.len(), "a vector")?;
+
+            Ok(items[index].clone())
+        }),
+    );
+
+    env.define(
+        Symbol::from("vector_set"),
+        Value::NativeFunc(|_env, args| {
+            let items = require_typed_arg::<&Rc<RefCell<Vec<Value>>>>("vector_set", &args, 0)?;
+            let len = items.borrow().len();
+            let index = require_index("vector_set", &args, 1, len, "a vector")?;
+            let item = require_arg("vector_set", &args, 2)?;
+
+            items.borrow_mut()[index] = item.clone();
+
+            Ok(Value::Vector(items.clone()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("vector_to_list"),
+        Value::NativeFunc(|env, args| {
+            let items = require_typed_arg::<&Rc<RefCell<Vec<Value>>>>("vector_to_list", &args, 0)?;
+            let list = items.borrow().iter().cloned().collect::<List>();
+
+            new_list(&env, list)
+        }),
+    );
+
+    env.define(
+        Symbol::from("list_to_vector"),
+        Value::NativeFunc(|env, args| {
+            let list = require_typed_arg::<&List>("list_to_vector", &args, 0)?;
+            let len = list.checked_len()?;
+            check_len(&env, len)?;
+
+            Ok(Value::from(list.into_iter().take(len).collect::<Vec<_>>()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("bytes"),
+        Value::NativeFunc(|env, args| {
+            check_len(&env, args.len())?;
+
+            (0..args.len())
+                .map(|index| require_byte("bytes", &args, index))
+                .collect::<Result<Vec<u8>, RuntimeError>>()
+                .map(Value::from)
+        }),
+    );
+
+    env.define(
+        Symbol::from("bytes_length"),
+        Value::NativeFunc(|_env, args| {
+            let bytes = require_typed_arg::<&Rc<RefCell<Vec<u8>>>>("bytes_length", &args, 0)?;
+
+            int_from_len("bytes_length", bytes.borrow().len())
+        }),
+    );
+
+    env.define(
+        Symbol::from("bytes_ref"),
+        Value::NativeFunc(|_env, args| {
+            let bytes = require_typed_arg::<&Rc<RefCell<Vec<u8>>>>("bytes_ref", &args, 0)?;
+            let bytes = bytes.borrow();
+            let index = require_index("bytes_ref", &args, 1, bytes.len(), "bytes")?;
+
+            int_from_byte("bytes_ref", bytes[index])
+        }),
+    );
+
+    env.define(
+        Symbol::from("bytes_set"),
+        Value::NativeFunc(|_env, args| {
+            let bytes = require_typed_arg::<&Rc<RefCell<Vec<u8>>>>("bytes_set", &args, 0)?;
+            let len = bytes.borrow().len();
+            let index = require_index("bytes_set", &args, 1, len, "bytes")?;
+            let byte = require_byte("bytes_set", &args, 2)?;
+
+            bytes.borrow_mut()[index] = byte;
+
+            Ok(Value::Bytes(bytes.clone()))
+        }),
+    );
+
+    // a copy of the bytes from `start` up to (but not including) `end`, which
+    // defaults to the end of them
+    env.define(
+        Symbol::from("bytes_slice"),
+        Value::NativeFunc(|_env, args| {
+            let bytes = require_typed_arg::<&Rc<RefCell<Vec<u8>>>>("bytes_slice", &args, 0)?;
+            let bytes = bytes.borrow();
+            let start = require_typed_arg::<IntType>("bytes_slice", &args, 1)?;
+            let end = args
+                .get(2)
+                .map(|_| require_typed_arg::<IntType>("bytes_slice", &args, 2))
+                .transpose()?;
+
+            let range = int_to_usize(&start).zip(match &end {
+                Some(end) => int_to_usize(end),
+                None => Some(bytes.len()),
+            });
+
+            range
+                .and_then(|(start, end)| bytes.get(start..end))
+                .map(|slice| Value::from(slice.to_vec()))
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "\"bytes_slice\" range {} to {} is out of range for bytes of length {}",
+                        start,
+                        end.map_or_else(|| format!("{}", bytes.len()), |end| format!("{}", end)),
+                        bytes.len()
+                    ))
+                })
+        }),
+    );
+
+    env.define(
+        Symbol::from("bytes_append"),
+        Value::NativeFunc(|env, args| {
+            let mut appended = Vec::new();
+
+            for index in 0..args.len() {
+                let bytes =
+                    require_typed_arg::<&Rc<RefCell<Vec<u8>>>>("bytes_append", &args, index)?;
+                appended.extend_from_slice(&bytes.borrow());
+                check_len(&env, appended.len())?;
+            }
+
+            Ok(Value::from(appended))
+        }),
+    );
+
+    env.define(
+        Symbol::from("string_to_utf8"),
+        Value::NativeFunc(|_env, args| {
+            let string = require_typed_arg::<&String>("string_to_utf8", &args, 0)?;
+
+            Ok(Value::from(string.as_bytes().to_vec()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("utf8_to_string"),
+        Value::NativeFunc(|env, args| {
+            let bytes = require_typed_arg::<&Rc<RefCell<Vec<u8>>>>("utf8_to_string", &args, 0)?;
+            let bytes = bytes.borrow();
+
+            core::str::from_utf8(&bytes)
+                .map(|string| string.to_owned())
+                .map_err(|err| {
+                    RuntimeError::new(format!(
+                        "\"utf8_to_string\" found invalid UTF-8 at byte {}",
+                        err.valid_up_to()
+                    ))
+                })
+                .and_then(|string| new_string(&env, string))
+        }),
+    );
+
+    // all of the bytes, as an int written with either its most (`:big`) or
+    // least (`:little`) significant byte first, and unsigned unless it's
+    // called with `:signed T`
+    env.define(
+        Symbol::from("bytes_to_int"),
+        Value::NativeFunc(|env, args| {
+            let bytes = require_typed_arg::<&Rc<RefCell<Vec<u8>>>>("bytes_to_int", &args, 0)?;
+            let bytes = bytes.borrow();
+            let big_endian = require_endianness("bytes_to_int", &args, 1)?;
+            let signed = keyword_args("bytes_to_int", &args, 2, &["signed"])?
+                .get("signed")
+                .is_some_and(|signed| is_truthy(&env, signed));
+
+            if !(1..=8).contains(&bytes.len()) {
+                return Err(RuntimeError::new(format!(
+                    "\"bytes_to_int\" can only read ints of 1 to 8 bytes; got {} bytes",
+                    bytes.len()
+                )));
+            }
+
+            let mut padded = [0; 8];
+            padded[8 - bytes.len()..].copy_from_slice(&bytes);
+            if !big_endian {
+                padded[8 - bytes.len()..].reverse();
+            }
+
+            let mut int = i128::from(u64::from_be_bytes(padded));
+            if signed && padded[8 - bytes.len()] >= 0x80 {
+                int -= 1 << (8 * bytes.len());
+            }
+
+            int_from_i128(int).map(Value::Int).ok_or_else(|| {
+                arithmetic_error("bytes_to_int", ArithmeticError::Overflow, String::new())
+            })
+        }),
+    );
+
+    // the reverse of `bytes_to_int`: `(int_to_bytes int size :big)`
+    env.define(
+        Symbol::from("int_to_bytes"),
+        Value::NativeFunc(|env, args| {
+            let int = require_typed_arg::<IntType>("int_to_bytes", &args, 0)?;
+            let size = require_typed_arg::<IntType>("int_to_bytes", &args, 1)?;
+            let big_endian = require_endianness("int_to_bytes", &args, 2)?;
+            let signed = keyword_args("int_to_bytes", &args, 3, &["signed"])?
+                .get("signed")
+                .is_some_and(|signed| is_truthy(&env, signed));
+
+            let size = int_to_usize(&size)
+                .filter(|size| (1..=8).contains(size))
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "\"int_to_bytes\" requires a size of 1 to 8 bytes; got {}",
+                        size
+                    ))
+                })?;
+
+            let bits = 8 * size as u32;
+            let (min, max) = if signed {
+                (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+            } else {
+                (0, (1i128 << bits) - 1)
+            };
+
+            let fitted = int_to_i128(&int)
+                .filter(|int| (min..=max).contains(int))
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "\"int_to_bytes\" {} doesn't fit in a {}-byte {} int",
+                        int,
+                        size,
+                        if signed { "signed" } else { "unsigned" }
+                    ))
+                })?;
+
+            // (in two's complement, if it's negative)
+            let mut bytes = (fitted as u64).to_be_bytes()[8 - size..].to_vec();
+            if !big_endian {
+                bytes.reverse();
+            }
+
+            Ok(Value::from(bytes))
+        }),
+    );
+
+    env.define(
+        Symbol::from("-"),
+        Value::NativeFunc(|env, args| {
+            let first_arg = require_arg("-", &args, 0)?;
+
+            if let Some(result) = foreign_arithmetic(&env, "-", &args, |a, b| a - b) {
+                return result;
+            }
+
+            if args.len() == 1 {
+                // negation
+                fold_arithmetic("-", Value::Int(IntType::from(0i8)), &args, |a, b| a - b)
+            } else {
+                fold_arithmetic("-", first_arg.clone(), &args[1..], |a, b| a - b)
+            }
+        }),
+    );
+
+    env.define(
+        Symbol::from("*"),
+        Value::NativeFunc(|env, args| {
+            if let Some(result) = foreign_arithmetic(&env, "*", &args, |a, b| a * b) {
+                return result;
+            }
+
+            fold_arithmetic("*", Value::Int(IntType::from(1i8)), &args, |a, b| a * b)
+        }),
+    );
+
+    env.define(
+        Symbol::from("/"),
+        Value::NativeFunc(|env, args| {
+            let first_arg = require_arg("/", &args, 0)?;
+
+            if let Some(result) = foreign_arithmetic(&env, "/", &args, |a, b| a / b) {
+                return result;
+            }
+
+            if args.len() == 1 {
+                // reciprocal (which for integers is truncated, like any other
+                // integer division)
+                fold_arithmetic("/", Value::Int(IntType::from(1i8)), &args, |a, b| a / b)
+            } else {
+                fold_arithmetic("/", first_arg.clone(), &args[1..], |a, b| a / b)
+            }
+        }),
+    );
+
+    env.define(
+        Symbol::from("truncate"),
+        Value::NativeFunc(|_env, args| {
+            let a = require_arg("truncate", &args, 0)?;
+            let b = require_arg("truncate", &args, 1)?;
+
+            if let (Ok(a), Ok(b)) = (
                 TryInto::<IntType>::try_into(a),
                 TryInto::<IntType>::try_into(b),
             ) {
-                return Ok(Value::Int(a / b));
+                return checked_div(&a, &b)
+                    .map(Value::Int)
+                    .map_err(|err| arithmetic_error("truncate", err, String::new()));
             }
 
-            Err(RuntimeError {
-                msg: String::from("Function \"truncate\" requires arguments to be integers"),
-            })
+            Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                String::from("Function \"truncate\" requires arguments to be integers"),
+            ))
         }),
     );
 
+    // the radix follows Scheme's `number->string`, so the number of decimal
+    // places for a float is a keyword argument instead
     env.define(
-        Symbol::from("not"),
+        Symbol::from("number_to_string"),
+        Value::NativeFunc(|_env, args| {
+            let number = require_arg("number_to_string", &args, 0)?;
+            let (radix, keywords_index) = match args.get(1) {
+                Some(Value::Symbol(symbol)) if symbol.is_keyword() => (10, 1),
+                Some(_) => (require_radix("number_to_string", &args, 1)?, 2),
+                None => (10, 1),
+            };
+            let keywords = keyword_args("number_to_string", &args, keywords_index, &["precision"])?;
+
+            match (number, keywords.get("precision")) {
+                (Value::Int(int), None) => Ok(Value::String(int_to_string_radix(int, radix))),
+                (Value::Float(float), _) if radix != 10 => Err(RuntimeError::new(format!(
+                        "\"number_to_string\" can only write integers in radix {}; got {}",
+                        radix, float
+                    ))),
+                (Value::Float(_), None) => Ok(Value::String(format!("{}", number))),
+                (Value::Float(float), Some(precision)) => {
+                    let places = TryInto::<IntType>::try_into(*precision)
+                        .ok()
+                        .and_then(|places| usize::try_from(places).ok())
+                        .ok_or_else(|| RuntimeError::new(format!(
+                                "\"number_to_string\" requires :precision to be a non-negative integer; got {}",
+                                precision
+                            )))?;
+
+                    Ok(Value::String(format!("{:.*}", places, float)))
+                }
+                (Value::Int(_), Some(_)) => Err(RuntimeError::new(format!(
+                        "\"number_to_string\" can only take :precision for floats; got {}",
+                        number
+                    ))),
+                _ => Err(RuntimeError::with_kind(ErrorKind::TypeError, format!(
+                        "\"number_to_string\" requires argument 1 to be a number; got {}",
+                        number
+                    ))),
+            }
+        }),
+    );
+
+    // the options are all keyword arguments: `:places` (digits after the
+    // point), `:scientific`, `:width` (padded with spaces, or zeros with
+    // `:zero_pad`), `:separator` (between groups of three digits) and `:radix`
+    env.define(
+        Symbol::from("number_format"),
+        Value::NativeFunc(|env, args| {
+            let number = require_arg("number_format", &args, 0)?;
+            let keywords = keyword_args(
+                "number_format",
+                &args,
+                1,
+                &[
+                    "places",
+                    "scientific",
+                    "width",
+                    "zero_pad",
+                    "separator",
+                    "radix",
+                ],
+            )?;
+            let places = keywords
+                .get("places")
+                .map(|places| require_count("number_format", "places", places))
+                .transpose()?;
+            let scientific = keywords
+                .get("scientific")
+                .is_some_and(|scientific| is_truthy(&env, scientific));
+            let width = keywords
+                .get("width")
+                .map(|width| require_count("number_format", "width", width))
+                .transpose()?
+                .unwrap_or(0);
+            let zero_pad = keywords
+                .get("zero_pad")
+                .is_some_and(|zero_pad| is_truthy(&env, zero_pad));
+            let separator = match keywords.get("separator") {
+                Some(Value::String(separator)) => Some(separator.as_str()),
+                Some(other) => {
+                    return Err(RuntimeError::new(format!(
+                        "\"number_format\" requires :separator to be a string; got {}",
+                        other
+                    )))
+                }
+                None => None,
+            };
+            let radix = keywords
+                .get("radix")
+                .map(|radix| radix_of("number_format", radix))
+                .transpose()?
+                .unwrap_or(10);
+
+            let formatted = match number {
+                Value::Float(float) if radix != 10 => {
+                    return Err(RuntimeError::new(format!(
+                        "\"number_format\" can only write integers in radix {}; got {}",
+                        radix, float
+                    )))
+                }
+                Value::Int(_) if radix != 10 && (places.is_some() || scientific) => {
+                    return Err(RuntimeError::new(format!(
+                        "\"number_format\" can't take :places or :scientific with radix {}",
+                        radix
+                    )))
+                }
+                Value::Float(float) if !float.is_finite() => format!("{}", number),
+                Value::Float(float) if scientific => float_to_scientific(*float, places),
+                Value::Int(int) if scientific => {
+                    float_to_scientific(int_type_to_float_type(int), places)
+                }
+                Value::Float(float) => match places {
+                    Some(places) => format!("{:.*}", places, float),
+                    None => format!("{}", number),
+                },
+                Value::Int(int) => {
+                    let mut formatted = int_to_string_radix(int, radix);
+                    if let Some(places @ 1..) = places {
+                        formatted.push('.');
+                        formatted.push_str(&"0".repeat(places));
+                    }
+
+                    formatted
+                }
+                _ => {
+                    return Err(RuntimeError::with_kind(
+                        ErrorKind::TypeError,
+                        format!(
+                            "\"number_format\" requires argument 1 to be a number; got {}",
+                            number
+                        ),
+                    ))
+                }
+            };
+
+            let is_finite = !matches!(number, Value::Float(float) if !float.is_finite());
+            let formatted = match separator {
+                Some(separator) if is_finite => group_digits(&formatted, separator, radix),
+                _ => formatted,
+            };
+
+            Ok(Value::String(pad_number(
+                formatted,
+                width,
+                zero_pad && is_finite,
+            )))
+        }),
+    );
+
+    // like Scheme's `string->number`, anything that isn't a number is NIL
+    // rather than an error
+    env.define(
+        Symbol::from("string_to_number"),
         Value::NativeFunc(|_env, args| {
+            let text = require_typed_arg::<&String>("string_to_number", &args, 0)?;
+            let radix = match args.get(1) {
+                Some(_) => require_radix("string_to_number", &args, 1)?,
+                None => 10,
+            };
+
+            parse_number_radix(text, radix)
+                .map(|number| number.unwrap_or(Value::NIL))
+                .map_err(|err| arithmetic_error("string_to_number", err, String::new()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("not"),
+        Value::NativeFunc(|env, args| {
             let a = require_arg("not", &args, 0)?;
-            let a: bool = a.into();
 
-            Ok(Value::from(!a))
+            Ok(Value::from(!is_truthy(&env, a)))
         }),
     );
 
     env.define(
         Symbol::from("=="),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let a = require_arg("==", &args, 0)?;
             let b = require_arg("==", &args, 1)?;
 
-            Ok(Value::from(a == b))
+            Ok(Value::from(ops_equal(env.borrow().context(), a, b)))
+        }),
+    );
+
+    env.define(
+        Symbol::from("equal"),
+        Value::NativeFunc(|env, args| {
+            let a = require_arg("equal", &args, 0)?;
+            let b = require_arg("equal", &args, 1)?;
+
+            Ok(Value::from(ops_equal(env.borrow().context(), a, b)))
         }),
     );
 
     env.define(
         Symbol::from("!="),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let a = require_arg("!=", &args, 0)?;
             let b = require_arg("!=", &args, 1)?;
 
-            Ok(Value::from(a != b))
+            Ok(Value::from(!ops_equal(env.borrow().context(), a, b)))
         }),
     );
 
     env.define(
         Symbol::from("<"),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let a = require_arg("<", &args, 0)?;
             let b = require_arg("<", &args, 1)?;
 
+            if let Some(ordering) = foreign_compare(&env, "<", a, b) {
+                return Ok(Value::from(ordering? < Ordering::Equal));
+            }
+
             Ok(Value::from(a < b))
         }),
     );
 
     env.define(
         Symbol::from("<="),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let a = require_arg("<=", &args, 0)?;
             let b = require_arg("<=", &args, 1)?;
 
+            if let Some(ordering) = foreign_compare(&env, "<=", a, b) {
+                return Ok(Value::from(ordering? <= Ordering::Equal));
+            }
+
             Ok(Value::from(a <= b))
         }),
     );
 
     env.define(
         Symbol::from(">"),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let a = require_arg(">", &args, 0)?;
             let b = require_arg(">", &args, 1)?;
 
+            if let Some(ordering) = foreign_compare(&env, ">", a, b) {
+                return Ok(Value::from(ordering? > Ordering::Equal));
+            }
+
             Ok(Value::from(a > b))
         }),
     );
 
     env.define(
         Symbol::from(">="),
-        Value::NativeFunc(|_env, args| {
+        Value::NativeFunc(|env, args| {
             let a = require_arg(">=", &args, 0)?;
             let b = require_arg(">=", &args, 1)?;
 
+            if let Some(ordering) = foreign_compare(&env, ">=", a, b) {
+                return Ok(Value::from(ordering? >= Ordering::Equal));
+            }
+
             Ok(Value::from(a >= b))
         }),
     );
@@ -475,7 +1985,60 @@ pub fn default_env() -> Env {
         Value::NativeFunc(|env, args| {
             let expr = require_arg("eval", &args, 0)?;
 
-            eval(env, expr)
+            match args.get(1) {
+                Some(_) => {
+                    let env = require_typed_arg::<&Rc<RefCell<Env>>>("eval", &args, 1)?;
+
+                    eval(env.clone(), expr)
+                }
+                None => eval(env, expr),
+            }
+        }),
+    );
+
+    env.define(
+        Symbol::from("current_env"),
+        Value::NativeFunc(|env, _args| Ok(Value::Env(env))),
+    );
+
+    env.define(
+        Symbol::from("make_env"),
+        Value::NativeFunc(|env, args| {
+            let new_env = match args.first() {
+                Some(_) => {
+                    let parent = require_typed_arg::<&Rc<RefCell<Env>>>("make_env", &args, 0)?;
+
+                    Env::extend(parent.clone())
+                }
+                None => Env::with_context(env.borrow().context().clone()),
+            };
+
+            Ok(Value::Env(new_env.into_shared()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("env_get"),
+        Value::NativeFunc(|_env, args| {
+            let env = require_typed_arg::<&Rc<RefCell<Env>>>("env_get", &args, 0)?;
+            let symbol = require_typed_arg::<&Symbol>("env_get", &args, 1)?;
+
+            env.borrow()
+                .get(symbol)
+                .ok_or_else(|| RuntimeError::new(format!("\"{}\" is not defined", symbol)))
+        }),
+    );
+
+    env.define(
+        Symbol::from("env_define"),
+        Value::NativeFunc(|_env, args| {
+            let env = require_typed_arg::<&Rc<RefCell<Env>>>("env_define", &args, 0)?;
+            let symbol = require_typed_arg::<&Symbol>("env_define", &args, 1)?;
+            let value = require_arg("env_define", &args, 2)?;
+
+            env.borrow_mut().define(symbol.clone(), value.clone());
+
+            Ok(value.clone())
         }),
     );
 
@@ -489,5 +2052,985 @@ pub fn default_env() -> Env {
         }),
     );
 
+    // more (or less) than one result, for `call_with_values` or `let_values`
+    // to take apart. Anything else only sees the first of them.
+    env.define(
+        Symbol::from("values"),
+        Value::NativeFunc(|_env, args| match <[Value; 1]>::try_from(args) {
+            Ok([value]) => Ok(value),
+            Err(args) => Ok(Value::Values(args.into())),
+        }),
+    );
+
+    env.define(
+        Symbol::from("call_with_values"),
+        Value::NativeFunc(|env, args| {
+            let producer = require_function("call_with_values", &args, 0)?;
+            let consumer = require_function("call_with_values", &args, 1)?;
+
+            let values = match call_function(env.clone(), producer, vec![])? {
+                Value::Values(values) => values.to_vec(),
+                value => vec![value],
+            };
+
+            call_function(env, consumer, values)
+        }),
+    );
+
+    // the number of arguments a function takes: an int if it's always the
+    // same, or else a list of the fewest and the most (`T` if there's no
+    // limit). Functions written in Rust can't be looked into, so they're NIL.
+    env.define(
+        Symbol::from("arity"),
+        Value::NativeFunc(|_env, args| {
+            let lambda = match require_arg("arity", &args, 0)? {
+                Value::Macro(lambda) => lambda,
+                _ => match require_function("arity", &args, 0)? {
+                    Value::Lambda(lambda) => lambda,
+                    _ => return Ok(Value::NIL),
+                },
+            };
+
+            match lambda.arity() {
+                (required, Some(most)) if required == most => int_from_len("arity", required),
+                (required, most) => Ok(Value::List(
+                    [
+                        int_from_len("arity", required)?,
+                        match most {
+                            Some(most) => int_from_len("arity", most)?,
+                            None => Value::True,
+                        },
+                    ]
+                    .into_iter()
+                    .collect(),
+                )),
+            }
+        }),
+    );
+
+    // the parameter list of a function or macro written in Lisp, or NIL for
+    // one written in Rust
+    env.define(
+        Symbol::from("params"),
+        Value::NativeFunc(|_env, args| match require_arg("params", &args, 0)? {
+            Value::Macro(lambda) => Ok(Value::List(lambda.params())),
+            _ => match require_function("params", &args, 0)? {
+                Value::Lambda(lambda) => Ok(Value::List(lambda.params())),
+                _ => Ok(Value::NIL),
+            },
+        }),
+    );
+
+    env.define(
+        Symbol::from("describe"),
+        Value::NativeFunc(|env, args| {
+            let value = require_arg("describe", &args, 0)?;
+
+            let env = env.borrow();
+            let context = env.context();
+            let report = describe(context, value);
+
+            context
+                .output
+                .borrow_mut()
+                .write_str(&report)
+                .map_err(|_| RuntimeError::new("\"describe\" failed to write its output"))?;
+            Ok(Value::NIL)
+        }),
+    );
+
+    // `(describe_env)` writes the names defined in the scope it's called from
+    // (not counting the ones it inherits), in order, along with the types of
+    // their values. `(describe_env env)` does the same for an environment from
+    // `current_env` or `make_env`.
+    env.define(
+        Symbol::from("describe_env"),
+        Value::NativeFunc(|env, args| {
+            let target = match args.first() {
+                Some(_) => {
+                    require_typed_arg::<&Rc<RefCell<Env>>>("describe_env", &args, 0)?.clone()
+                }
+                None => env.clone(),
+            };
+
+            let target = target.borrow();
+            let mut entries = target.entries().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+
+            let env = env.borrow();
+            let context = env.context();
+            let mut report = String::new();
+            for (name, value) in entries {
+                let _ = writeln!(report, "{}: {}", name, context.type_name(value));
+            }
+
+            context
+                .output
+                .borrow_mut()
+                .write_str(&report)
+                .map_err(|_| RuntimeError::new("\"describe_env\" failed to write its output"))?;
+            Ok(Value::NIL)
+        }),
+    );
+
+    env.define(
+        Symbol::from("identity"),
+        Value::NativeFunc(|_env, args| require_arg("identity", &args, 0).cloned()),
+    );
+
+    env.define(
+        Symbol::from("const"),
+        Value::NativeFunc(|_env, args| {
+            let value = require_arg("const", &args, 0)?;
+
+            Ok(Value::Combinator(Rc::new(Combinator::Const(value.clone()))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("partial"),
+        Value::NativeFunc(|env, args| make_partial("partial", &env, args)),
+    );
+
+    env.define(
+        Symbol::from("curry"),
+        Value::NativeFunc(|env, args| make_partial("curry", &env, args)),
+    );
+
+    env.define(
+        Symbol::from("compose"),
+        Value::NativeFunc(|env, args| {
+            let funcs = (0..args.len())
+                .map(|index| require_combined("compose", &env, &args, index))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match args.len() {
+                0 => Ok(Value::NativeFunc(|_env, args| {
+                    require_arg("compose", &args, 0).cloned()
+                })),
+                1 => Ok(args[0].clone()),
+                _ => Ok(Value::Combinator(Rc::new(Combinator::Compose(funcs)))),
+            }
+        }),
+    );
+
+    env.define(
+        Symbol::from("flip"),
+        Value::NativeFunc(|env, args| {
+            let func = require_combined("flip", &env, &args, 0)?;
+
+            Ok(Value::Combinator(Rc::new(Combinator::Flip(func))))
+        }),
+    );
+
+    env.define(
+        Symbol::from("complement"),
+        Value::NativeFunc(|env, args| {
+            let func = require_combined("complement", &env, &args, 0)?;
+
+            Ok(Value::Combinator(Rc::new(Combinator::Complement(func))))
+        }),
+    );
+
+    // `(with_timeout seconds expr fallback)` evaluates `expr`, giving up on it
+    // once it's taken longer than `seconds`: then `fallback` is evaluated
+    // instead if there is one, and otherwise it's an error. The deadline is
+    // checked between steps of evaluation, so a slow native function can
+    // overshoot it unless it checks `Context::deadline_exceeded()` itself.
+    #[cfg(feature = "std")]
+    env.context()
+        .override_special_form("with_timeout", with_timeout);
+
+    // the number of steps (see `Context::steps()`) taken so far in the
+    // current top-level evaluation, for programs measuring their own cost
+    env.define(
+        Symbol::from("instruction_count"),
+        Value::NativeFunc(|env, _args| {
+            let env = env.borrow();
+            let context = env.context();
+            let count = context.steps() - context.top_level_start.get();
+
+            int_from_i128(count.into()).map(Value::Int).ok_or_else(|| {
+                arithmetic_error(
+                    "instruction_count",
+                    ArithmeticError::Overflow,
+                    String::new(),
+                )
+            })
+        }),
+    );
+
+    // `(with_step_limit steps expr fallback)` evaluates `expr`, giving up on
+    // it once it's taken more than `steps` steps itself: then `fallback` is
+    // evaluated instead if there is one, and otherwise it's an error. Limits
+    // nest like `with_timeout` deadlines, and the steps taken inside one
+    // count towards the ones outside it too.
+    env.context()
+        .override_special_form("with_step_limit", with_step_limit);
+
+    // a mistake in the prelude is a bug in this crate, so it's not reported
+    // to the caller
+    let env = env
+        .eval_prelude(PRELUDE)
+        .unwrap_or_else(|err| panic!("{}", err.msg));
+
+    // everything defined so far is a builtin, as far as the strict mode is
+    // concerned
+    if let Some(builtins) = env.parent() {
+        let names = builtins
+            .borrow()
+            .entries()
+            .map(|(name, _)| name.clone())
+            .collect();
+        *env.context().builtins.borrow_mut() = names;
+    }
+
     env
 }
+
+/// The `with_timeout` special form. Deadlines nest, and each one only handles
+/// its own timeout (an outer one that passes first is left to that one).
+#[cfg(feature = "std")]
+// `FloatType` may already be `f64`
+#[allow(clippy::unnecessary_cast)]
+fn with_timeout(env: Rc<RefCell<Env>>, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let seconds = eval(env.clone(), require_arg("with_timeout", &args, 0)?)?;
+    let duration = match &seconds {
+        Value::Int(int) => int_to_i128(int).map(|int| int as f64),
+        Value::Float(float) => Some(*float as f64),
+        _ => None,
+    }
+    .and_then(|seconds| std::time::Duration::try_from_secs_f64(seconds).ok())
+    .ok_or_else(|| {
+        RuntimeError::with_kind(
+            ErrorKind::TypeError,
+            format!(
+            "\"with_timeout\" requires argument 1 to be a non-negative number of seconds; got {}",
+            seconds
+        ),
+        )
+    })?;
+    let expr = require_arg("with_timeout", &args, 1)?;
+
+    let context = env.borrow().context().clone();
+    let Some(deadline) = std::time::Instant::now().checked_add(duration) else {
+        return eval(env, expr);
+    };
+
+    let depth = context.deadlines.borrow().len();
+    context.deadlines.borrow_mut().push(deadline);
+    let result = eval(env.clone(), expr);
+    let expired = context.expired_deadline();
+    context.deadlines.borrow_mut().truncate(depth);
+
+    match result {
+        Err(err) if is_budget_error(&err, TIMED_OUT) && expired == Some(depth) => match args.get(2)
+        {
+            Some(fallback) => eval(env, fallback),
+            None => Err(RuntimeError::with_kind(
+                ErrorKind::BudgetExhausted,
+                format!("\"with_timeout\" timed out after {} seconds", seconds),
+            )),
+        },
+        result => result,
+    }
+}
+
+/// The `with_step_limit` special form. Like `with_timeout`, each limit only
+/// handles its own (an outer one that's gone over first is left to that one).
+fn with_step_limit(env: Rc<RefCell<Env>>, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let steps = eval(env.clone(), require_arg("with_step_limit", &args, 0)?)?;
+    let limit = match &steps {
+        Value::Int(int) => int_to_i128(int).and_then(|int| u64::try_from(int).ok()),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        RuntimeError::with_kind(
+            ErrorKind::TypeError,
+            format!(
+                "\"with_step_limit\" requires argument 1 to be a non-negative integer; got {}",
+                steps
+            ),
+        )
+    })?;
+    let expr = require_arg("with_step_limit", &args, 1)?;
+
+    let context = env.borrow().context().clone();
+    let depth = context.step_limits.borrow().len();
+    context
+        .step_limits
+        .borrow_mut()
+        .push(context.steps().saturating_add(limit));
+    let result = eval(env.clone(), expr);
+    let exceeded = context.exceeded_step_limit();
+    context.step_limits.borrow_mut().truncate(depth);
+
+    match result {
+        Err(err) if is_budget_error(&err, STEP_LIMIT_EXCEEDED) && exceeded == Some(depth) => {
+            match args.get(2) {
+                Some(fallback) => eval(env, fallback),
+                None => Err(RuntimeError::with_kind(
+                    ErrorKind::BudgetExhausted,
+                    format!("\"with_step_limit\" went over its limit of {} steps", steps),
+                )),
+            }
+        }
+        result => result,
+    }
+}
+
+/// Whether `err` is the interpreter running out of a budget, with `msg` (saying
+/// which kind of budget)
+fn is_budget_error(err: &RuntimeError, msg: &str) -> bool {
+    err.kind == ErrorKind::BudgetExhausted && err.msg == msg
+}
+
+/// The part of `list` starting at the first item that `matches`, or NIL
+fn tail_from(list: &List, matches: impl Fn(&Value) -> bool) -> List {
+    let mut rest = list.clone();
+
+    while let Ok(item) = rest.car() {
+        if matches(&item) {
+            return rest;
+        }
+
+        rest = rest.cdr();
+    }
+
+    List::NIL
+}
+
+/// `member` (copied), if it can be put in a set. Only atoms can, since a
+/// container could be changed after it was added, and then not be found.
+fn require_set_member(func_name: &str, member: &Value) -> Result<Value, RuntimeError> {
+    match member {
+        Value::True
+        | Value::False
+        | Value::Int(_)
+        | Value::Float(_)
+        | Value::String(_)
+        | Value::Char(_)
+        | Value::Symbol(_) => Ok(member.clone()),
+        Value::List(list) if *list == List::NIL => Ok(member.clone()),
+        _ => Err(RuntimeError::new(format!(
+                "\"{}\" can only put atoms (numbers, strings, chars, symbols and booleans) in a set; got {}",
+                func_name, member
+            ))),
+    }
+}
+
+/// All of `args` as sets (of which there must be at least one), split into
+/// the first one and the rest
+fn require_sets<'a>(
+    func_name: &str,
+    args: &'a [Value],
+) -> Result<(&'a SetRc, Vec<&'a SetRc>), RuntimeError> {
+    let first = require_typed_arg::<&SetRc>(func_name, args, 0)?;
+    let others = (1..args.len())
+        .map(|index| require_typed_arg::<&SetRc>(func_name, args, index))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((first, others))
+}
+
+/// A copy of the entries in `hash`, so that they stay the same while they're
+/// iterated over, even if the hash is changed (by a function being called
+/// for each of them, etc)
+fn hash_entries(hash: &HashMapRc) -> Vec<(Value, Value)> {
+    hash.borrow()
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// The lists in `args` (from index `start`, of which there must be at least
+/// one), iterated together: each step gives the next item of every list,
+/// until the shortest one runs out
+fn items_in_step(
+    func_name: &str,
+    args: &[Value],
+    start: usize,
+) -> Result<impl Iterator<Item = Vec<Value>>, RuntimeError> {
+    let mut lists = (start..args.len().max(start + 1))
+        .map(|index| require_typed_arg::<&List>(func_name, args, index).map(List::into_iter))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(core::iter::from_fn(move || {
+        lists
+            .iter_mut()
+            .map(Iterator::next)
+            .collect::<Option<Vec<Value>>>()
+    }))
+}
+
+/// `list_star` (or `cons_star`, as `name`): the leading arguments consed
+/// onto the last one, or just the last one if it's the only one
+fn list_star(env: Rc<RefCell<Env>>, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+    let last = args.len().saturating_sub(1);
+    let tail = require_arg(name, args, last)?;
+    env.borrow().context().allocate_conses(last)?;
+
+    Ok(cons_all(&args[..last], tail.clone()))
+}
+
+/// `min_by` or `max_by`: the first item in the list (argument 2) whose key,
+/// from the function (argument 1), compares as `wanted` to all the others
+fn extreme_by(
+    env: Rc<RefCell<Env>>,
+    func_name: &str,
+    args: &[Value],
+    wanted: Ordering,
+) -> Result<Value, RuntimeError> {
+    let func = require_arg(func_name, args, 0)?;
+    let list = require_typed_arg::<&List>(func_name, args, 1)?;
+
+    let mut best: Option<(Value, Value)> = None;
+
+    for item in list {
+        let key = call_function(env.clone(), func, vec![item.clone()])?.into_single();
+
+        let is_better = match &best {
+            Some((best_key, _)) => {
+                let ordering = foreign_compare(&env, func_name, &key, best_key)
+                    .unwrap_or_else(|| Ok(key.cmp(best_key)))?;
+
+                ordering == wanted
+            }
+            None => true,
+        };
+
+        if is_better {
+            best = Some((key, item));
+        }
+    }
+
+    best.map(|(_, item)| item)
+        .ok_or_else(|| RuntimeError::new(format!("\"{}\" requires a non-empty list", func_name)))
+}
+
+/// A count (of items, etc) as an int, or an overflow error if it's too big
+fn int_from_len(func_name: &str, len: usize) -> Result<Value, RuntimeError> {
+    // this can't fail with the `bigint` feature
+    #[allow(clippy::unnecessary_fallible_conversions)]
+    let int = IntType::try_from(len);
+
+    int.map(Value::Int)
+        .map_err(|_| arithmetic_error(func_name, ArithmeticError::Overflow, String::new()))
+}
+
+/// A new list, counted towards the context's `resource_limits`
+fn new_list(env: &Rc<RefCell<Env>>, list: List) -> Result<Value, RuntimeError> {
+    env.borrow().context().allocate_list(|| list.len())?;
+
+    Ok(Value::List(list))
+}
+
+/// A new string, counted towards the context's `resource_limits`
+fn new_string(env: &Rc<RefCell<Env>>, string: String) -> Result<Value, RuntimeError> {
+    env.borrow().context().allocate_string(string.len())?;
+
+    Ok(Value::String(string))
+}
+
+/// Check the length of a new (or grown) vector, hash map, set or bytes
+/// against the context's `resource_limits`
+fn check_len(env: &Rc<RefCell<Env>>, len: usize) -> Result<(), RuntimeError> {
+    env.borrow().context().check_collection_len(len)
+}
+
+/// Get argument `index` of `args`, requiring it to be something callable
+fn require_function<'a>(
+    func_name: &str,
+    args: &'a [Value],
+    index: usize,
+) -> Result<&'a Value, RuntimeError> {
+    let func = require_arg(func_name, args, index)?;
+
+    match func {
+        Value::NativeFunc(_)
+        | Value::NativeClosure(_)
+        | Value::Combinator(_)
+        | Value::Lambda(_) => Ok(func),
+        _ => Err(RuntimeError::with_kind(
+            ErrorKind::TypeError,
+            format!(
+                "\"{}\" requires argument {} to be a function; got {}",
+                func_name,
+                index + 1,
+                func
+            ),
+        )),
+    }
+}
+
+/// Get argument `index` of `args` for one of the combinator builtins,
+/// requiring it to be a function, along with the name it's bound to in `env`
+/// (for printing the function that's made from it)
+fn require_combined(
+    func_name: &str,
+    env: &Rc<RefCell<Env>>,
+    args: &[Value],
+    index: usize,
+) -> Result<Function, RuntimeError> {
+    let value = require_function(func_name, args, index)?.clone();
+    let name = env.borrow().name_of(&value);
+
+    Ok(Function { value, name })
+}
+
+/// `partial`, or `curry` (which is the same, but prints as itself)
+fn make_partial(
+    builtin: &'static str,
+    env: &Rc<RefCell<Env>>,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let func = require_combined(builtin, env, &args, 0)?;
+
+    Ok(Value::Combinator(Rc::new(Combinator::Partial {
+        builtin,
+        func,
+        args: args[1..].to_vec(),
+    })))
+}
+
+/// Get argument `index` of `args`, requiring it to be an int from 0 to 255
+fn require_byte(func_name: &str, args: &[Value], index: usize) -> Result<u8, RuntimeError> {
+    let byte = require_arg(func_name, args, index)?;
+
+    TryInto::<IntType>::try_into(byte)
+        .ok()
+        .and_then(|byte| u8::try_from(byte).ok())
+        .ok_or_else(|| {
+            RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!(
+                    "\"{}\" requires argument {} to be a byte (an integer from 0 to 255); got {}",
+                    func_name,
+                    index + 1,
+                    byte
+                ),
+            )
+        })
+}
+
+/// Get argument `index` of `args`, requiring it to be an index into bytes (or
+/// whatever `of` names, for the error) of length `len`
+fn require_index(
+    func_name: &str,
+    args: &[Value],
+    index: usize,
+    len: usize,
+    of: &str,
+) -> Result<usize, RuntimeError> {
+    let item_index = require_typed_arg::<IntType>(func_name, args, index)?;
+
+    int_to_usize(&item_index)
+        .filter(|item_index| *item_index < len)
+        .ok_or_else(|| {
+            RuntimeError::new(format!(
+                "\"{}\" index {} is out of range for {} of length {}",
+                func_name, item_index, of, len
+            ))
+        })
+}
+
+/// Get argument `index` of `args`, requiring it to be either `:big` or
+/// `:little`, and return whether it's big-endian
+fn require_endianness(func_name: &str, args: &[Value], index: usize) -> Result<bool, RuntimeError> {
+    match require_arg(func_name, args, index)? {
+        Value::Symbol(symbol) if &*symbol.0 == ":big" => Ok(true),
+        Value::Symbol(symbol) if &*symbol.0 == ":little" => Ok(false),
+        other => Err(RuntimeError::with_kind(
+            ErrorKind::TypeError,
+            format!(
+                "\"{}\" requires argument {} to be :big or :little; got {}",
+                func_name,
+                index + 1,
+                other
+            ),
+        )),
+    }
+}
+
+/// A byte as an int, or an overflow error if it's too big (which can only
+/// happen with the `i8` feature)
+fn int_from_byte(func_name: &str, byte: u8) -> Result<Value, RuntimeError> {
+    #[allow(clippy::unnecessary_fallible_conversions)]
+    let int = IntType::try_from(byte);
+
+    int.map(Value::Int)
+        .map_err(|_| arithmetic_error(func_name, ArithmeticError::Overflow, String::new()))
+}
+
+/// Get argument `index` of `args`, requiring it to be one of the radixes that
+/// numbers can be written in
+fn require_radix(func_name: &str, args: &[Value], index: usize) -> Result<u32, RuntimeError> {
+    radix_of(func_name, require_arg(func_name, args, index)?)
+}
+
+fn radix_of(func_name: &str, radix: &Value) -> Result<u32, RuntimeError> {
+    match TryInto::<IntType>::try_into(radix)
+        .ok()
+        .and_then(|radix| u32::try_from(radix).ok())
+    {
+        Some(radix @ (2 | 8 | 10 | 16)) => Ok(radix),
+        _ => Err(RuntimeError::new(format!(
+            "\"{}\" requires a radix of 2, 8, 10 or 16; got {}",
+            func_name, radix
+        ))),
+    }
+}
+
+/// The value of a keyword argument that has to be a non-negative integer
+fn require_count(func_name: &str, keyword: &str, value: &Value) -> Result<usize, RuntimeError> {
+    TryInto::<IntType>::try_into(value)
+        .ok()
+        .and_then(|count| usize::try_from(count).ok())
+        .ok_or_else(|| {
+            RuntimeError::new(format!(
+                "\"{}\" requires :{} to be a non-negative integer; got {}",
+                func_name, keyword, value
+            ))
+        })
+}
+
+/// Put `separator` between each group of three digits in the whole part of a
+/// formatted number
+fn group_digits(formatted: &str, separator: &str, radix: u32) -> String {
+    let (sign, unsigned) = match formatted.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", formatted),
+    };
+    // in radix 16, `e` is a digit rather than an exponent
+    let whole_len = unsigned
+        .find(|ch: char| ch == '.' || (radix == 10 && ch == 'e'))
+        .unwrap_or(unsigned.len());
+    let (whole, rest) = unsigned.split_at(whole_len);
+
+    let mut grouped = String::from(sign);
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole_len - index) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(rest);
+
+    grouped
+}
+
+/// Pad a formatted number out to `width` characters, with spaces in front of
+/// it, or with zeros after its sign
+fn pad_number(formatted: String, width: usize, zero_pad: bool) -> String {
+    let padding = width.saturating_sub(formatted.chars().count());
+    if padding == 0 {
+        return formatted;
+    }
+
+    let fill = if zero_pad { "0" } else { " " }.repeat(padding);
+    match formatted.strip_prefix('-') {
+        Some(unsigned) if zero_pad => format!("-{}{}", fill, unsigned),
+        _ => format!("{}{}", fill, formatted),
+    }
+}
+
+/// The new value for one of the update builtins: `update` if it's a plain
+/// value, or if it's a function, the result of calling it with the old value
+fn apply_update(env: Rc<RefCell<Env>>, update: &Value, old: Value) -> Result<Value, RuntimeError> {
+    match update {
+        Value::NativeFunc(_)
+        | Value::NativeClosure(_)
+        | Value::Combinator(_)
+        | Value::Lambda(_) => call_function(env, update, vec![old]).map(Value::into_single),
+        _ => Ok(update.clone()),
+    }
+}
+
+/// Left-fold `op` over `args`, starting from `initial`
+fn fold_arithmetic(
+    func_name: &str,
+    initial: Value,
+    args: &[Value],
+    op: fn(&Value, &Value) -> Result<Value, ArithmeticError>,
+) -> Result<Value, RuntimeError> {
+    args.iter().try_fold(initial, |acc, arg| {
+        op(&acc, arg).map_err(|err| {
+            let expected = match func_name {
+                "+" => "numbers or strings",
+                _ => "numbers",
+            };
+
+            arithmetic_error(
+                func_name,
+                err,
+                format!(
+                    "Function \"{}\" requires arguments to be {}; found {}",
+                    func_name, expected, arg
+                ),
+            )
+        })
+    })
+}
+
+/// Apply an arithmetic function to arguments including foreign values, with
+/// the `LispOps` of their types, or `None` if there aren't any foreign values
+/// (so the arguments are handled as usual)
+fn foreign_arithmetic(
+    env: &Rc<RefCell<Env>>,
+    func_name: &str,
+    args: &[Value],
+    op: fn(&Value, &Value) -> Result<Value, ArithmeticError>,
+) -> Option<Result<Value, RuntimeError>> {
+    if !args.iter().any(|arg| matches!(arg, Value::Foreign(_))) {
+        return None;
+    }
+
+    let env = env.borrow();
+    let context = env.context();
+    let apply = |a: &Value, b: &Value| match (context.lisp_ops(a), context.lisp_ops(b)) {
+        (Some(ops), _) => match func_name {
+            "+" => ops.add(b),
+            "-" => ops.sub(b),
+            "*" => ops.mul(b),
+            _ => ops.div(b),
+        },
+        (None, Some(ops)) if func_name == "+" => ops.add(a),
+        (None, Some(ops)) if func_name == "*" => ops.mul(a),
+        _ if matches!(a, Value::Foreign(_)) || matches!(b, Value::Foreign(_)) => Err(
+            mismatched_types(func_name, context.type_name(a), context.type_name(b)),
+        ),
+        _ => fold_arithmetic(func_name, a.clone(), core::slice::from_ref(b), op),
+    };
+
+    Some(match args {
+        [arg] if func_name == "-" => match context.lisp_ops(arg) {
+            Some(ops) => ops.neg(),
+            None => return None,
+        },
+        [first, rest @ ..] => rest
+            .iter()
+            .try_fold(first.clone(), |acc, arg| apply(&acc, arg)),
+        [] => return None,
+    })
+}
+
+/// Compare two values with the `LispOps` of either, or `None` if neither has
+/// them
+fn foreign_compare(
+    env: &Rc<RefCell<Env>>,
+    func_name: &str,
+    a: &Value,
+    b: &Value,
+) -> Option<Result<Ordering, RuntimeError>> {
+    let env = env.borrow();
+    let context = env.context();
+
+    let ordering = match (context.lisp_ops(a), context.lisp_ops(b)) {
+        (Some(ops), _) => ops.compare(b),
+        (None, Some(ops)) => ops.compare(a).map(Ordering::reverse),
+        (None, None) => return None,
+    };
+
+    Some(
+        ordering
+            .ok_or_else(|| mismatched_types(func_name, context.type_name(a), context.type_name(b))),
+    )
+}
+
+/// Join strings and numbers into one string, allocating it once
+fn concat_strings(func_name: &str, pieces: &[Value]) -> Result<String, RuntimeError> {
+    let capacity = pieces
+        .iter()
+        .map(|piece| match piece {
+            Value::String(piece) => piece.len(),
+            _ => 0,
+        })
+        .sum();
+
+    let mut buffer = String::with_capacity(capacity);
+    for piece in pieces {
+        append_piece(func_name, &mut buffer, piece)?;
+    }
+
+    Ok(buffer)
+}
+
+/// Fill in the directives of a `format` template with `args`, in order: `~a`
+/// is an argument the way it's displayed (strings and chars as they are),
+/// `~s` is one the way it's printed (strings in quotes, etc), `~%` is a
+/// newline and `~~` is a `~`
+fn format_string(
+    func_name: &str,
+    context: &Context,
+    template: &str,
+    args: &[Value],
+) -> Result<String, RuntimeError> {
+    let mut formatted = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut chars = template.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '~' {
+            formatted.push(ch);
+            continue;
+        }
+
+        let directive = chars.next();
+        match directive {
+            Some('a' | 's') => {
+                let arg = args.next().ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "Function \"{}\" has more directives in its template than arguments",
+                        func_name
+                    ))
+                })?;
+
+                let _ = match (directive, arg) {
+                    (Some('a'), Value::String(arg)) => write!(formatted, "{}", arg),
+                    (Some('a'), Value::Char(arg)) => write!(formatted, "{}", arg),
+                    _ => write!(formatted, "{}", context.display(arg)),
+                };
+            }
+            Some('%') => formatted.push('\n'),
+            Some('~') => formatted.push('~'),
+            Some(other) => {
+                return Err(RuntimeError::new(format!(
+                    "Function \"{}\" doesn't know the directive ~{}",
+                    func_name, other
+                )))
+            }
+            None => {
+                return Err(RuntimeError::new(format!(
+                    "Function \"{}\" requires its template not to end with a ~",
+                    func_name
+                )))
+            }
+        }
+    }
+
+    if args.next().is_some() {
+        return Err(RuntimeError::new(format!(
+            "Function \"{}\" has more arguments than directives in its template",
+            func_name
+        )));
+    }
+
+    Ok(formatted)
+}
+
+/// Log a message built from a `format` template and its arguments, for
+/// `log_info`, etc. The arguments are evaluated either way, but the message is
+/// only built if it's logged.
+fn log(
+    func_name: &str,
+    level: LogLevel,
+    env: Rc<RefCell<Env>>,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let template = require_typed_arg::<&String>(func_name, &args, 0)?;
+
+    let env = env.borrow();
+    let context = env.context();
+    if context.is_logged(level) {
+        context.log(
+            level,
+            &format_string(func_name, context, template, &args[1..])?,
+        );
+    }
+
+    Ok(Value::NIL)
+}
+
+/// A report on `value`, for `describe`: how it's printed and its type, then
+/// whatever else there is to know about that type of value, one thing per
+/// line. (The source of functions isn't kept, so their body is shown as it
+/// was read.)
+fn describe(context: &Context, value: &Value) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "{}", context.display(value));
+    let _ = writeln!(report, "  type: {}", context.type_name(value));
+
+    match value {
+        Value::Lambda(lambda) | Value::Macro(lambda) => {
+            if let Some(name) = &lambda.name {
+                let _ = writeln!(report, "  name: {}", name);
+            }
+
+            let params = lambda.params();
+            let _ = match params.is_empty() {
+                true => writeln!(report, "  parameters: ()"),
+                false => writeln!(report, "  parameters: {}", params),
+            };
+
+            if let Some(doc) = lambda.docstring() {
+                let _ = writeln!(report, "  documentation: {}", doc);
+            }
+
+            report.push_str("  body:\n");
+            for expr in lambda.code() {
+                let _ = writeln!(report, "    {}", context.display(expr));
+            }
+        }
+        Value::NativeFunc(func) => {
+            if let Some(name) = context.native_name(*func) {
+                let _ = writeln!(report, "  name: {}", name);
+            }
+        }
+        Value::List(list) => {
+            let _ = match list.checked_len() {
+                Ok(len) => writeln!(report, "  length: {}", len),
+                Err(err) => writeln!(report, "  length: unknown ({})", err.msg),
+            };
+        }
+        Value::String(string) => {
+            let _ = writeln!(
+                report,
+                "  length: {} chars, {} bytes",
+                string.chars().count(),
+                string.len()
+            );
+        }
+        _ => {}
+    }
+
+    report
+}
+
+/// Append a string or char, or a formatted number, to `buffer`
+fn append_piece(func_name: &str, buffer: &mut String, piece: &Value) -> Result<(), RuntimeError> {
+    match piece {
+        Value::String(piece) => buffer.push_str(piece),
+        Value::Char(piece) => buffer.push(*piece),
+        Value::Int(piece) => {
+            let _ = write!(buffer, "{}", piece);
+        }
+        Value::Float(piece) => {
+            let _ = write!(buffer, "{}", piece);
+        }
+        _ => {
+            return Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!(
+                    "Function \"{}\" requires arguments to be numbers or strings; found {}",
+                    func_name, piece
+                ),
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+/// The error for an arithmetic function whose operation failed.
+/// `wrong_types` describes what the function expects its arguments to be.
+fn arithmetic_error(func_name: &str, err: ArithmeticError, wrong_types: String) -> RuntimeError {
+    match err {
+        ArithmeticError::WrongTypes => RuntimeError::with_kind(ErrorKind::TypeError, wrong_types),
+        ArithmeticError::Overflow => {
+            RuntimeError::new(format!("Integer overflow in \"{}\"", func_name))
+        }
+        ArithmeticError::DivideByZero => {
+            RuntimeError::new(format!("Division by zero in \"{}\"", func_name))
+        }
+    }
+}
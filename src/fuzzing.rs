@@ -0,0 +1,110 @@
+//! [`proptest`] strategies for testing the interpreter with random inputs:
+//! small programs from a constrained grammar, and `Value` trees (through
+//! `Arbitrary`). Everything generated is bounded in size and depth, and the
+//! programs always terminate, so a failure points at a real bug rather than a
+//! runaway test.
+
+use proptest::{
+    arbitrary::Arbitrary,
+    collection::vec,
+    prelude::*,
+    sample::select,
+    strategy::{BoxedStrategy, Strategy},
+};
+
+use crate::model::{FloatType, IntType, Symbol, Value};
+
+/// The variables generated programs use (which may or may not be bound)
+const VARIABLES: &[&str] = &["a", "b", "c"];
+
+const UNARY: &[&str] = &["car", "cdr", "length", "reverse", "is_null", "not"];
+
+const BINARY: &[&str] = &[
+    "+", "-", "*", "/", "<", "<=", "==", "!=", "cons", "nth", "and", "or",
+];
+
+/// A program made of arithmetic, `let`, `if`, `lambda` and list operations.
+/// It may fail with a runtime error (by calling `car` on an int, say), but
+/// evaluating it always finishes.
+///
+/// It starts by defining a tail-recursive `count_down` function, which the rest
+/// of the program may call with a small count.
+pub fn program() -> impl Strategy<Value = String> {
+    (expression(false), expression(true)).prop_map(|(step, body)| {
+        format!(
+            "(defun count_down (a b) (if (<= a 0) b (count_down (- a 1) {})))\n{}",
+            step, body
+        )
+    })
+}
+
+/// A single expression from the grammar for [`program()`], with calls to
+/// `count_down` if `with_calls`
+pub fn expression(with_calls: bool) -> BoxedStrategy<String> {
+    let leaf = prop_oneof![
+        (-20i8..20).prop_map(|n| n.to_string()),
+        (-20i8..20, 1u16..8).prop_map(|(whole, eighths)| format!("{}.{}", whole, eighths * 125)),
+        "[a-z ]{0,4}".prop_map(|s| format!("\"{}\"", s)),
+        select(&["T", "F", "NIL", "'(1 2 3)"][..]).prop_map(String::from),
+        select(VARIABLES).prop_map(String::from),
+    ];
+
+    leaf.prop_recursive(5, 64, 4, move |expr| {
+        let call = if with_calls {
+            (0u8..50, expr.clone())
+                .prop_map(|(n, acc)| format!("(count_down {} {})", n, acc))
+                .boxed()
+        } else {
+            expr.clone()
+        };
+
+        prop_oneof![
+            (select(UNARY), expr.clone()).prop_map(|(op, a)| format!("({} {})", op, a)),
+            (select(BINARY), expr.clone(), expr.clone())
+                .prop_map(|(op, a, b)| format!("({} {} {})", op, a, b)),
+            (expr.clone(), expr.clone(), expr.clone())
+                .prop_map(|(cond, then, other)| format!("(if {} {} {})", cond, then, other)),
+            (select(VARIABLES), expr.clone(), expr.clone())
+                .prop_map(|(var, value, body)| format!("(let (({} {})) {})", var, value, body)),
+            (select(VARIABLES), expr.clone(), expr.clone())
+                .prop_map(|(var, body, arg)| format!("((lambda ({}) {}) {})", var, body, arg)),
+            (select(VARIABLES), expr.clone(), expr.clone()).prop_map(|(var, body, list)| {
+                format!("(map (lambda ({}) {}) {})", var, body, list)
+            }),
+            vec(expr.clone(), 0..4).prop_map(|items| format!("(list {})", items.join(" "))),
+            call,
+        ]
+    })
+    .boxed()
+}
+
+/// Data values (ints, floats, strings, chars, symbols, booleans, bytes and
+/// lists of them) which print as source that parses back to an equal value:
+/// floats always have a fractional part, strings don't contain `"`, and
+/// symbols can't be mistaken for anything else.
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Value>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let leaf = prop_oneof![
+            any::<i8>().prop_map(|n| Value::Int(IntType::from(n))),
+            (any::<i16>(), 1u8..8).prop_map(|(whole, eighths)| Value::Float(
+                FloatType::from(whole) + FloatType::from(eighths) / 8.0
+            )),
+            "[^\"]{0,12}".prop_map(Value::String),
+            any::<char>().prop_map(Value::Char),
+            "[a-z_:][a-z0-9_]{1,8}"
+                .prop_filter("symbols mustn't read as NIL", |name| name != "nil")
+                .prop_map(|name| Value::Symbol(Symbol::from(name))),
+            Just(Value::True),
+            Just(Value::False),
+            vec(any::<u8>(), 0..8).prop_map(Value::from),
+        ];
+
+        leaf.prop_recursive(4, 64, 8, |value| {
+            vec(value, 0..8).prop_map(|items| Value::List(items.iter().collect()))
+        })
+        .boxed()
+    }
+}
@@ -1,50 +1,144 @@
 use crate::{
-    model::{Env, Lambda, List, RuntimeError, Symbol, Value},
-    utils::{require_arg, require_typed_arg},
+    collections::{HashMap, HashSet},
+    model::{
+        ClosureCapture, Combinator, DoLoop, DoStage, Env, ErrorKind, ForLoop, ForStage, Frame,
+        Generator, GeneratorState, Lambda, List, Promise, RuntimeError, SpecialFormHandler, Symbol,
+        Value, CANCELLED,
+    },
+    syntax_rules::make_syntax_rules,
+    utils::{keyword_args, require_arg, require_typed_arg},
 };
-use std::{cell::RefCell, rc::Rc};
+use alloc::{borrow::ToOwned, rc::Rc, string::String, vec::Vec};
+use core::cell::RefCell;
 
 /// Evaluate a single Lisp expression in the context of a given environment.
 pub fn eval(env: Rc<RefCell<Env>>, expression: &Value) -> Result<Value, RuntimeError> {
-    eval_inner(env, expression, Context::new())
+    let context = env.borrow().context().clone();
+
+    context.top_level(|| eval_inner(env, expression, Context::new()))
 }
 
 /// Evaluate a series of s-expressions. Each expression is evaluated in
-/// order and the final one's return value is returned.
+/// order and the final one's return value is returned. An empty series (like
+/// the forms parsed from a file of nothing but comments) evaluates to NIL.
 pub fn eval_block(
     env: Rc<RefCell<Env>>,
     clauses: impl Iterator<Item = Value>,
 ) -> Result<Value, RuntimeError> {
-    eval_block_inner(env, clauses, Context::new())
+    let context = env.borrow().context().clone();
+
+    context.top_level(|| {
+        let mut result = Value::NIL;
+        for clause_result in eval_each(env, clauses) {
+            result = clause_result?;
+        }
+
+        Ok(result)
+    })
 }
 
-fn eval_block_inner(
+/// Evaluate a series of s-expressions one at a time, as the returned iterator
+/// is advanced, yielding each one's result. Each is evaluated on its own, like
+/// with `eval()`, so an error doesn't stop the rest: the caller decides
+/// whether to carry on by asking for the next result or not.
+///
+/// ```
+/// use rust_lisp::{default_env, interpreter::eval_each, parser::parse};
+///
+/// let env = default_env().into_shared();
+/// let exprs = parse("(define x 1) (car x) (+ x 1)").map(|expr| expr.unwrap());
+///
+/// let results = eval_each(env, exprs).collect::<Vec<_>>();
+/// assert!(results[1].is_err());
+/// assert_eq!(results[2].as_ref().unwrap().to_string(), "2");
+/// ```
+pub fn eval_each(
     env: Rc<RefCell<Env>>,
     clauses: impl Iterator<Item = Value>,
+) -> impl Iterator<Item = Result<Value, RuntimeError>> {
+    clauses.map(move |clause| eval(env.clone(), &clause))
+}
+
+/// Parse all of the forms in `source` and evaluate them in order, like
+/// `eval_block()`, returning the last one's value. It's parsed with the
+/// environment's parser, so any extensions to the syntax apply, and nothing
+/// is evaluated unless all of it parses.
+///
+/// ```
+/// use rust_lisp::{default_env, eval_str, lisp};
+///
+/// let env = default_env().into_shared();
+///
+/// let result = eval_str(env, "(define x 20) (+ x 22)");
+/// assert_eq!(result, Ok(lisp! { 42 }));
+/// ```
+///
+/// A parse error is returned as a RuntimeError, rendered with where it is:
+///
+/// ```
+/// use rust_lisp::{default_env, eval_str};
+///
+/// let env = default_env().into_shared();
+///
+/// let err = eval_str(env, "(+ 1 2)\n(print (+ 3 4)").unwrap_err();
+/// assert!(err.msg.starts_with("Parse error: Unclosed list"), "{}", err.msg);
+/// assert!(err.msg.contains("<source>:2:1"), "{}", err.msg);
+/// ```
+pub fn eval_str(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    let parser = env.borrow().context().parser.borrow().clone();
+    let exprs = parser
+        .parse(source)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| {
+            RuntimeError::with_kind(
+                ErrorKind::ParseError,
+                err.render(source, "<source>").trim_end().to_owned(),
+            )
+        })?;
+
+    eval_block(env, exprs.into_iter())
+}
+
+/// Evaluate a single Lisp expression, like `eval()`, and also return any
+/// warnings recorded while it was evaluated. Evaluation continues past a
+/// warning, so they're returned whether or not it succeeded.
+pub fn eval_with_warnings(
+    env: Rc<RefCell<Env>>,
+    expression: &Value,
+) -> (Result<Value, RuntimeError>, Vec<String>) {
+    let start = env.borrow().context().warnings.borrow().len();
+    let result = eval(env.clone(), expression);
+
+    let env = env.borrow();
+    let mut warnings = env.context().warnings.borrow_mut();
+    let start = start.min(warnings.len());
+
+    (result, warnings.split_off(start))
+}
+
+/// Evaluate a series of expressions, which can be borrowed (like the body of
+/// a lambda) rather than owned, so they don't need to be cloned to be run
+fn eval_block_inner<V: core::borrow::Borrow<Value>>(
+    env: Rc<RefCell<Env>>,
+    clauses: impl Iterator<Item = V>,
     context: Context,
 ) -> Result<Value, RuntimeError> {
-    let mut current_expr: Option<Value> = None;
+    let mut clauses = clauses.peekable();
 
-    for clause in clauses {
-        if let Some(expr) = current_expr {
-            match eval_inner(env.clone(), &expr, context.found_tail(true)) {
-                Ok(_) => (),
-                Err(e) => {
-                    return Err(e);
-                }
-            }
+    while let Some(clause) = clauses.next() {
+        // the last clause takes the place of the whole block, so it's in the
+        // tail position if the block is
+        if clauses.peek().is_none() {
+            return eval_inner(env, clause.borrow(), context);
         }
 
-        current_expr = Some(clause);
+        // the others can't be tail calls, and an error in any of them stops
+        // the block there
+        eval_inner(env.clone(), clause.borrow(), context.found_tail(true))?;
     }
 
-    if let Some(expr) = &current_expr {
-        eval_inner(env, expr, context)
-    } else {
-        Err(RuntimeError {
-            msg: "Unrecognized expression".to_owned(),
-        })
-    }
+    // like in most lisps, an empty body evaluates to NIL
+    Ok(Value::NIL)
 }
 
 /// `found_tail` and `in_func` are used when locating the tail position for
@@ -61,16 +155,19 @@ fn eval_inner(
 ) -> Result<Value, RuntimeError> {
     if context.quoting {
         match expression {
-            Value::List(list) if *list != List::NIL => match &list.car()? {
-                Value::Symbol(Symbol(keyword)) if keyword == "comma" => {
+            Value::List(list) if *list != List::NIL => match special_form(list) {
+                Some(SpecialForm::Comma) => {
                     // do nothing, handle it down below
                 }
                 _ => {
-                    return list
-                        .into_iter()
-                        .map(|el| eval_inner(env.clone(), &el, context))
-                        .collect::<Result<List, RuntimeError>>()
-                        .map(Value::List);
+                    // copied cell by cell, so a template with datum labels
+                    // keeps its shape (and one that contains itself ends)
+                    return expression.map_graph(|value| match value {
+                        Value::List(list) if is_comma(list) => {
+                            eval_inner(env.clone(), value, context.found_tail(true)).map(Some)
+                        }
+                        _ => Ok(None),
+                    });
                 }
             },
             _ => return Ok(expression.clone()),
@@ -79,50 +176,80 @@ fn eval_inner(
 
     match expression {
         // look up symbol
-        Value::Symbol(symbol) => env.borrow().get(symbol).ok_or_else(|| RuntimeError {
-            msg: format!("\"{}\" is not defined", symbol),
-        }),
+        Value::Symbol(symbol) => lookup(&env, symbol),
+
+        // the empty combination isn't a call: `()` is NIL, which evaluates to
+        // itself like any other plain value
+        Value::List(list) if *list == List::NIL => Ok(Value::NIL),
 
         // s-expression
-        Value::List(list) if *list != List::NIL => {
-            match &list.car()? {
+        Value::List(list) => {
+            check_cancelled(&env)?;
+            let _nesting = env.borrow().context().nest()?;
+
+            // a registered form is only ever called, never deferred, so it
+            // doesn't touch the tail-call bookkeeping of the built-in ones
+            if let Some(handler) = registered_form(&env, list) {
+                return handler(env, list.cdr().into_iter().collect());
+            }
+
+            let form = special_form(list);
+            let keyword = form.map(SpecialForm::keyword).unwrap_or_default();
+
+            match form {
                 // special forms
-                Value::Symbol(Symbol(keyword)) if keyword == "comma" => {
+                Some(SpecialForm::Comma) => {
                     eval_inner(env, &list.cdr().car()?, context.quoting(false))
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "quote" => {
-                    eval_inner(env, &list.cdr().car()?, context.quoting(true))
+                Some(SpecialForm::Quote) => {
+                    let template = list.cdr().car()?;
+                    let template = auto_gensyms(&env, &template).unwrap_or(template);
+
+                    eval_inner(env, &template, context.quoting(true))
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "define" || keyword == "set" => {
+                Some(SpecialForm::Define | SpecialForm::Set) => {
                     let args = &list.cdr().into_iter().collect::<Vec<Value>>();
 
                     let symbol = require_typed_arg::<&Symbol>(keyword, args, 0)?;
                     let value_expr = require_arg(keyword, args, 1)?;
 
-                    let value = eval_inner(env.clone(), value_expr, context.found_tail(true))?;
+                    if keyword == "define" {
+                        env.borrow().check_definition(keyword, symbol)?;
+                    } else {
+                        env.borrow().check_set(keyword, symbol)?;
+                    }
+
+                    let value = eval_inner(env.clone(), value_expr, context.found_tail(true))?
+                        .into_single();
 
                     if keyword == "define" {
+                        let value = value.named(symbol);
                         env.borrow_mut().define(symbol.clone(), value.clone());
+
+                        Ok(value)
                     } else {
                         env.borrow_mut().set(symbol.clone(), value.clone())?;
-                    }
 
-                    Ok(value)
+                        Ok(value)
+                    }
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "defmacro" => {
+                Some(SpecialForm::Defmacro) => {
                     let args = &list.cdr().into_iter().collect::<Vec<Value>>();
 
                     let symbol = require_typed_arg::<&Symbol>(keyword, args, 0)?;
+                    env.borrow().check_definition(keyword, symbol)?;
                     let argnames_list = require_typed_arg::<&List>(keyword, args, 1)?;
-                    let argnames = value_to_argnames(argnames_list.clone())?;
-                    let body = Rc::new(Value::List(list.cdr().cdr().cdr()));
+                    let (argnames, key_defaults) = value_to_argnames(&env, argnames_list.clone())?;
+                    let body = list.cdr().cdr().cdr().into_iter().collect();
 
                     let lambda = Value::Macro(Lambda {
+                        name: Some(symbol.clone()),
                         closure: env.clone(),
                         argnames,
+                        key_defaults,
                         body,
                     });
 
@@ -131,171 +258,379 @@ fn eval_inner(
                     Ok(Value::NIL)
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "defun" => {
+                Some(SpecialForm::DefineSyntax) => {
+                    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+                    let symbol = require_typed_arg::<&Symbol>(keyword, args, 0)?;
+                    env.borrow().check_definition(keyword, symbol)?;
+                    let rules = require_arg(keyword, args, 1)?;
+
+                    let syntax = make_syntax_rules(env.clone(), symbol, rules)?;
+                    env.borrow_mut().define(symbol.clone(), syntax);
+
+                    Ok(Value::NIL)
+                }
+
+                Some(SpecialForm::Defun) => {
                     let args = &list.cdr().into_iter().collect::<Vec<Value>>();
 
                     let symbol = require_typed_arg::<&Symbol>(keyword, args, 0)?;
+                    env.borrow().check_definition(keyword, symbol)?;
                     let argnames_list = require_typed_arg::<&List>(keyword, args, 1)?;
-                    let argnames = value_to_argnames(argnames_list.clone())?;
-                    let body = Rc::new(Value::List(list.cdr().cdr().cdr()));
+                    let (argnames, key_defaults) = value_to_argnames(&env, argnames_list.clone())?;
+                    let body = list.cdr().cdr().cdr().into_iter().collect();
 
-                    let lambda = Value::Lambda(Lambda {
+                    let lambda = Value::Lambda(capture(Lambda {
+                        name: Some(symbol.clone()),
                         closure: env.clone(),
                         argnames,
+                        key_defaults,
                         body,
-                    });
+                    }));
 
                     env.borrow_mut().define(symbol.clone(), lambda);
 
                     Ok(Value::NIL)
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "lambda" => {
+                Some(SpecialForm::Lambda) => {
                     let args = &list.cdr().into_iter().collect::<Vec<Value>>();
 
                     let argnames_list = require_typed_arg::<&List>(keyword, args, 0)?;
-                    let argnames = value_to_argnames(argnames_list.clone())?;
-                    let body = Rc::new(Value::List(list.cdr().cdr()));
+                    let (argnames, key_defaults) = value_to_argnames(&env, argnames_list.clone())?;
+                    let body = list.cdr().cdr().into_iter().collect();
 
-                    Ok(Value::Lambda(Lambda {
+                    Ok(Value::Lambda(capture(Lambda {
+                        name: None,
                         closure: env,
                         argnames,
+                        key_defaults,
                         body,
-                    }))
+                    })))
+                }
+
+                Some(SpecialForm::CaseLambda) => {
+                    let clauses = list
+                        .cdr()
+                        .into_iter()
+                        .map(|clause| {
+                            let parts = match &clause {
+                                Value::List(clause) => match clause.car() {
+                                    Ok(Value::List(argnames_list)) => {
+                                        Some((argnames_list, clause.cdr()))
+                                    }
+                                    _ => None,
+                                },
+                                _ => None,
+                            };
+
+                            let Some((argnames_list, body)) = parts else {
+                                return Err(RuntimeError::new(format!(
+                                    "Expected a (params body...) clause in case_lambda, found {}",
+                                    clause
+                                )));
+                            };
+                            let (argnames, key_defaults) = value_to_argnames(&env, argnames_list)?;
+
+                            Ok(capture(Lambda {
+                                name: None,
+                                closure: env.clone(),
+                                argnames,
+                                key_defaults,
+                                body: body.into_iter().collect(),
+                            }))
+                        })
+                        .collect::<Result<Vec<Lambda>, RuntimeError>>()?;
+
+                    Ok(case_lambda(clauses))
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "let" => {
-                    let let_env = Rc::new(RefCell::new(Env::extend(env)));
+                Some(SpecialForm::Let) => {
+                    let let_env = Env::extend_local(env).into_shared();
 
                     let args = &list.cdr().into_iter().collect::<Vec<Value>>();
 
                     let declarations = require_typed_arg::<&List>(keyword, args, 0)?;
+                    check_let_names(declarations)?;
 
                     for decl in declarations.into_iter() {
                         let decl = &decl;
 
-                        let decl_cons: &List = decl.try_into().map_err(|_| RuntimeError {
-                            msg: format!("Expected declaration clause, found {}", decl),
+                        let decl_cons: &List = decl.try_into().map_err(|_| {
+                            RuntimeError::new(format!(
+                                "Expected declaration clause, found {}",
+                                decl
+                            ))
                         })?;
                         let symbol = &decl_cons.car()?;
-                        let symbol: &Symbol = symbol.try_into().map_err(|_| RuntimeError {
-                            msg: format!("Expected symbol for let declaration, found {}", symbol),
+                        let symbol: &Symbol = symbol.try_into().map_err(|_| {
+                            RuntimeError::new(format!(
+                                "Expected symbol for let declaration, found {}",
+                                symbol
+                            ))
                         })?;
                         let expr = &decl_cons.cdr().car()?;
 
                         let result = eval_inner(let_env.clone(), expr, context.found_tail(true))?;
-                        let_env.borrow_mut().define(symbol.clone(), result);
+                        let_env
+                            .borrow_mut()
+                            .define(symbol.clone(), result.into_single());
                     }
 
                     let body = &Value::List(list.cdr().cdr());
-                    let body: &List = body.try_into().map_err(|_| RuntimeError {
-                        msg: format!(
+                    let body: &List = body.try_into().map_err(|_| {
+                        RuntimeError::new(format!(
                             "Expected expression(s) after let-declarations, found {}",
                             body
-                        ),
+                        ))
                     })?;
 
+                    declare_internal_defines(&let_env, body);
                     eval_block_inner(let_env, body.into_iter(), context)
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "begin" => {
-                    eval_block_inner(env, list.cdr().into_iter(), context)
+                Some(SpecialForm::LetValues) => {
+                    let let_env = Env::extend_local(env).into_shared();
+
+                    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+                    let clauses = require_typed_arg::<&List>(keyword, args, 0)?;
+
+                    for clause in clauses.into_iter() {
+                        let (names, expr) = let_values_clause(&clause)?;
+
+                        let values = eval_inner(let_env.clone(), &expr, context.found_tail(true))?;
+                        bind_values(&let_env, &names, values)?;
+                    }
+
+                    let body = list.cdr().cdr();
+
+                    declare_internal_defines(&let_env, &body);
+                    eval_block_inner(let_env, body.into_iter(), context)
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "cond" => {
+                Some(SpecialForm::Begin) => eval_block_inner(env, list.cdr().into_iter(), context),
+
+                Some(SpecialForm::Cond) => {
                     let clauses = list.cdr();
 
                     for clause in clauses.into_iter() {
                         let clause = &clause;
 
-                        let clause: &List = clause.try_into().map_err(|_| RuntimeError {
-                            msg: format!("Expected conditional clause, found {}", clause),
+                        let clause: &List = clause.try_into().map_err(|_| {
+                            RuntimeError::new(format!(
+                                "Expected conditional clause, found {}",
+                                clause
+                            ))
                         })?;
 
                         let condition = &clause.car()?;
-                        let then = &clause.cdr().car()?;
 
-                        if eval_inner(env.clone(), condition, context.found_tail(true))?.into() {
-                            return eval_inner(env, then, context);
+                        let condition =
+                            eval_inner(env.clone(), condition, context.found_tail(true))?;
+
+                        if is_truthy(&env, &condition) {
+                            return eval_block_inner(env, clause.cdr().into_iter(), context);
                         }
                     }
 
                     Ok(Value::NIL)
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "if" => {
+                Some(SpecialForm::If) => {
                     let args = &list.cdr().into_iter().collect::<Vec<Value>>();
 
                     let condition = require_arg(keyword, args, 0)?;
                     let then_expr = require_arg(keyword, args, 1)?;
                     let else_expr = require_arg(keyword, args, 2).ok();
 
-                    if eval_inner(env.clone(), condition, context.found_tail(true))?.into() {
+                    let condition = eval_inner(env.clone(), condition, context.found_tail(true))?;
+
+                    if is_truthy(&env, &condition) {
                         eval_inner(env, then_expr, context)
                     } else {
                         else_expr
-                            .map(|expr| eval_inner(env, &expr, context))
+                            .map(|expr| eval_inner(env, expr, context))
                             .unwrap_or(Ok(Value::NIL))
                     }
                 }
 
-                Value::Symbol(Symbol(keyword)) if keyword == "and" || keyword == "or" => {
+                Some(SpecialForm::And | SpecialForm::Or) => {
                     let args = &list.cdr().into_iter().collect::<Vec<Value>>();
-                    let is_or = keyword.as_str() == "or";
+                    let is_or = keyword == "or";
+
+                    let (last, rest) = match args.split_last() {
+                        Some(split) => split,
+                        // there were zero arguments
+                        None => return Ok((!is_or).into()),
+                    };
 
-                    let mut last_result: Option<Value> = None;
-                    for arg in args {
+                    for arg in rest {
                         let result = eval_inner(env.clone(), arg, context.found_tail(true))?;
-                        let truthy: bool = (&result).into();
 
-                        if is_or == truthy {
+                        if is_or == is_truthy(&env, &result) {
                             return Ok(result);
                         }
+                    }
+
+                    // the last argument's value is the result either way, so
+                    // it's in tail position
+                    eval_inner(env, last, context)
+                }
+
+                Some(SpecialForm::Delay) => {
+                    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+                    let expr = require_arg(keyword, args, 0)?;
+
+                    Ok(Value::Promise(Rc::new(RefCell::new(Promise::Delayed {
+                        expr: expr.clone(),
+                        env,
+                    }))))
+                }
+
+                Some(SpecialForm::Yield) => Err(RuntimeError::new(YIELD_OUTSIDE_GENERATOR)),
+
+                Some(SpecialForm::Splice) => Err(RuntimeError::new(
+                    "\"splice\" can only be used as an argument of a function call".to_owned(),
+                )),
+
+                Some(SpecialForm::For) => {
+                    let for_loop = for_loop(keyword, list)?;
+
+                    let mut results = Vec::new();
+                    eval_for(env, &for_loop, &for_loop.clauses, &mut results)?;
+
+                    Ok(Value::List(List::from_vec(results)))
+                }
+
+                Some(SpecialForm::Do) => {
+                    let do_loop = do_loop(keyword, list)?;
+
+                    // the initial values are evaluated outside of the loop
+                    let mut values = do_loop
+                        .clauses
+                        .iter()
+                        .map(|(_, init, _)| eval_inner(env.clone(), init, context.found_tail(true)))
+                        .collect::<Result<Vec<Value>, RuntimeError>>()?;
+
+                    loop {
+                        // each iteration gets its own bindings, so a closure
+                        // made in the body keeps the values it was made with
+                        let mut iteration = Env::extend_local(env.clone());
+                        for ((name, ..), value) in do_loop.clauses.iter().zip(values) {
+                            iteration.define(name.clone(), value);
+                        }
+                        let iteration = iteration.into_shared();
 
-                        last_result = Some(result);
+                        // the test and steps might not call anything
+                        check_cancelled(&iteration)?;
+
+                        let done =
+                            eval_inner(iteration.clone(), &do_loop.test, context.found_tail(true))?;
+                        if is_truthy(&iteration, &done) {
+                            return eval_block_inner(iteration, do_loop.exit.into_iter(), context);
+                        }
+
+                        for form in &do_loop.body {
+                            eval_inner(iteration.clone(), &form, context.found_tail(true))?;
+                        }
+
+                        // every step sees this iteration's values, so they're
+                        // updated all at once, and a variable without a step
+                        // keeps its value (including any `set` in the body)
+                        values = do_loop
+                            .clauses
+                            .iter()
+                            .map(|(name, _, step)| match step {
+                                Some(step) => {
+                                    eval_inner(iteration.clone(), step, context.found_tail(true))
+                                }
+                                None => lookup(&iteration, name),
+                            })
+                            .collect::<Result<Vec<Value>, RuntimeError>>()?;
                     }
+                }
 
-                    Ok(if let Some(last_result) = last_result {
-                        last_result
+                Some(form @ (SpecialForm::Trace | SpecialForm::Untrace)) => {
+                    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+                    let names = (0..args.len())
+                        .map(|index| require_typed_arg::<&Symbol>(keyword, args, index).cloned())
+                        .collect::<Result<Vec<Symbol>, RuntimeError>>()?;
+
+                    let context = env.borrow().context().clone();
+                    let mut traced = context.traced.borrow_mut();
+
+                    if form == SpecialForm::Trace {
+                        traced.extend(names);
+                    } else if names.is_empty() {
+                        traced.clear();
                     } else {
-                        // there were zero arguments
-                        (!is_or).into()
-                    })
+                        for name in &names {
+                            traced.remove(name);
+                        }
+                    }
+
+                    // like in Common Lisp, the result is the names of all the
+                    // functions being traced
+                    let mut names = traced.iter().cloned().collect::<Vec<Symbol>>();
+                    names.sort_by(|a, b| a.0.cmp(&b.0));
+
+                    Ok(Value::List(names.into_iter().map(Value::Symbol).collect()))
                 }
 
                 // function call or macro expand
-                _ => {
-                    let mut func_or_macro =
-                        eval_inner(env.clone(), &list.car()?, context.found_tail(true))?;
+                None => {
+                    // the function is usually named by a symbol, which can be
+                    // looked up without cloning it out of the list first
+                    let func_or_macro = match list
+                        .with_car(|car| match car {
+                            Value::Symbol(symbol) => Some(lookup(&env, symbol)),
+                            _ => None,
+                        })
+                        .flatten()
+                    {
+                        Some(func_or_macro) => func_or_macro?,
+                        None => eval_inner(env.clone(), &list.car()?, context.found_tail(true))?,
+                    };
 
                     if matches!(func_or_macro, Value::Macro(_)) {
                         let args = list.into_iter().skip(1).collect::<Vec<Value>>();
 
-                        let expanded =
-                            call_function_or_macro(env.clone(), &mut func_or_macro, args)?;
+                        let expanded = expand_macro(&env, &func_or_macro, args)?;
 
                         eval_inner(env.clone(), &expanded, Context::new())
                     } else {
-                        let args = list
-                            .into_iter()
-                            .skip(1)
-                            .map(|car| eval_inner(env.clone(), &car, context.found_tail(true)))
-                            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+                        let mut args = Vec::new();
+                        for arg_expr in list.into_iter().skip(1) {
+                            let spliced = splice_arg(&arg_expr);
+                            let value = eval_inner(
+                                env.clone(),
+                                spliced.as_ref().unwrap_or(&arg_expr),
+                                context.found_tail(true),
+                            )?;
+
+                            push_arg(&mut args, value, spliced.is_some())?;
+                        }
 
+                        // native functions don't recurse into the
+                        // evaluator, and need to be called with the
+                        // environment they were called from (for
+                        // `current_env`, etc), so only lambdas are deferred.
+                        // A tail call that a native function hands back
+                        // (like `partial` or `case_lambda` do) is left to
+                        // the caller's trampoline, though.
                         if !context.found_tail && context.in_func {
-                            Ok(Value::TailCall {
-                                func: Rc::new(func_or_macro),
-                                args,
-                            })
-                        } else {
-                            let mut res =
-                                call_function_or_macro(env.clone(), &mut func_or_macro, args);
-
-                            while let Ok(Value::TailCall { func, args }) = res {
-                                res = call_function_or_macro(env.clone(), func.as_ref(), args);
+                            if matches!(func_or_macro, Value::Lambda(_)) {
+                                Ok(Value::TailCall {
+                                    func: Rc::new(func_or_macro),
+                                    args,
+                                })
+                            } else {
+                                call_function_or_macro(env.clone(), &func_or_macro, args)
                             }
-
-                            res
+                        } else {
+                            call_function(env.clone(), &func_or_macro, args)
                         }
                     }
                 }
@@ -308,21 +643,252 @@ fn eval_inner(
 }
 // 🦀 Boo! Did I scare ya? Haha!
 
-fn value_to_argnames(argnames: List) -> Result<Vec<Symbol>, RuntimeError> {
-    argnames
+/// Whether `value` counts as true, under the truthiness set for `env`'s
+/// interpreter
+pub(crate) fn is_truthy(env: &Rc<RefCell<Env>>, value: &Value) -> bool {
+    env.borrow().context().is_truthy(value)
+}
+
+/// Count a step of evaluation (see `Context::steps()`), and stop with an
+/// error if the interpreter's evaluation has been cancelled, through a
+/// `CancelHandle`, or a `with_timeout` deadline or `with_step_limit` limit
+/// has passed
+fn check_cancelled(env: &Rc<RefCell<Env>>) -> Result<(), RuntimeError> {
+    let env = env.borrow();
+    let context = env.context();
+
+    if context.cancel.is_cancelled() {
+        return Err(RuntimeError::with_kind(ErrorKind::Interrupted, CANCELLED));
+    }
+
+    context.take_step()?;
+
+    #[cfg(feature = "std")]
+    context.check_deadlines()?;
+
+    Ok(())
+}
+
+/// The value of a symbol: keywords evaluate to themselves, and anything else
+/// is looked up in the environment
+pub(crate) fn lookup(env: &Rc<RefCell<Env>>, symbol: &Symbol) -> Result<Value, RuntimeError> {
+    if symbol.is_keyword() {
+        return Ok(Value::Symbol(symbol.clone()));
+    }
+
+    let env = env.borrow();
+
+    env.get(symbol).ok_or_else(|| {
+        RuntimeError::new(if env.is_unassigned(symbol) {
+            format!("\"{}\" was used before it was defined", symbol)
+        } else if is_special_form(&symbol.0) {
+            format!("\"{}\" is a special form, which can only be called", symbol)
+        } else {
+            format!("\"{}\" is not defined", symbol)
+        })
+    })
+}
+
+/// The names defined by the run of `define` (and `defun`) forms at the start
+/// of a body
+pub(crate) fn internal_defines(
+    body: impl IntoIterator<Item = impl core::borrow::Borrow<Value>>,
+) -> Vec<Symbol> {
+    let mut names = Vec::new();
+
+    for form in body {
+        let Value::List(form) = form.borrow() else {
+            break;
+        };
+        let is_define = form
+            .with_car(|head| {
+                matches!(head, Value::Symbol(Symbol(head)) if matches!(&**head, "define" | "defun"))
+            })
+            .unwrap_or(false);
+
+        match form.cdr().car() {
+            Ok(Value::Symbol(name)) if is_define => names.push(name),
+            _ => break,
+        }
+    }
+
+    names
+}
+
+/// Declare the names of a body's internal defines (see `internal_defines()`)
+/// in the environment it's about to be evaluated in, like Scheme's
+/// `letrec*`: they can all refer to each other, but using one before its
+/// definition has been evaluated is an error, instead of finding some other
+/// definition of the same name further out.
+pub(crate) fn declare_internal_defines(
+    env: &Rc<RefCell<Env>>,
+    body: impl IntoIterator<Item = impl core::borrow::Borrow<Value>>,
+) {
+    for name in internal_defines(body) {
+        env.borrow_mut().declare(name);
+    }
+}
+
+type KeyDefaults = Vec<(Symbol, Value)>;
+
+/// The names of a lambda's parameters, and the default value expressions of
+/// its `&key` parameters. These can be (in order) plain names, `&rest` and a
+/// name, and `&key` and any number of names or `(name default)` pairs.
+fn value_to_argnames(
+    env: &Rc<RefCell<Env>>,
+    argnames: List,
+) -> Result<(Vec<Symbol>, KeyDefaults), RuntimeError> {
+    let mut names = Vec::new();
+    let mut key_defaults = Vec::new();
+    let mut in_keys = false;
+
+    for (index, arg) in argnames.into_iter().enumerate() {
+        match arg {
+            Value::Symbol(s) => {
+                if &*s.0 == "&key" {
+                    if in_keys {
+                        return Err(RuntimeError::new("Expected &key at most once"));
+                    }
+                    in_keys = true;
+                } else if &*s.0 == "&rest" && in_keys {
+                    return Err(RuntimeError::new(
+                        "Expected &rest to come before &key".to_owned(),
+                    ));
+                } else if &*s.0 == "..." {
+                    env.borrow().context().warn(
+                        "The unnamed ... rest parameter is deprecated; use &rest and a name instead",
+                    );
+                }
+
+                names.push(s);
+            }
+            Value::List(pair) if in_keys => match (pair.car(), pair.cdr().car()) {
+                (Ok(Value::Symbol(name)), Ok(default)) if pair.cdr().cdr() == List::NIL => {
+                    names.push(name.clone());
+                    key_defaults.push((name, default));
+                }
+                _ => {
+                    return Err(RuntimeError::new(format!(
+                        "Expected a name or a (name default) pair after &key, but arg {} is {}",
+                        index, pair
+                    )))
+                }
+            },
+            _ => {
+                return Err(RuntimeError::new(format!(
+                    "Expected a symbol for parameter {}, found {}",
+                    index + 1,
+                    arg
+                )))
+            }
+        }
+    }
+
+    check_binding_names(
+        "parameter",
+        names
+            .iter()
+            .filter(|name| !matches!(&*name.0, "&rest" | "&key" | "...")),
+    )?;
+
+    if let Some(index) = names.iter().position(|arg| &*arg.0 == "&rest") {
+        let rest_end = names
+            .iter()
+            .position(|arg| &*arg.0 == "&key")
+            .unwrap_or(names.len());
+
+        if rest_end != index + 2 {
+            return Err(RuntimeError::new(
+                "Expected exactly one arg name after &rest".to_owned(),
+            ));
+        }
+    }
+
+    Ok((names, key_defaults))
+}
+
+/// Check the names declared by a `let`, before it binds any of them.
+/// Malformed declarations are skipped here, and reported when they're
+/// reached.
+pub(crate) fn check_let_names(declarations: &List) -> Result<(), RuntimeError> {
+    let names = declarations
         .into_iter()
-        .enumerate()
-        .map(|(index, arg)| match arg {
-            Value::Symbol(s) => Ok(s),
-            _ => Err(RuntimeError {
-                msg: format!(
-                    "Expected list of arg names, but arg {} is a {}",
-                    index,
-                    arg.type_name()
-                ),
-            }),
+        .filter_map(|decl| match decl {
+            Value::List(decl) => match decl.car() {
+                Ok(Value::Symbol(symbol)) => Some(symbol),
+                _ => None,
+            },
+            _ => None,
         })
-        .collect()
+        .collect::<Vec<Symbol>>();
+
+    check_binding_names("let binding", names.iter())
+}
+
+/// Make sure none of the names bound together are the same, and none of them
+/// are special forms (which couldn't be called if they were shadowed)
+fn check_binding_names<'a>(
+    kind: &str,
+    names: impl Iterator<Item = &'a Symbol> + Clone,
+) -> Result<(), RuntimeError> {
+    let mut seen = Vec::new();
+    let mut duplicates = Vec::new();
+    for name in names.clone() {
+        if seen.contains(&name) {
+            if !duplicates.contains(&name) {
+                duplicates.push(name);
+            }
+        } else {
+            seen.push(name);
+        }
+    }
+
+    if !duplicates.is_empty() {
+        return Err(RuntimeError::new(format!(
+            "Duplicate {} names: {}",
+            kind,
+            join_names(&duplicates)
+        )));
+    }
+
+    let special_forms = names
+        .filter(|name| SpecialForm::from_keyword(&name.0).is_some())
+        .collect::<Vec<&Symbol>>();
+
+    if !special_forms.is_empty() {
+        return Err(RuntimeError::new(format!(
+            "Special forms can't be used as {} names: {}",
+            kind,
+            join_names(&special_forms)
+        )));
+    }
+
+    Ok(())
+}
+
+fn join_names(names: &[&Symbol]) -> String {
+    names
+        .iter()
+        .map(|name| &*name.0)
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+/// Call a function (lisp or native) with some already-evaluated arguments,
+/// running any resulting tail-calls to completion. Native functions should
+/// use this to call functions they've been passed.
+pub fn call_function(
+    env: Rc<RefCell<Env>>,
+    func: &Value,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let mut res = call_function_or_macro(env.clone(), func, args);
+
+    while let Ok(Value::TailCall { func, args }) = res {
+        res = call_function_or_macro(env.clone(), func.as_ref(), args);
+    }
+
+    res
 }
 
 /// Calling a function is separated from the main `eval_inner()` function
@@ -332,11 +898,25 @@ fn call_function_or_macro(
     env: Rc<RefCell<Env>>,
     func: &Value,
     args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    if let Some(name) = traced_name(&env, func) {
+        return call_traced(env, name, func, args);
+    }
+
+    call_untraced(env, func, args)
+}
+
+fn call_untraced(
+    env: Rc<RefCell<Env>>,
+    func: &Value,
+    args: Vec<Value>,
 ) -> Result<Value, RuntimeError> {
     if let Value::NativeFunc(func) = func {
-        func(env, args)
+        call_native(|| func(env, args))
     } else if let Value::NativeClosure(closure) = func {
-        closure.borrow_mut()(env, args)
+        call_native(|| closure.borrow_mut()(env, args))
+    } else if let Value::Combinator(combinator) = func {
+        call_combinator(env, combinator, args)
     } else {
         let lambda = match func {
             Value::Lambda(lamb) => Some(lamb),
@@ -345,26 +925,13 @@ fn call_function_or_macro(
         };
 
         if let Some(lambda) = lambda {
-            // bind args
-            let mut arg_env = Env::extend(lambda.closure.clone());
-            for (index, arg_name) in lambda.argnames.iter().enumerate() {
-                if arg_name.0 == "..." {
-                    // rest parameters
-                    arg_env.define(
-                        Symbol::from("..."),
-                        Value::List(args.into_iter().skip(index).collect()),
-                    );
-                    break;
-                } else {
-                    arg_env.define(arg_name.clone(), args[index].clone());
-                }
-            }
+            let arg_env = bind_args(lambda, args)?;
 
             // evaluate each line of body
-            let clauses: &List = lambda.body.as_ref().try_into()?;
+            declare_internal_defines(&arg_env, lambda.body.iter());
             eval_block_inner(
-                Rc::new(RefCell::new(arg_env)),
-                clauses.into_iter(),
+                arg_env,
+                lambda.body.iter(),
                 Context {
                     found_tail: false,
                     in_func: true,
@@ -372,11 +939,1564 @@ fn call_function_or_macro(
                 },
             )
         } else {
-            Err(RuntimeError {
-                msg: format!("{} is not callable", func),
+            Err(not_callable(func))
+        }
+    }
+}
+
+/// Call a function made by one of the combinator builtins. The function it
+/// ends up calling is handed back as a tail call, so a chain of them (or
+/// recursion through one) doesn't grow the stack.
+fn call_combinator(
+    env: Rc<RefCell<Env>>,
+    combinator: &Combinator,
+    mut args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    match combinator {
+        Combinator::Partial {
+            func, args: prefix, ..
+        } => Ok(Value::TailCall {
+            func: Rc::new(func.value.clone()),
+            args: prefix.iter().cloned().chain(args).collect(),
+        }),
+        Combinator::Compose(funcs) => {
+            // the leftmost function is called last, with the result of the
+            // others
+            let (outer, inner) = funcs.split_first().unwrap();
+
+            for func in inner.iter().rev() {
+                args = vec![call_function(env.clone(), &func.value, args)?];
+            }
+
+            Ok(Value::TailCall {
+                func: Rc::new(outer.value.clone()),
+                args,
+            })
+        }
+        Combinator::Const(value) => Ok(value.clone()),
+        Combinator::Flip(func) => {
+            if args.len() < 2 {
+                return Err(RuntimeError::new(format!(
+                    "{} requires at least 2 arguments, but got {}",
+                    combinator,
+                    args.len()
+                )));
+            }
+
+            args.swap(0, 1);
+
+            Ok(Value::TailCall {
+                func: Rc::new(func.value.clone()),
+                args,
             })
         }
+        Combinator::Complement(func) => {
+            // the result is needed here, so this can't be a tail call
+            let result = call_function(env.clone(), &func.value, args)?;
+
+            Ok(Value::from(!is_truthy(&env, &result)))
+        }
+    }
+}
+
+/// Call a native function. With the `catch_panics` feature, a panic inside it
+/// is caught and turned into an error, instead of unwinding through the
+/// interpreter and into the host.
+#[cfg(feature = "catch_panics")]
+fn call_native(func: impl FnOnce() -> Result<Value, RuntimeError>) -> Result<Value, RuntimeError> {
+    // the interpreter's own state is only borrowed from `RefCell`s, which are
+    // released as the panic unwinds, so it's left consistent
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(func)).unwrap_or_else(|payload| {
+        let reason = payload
+            .downcast_ref::<&str>()
+            .map(|reason| reason.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "(no message)".to_owned());
+
+        Err(RuntimeError::new(format!(
+            "native function panicked: {}",
+            reason
+        )))
+    })
+}
+
+#[cfg(not(feature = "catch_panics"))]
+fn call_native(func: impl FnOnce() -> Result<Value, RuntimeError>) -> Result<Value, RuntimeError> {
+    func()
+}
+
+/// The name `func` is being traced under, if it's the current value of one of
+/// the symbols passed to `trace`
+fn traced_name(env: &Rc<RefCell<Env>>, func: &Value) -> Option<Symbol> {
+    let env = env.borrow();
+    let traced = env.context().traced.borrow();
+
+    if traced.is_empty() {
+        return None;
+    }
+
+    traced
+        .iter()
+        .find(|name| env.get(name).is_some_and(|value| value.is_identical(func)))
+        .cloned()
+}
+
+/// Call a function that's being traced, writing a line to the output
+/// (indented by how many traced calls are in progress) when it's called, and
+/// another when it returns or fails. Any tail call it makes is run before it
+/// counts as having returned, so those show up nested inside it like other
+/// calls, instead of replacing it.
+fn call_traced(
+    env: Rc<RefCell<Env>>,
+    name: Symbol,
+    func: &Value,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let context = env.borrow().context().clone();
+    let depth = context.trace_depth.get();
+    let indent = "  ".repeat(depth);
+
+    let call = Value::List(
+        args.iter()
+            .cloned()
+            .collect::<List>()
+            .cons(Value::Symbol(name.clone())),
+    );
+    // problems writing the trace shouldn't change what the program does
+    let _ = writeln!(context.output.borrow_mut(), "{}{}", indent, call);
+
+    context.trace_depth.set(depth + 1);
+    let mut res = call_untraced(env.clone(), func, args);
+    while let Ok(Value::TailCall { func, args }) = res {
+        res = call_function_or_macro(env.clone(), func.as_ref(), args);
+    }
+    context.trace_depth.set(depth);
+
+    let _ = match &res {
+        Ok(value) => writeln!(
+            context.output.borrow_mut(),
+            "{}{} returned {}",
+            indent,
+            name,
+            value
+        ),
+        Err(err) => writeln!(
+            context.output.borrow_mut(),
+            "{}{} failed: {}",
+            indent,
+            name,
+            err.msg
+        ),
+    };
+
+    res
+}
+
+/// Replace a new function's closure with one that keeps only what it needs,
+/// if the interpreter captures closures minimally
+fn capture(mut lambda: Lambda) -> Lambda {
+    let mode = lambda.closure.borrow().context().closure_capture.get();
+
+    if mode == ClosureCapture::Minimal {
+        if let Some(closure) = Env::capture(&lambda.closure, lambda.free_variables()) {
+            lambda.closure = closure;
+        }
+    }
+
+    lambda
+}
+
+/// Bind each of `names` to one of `values` (which can be one value, or
+/// multiple from `values`) for a `let_values` clause. The names can end with
+/// `&rest` and a name, which is bound to a list of any values left over.
+fn bind_values(env: &Rc<RefCell<Env>>, names: &List, values: Value) -> Result<(), RuntimeError> {
+    let names = names
+        .into_iter()
+        .map(|name| match name {
+            Value::Symbol(name) => Ok(name),
+            other => Err(RuntimeError::new(format!(
+                "Expected symbol for let_values binding, found {}",
+                other
+            ))),
+        })
+        .collect::<Result<Vec<Symbol>, RuntimeError>>()?;
+
+    let (required, rest) = match names.iter().position(|name| &*name.0 == "&rest") {
+        Some(index) => (&names[..index], names.get(index + 1)),
+        None => (&names[..], None),
+    };
+    check_binding_names("let_values binding", required.iter().chain(rest))?;
+
+    let values = match values {
+        Value::Values(values) => values.to_vec(),
+        value => vec![value],
+    };
+
+    if values.len() < required.len() || (rest.is_none() && values.len() > required.len()) {
+        return Err(RuntimeError::new(format!(
+            "\"let_values\" expected {}{} values for ({}), got {}",
+            required.len(),
+            if rest.is_some() { " or more" } else { "" },
+            names
+                .iter()
+                .map(|name| &*name.0)
+                .collect::<Vec<&str>>()
+                .join(" "),
+            values.len()
+        )));
+    }
+
+    let mut env = env.borrow_mut();
+    let mut values = values.into_iter();
+    for name in required {
+        env.define(name.clone(), values.next().unwrap_or(Value::NIL));
+    }
+    if let Some(rest) = rest {
+        env.define(rest.clone(), Value::List(values.collect()));
+    }
+
+    Ok(())
+}
+
+/// Create the environment for a call to `lambda`, with its arguments bound
+fn bind_args(lambda: &Lambda, mut args: Vec<Value>) -> Result<Rc<RefCell<Env>>, RuntimeError> {
+    let arg_env = Env::extend_local(lambda.closure.clone()).into_shared();
+
+    let (params, keys) = match lambda.argnames.iter().position(|arg| &*arg.0 == "&key") {
+        Some(index) => (&lambda.argnames[..index], &lambda.argnames[index + 1..]),
+        None => (&lambda.argnames[..], &[][..]),
+    };
+    // any keyword arguments come after the positional ones, although like in
+    // Common Lisp, they're also included in a rest parameter
+    let positional_count = params
+        .iter()
+        .position(|arg| &*arg.0 == "&rest" || &*arg.0 == "...")
+        .unwrap_or(params.len());
+
+    let key_values = if keys.is_empty() {
+        Vec::new()
+    } else {
+        let accepted = keys.iter().map(|key| &*key.0).collect::<Vec<_>>();
+        let name = lambda.name.as_ref().map_or("lambda", |name| &*name.0);
+        let values = keyword_args(name, &args, positional_count, &accepted)?;
+
+        keys.iter()
+            .map(|key| values.get(&*key.0).map(|&value| value.clone()))
+            .collect()
+    };
+
+    if args.len() < positional_count {
+        return Err(RuntimeError::new(format!(
+            "Expected {}{} arguments, but got {}",
+            if positional_count < params.len() {
+                "at least "
+            } else {
+                ""
+            },
+            positional_count,
+            args.len()
+        )));
+    }
+
+    // so every fixed parameter has an argument, and a rest parameter gets
+    // whatever's left after them, which may be nothing
+    let mut arg_env_mut = arg_env.borrow_mut();
+    for (index, arg_name) in params.iter().enumerate() {
+        if &*arg_name.0 == "&rest" {
+            // rest parameter (`value_to_argnames()` makes sure it's named)
+            if let Some(rest_name) = params.get(index + 1) {
+                arg_env_mut.define(
+                    rest_name.clone(),
+                    Value::List(List::from_vec(args.split_off(index))),
+                );
+            }
+            break;
+        } else if &*arg_name.0 == "..." {
+            // deprecated: the old, unnamed rest parameter
+            arg_env_mut.define(
+                Symbol::from("..."),
+                Value::List(List::from_vec(args.split_off(index))),
+            );
+            break;
+        } else {
+            arg_env_mut.define(arg_name.clone(), args[index].clone());
+        }
+    }
+    drop(arg_env_mut);
+
+    // defaults are evaluated in order, after the other arguments are bound,
+    // so they can refer to them
+    for (key, value) in keys.iter().zip(key_values) {
+        let value = match value {
+            Some(value) => value,
+            None => match lambda.key_defaults.iter().find(|(name, _)| name == key) {
+                Some((_, default)) => eval_inner(arg_env.clone(), default, Context::new())?,
+                None => Value::NIL,
+            },
+        };
+
+        arg_env.borrow_mut().define(key.clone(), value);
     }
+
+    Ok(arg_env)
+}
+
+/// A function which calls the first of `clauses` that takes exactly as many
+/// arguments as it's given, or else the first one whose `&rest` (or `&key`)
+/// parameter lets it take that many
+fn case_lambda(clauses: Vec<Lambda>) -> Value {
+    Value::NativeClosure(Rc::new(RefCell::new(move |_env, args: Vec<Value>| {
+        let required = |clause: &Lambda| {
+            clause
+                .argnames
+                .iter()
+                .position(|arg| matches!(&*arg.0, "&rest" | "&key" | "..."))
+        };
+
+        let chosen = clauses
+            .iter()
+            .find(|clause| required(clause).is_none() && clause.argnames.len() == args.len())
+            .or_else(|| {
+                clauses
+                    .iter()
+                    .find(|clause| required(clause).is_some_and(|required| required <= args.len()))
+            })
+            .ok_or_else(|| {
+                RuntimeError::new(format!(
+                    "No case_lambda clause takes {} arguments",
+                    args.len()
+                ))
+            })?;
+
+        // called as a tail call, so this closure isn't still borrowed if
+        // the clause calls it again
+        Ok(Value::TailCall {
+            func: Rc::new(Value::Lambda(chosen.clone())),
+            args,
+        })
+    })))
+}
+
+/// The forms which `eval_inner()` evaluates specially, instead of as a
+/// function call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SpecialForm {
+    Comma,
+    Quote,
+    Define,
+    Set,
+    Defmacro,
+    Defun,
+    Lambda,
+    CaseLambda,
+    Let,
+    LetValues,
+    Begin,
+    Cond,
+    If,
+    And,
+    Or,
+    Delay,
+    Yield,
+    For,
+    Do,
+    Trace,
+    Untrace,
+    DefineSyntax,
+    Splice,
+}
+
+/// The keyword of each special form, sorted by keyword so it can be searched
+const SPECIAL_FORMS: [(&str, SpecialForm); 23] = [
+    ("and", SpecialForm::And),
+    ("begin", SpecialForm::Begin),
+    ("case_lambda", SpecialForm::CaseLambda),
+    ("comma", SpecialForm::Comma),
+    ("cond", SpecialForm::Cond),
+    ("define", SpecialForm::Define),
+    ("define_syntax", SpecialForm::DefineSyntax),
+    ("defmacro", SpecialForm::Defmacro),
+    ("defun", SpecialForm::Defun),
+    ("delay", SpecialForm::Delay),
+    ("do", SpecialForm::Do),
+    ("for", SpecialForm::For),
+    ("if", SpecialForm::If),
+    ("lambda", SpecialForm::Lambda),
+    ("let", SpecialForm::Let),
+    ("let_values", SpecialForm::LetValues),
+    ("or", SpecialForm::Or),
+    ("quote", SpecialForm::Quote),
+    ("set", SpecialForm::Set),
+    ("splice", SpecialForm::Splice),
+    ("trace", SpecialForm::Trace),
+    ("untrace", SpecialForm::Untrace),
+    ("yield", SpecialForm::Yield),
+];
+
+impl SpecialForm {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        SPECIAL_FORMS
+            .binary_search_by(|(other, _)| (*other).cmp(keyword))
+            .ok()
+            .map(|index| SPECIAL_FORMS[index].1)
+    }
+
+    pub(crate) fn keyword(self) -> &'static str {
+        SPECIAL_FORMS
+            .iter()
+            .find(|(_, form)| *form == self)
+            .map(|(keyword, _)| *keyword)
+            .unwrap_or_default()
+    }
+}
+
+/// Read a `do` form: its (name init step) clauses, its (test result...)
+/// clause, and its body
+fn do_loop(keyword: &str, list: &List) -> Result<DoLoop, RuntimeError> {
+    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+    let clauses = require_typed_arg::<&List>(keyword, args, 0)?
+        .into_iter()
+        .map(|clause| {
+            let parts = match &clause {
+                Value::List(parts) => parts.into_iter().collect::<Vec<Value>>(),
+                _ => return Err(clause),
+            };
+
+            match parts.as_slice() {
+                [Value::Symbol(symbol), init] => Ok((symbol.clone(), init.clone(), None)),
+                [Value::Symbol(symbol), init, step] => {
+                    Ok((symbol.clone(), init.clone(), Some(step.clone())))
+                }
+                _ => Err(clause),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|clause| {
+            RuntimeError::new(format!(
+                "Expected (name init step) clause in do, found {}",
+                clause
+            ))
+        })?;
+    check_binding_names("do variable", clauses.iter().map(|(name, ..)| name))?;
+
+    let exit = match require_arg(keyword, args, 1)? {
+        Value::List(exit) if *exit != List::NIL => exit,
+        other => {
+            return Err(RuntimeError::new(format!(
+                "Expected (test result...) clause in do, found {}",
+                other
+            )))
+        }
+    };
+    Ok(DoLoop {
+        clauses,
+        test: exit.car()?,
+        exit: exit.cdr(),
+        body: list.cdr().cdr().cdr(),
+    })
+}
+
+/// Read a `for` form: its (name list) clauses, the `(when condition)`
+/// guards at the start of its body, and the rest of the body
+fn for_loop(keyword: &str, list: &List) -> Result<ForLoop, RuntimeError> {
+    let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+    let clauses = require_typed_arg::<&List>(keyword, args, 0)?
+        .into_iter()
+        .map(|clause| {
+            let pair = match &clause {
+                Value::List(pair) => pair,
+                _ => return Err(clause),
+            };
+
+            match (pair.car(), pair.cdr().car()) {
+                (Ok(Value::Symbol(symbol)), Ok(expr)) if pair.cdr().cdr() == List::NIL => {
+                    Ok((symbol, expr))
+                }
+                _ => Err(clause),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|clause| {
+            RuntimeError::new(format!(
+                "Expected (name list) clause in for, found {}",
+                clause
+            ))
+        })?;
+
+    // guards are (when condition) forms at the start of the body
+    let is_guard = |form: &Value| match form {
+        Value::List(form) => {
+            matches!(form.car(), Ok(Value::Symbol(Symbol(head))) if &*head == "when")
+        }
+        _ => false,
+    };
+    let guards = args[1..]
+        .iter()
+        .take_while(|form| is_guard(form))
+        .map(|guard| match guard {
+            Value::List(guard) => guard.cdr().car(),
+            _ => unreachable!(),
+        })
+        .collect::<Result<Vec<Value>, RuntimeError>>()?;
+    let body = args[1 + guards.len()..].iter().collect();
+
+    Ok(ForLoop {
+        clauses,
+        guards,
+        body,
+    })
+}
+
+/// Evaluate the body of a `for` for each combination of items from the lists
+/// in `clauses` (with the last clause varying fastest), binding each item in
+/// a new environment and skipping the combinations where any of the guards
+/// are false
+fn eval_for(
+    env: Rc<RefCell<Env>>,
+    for_loop: &ForLoop,
+    clauses: &[(Symbol, Value)],
+    results: &mut Vec<Value>,
+) -> Result<(), RuntimeError> {
+    let ((symbol, expr), rest) = match clauses.split_first() {
+        Some(clause) => clause,
+        None => {
+            for guard in &for_loop.guards {
+                if !is_truthy(&env, &eval_inner(env.clone(), guard, Context::new())?) {
+                    return Ok(());
+                }
+            }
+
+            results.push(eval_block_inner(
+                env,
+                for_loop.body.into_iter(),
+                Context::new(),
+            )?);
+
+            return Ok(());
+        }
+    };
+
+    let items = eval_inner(env.clone(), expr, Context::new())?;
+    let items = for_items(symbol, &items)?;
+
+    for item in items.into_iter() {
+        let item_env = Env::extend_local(env.clone()).into_shared();
+        item_env.borrow_mut().define(symbol.clone(), item);
+
+        eval_for(item_env, for_loop, rest, results)?;
+    }
+
+    Ok(())
+}
+
+/// The items that a `for` clause's list evaluated to
+fn for_items<'a>(symbol: &Symbol, items: &'a Value) -> Result<&'a List, RuntimeError> {
+    items.try_into().map_err(|_| {
+        RuntimeError::new(format!(
+            "\"for\" requires the value for {} to be a list; got {}",
+            symbol, items
+        ))
+    })
+}
+
+/// The names and expression of a `let_values` clause, `((name...) expression)`
+fn let_values_clause(clause: &Value) -> Result<(List, Value), RuntimeError> {
+    let parts = match clause {
+        Value::List(parts) => parts.into_iter().collect::<Vec<Value>>(),
+        _ => Vec::new(),
+    };
+
+    match parts.as_slice() {
+        [Value::List(names), expr] => Ok((names.clone(), expr.clone())),
+        _ => Err(RuntimeError::new(format!(
+            "Expected ((name...) expression) clause in let_values, found {}",
+            clause
+        ))),
+    }
+}
+
+/// Call a macro with its (unevaluated) arguments, and return its expansion.
+/// Functions and macros that the expansion calls by a name the macro itself
+/// introduced (rather than one from its arguments) are replaced with what
+/// they are where the macro was defined, if the name means something else
+/// where it's being used. That way a caller can have its own `list` without
+/// breaking a macro that expands into calls to the builtin one.
+pub(crate) fn expand_macro(
+    env: &Rc<RefCell<Env>>,
+    macro_: &Value,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let mut from_args = HashSet::new();
+    for arg in &args {
+        arg.walk_graph(&mut |value| {
+            if let Value::Symbol(symbol) = value {
+                from_args.insert(Rc::as_ptr(&symbol.0));
+            }
+        });
+    }
+
+    let expanded = call_function(env.clone(), macro_, args)?;
+
+    let Value::Macro(lambda) = macro_ else {
+        return Ok(expanded);
+    };
+
+    let resolve = |symbol: &Symbol| {
+        if from_args.contains(&Rc::as_ptr(&symbol.0)) {
+            return None;
+        }
+
+        let defined = lambda.closure.borrow().get(symbol)?;
+        let is_callable = matches!(
+            defined,
+            Value::Lambda(_)
+                | Value::Macro(_)
+                | Value::NativeFunc(_)
+                | Value::NativeClosure(_)
+                | Value::Combinator(_)
+        );
+
+        match env.borrow().get(symbol) {
+            Some(used) if used.is_identical(&defined) => None,
+            _ if is_callable => Some(defined),
+            _ => None,
+        }
+    };
+
+    Ok(resolve_calls(&expanded, &resolve).unwrap_or(expanded))
+}
+
+/// Replace the symbols that `expr` calls (for which `resolve` returns a value),
+/// or `None` if there aren't any. Quoted data, parameter lists, and the names
+/// bound by `let` and the like are left alone.
+fn resolve_calls(expr: &Value, resolve: &dyn Fn(&Symbol) -> Option<Value>) -> Option<Value> {
+    let Value::List(list) = expr else {
+        return None;
+    };
+    let keyword = match list.car() {
+        Ok(Value::Symbol(symbol)) if is_special_form(&symbol.0) => Some(symbol),
+        Ok(_) => None,
+        Err(_) => return None,
+    };
+
+    let each = |_, expr: &Value| resolve_calls(expr, resolve);
+    let bindings =
+        |bindings: &Value| map_items(bindings, 0, |_, binding| map_items(binding, 1, each));
+
+    match keyword.as_ref().map(|symbol| &*symbol.0) {
+        Some("quote" | "define_syntax") => None,
+        Some("lambda") => map_items(expr, 2, each),
+        Some("defun" | "defmacro") => map_items(expr, 3, each),
+        Some("case_lambda") => map_items(expr, 1, |_, clause| map_items(clause, 1, each)),
+        Some("let" | "for" | "let_values") => map_items(expr, 1, |index, item| match index {
+            1 => bindings(item),
+            _ => resolve_calls(item, resolve),
+        }),
+        Some("do") => map_items(expr, 1, |index, item| match index {
+            1 => bindings(item),
+            2 => map_items(item, 0, each),
+            _ => resolve_calls(item, resolve),
+        }),
+        Some("cond") => map_items(expr, 1, |_, clause| map_items(clause, 0, each)),
+        Some(_) => map_items(expr, 1, each),
+        None => map_items(expr, 0, |index, item| match item {
+            Value::Symbol(symbol) if index == 0 => resolve(symbol),
+            _ => resolve_calls(item, resolve),
+        }),
+    }
+}
+
+/// A copy of the list with the items from `start` on replaced by `replace`
+/// (where it returns a value), or `None` if none of them were
+fn map_items(
+    list: &Value,
+    start: usize,
+    replace: impl Fn(usize, &Value) -> Option<Value>,
+) -> Option<Value> {
+    let Value::List(list) = list else {
+        return None;
+    };
+
+    let mut changed = false;
+    let mut items = Vec::new();
+    for (index, item) in list.into_iter().enumerate() {
+        match (index >= start).then(|| replace(index, &item)).flatten() {
+            Some(replaced) => {
+                changed = true;
+                items.push(replaced);
+            }
+            None => items.push(item),
+        }
+    }
+
+    changed.then(|| Value::List(items.into_iter().collect()))
+}
+
+/// The template with each symbol ending in `#` (like `tmp#`) replaced by a
+/// newly generated one, the same one everywhere it appears, or `None` if it
+/// doesn't have any. That way a macro can bind temporary variables without
+/// capturing the caller's.
+fn auto_gensyms(env: &Rc<RefCell<Env>>, template: &Value) -> Option<Value> {
+    fn is_auto_gensym(symbol: &Symbol) -> bool {
+        symbol.0.len() > 1 && symbol.0.ends_with('#')
+    }
+
+    let mut found = false;
+    template.walk_graph(&mut |value| {
+        found |= matches!(value, Value::Symbol(symbol) if is_auto_gensym(symbol));
+    });
+
+    if !found {
+        return None;
+    }
+
+    let mut renames = HashMap::new();
+    let renamed = template.map_graph(|value| match value {
+        Value::Symbol(symbol) if is_auto_gensym(symbol) => {
+            let renamed = renames.entry(symbol.clone()).or_insert_with(|| {
+                let count = env.borrow().context().next_gensym();
+                Value::Symbol(Symbol::from(format!("{}{}", symbol, count)))
+            });
+
+            Ok::<_, core::convert::Infallible>(Some(renamed.clone()))
+        }
+        _ => Ok(None),
+    });
+
+    renamed.ok()
+}
+
+/// Whether `name` is the keyword of a special form (like `if` or `lambda`)
+pub(crate) fn is_special_form(name: &str) -> bool {
+    SpecialForm::from_keyword(name).is_some()
+}
+
+/// Whether the s-expression is a `comma` form, like `,x`
+fn is_comma(list: &List) -> bool {
+    matches!(special_form(list), Some(SpecialForm::Comma))
+}
+
+/// The handler for the s-expression, if it's one of the special forms
+/// registered on the context (which take precedence over the built-in ones)
+fn registered_form(env: &Rc<RefCell<Env>>, list: &List) -> Option<Rc<SpecialFormHandler>> {
+    let env = env.borrow();
+    let forms = env.context().special_forms.borrow();
+
+    if forms.is_empty() {
+        return None;
+    }
+
+    list.with_car(|car| match car {
+        Value::Symbol(symbol) => forms.get(symbol).cloned(),
+        _ => None,
+    })
+    .flatten()
+}
+
+/// The expression in an argument written as `(splice expr)` (or `@expr`),
+/// whose value is a list of arguments to splice into the call
+pub(crate) fn splice_arg(arg_expr: &Value) -> Option<Value> {
+    match arg_expr {
+        Value::List(list) if special_form(list) == Some(SpecialForm::Splice) => {
+            list.cdr().car().ok()
+        }
+        _ => None,
+    }
+}
+
+/// Add the value of an argument expression to a call's arguments, or all of
+/// its items if it was spliced
+pub(crate) fn push_arg(
+    args: &mut Vec<Value>,
+    value: Value,
+    spliced: bool,
+) -> Result<(), RuntimeError> {
+    if !spliced {
+        args.push(value.into_single());
+        return Ok(());
+    }
+
+    match value {
+        Value::List(list) => {
+            let len = list.checked_len()?;
+            args.extend(list.into_iter().take(len));
+            Ok(())
+        }
+        value => Err(RuntimeError::with_kind(
+            ErrorKind::TypeError,
+            format!("\"splice\" requires argument 1 to be a list; got {}", value),
+        )),
+    }
+}
+
+/// Which special form (if any) a (non-empty) s-expression is. This is checked
+/// for every s-expression that gets evaluated, so it looks at the head symbol
+/// in-place instead of cloning it, and compares it against the keywords all
+/// at once.
+pub(crate) fn special_form(list: &List) -> Option<SpecialForm> {
+    list.with_car(|car| match car {
+        Value::Symbol(Symbol(keyword)) => SpecialForm::from_keyword(keyword),
+        _ => None,
+    })
+    .flatten()
+}
+
+/// Create a generator which will run the body of `lambda`, called with `args`
+pub(crate) fn make_generator(lambda: &Lambda, args: Vec<Value>) -> Result<Generator, RuntimeError> {
+    let arg_env = bind_args(lambda, args)?;
+    declare_internal_defines(&arg_env, lambda.body.iter());
+
+    Ok(Generator::new(vec![Frame::Block {
+        env: arg_env,
+        rest: lambda.body.iter().collect(),
+    }]))
+}
+
+/// Run a generator until it yields a value, or until its body completes (in
+/// which case `None` is returned and the generator is exhausted). An error
+/// also exhausts the generator.
+pub(crate) fn resume_generator(
+    generator: &RefCell<Generator>,
+) -> Result<Option<Value>, RuntimeError> {
+    let state = core::mem::replace(&mut generator.borrow_mut().state, GeneratorState::Running);
+
+    match state {
+        GeneratorState::Suspended(mut frames) => {
+            let res = run_frames(&mut frames);
+
+            generator.borrow_mut().state = match res {
+                Ok(Some(_)) => GeneratorState::Suspended(frames),
+                _ => GeneratorState::Exhausted,
+            };
+
+            res
+        }
+        GeneratorState::Running => Err(RuntimeError::new("Generator is already running")),
+        GeneratorState::Exhausted => {
+            generator.borrow_mut().state = GeneratorState::Exhausted;
+            Ok(None)
+        }
+    }
+}
+
+/// What the stepwise evaluator should do next
+enum Step {
+    Eval(Value, Rc<RefCell<Env>>),
+    Return(Value),
+}
+
+/// A stepwise version of `eval_inner()`, which keeps its continuation in
+/// `frames` instead of on the Rust stack so that it can be suspended by
+/// `yield`. Forms that don't need to be suspendable are handed off to
+/// `eval_inner()`.
+fn run_frames(frames: &mut Vec<Frame>) -> Result<Option<Value>, RuntimeError> {
+    // the value of the `yield` expression that we're resuming from
+    let mut step = Step::Return(Value::NIL);
+
+    loop {
+        step = match step {
+            Step::Eval(expr, env) => match &expr {
+                Value::Symbol(symbol) => Step::Return(lookup(&env, symbol)?),
+
+                // `()` evaluates to itself, as in `eval_inner()`
+                Value::List(list) if *list == List::NIL => Step::Return(Value::NIL),
+
+                Value::List(list) => {
+                    check_cancelled(&env)?;
+
+                    let form = special_form(list);
+                    let keyword = form.map(SpecialForm::keyword).unwrap_or_default();
+
+                    match form {
+                        // registered forms can't be suspended in the middle
+                        _ if registered_form(&env, list).is_some() => {
+                            Step::Return(eval_inner(env, &expr, Context::new())?)
+                        }
+
+                        Some(SpecialForm::Yield) => {
+                            frames.push(Frame::Yield);
+
+                            match list.cdr().car() {
+                                Ok(value_expr) => Step::Eval(value_expr, env),
+                                Err(_) => Step::Return(Value::NIL),
+                            }
+                        }
+
+                        Some(SpecialForm::Begin) => {
+                            frames.push(Frame::Block {
+                                env,
+                                rest: list.cdr(),
+                            });
+
+                            Step::Return(Value::NIL)
+                        }
+
+                        Some(SpecialForm::Define | SpecialForm::Set) => {
+                            let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+                            let symbol = require_typed_arg::<&Symbol>(keyword, args, 0)?;
+                            let value_expr = require_arg(keyword, args, 1)?;
+
+                            if keyword == "define" {
+                                env.borrow().check_definition(keyword, symbol)?;
+                            } else {
+                                env.borrow().check_set(keyword, symbol)?;
+                            }
+
+                            frames.push(Frame::Define {
+                                env: env.clone(),
+                                symbol: symbol.clone(),
+                                is_set: keyword == "set",
+                            });
+
+                            Step::Eval(value_expr.clone(), env)
+                        }
+
+                        Some(SpecialForm::Let) => {
+                            let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+                            let declarations = require_typed_arg::<&List>(keyword, args, 0)?;
+                            check_let_names(declarations)?;
+                            let let_env = Env::extend_local(env).into_shared();
+
+                            start_let_declaration(
+                                frames,
+                                let_env,
+                                declarations.clone(),
+                                list.cdr().cdr(),
+                            )?
+                        }
+
+                        Some(SpecialForm::LetValues) => {
+                            let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+                            let clauses = require_typed_arg::<&List>(keyword, args, 0)?;
+                            let let_env = Env::extend_local(env).into_shared();
+
+                            next_let_values_clause(
+                                frames,
+                                let_env,
+                                clauses.clone(),
+                                list.cdr().cdr(),
+                            )?
+                        }
+
+                        Some(SpecialForm::For) => {
+                            let for_loop = Rc::new(for_loop(keyword, list)?);
+
+                            start_for_combination(frames, for_loop, Vec::new(), Vec::new(), env)
+                        }
+
+                        Some(SpecialForm::Do) => {
+                            let do_loop = Rc::new(do_loop(keyword, list)?);
+
+                            next_do_init(frames, do_loop, env, Vec::new())?
+                        }
+
+                        Some(SpecialForm::Comma) => Step::Eval(list.cdr().car()?, env),
+
+                        // a quote's commas are evaluated all at once, so they
+                        // can't be suspended
+                        Some(SpecialForm::Quote) => {
+                            Step::Return(eval_inner(env, &expr, Context::new()).map_err(|err| {
+                                if err.msg == YIELD_OUTSIDE_GENERATOR {
+                                    RuntimeError::new(
+                                        "\"yield\" can't be used in a comma inside a quote",
+                                    )
+                                } else {
+                                    err
+                                }
+                            })?)
+                        }
+
+                        Some(SpecialForm::Cond) => next_cond_clause(frames, env, list.cdr())?,
+
+                        Some(SpecialForm::If) => {
+                            let args = &list.cdr().into_iter().collect::<Vec<Value>>();
+
+                            let condition = require_arg(keyword, args, 0)?;
+                            let then_expr = require_arg(keyword, args, 1)?;
+                            let else_expr = require_arg(keyword, args, 2).ok();
+
+                            frames.push(Frame::If {
+                                env: env.clone(),
+                                then_expr: then_expr.clone(),
+                                else_expr: else_expr.cloned(),
+                            });
+
+                            Step::Eval(condition.clone(), env)
+                        }
+
+                        Some(SpecialForm::And | SpecialForm::Or) => {
+                            let is_or = keyword == "or";
+                            let operands = list.cdr();
+
+                            if operands == List::NIL {
+                                // there were zero arguments
+                                Step::Return((!is_or).into())
+                            } else {
+                                frames.push(Frame::AndOr {
+                                    env: env.clone(),
+                                    is_or,
+                                    rest: operands.cdr(),
+                                });
+
+                                Step::Eval(operands.car()?, env)
+                            }
+                        }
+
+                        // the remaining special forms don't evaluate anything
+                        // that could `yield` straight away: the bodies of
+                        // functions and promises are evaluated when they're
+                        // called or forced
+                        Some(_) => Step::Return(eval_inner(env, &expr, Context::new())?),
+
+                        // function call or macro expand
+                        None => {
+                            frames.push(Frame::Call {
+                                env: env.clone(),
+                                func: None,
+                                args: vec![],
+                                rest: list.cdr(),
+                                splicing: false,
+                            });
+
+                            Step::Eval(list.car()?, env)
+                        }
+                    }
+                }
+
+                // plain value
+                _ => Step::Return(expr.clone()),
+            },
+
+            Step::Return(value) => match frames.pop() {
+                None => return Ok(None),
+
+                Some(Frame::Yield) => return Ok(Some(value)),
+
+                Some(Frame::Block { env, rest }) => {
+                    if let Ok(expr) = rest.car() {
+                        let rest = rest.cdr();
+
+                        // the last expression takes the place of the block
+                        if rest != List::NIL {
+                            frames.push(Frame::Block {
+                                env: env.clone(),
+                                rest,
+                            });
+                        }
+
+                        Step::Eval(expr, env)
+                    } else {
+                        // an empty body evaluates to NIL
+                        Step::Return(Value::NIL)
+                    }
+                }
+
+                Some(Frame::Define {
+                    env,
+                    symbol,
+                    is_set,
+                }) => {
+                    let value = value.into_single();
+
+                    if is_set {
+                        env.borrow_mut().set(symbol, value.clone())?;
+
+                        Step::Return(value)
+                    } else {
+                        let value = value.named(&symbol);
+                        env.borrow_mut().define(symbol, value.clone());
+
+                        Step::Return(value)
+                    }
+                }
+
+                Some(Frame::Let {
+                    env,
+                    symbol,
+                    declarations,
+                    body,
+                }) => {
+                    env.borrow_mut().define(symbol, value.into_single());
+
+                    start_let_declaration(frames, env, declarations, body)?
+                }
+
+                Some(Frame::LetValues {
+                    env,
+                    names,
+                    clauses,
+                    body,
+                }) => {
+                    bind_values(&env, &names, value)?;
+
+                    next_let_values_clause(frames, env, clauses, body)?
+                }
+
+                Some(Frame::For {
+                    for_loop,
+                    mut levels,
+                    mut results,
+                    stage,
+                }) => match stage {
+                    ForStage::Items(env) => {
+                        let (symbol, _) = &for_loop.clauses[levels.len()];
+                        let items = for_items(symbol, &value)?.clone();
+                        levels.push((env, items));
+
+                        next_for_item(frames, for_loop, levels, results)
+                    }
+                    ForStage::Guard(index, env) => {
+                        if is_truthy(&env, &value) {
+                            next_for_guard(frames, for_loop, levels, results, index + 1, env)
+                        } else {
+                            next_for_item(frames, for_loop, levels, results)
+                        }
+                    }
+                    ForStage::Body => {
+                        results.push(value);
+
+                        next_for_item(frames, for_loop, levels, results)
+                    }
+                },
+
+                Some(Frame::Do {
+                    do_loop,
+                    env,
+                    mut values,
+                    stage,
+                }) => match stage {
+                    DoStage::Init => {
+                        values.push(value);
+
+                        next_do_init(frames, do_loop, env, values)?
+                    }
+                    DoStage::Test(iteration) => {
+                        if is_truthy(&iteration, &value) {
+                            frames.push(Frame::Block {
+                                env: iteration,
+                                rest: do_loop.exit.clone(),
+                            });
+                        } else {
+                            let body = do_loop.body.clone();
+                            frames.push(Frame::Do {
+                                do_loop,
+                                env,
+                                values: Vec::new(),
+                                stage: DoStage::Body(iteration.clone()),
+                            });
+                            frames.push(Frame::Block {
+                                env: iteration,
+                                rest: body,
+                            });
+                        }
+
+                        Step::Return(Value::NIL)
+                    }
+                    DoStage::Body(iteration) => {
+                        next_do_step(frames, do_loop, env, values, iteration)?
+                    }
+                    DoStage::Step(iteration) => {
+                        values.push(value);
+
+                        next_do_step(frames, do_loop, env, values, iteration)?
+                    }
+                },
+
+                Some(Frame::If {
+                    env,
+                    then_expr,
+                    else_expr,
+                }) => {
+                    if is_truthy(&env, &value) {
+                        Step::Eval(then_expr, env)
+                    } else if let Some(else_expr) = else_expr {
+                        Step::Eval(else_expr, env)
+                    } else {
+                        Step::Return(Value::NIL)
+                    }
+                }
+
+                Some(Frame::Cond { env, body, rest }) => {
+                    if is_truthy(&env, &value) {
+                        frames.push(Frame::Block { env, rest: body });
+
+                        Step::Return(Value::NIL)
+                    } else {
+                        next_cond_clause(frames, env, rest)?
+                    }
+                }
+
+                Some(Frame::AndOr { env, is_or, rest }) => {
+                    if is_or == is_truthy(&env, &value) || rest == List::NIL {
+                        Step::Return(value)
+                    } else {
+                        frames.push(Frame::AndOr {
+                            env: env.clone(),
+                            is_or,
+                            rest: rest.cdr(),
+                        });
+
+                        Step::Eval(rest.car()?, env)
+                    }
+                }
+
+                Some(Frame::Call {
+                    env,
+                    func: None,
+                    rest,
+                    ..
+                }) if matches!(value, Value::Macro(_)) => {
+                    let args = rest.into_iter().collect::<Vec<Value>>();
+                    let expanded = expand_macro(&env, &value, args)?;
+
+                    Step::Eval(expanded, env)
+                }
+
+                Some(Frame::Call {
+                    env,
+                    func,
+                    mut args,
+                    rest,
+                    splicing,
+                }) => {
+                    let func = match func {
+                        Some(func) => {
+                            push_arg(&mut args, value, splicing)?;
+                            func
+                        }
+                        None => value,
+                    };
+
+                    if rest != List::NIL {
+                        let arg_expr = rest.car()?;
+                        let spliced = splice_arg(&arg_expr);
+
+                        frames.push(Frame::Call {
+                            env: env.clone(),
+                            func: Some(func),
+                            args,
+                            rest: rest.cdr(),
+                            splicing: spliced.is_some(),
+                        });
+
+                        Step::Eval(spliced.unwrap_or(arg_expr), env)
+                    } else {
+                        apply_in_frames(frames, env, func, args)?
+                    }
+                }
+            },
+        };
+    }
+}
+
+/// Bind the next `let` declaration, or start evaluating the body if there are
+/// none left
+fn start_let_declaration(
+    frames: &mut Vec<Frame>,
+    let_env: Rc<RefCell<Env>>,
+    declarations: List,
+    body: List,
+) -> Result<Step, RuntimeError> {
+    if declarations == List::NIL {
+        declare_internal_defines(&let_env, &body);
+        frames.push(Frame::Block {
+            env: let_env,
+            rest: body,
+        });
+
+        return Ok(Step::Return(Value::NIL));
+    }
+
+    let decl = &declarations.car()?;
+    let decl_cons: &List = decl
+        .try_into()
+        .map_err(|_| RuntimeError::new(format!("Expected declaration clause, found {}", decl)))?;
+    let symbol = &decl_cons.car()?;
+    let symbol: &Symbol = symbol.try_into().map_err(|_| {
+        RuntimeError::new(format!(
+            "Expected symbol for let declaration, found {}",
+            symbol
+        ))
+    })?;
+    let expr = decl_cons.cdr().car()?;
+
+    frames.push(Frame::Let {
+        env: let_env.clone(),
+        symbol: symbol.clone(),
+        declarations: declarations.cdr(),
+        body,
+    });
+
+    Ok(Step::Eval(expr, let_env))
+}
+
+/// Evaluate the next `let_values` clause, or start evaluating the body if
+/// there are none left
+fn next_let_values_clause(
+    frames: &mut Vec<Frame>,
+    let_env: Rc<RefCell<Env>>,
+    clauses: List,
+    body: List,
+) -> Result<Step, RuntimeError> {
+    if clauses == List::NIL {
+        declare_internal_defines(&let_env, &body);
+        frames.push(Frame::Block {
+            env: let_env,
+            rest: body,
+        });
+
+        return Ok(Step::Return(Value::NIL));
+    }
+
+    let (names, expr) = let_values_clause(&clauses.car()?)?;
+
+    frames.push(Frame::LetValues {
+        env: let_env.clone(),
+        names,
+        clauses: clauses.cdr(),
+        body,
+    });
+
+    Ok(Step::Eval(expr, let_env))
+}
+
+/// Evaluate the initial value of the next `do` variable, or start the first
+/// iteration once they've all been evaluated
+fn next_do_init(
+    frames: &mut Vec<Frame>,
+    do_loop: Rc<DoLoop>,
+    env: Rc<RefCell<Env>>,
+    values: Vec<Value>,
+) -> Result<Step, RuntimeError> {
+    match do_loop.clauses.get(values.len()) {
+        Some((_, init, _)) => {
+            let init = init.clone();
+            frames.push(Frame::Do {
+                do_loop,
+                env: env.clone(),
+                values,
+                stage: DoStage::Init,
+            });
+
+            Ok(Step::Eval(init, env))
+        }
+        None => start_do_iteration(frames, do_loop, env, values),
+    }
+}
+
+/// Evaluate the step of the next `do` variable (a variable without one keeps
+/// its value), or start the next iteration once they've all been evaluated
+fn next_do_step(
+    frames: &mut Vec<Frame>,
+    do_loop: Rc<DoLoop>,
+    env: Rc<RefCell<Env>>,
+    mut values: Vec<Value>,
+    iteration: Rc<RefCell<Env>>,
+) -> Result<Step, RuntimeError> {
+    while let Some((name, _, step)) = do_loop.clauses.get(values.len()) {
+        if let Some(step) = step {
+            let step = step.clone();
+            frames.push(Frame::Do {
+                do_loop,
+                env,
+                values,
+                stage: DoStage::Step(iteration.clone()),
+            });
+
+            return Ok(Step::Eval(step, iteration));
+        }
+
+        values.push(lookup(&iteration, name)?);
+    }
+
+    start_do_iteration(frames, do_loop, env, values)
+}
+
+/// Bind the `do` variables to `values` in a new environment for the
+/// iteration, and evaluate the test
+fn start_do_iteration(
+    frames: &mut Vec<Frame>,
+    do_loop: Rc<DoLoop>,
+    env: Rc<RefCell<Env>>,
+    values: Vec<Value>,
+) -> Result<Step, RuntimeError> {
+    let mut iteration = Env::extend_local(env.clone());
+    for ((name, ..), value) in do_loop.clauses.iter().zip(values) {
+        iteration.define(name.clone(), value);
+    }
+    let iteration = iteration.into_shared();
+
+    check_cancelled(&iteration)?;
+
+    let test = do_loop.test.clone();
+    frames.push(Frame::Do {
+        do_loop,
+        env,
+        values: Vec::new(),
+        stage: DoStage::Test(iteration.clone()),
+    });
+
+    Ok(Step::Eval(test, iteration))
+}
+
+/// Evaluate the list of the next `for` clause in `env` (which binds the items
+/// of the ones before it), or if they're all bound, start on the guards
+fn start_for_combination(
+    frames: &mut Vec<Frame>,
+    for_loop: Rc<ForLoop>,
+    levels: Vec<(Rc<RefCell<Env>>, List)>,
+    results: Vec<Value>,
+    env: Rc<RefCell<Env>>,
+) -> Step {
+    match for_loop.clauses.get(levels.len()) {
+        Some((_, expr)) => {
+            let expr = expr.clone();
+            frames.push(Frame::For {
+                for_loop,
+                levels,
+                results,
+                stage: ForStage::Items(env.clone()),
+            });
+
+            Step::Eval(expr, env)
+        }
+        None => next_for_guard(frames, for_loop, levels, results, 0, env),
+    }
+}
+
+/// Evaluate the `for` guard at `index`, or the body if the guards are all true
+fn next_for_guard(
+    frames: &mut Vec<Frame>,
+    for_loop: Rc<ForLoop>,
+    levels: Vec<(Rc<RefCell<Env>>, List)>,
+    results: Vec<Value>,
+    index: usize,
+    env: Rc<RefCell<Env>>,
+) -> Step {
+    match for_loop.guards.get(index) {
+        Some(guard) => {
+            let guard = guard.clone();
+            frames.push(Frame::For {
+                for_loop,
+                levels,
+                results,
+                stage: ForStage::Guard(index, env.clone()),
+            });
+
+            Step::Eval(guard, env)
+        }
+        None => {
+            let body = for_loop.body.clone();
+            frames.push(Frame::For {
+                for_loop,
+                levels,
+                results,
+                stage: ForStage::Body,
+            });
+            frames.push(Frame::Block { env, rest: body });
+
+            Step::Return(Value::NIL)
+        }
+    }
+}
+
+/// Bind the next item of the innermost `for` clause that has any left, or
+/// evaluate to the collected results once they've all been gone through
+fn next_for_item(
+    frames: &mut Vec<Frame>,
+    for_loop: Rc<ForLoop>,
+    mut levels: Vec<(Rc<RefCell<Env>>, List)>,
+    results: Vec<Value>,
+) -> Step {
+    while let Some((env, items)) = levels.last() {
+        let Ok(item) = items.car() else {
+            levels.pop();
+            continue;
+        };
+
+        let (symbol, _) = &for_loop.clauses[levels.len() - 1];
+        let item_env = Env::extend_local(env.clone()).into_shared();
+        item_env.borrow_mut().define(symbol.clone(), item);
+
+        if let Some((_, items)) = levels.last_mut() {
+            *items = items.cdr();
+        }
+
+        return start_for_combination(frames, for_loop, levels, results, item_env);
+    }
+
+    Step::Return(Value::List(List::from_vec(results)))
+}
+
+/// Test the next `cond` clause, or evaluate to NIL if there are none left
+fn next_cond_clause(
+    frames: &mut Vec<Frame>,
+    env: Rc<RefCell<Env>>,
+    clauses: List,
+) -> Result<Step, RuntimeError> {
+    if clauses == List::NIL {
+        return Ok(Step::Return(Value::NIL));
+    }
+
+    let clause = &clauses.car()?;
+    let clause: &List = clause
+        .try_into()
+        .map_err(|_| RuntimeError::new(format!("Expected conditional clause, found {}", clause)))?;
+
+    let condition = clause.car()?;
+
+    frames.push(Frame::Cond {
+        env: env.clone(),
+        body: clause.cdr(),
+        rest: clauses.cdr(),
+    });
+
+    Ok(Step::Eval(condition, env))
+}
+
+/// The error for a `yield` that isn't run by a generator
+const YIELD_OUTSIDE_GENERATOR: &str = "\"yield\" can only be used inside a generator";
+
+/// Call a function from the stepwise evaluator. Lisp functions have their
+/// bodies evaluated stepwise too (so they can `yield`), while native functions
+/// are simply called.
+fn apply_in_frames(
+    frames: &mut Vec<Frame>,
+    env: Rc<RefCell<Env>>,
+    func: Value,
+    args: Vec<Value>,
+) -> Result<Step, RuntimeError> {
+    match &func {
+        Value::Lambda(lambda) => {
+            let env = bind_args(lambda, args)?;
+            declare_internal_defines(&env, lambda.body.iter());
+
+            frames.push(Frame::Block {
+                env,
+                rest: lambda.body.iter().collect(),
+            });
+
+            Ok(Step::Return(Value::NIL))
+        }
+        Value::NativeFunc(_) | Value::NativeClosure(_) | Value::Combinator(_) => {
+            match call_function_or_macro(env.clone(), &func, args)? {
+                Value::TailCall { func, args } => {
+                    apply_in_frames(frames, env, func.as_ref().clone(), args)
+                }
+                value => Ok(Step::Return(value)),
+            }
+        }
+        _ => Err(not_callable(&func)),
+    }
+}
+
+/// The error for a call whose head evaluated to something other than a
+/// function, such as `(nil 1 2)`
+fn not_callable(func: &Value) -> RuntimeError {
+    RuntimeError::new(format!("{} is not callable", func))
 }
 
 #[derive(Debug, Clone, Copy)]
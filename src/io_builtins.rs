@@ -0,0 +1,216 @@
+use crate::{
+    interpreter::{eval_block, is_truthy},
+    model::{int_to_i128, Env, ErrorKind, RuntimeError, Symbol, Value},
+    serialize::{restore_session, serialize_session},
+    utils::{keyword_args, require_arg, require_typed_arg},
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+/// Add the filesystem builtins to `env`. Paths are strings, and relative ones
+/// are relative to the current directory of the process. File names which
+/// aren't valid UTF-8 are converted lossily (with `�` in place of the invalid
+/// parts) by `list_dir`.
+pub(crate) fn define_io_builtins(env: &mut Env) {
+    env.define(
+        Symbol::from("read_file"),
+        Value::NativeFunc(|env, args| {
+            let path = require_typed_arg::<&String>("read_file", &args, 0)?;
+            let keywords = keyword_args("read_file", &args, 1, &["bytes"])?;
+
+            if keywords
+                .get("bytes")
+                .is_some_and(|bytes| is_truthy(&env, bytes))
+            {
+                fs::read(path)
+                    .map(Value::from)
+                    .map_err(|err| io_error("read_file", path, err))
+            } else {
+                fs::read_to_string(path)
+                    .map(Value::String)
+                    .map_err(|err| io_error("read_file", path, err))
+            }
+        }),
+    );
+
+    // the file's code is run in the environment `load` is called from, but
+    // only if all of it parses (with the interpreter's parser, so any
+    // extensions to the syntax apply)
+    env.define(
+        Symbol::from("load"),
+        Value::NativeFunc(|env, args| {
+            let path = require_typed_arg::<&String>("load", &args, 0)?;
+            let source = fs::read_to_string(path).map_err(|err| io_error("load", path, err))?;
+
+            let parser = env.borrow().context().parser.borrow().clone();
+            let exprs = parser
+                .parse(&source)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| {
+                    RuntimeError::with_kind(
+                        ErrorKind::ParseError,
+                        err.render(&source, path).trim_end().to_owned(),
+                    )
+                })?;
+
+            eval_block(env, exprs.into_iter())
+        }),
+    );
+
+    env.define(
+        Symbol::from("write_file"),
+        Value::NativeFunc(|_env, args| {
+            let path = require_typed_arg::<&String>("write_file", &args, 0)?;
+            let contents = require_contents("write_file", &args)?;
+
+            fs::write(path, contents).map_err(|err| io_error("write_file", path, err))?;
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    env.define(
+        Symbol::from("append_file"),
+        Value::NativeFunc(|_env, args| {
+            let path = require_typed_arg::<&String>("append_file", &args, 0)?;
+            let contents = require_contents("append_file", &args)?;
+
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(&contents))
+                .map_err(|err| io_error("append_file", path, err))?;
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    env.define(
+        Symbol::from("file_exists"),
+        Value::NativeFunc(|_env, args| {
+            let path = require_typed_arg::<&String>("file_exists", &args, 0)?;
+
+            fs::exists(path)
+                .map(Value::from)
+                .map_err(|err| io_error("file_exists", path, err))
+        }),
+    );
+
+    env.define(
+        Symbol::from("delete_file"),
+        Value::NativeFunc(|_env, args| {
+            let path = require_typed_arg::<&String>("delete_file", &args, 0)?;
+
+            fs::remove_file(path).map_err(|err| io_error("delete_file", path, err))?;
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    env.define(
+        Symbol::from("list_dir"),
+        Value::NativeFunc(|_env, args| {
+            let path = require_typed_arg::<&String>("list_dir", &args, 0)?;
+
+            let mut names = fs::read_dir(path)
+                .and_then(|entries| {
+                    entries
+                        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into()))
+                        .collect::<Result<Vec<String>, _>>()
+                })
+                .map_err(|err| io_error("list_dir", path, err))?;
+
+            // the order entries are read in depends on the platform
+            names.sort();
+
+            Ok(Value::List(names.into_iter().map(Value::String).collect()))
+        }),
+    );
+
+    env.define(
+        Symbol::from("create_dir"),
+        Value::NativeFunc(|_env, args| {
+            let path = require_typed_arg::<&String>("create_dir", &args, 0)?;
+
+            fs::create_dir_all(path).map_err(|err| io_error("create_dir", path, err))?;
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    // the bindings of the environment it's called from (the top level, at the
+    // REPL) are written to the file as Lisp source, as well as they can be
+    // (see `serialize_session()`), and it returns the warnings about the ones
+    // that weren't saved, or not entirely
+    env.define(
+        Symbol::from("save_session"),
+        Value::NativeFunc(|env, args| {
+            let path = require_typed_arg::<&String>("save_session", &args, 0)?;
+            let (source, warnings) = serialize_session(&env.borrow());
+
+            fs::write(path, source).map_err(|err| io_error("save_session", path, err))?;
+
+            Ok(Value::List(
+                warnings.into_iter().map(Value::String).collect(),
+            ))
+        }),
+    );
+
+    // restores a session saved by `save_session` into the environment it's
+    // called from, replacing what's already defined there unless
+    // `:overwrite F`, and returns the names that were restored
+    env.define(
+        Symbol::from("load_session"),
+        Value::NativeFunc(|env, args| {
+            let path = require_typed_arg::<&String>("load_session", &args, 0)?;
+            let keywords = keyword_args("load_session", &args, 1, &["overwrite"])?;
+            let overwrite = keywords
+                .get("overwrite")
+                .is_none_or(|overwrite| is_truthy(&env, overwrite));
+
+            let source =
+                fs::read_to_string(path).map_err(|err| io_error("load_session", path, err))?;
+            let restored = restore_session(env, &source, overwrite)?;
+
+            Ok(Value::List(
+                restored.into_iter().map(Value::Symbol).collect(),
+            ))
+        }),
+    );
+}
+
+/// The second of `args`, as the bytes to write to a file: either a string,
+/// bytes, or a list of integers from 0 to 255
+fn require_contents(func_name: &str, args: &[Value]) -> Result<Vec<u8>, RuntimeError> {
+    match require_arg(func_name, args, 1)? {
+        Value::String(string) => Ok(string.as_bytes().to_vec()),
+        Value::Bytes(bytes) => Ok(bytes.borrow().clone()),
+        Value::List(list) => list
+            .into_iter()
+            .map(|item| match &item {
+                Value::Int(int) => int_to_i128(int).and_then(|int| u8::try_from(int).ok()),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| {
+                RuntimeError::new(format!(
+                    "\"{}\" requires a list to only contain bytes (integers from 0 to 255); got {}",
+                    func_name, list
+                ))
+            }),
+        other => Err(RuntimeError::with_kind(
+            ErrorKind::TypeError,
+            format!(
+                "\"{}\" requires argument 2 to be a string, bytes or a list of bytes; got {}",
+                func_name, other
+            ),
+        )),
+    }
+}
+
+fn io_error(func_name: &str, path: &str, err: std::io::Error) -> RuntimeError {
+    RuntimeError::new(format!("\"{}\" failed for {}: {}", func_name, path, err))
+}
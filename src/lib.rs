@@ -1,34 +1,96 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 
+#[macro_use]
+extern crate alloc;
+
+pub mod collections;
+pub mod compiler;
+pub mod convert;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 pub mod interpreter;
 pub mod model;
 pub mod parser;
+pub mod serialize;
 pub mod utils;
 
 mod default_environment;
+#[cfg(feature = "io")]
+mod io_builtins;
+mod prelude;
+#[cfg(feature = "process")]
+mod process_builtins;
+#[cfg(feature = "regex")]
+mod regex_builtins;
+mod syntax_rules;
 pub use default_environment::default_env;
+pub use interpreter::eval_str;
 
 #[macro_use]
 mod macros;
 
+// used by the exported macros, which can't rely on `std` being available
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec;
+}
+
+#[cfg(feature = "std")]
 use model::Env;
-use std::io::{self, prelude::*};
-use std::{cell::RefCell, rc::Rc};
+#[cfg(feature = "std")]
+use std::{
+    cell::RefCell,
+    io::{self, prelude::*},
+    rc::Rc,
+};
 
 // 🦀 I am all over this project!
 /// Starts a REPL prompt at stdin/stdout. **This will block the current thread.**
+#[cfg(feature = "std")]
 pub fn start_repl(env: Option<Env>) {
     let env_rc = Rc::new(RefCell::new(env.unwrap_or_else(default_env)));
+    let cancel = env_rc.borrow().context().cancel_handle();
+
+    // Ctrl-C stops whatever's being evaluated, instead of the whole process
+    // (which Ctrl-D ends instead). This fails if a handler has already been
+    // set, by the host or an earlier REPL, which is left as it is.
+    #[cfg(feature = "ctrlc")]
+    {
+        let cancel = cancel.clone();
+        let _ = ctrlc::set_handler(move || cancel.cancel());
+    }
 
     print!("> ");
     io::stdout().flush().unwrap();
     for line in io::stdin().lock().lines() {
-        match interpreter::eval_block(
-            env_rc.clone(),
-            parser::parse(&line.unwrap()).filter_map(|a| a.ok()),
-        ) {
-            Ok(val) => println!("{}", val),
-            Err(e) => println!("{}", e),
+        let line = line.unwrap();
+
+        // nothing on a line is evaluated unless all of it parses
+        let parser = env_rc.borrow().context().parser.borrow().clone();
+        let exprs = match parser.parse(&line).collect::<Result<Vec<_>, _>>() {
+            Ok(exprs) => exprs,
+            Err(err) => {
+                print!("{}\n> ", err.render(&line, "<repl>"));
+                io::stdout().flush().unwrap();
+                continue;
+            }
+        };
+
+        // a Ctrl-C while waiting for the line doesn't cancel it
+        cancel.reset();
+
+        match interpreter::eval_block(env_rc.clone(), exprs.into_iter()) {
+            Ok(val) => println!("{}", env_rc.borrow().context().display(&val)),
+            Err(e) => {
+                // `exit` ends the session (but not the host program)
+                #[cfg(feature = "process")]
+                if env_rc.borrow().context().take_exit_status().is_some() {
+                    return;
+                }
+
+                println!("{}", e)
+            }
         };
 
         print!("> ");
@@ -49,19 +49,19 @@ macro_rules! lisp {
 
     // Symbols
     ($sym:ident) => {
-        $crate::model::Value::Symbol($crate::model::Symbol(String::from(stringify!( $sym ))))
+        $crate::model::Value::Symbol($crate::model::Symbol::from(stringify!( $sym )))
     };
     // these aren't valid Rust identifiers
-    ( + ) =>  { $crate::model::Value::Symbol($crate::model::Symbol(String::from("+"))) };
-    ( - ) =>  { $crate::model::Value::Symbol($crate::model::Symbol(String::from("-"))) };
-    ( * ) =>  { $crate::model::Value::Symbol($crate::model::Symbol(String::from("*"))) };
-    ( / ) =>  { $crate::model::Value::Symbol($crate::model::Symbol(String::from("/"))) };
-    ( == ) => { $crate::model::Value::Symbol($crate::model::Symbol(String::from("=="))) };
-    ( != ) => { $crate::model::Value::Symbol($crate::model::Symbol(String::from("!="))) };
-    ( < ) =>  { $crate::model::Value::Symbol($crate::model::Symbol(String::from("<"))) };
-    ( <= ) => { $crate::model::Value::Symbol($crate::model::Symbol(String::from("<="))) };
-    ( > ) =>  { $crate::model::Value::Symbol($crate::model::Symbol(String::from(">"))) };
-    ( >= ) => { $crate::model::Value::Symbol($crate::model::Symbol(String::from(">="))) };
+    ( + ) =>  { $crate::model::Value::Symbol($crate::model::Symbol::from("+")) };
+    ( - ) =>  { $crate::model::Value::Symbol($crate::model::Symbol::from("-")) };
+    ( * ) =>  { $crate::model::Value::Symbol($crate::model::Symbol::from("*")) };
+    ( / ) =>  { $crate::model::Value::Symbol($crate::model::Symbol::from("/")) };
+    ( == ) => { $crate::model::Value::Symbol($crate::model::Symbol::from("==")) };
+    ( != ) => { $crate::model::Value::Symbol($crate::model::Symbol::from("!=")) };
+    ( < ) =>  { $crate::model::Value::Symbol($crate::model::Symbol::from("<")) };
+    ( <= ) => { $crate::model::Value::Symbol($crate::model::Symbol::from("<=")) };
+    ( > ) =>  { $crate::model::Value::Symbol($crate::model::Symbol::from(">")) };
+    ( >= ) => { $crate::model::Value::Symbol($crate::model::Symbol::from(">=")) };
 
 
     // Literals
@@ -73,3 +73,41 @@ macro_rules! lisp {
         $crate::parser::parse(stringify!($e)).next().unwrap().unwrap()
     };
 }
+
+/// A macro for creating a lisp symbol (as a `Value`) from a string
+/// ```
+/// use rust_lisp::{model::{Symbol, Value}, sym};
+///
+/// assert_eq!(sym!("foo"), Value::Symbol(Symbol::from("foo")));
+/// ```
+#[macro_export]
+macro_rules! sym {
+    ($name:expr) => {
+        $crate::model::Value::Symbol($crate::model::Symbol::from($name))
+    };
+}
+
+/// A macro for creating a `Value::List` from Rust values. Each item can be
+/// anything that converts `Into<Value>`, including other lists; use `sym!` for
+/// symbols.
+/// ```
+/// use rust_lisp::{default_env, interpreter::eval, lisp_list, model::Value, sym};
+/// use std::{cell::RefCell, rc::Rc};
+///
+/// let name = "world";
+/// let expr = lisp_list![sym!("+"), "hello ", name];
+///
+/// let env = Rc::new(RefCell::new(default_env()));
+/// assert_eq!(eval(env.clone(), &expr), Ok(Value::from("hello world")));
+///
+/// let expr = lisp_list![sym!("length"), lisp_list![sym!("list"), 1, 2.5, true]];
+/// assert_eq!(eval(env, &expr), Ok(Value::from(3)));
+/// ```
+#[macro_export]
+macro_rules! lisp_list {
+    ( $($val:expr),* $(,)? ) => {
+        $crate::model::Value::List($crate::model::List::from_vec($crate::__private::vec![
+            $( $crate::model::Value::from($val) ),*
+        ]))
+    };
+}
@@ -0,0 +1,33 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A way to stop an interpreter's evaluation while it's running, from another
+/// thread (or a signal handler, etc), made by `Context::cancel_handle()`.
+///
+/// After `cancel()`, the evaluation stops at its next step with a
+/// `RuntimeError` of kind `ErrorKind::Interrupted`. Lisp code has no way to
+/// catch it (the fallbacks of `with_step_limit` and `with_timeout` only
+/// replace their own limit running out), and the handle stays cancelled until
+/// it's `reset()`, so even a native function that carries on after an error
+/// stops again at the next step. Until it's reset, every evaluation with that interpreter fails.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    /// Stop the evaluation that's in progress (or the next one to start)
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel()` has been called since the handle was last reset
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Let the interpreter evaluate again, after a cancellation
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,20 @@
+/// What a function defined in Lisp keeps of the scope it was made in. This is
+/// set per interpreter, on its [`Context`](super::Context), and applies to
+/// functions made after it's changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClosureCapture {
+    /// The function keeps the whole environment it was made in (and so
+    /// everything defined in it and around it), and sees any later changes to
+    /// it
+    #[default]
+    ByReference,
+    /// The function keeps copies of only the local variables it refers to (see
+    /// [`Lambda::free_variables()`](super::Lambda::free_variables)), made when
+    /// the function is. Global definitions are still shared. A later `set` of
+    /// one of those variables outside of the function isn't seen by it, and a
+    /// `set` inside of it only changes its own copy. Variables that only
+    /// appear in the expansion of a macro aren't seen, and a function that
+    /// refers to a local definition which hasn't been made yet (like a local
+    /// function calling itself) captures by reference anyway.
+    Minimal,
+}
@@ -0,0 +1,98 @@
+use alloc::vec::Vec;
+
+use super::{Symbol, Value};
+
+/// A function made out of other values by one of the combinator builtins.
+/// Unlike a native closure, it keeps what it was made from where it can be
+/// seen, so that it prints as something like `#<partial + (1)>`.
+#[derive(Debug)]
+pub enum Combinator {
+    /// From `partial` (or `curry`, which is `builtin`): calls `func` with
+    /// `args` before the arguments it's given
+    Partial {
+        builtin: &'static str,
+        func: Function,
+        args: Vec<Value>,
+    },
+
+    /// From `compose`: calls the last function with the arguments it's given,
+    /// and then each of the others in turn (right to left) with the result
+    Compose(Vec<Function>),
+
+    /// From `const`: returns the value, whatever it's called with
+    Const(Value),
+
+    /// From `flip`: calls the function with its first two arguments swapped
+    Flip(Function),
+
+    /// From `complement`: calls the function, and returns whether its result
+    /// was false
+    Complement(Function),
+}
+
+/// A function that was given to a combinator, along with the name it was
+/// bound to there (if any), for printing
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub value: Value,
+    pub name: Option<Symbol>,
+}
+
+impl Combinator {
+    /// The values it was made from
+    pub fn values(&self) -> Vec<Value> {
+        match self {
+            Combinator::Partial { func, args, .. } => core::iter::once(func.value.clone())
+                .chain(args.iter().cloned())
+                .collect(),
+            Combinator::Compose(funcs) => funcs.iter().map(|func| func.value.clone()).collect(),
+            Combinator::Const(value) => vec![value.clone()],
+            Combinator::Flip(func) | Combinator::Complement(func) => vec![func.value.clone()],
+        }
+    }
+}
+
+impl core::fmt::Display for Combinator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Combinator::Partial {
+                builtin,
+                func,
+                args,
+            } => {
+                write!(f, "#<{} {} (", builtin, func)?;
+
+                for (index, arg) in args.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(" ")?;
+                    }
+
+                    write!(f, "{}", arg)?;
+                }
+
+                f.write_str(")>")
+            }
+            Combinator::Compose(funcs) => {
+                f.write_str("#<compose")?;
+
+                for func in funcs {
+                    write!(f, " {}", func)?;
+                }
+
+                f.write_str(">")
+            }
+            Combinator::Const(value) => write!(f, "#<const {}>", value),
+            Combinator::Flip(func) => write!(f, "#<flip {}>", func),
+            Combinator::Complement(func) => write!(f, "#<complement {}>", func),
+        }
+    }
+}
+
+impl core::fmt::Display for Function {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
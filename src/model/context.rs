@@ -0,0 +1,525 @@
+use crate::collections::{HashMap, HashSet};
+use alloc::{boxed::Box, rc::Rc, string::String, vec::Vec};
+use core::any::TypeId;
+use core::cell::{Cell, RefCell};
+use core::fmt::{Debug, Write};
+
+use super::{
+    Allocated, CancelHandle, ClosureCapture, Env, ErrorKind, LogLevel, NativeFunc, OpsGetter,
+    ResourceLimits, RuntimeError, StrictMode, Symbol, Truthiness, Value,
+};
+use crate::{interpreter::is_special_form, parser::Parser};
+#[cfg(feature = "gc")]
+use alloc::rc::Weak;
+
+/// A special form registered by the embedder, which is called with the
+/// environment it's evaluated in and its arguments, unevaluated
+pub type SpecialFormHandler = dyn Fn(Rc<RefCell<Env>>, Vec<Value>) -> Result<Value, RuntimeError>;
+
+/// A callback set with `Context::set_warning_handler()`
+pub type WarningHandler = dyn Fn(&str);
+
+/// A callback set with `Context::set_logger()`
+pub type Logger = dyn Fn(LogLevel, &str);
+
+/// State which belongs to an interpreter as a whole, rather than to any one
+/// scope. Every environment extending the same root environment shares the
+/// same context, so native functions can reach it through whichever
+/// environment they're called with.
+pub struct Context {
+    /// Per-symbol property lists, as set by `put`
+    pub plists: RefCell<HashMap<Symbol, HashMap<Symbol, Value>>>,
+
+    /// The names of the functions whose calls are being traced, as set by
+    /// `trace`
+    pub traced: RefCell<HashSet<Symbol>>,
+
+    /// Where the interpreter's output (from `print`, `trace`, etc) is written.
+    /// This is stdout by default, or nowhere without the `std` feature.
+    pub output: RefCell<Box<dyn Write>>,
+
+    /// Which values count as false in conditionals and for `not`
+    pub truthiness: Cell<Truthiness>,
+
+    /// What functions keep of the scope they're made in
+    pub closure_capture: Cell<ClosureCapture>,
+
+    /// Which definitions are flagged as likely mistakes
+    pub strict_mode: Cell<StrictMode>,
+
+    /// The names of the builtins (everything defined by `default_env()`), for
+    /// the strict mode
+    pub(crate) builtins: RefCell<HashSet<Symbol>>,
+
+    /// How code read at run time (by `load`, the REPL, etc) is parsed, along
+    /// with any extensions to the syntax registered on it
+    pub parser: RefCell<Parser>,
+
+    /// Non-fatal problems noticed during evaluation, as emitted by `warn()` or
+    /// the `warn` builtin, in the order they happened
+    pub warnings: RefCell<Vec<String>>,
+
+    /// Where warnings go instead of `warnings`, if set with
+    /// `set_warning_handler()`
+    warning_handler: RefCell<Option<Box<WarningHandler>>>,
+
+    /// The least serious messages that are logged; anything below it is
+    /// dropped
+    pub log_level: Cell<LogLevel>,
+
+    /// Where logged messages go, if set with `set_logger()`
+    logger: RefCell<Option<Box<Logger>>>,
+
+    /// The special forms registered with `register_special_form()` or
+    /// `override_special_form()`, by name
+    pub special_forms: RefCell<HashMap<Symbol, Rc<SpecialFormHandler>>>,
+
+    /// Shared with the handles from `cancel_handle()`, and checked at each
+    /// step of evaluation
+    pub(crate) cancel: CancelHandle,
+
+    /// When each `with_timeout` in progress has to finish by, outermost first
+    #[cfg(feature = "std")]
+    pub(crate) deadlines: RefCell<Vec<std::time::Instant>>,
+
+    /// How many steps of evaluation have been taken, ever (see `steps()`)
+    pub(crate) steps: Cell<u64>,
+
+    /// The step count when the current top-level evaluation started, for
+    /// `instruction_count`
+    pub(crate) top_level_start: Cell<u64>,
+
+    /// How deeply `eval()` and `eval_block()` are nested, to tell when a
+    /// top-level evaluation starts
+    pub(crate) eval_depth: Cell<usize>,
+
+    /// The step count each `with_step_limit` in progress can go up to,
+    /// outermost first
+    pub(crate) step_limits: RefCell<Vec<u64>>,
+
+    /// How many traced calls are currently in progress
+    pub(crate) trace_depth: Cell<usize>,
+
+    /// How deeply combinations (calls, and special forms like `if`) can be
+    /// nested while they're evaluated, or `None` for no limit. Tail calls
+    /// don't nest. Without a limit, deep enough recursion overflows the stack.
+    pub max_depth: Cell<Option<usize>>,
+
+    /// How deeply combinations are nested right now, while there's a
+    /// `max_depth`
+    pub(crate) nesting: Cell<usize>,
+
+    /// How much memory each top-level evaluation can allocate
+    pub resource_limits: Cell<ResourceLimits>,
+
+    /// What's been allocated towards the `resource_limits` in the current
+    /// top-level evaluation
+    pub(crate) allocated: Cell<Allocated>,
+
+    /// The name each native function was first defined with, by its address,
+    /// for printing
+    pub(crate) native_names: RefCell<HashMap<usize, Symbol>>,
+
+    /// How foreign values of each type work with the builtins, by type
+    pub(crate) foreign_ops: RefCell<HashMap<TypeId, OpsGetter>>,
+
+    /// How many symbols have been generated, so each one can be numbered
+    /// uniquely
+    pub(crate) gensym_count: Cell<usize>,
+
+    /// Compiled regular expressions, by pattern, so that using the same
+    /// pattern again (in a loop, etc) doesn't compile it again
+    #[cfg(feature = "regex")]
+    pub(crate) regex_cache: RefCell<HashMap<String, regex::Regex>>,
+
+    /// The status passed to `exit`, if it's been called
+    #[cfg(feature = "process")]
+    pub(crate) exit_status: Cell<Option<i32>>,
+
+    /// Every environment made by `Env::into_shared()` with this context, for
+    /// `collect_garbage()`
+    #[cfg(feature = "gc")]
+    pub(crate) tracked_envs: RefCell<Vec<Weak<RefCell<Env>>>>,
+
+    /// How many environments can be tracked before garbage is collected
+    /// automatically
+    #[cfg(feature = "gc")]
+    pub(crate) gc_threshold: Cell<usize>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the interpreter's output somewhere other than stdout
+    pub fn set_output(&self, output: impl Write + 'static) {
+        *self.output.borrow_mut() = Box::new(output);
+    }
+
+    /// Whether `value` counts as true, under this interpreter's truthiness
+    pub fn is_truthy(&self, value: &Value) -> bool {
+        self.truthiness.get().is_truthy(value)
+    }
+
+    /// The name a native function was defined with, if it's been defined in
+    /// this interpreter
+    pub fn native_name(&self, func: NativeFunc) -> Option<Symbol> {
+        self.native_names.borrow().get(&(func as usize)).cloned()
+    }
+
+    /// Remember `name` as the name of `func`, unless it already has one
+    pub(crate) fn name_native(&self, func: NativeFunc, name: &Symbol) {
+        self.native_names
+            .borrow_mut()
+            .entry(func as usize)
+            .or_insert_with(|| name.clone());
+    }
+
+    /// A handle for cancelling this interpreter's evaluation from elsewhere,
+    /// which can be sent to another thread
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Record a warning, without interrupting evaluation
+    pub fn warn(&self, msg: impl Into<String>) {
+        let msg = msg.into();
+
+        match &*self.warning_handler.borrow() {
+            Some(handler) => handler(&msg),
+            None => self.warnings.borrow_mut().push(msg),
+        }
+    }
+
+    /// Have each warning passed to `handler` as it happens, instead of being
+    /// recorded for `take_warnings()`
+    pub fn set_warning_handler(&self, handler: impl Fn(&str) + 'static) {
+        *self.warning_handler.borrow_mut() = Some(Box::new(handler));
+    }
+
+    /// Log a message, if `level` isn't below the `log_level`. It goes to the
+    /// logger set with `set_logger()` if there is one, or else to the `log`
+    /// crate with the `log` feature, or else to stderr (or nowhere, without
+    /// the `std` feature).
+    pub fn log(&self, level: LogLevel, msg: &str) {
+        if !self.is_logged(level) {
+            return;
+        }
+
+        match &*self.logger.borrow() {
+            Some(logger) => logger(level, msg),
+            None => default_log(level, msg),
+        }
+    }
+
+    /// Whether messages at `level` are logged, so that building them can be
+    /// skipped if they aren't
+    pub fn is_logged(&self, level: LogLevel) -> bool {
+        level >= self.log_level.get()
+    }
+
+    /// Have logged messages passed to `logger`, to send them on to whichever
+    /// logging framework the host uses
+    pub fn set_logger(&self, logger: impl Fn(LogLevel, &str) + 'static) {
+        *self.logger.borrow_mut() = Some(Box::new(logger));
+    }
+
+    /// Remove and return all the warnings recorded so far
+    pub fn take_warnings(&self) -> Vec<String> {
+        self.warnings.take()
+    }
+
+    /// A number that hasn't been used for a generated symbol yet
+    pub(crate) fn next_gensym(&self) -> usize {
+        let count = self.gensym_count.get() + 1;
+        self.gensym_count.set(count);
+        count
+    }
+
+    /// Add a special form: whenever an s-expression starting with `name` is
+    /// evaluated, `handler` is called with its arguments unevaluated, and
+    /// returns its value. The handler can evaluate whichever arguments it
+    /// needs to with `eval()`. Built-in forms (like `if`) can only be replaced
+    /// with `override_special_form()`.
+    pub fn register_special_form(
+        &self,
+        name: &str,
+        handler: impl Fn(Rc<RefCell<Env>>, Vec<Value>) -> Result<Value, RuntimeError> + 'static,
+    ) -> Result<(), RuntimeError> {
+        if is_special_form(name) {
+            return Err(RuntimeError::new(format!(
+                    "\"{}\" is a built-in special form, and can only be replaced with override_special_form()",
+                    name
+                )));
+        }
+
+        self.override_special_form(name, handler);
+
+        Ok(())
+    }
+
+    /// Like `register_special_form()`, but also allowed to replace a built-in
+    /// form
+    pub fn override_special_form(
+        &self,
+        name: &str,
+        handler: impl Fn(Rc<RefCell<Env>>, Vec<Value>) -> Result<Value, RuntimeError> + 'static,
+    ) {
+        self.special_forms
+            .borrow_mut()
+            .insert(Symbol::from(name), Rc::new(handler));
+    }
+
+    /// How many steps of evaluation this interpreter has taken, in all. A
+    /// step is taken for each combination (a call, or a special form like
+    /// `if`) that's evaluated, and for each iteration of a `do` loop; looking
+    /// up a variable or evaluating a constant isn't one. Only interpreted code
+    /// is counted, not the top level of a compiled expression.
+    pub fn steps(&self) -> u64 {
+        self.steps.get()
+    }
+
+    /// Count a step of evaluation, failing if that goes over the limit of a
+    /// `with_step_limit`
+    pub(crate) fn take_step(&self) -> Result<(), RuntimeError> {
+        self.steps.set(self.steps.get() + 1);
+
+        if self.exceeded_step_limit().is_some() {
+            Err(RuntimeError::with_kind(
+                ErrorKind::BudgetExhausted,
+                STEP_LIMIT_EXCEEDED,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The outermost of the `with_step_limit` limits that have been gone over
+    /// (as an index into `step_limits`)
+    pub(crate) fn exceeded_step_limit(&self) -> Option<usize> {
+        let step_limits = self.step_limits.borrow();
+        if step_limits.is_empty() {
+            return None;
+        }
+
+        let steps = self.steps.get();
+        step_limits.iter().position(|limit| steps > *limit)
+    }
+
+    /// Count a combination that's being evaluated towards the `max_depth`
+    /// (if there is one) until the returned guard is dropped, failing if that
+    /// goes over it
+    pub(crate) fn nest(self: &Rc<Self>) -> Result<Option<Nesting>, RuntimeError> {
+        let Some(max_depth) = self.max_depth.get() else {
+            return Ok(None);
+        };
+
+        let nesting = self.nesting.get();
+        if nesting >= max_depth {
+            return Err(RuntimeError::with_kind(
+                ErrorKind::DepthLimit,
+                DEPTH_LIMIT_EXCEEDED,
+            ));
+        }
+
+        self.nesting.set(nesting + 1);
+        Ok(Some(Nesting(self.clone())))
+    }
+
+    /// Run `eval`, as a top-level evaluation if nothing else is being
+    /// evaluated, so that `instruction_count` (and the `resource_limits`)
+    /// count from its start
+    pub(crate) fn top_level<R>(&self, eval: impl FnOnce() -> R) -> R {
+        let depth = self.eval_depth.get();
+        if depth == 0 {
+            self.top_level_start.set(self.steps.get());
+            self.reset_allocated();
+        }
+
+        self.eval_depth.set(depth + 1);
+        let result = eval();
+        self.eval_depth.set(depth);
+
+        result
+    }
+
+    /// Whether a deadline set by `with_timeout` has passed, so a slow native
+    /// function can give up early instead of waiting for evaluation to be
+    /// stopped after it returns
+    #[cfg(feature = "std")]
+    pub fn deadline_exceeded(&self) -> bool {
+        self.expired_deadline().is_some()
+    }
+
+    /// The outermost of the `with_timeout` deadlines that have passed (as an
+    /// index into `deadlines`)
+    #[cfg(feature = "std")]
+    pub(crate) fn expired_deadline(&self) -> Option<usize> {
+        let deadlines = self.deadlines.borrow();
+        if deadlines.is_empty() {
+            return None;
+        }
+
+        let now = std::time::Instant::now();
+        deadlines.iter().position(|deadline| now >= *deadline)
+    }
+
+    /// Fail if a `with_timeout` deadline has passed. This is checked at every
+    /// step of evaluation, but only reads the clock every
+    /// `DEADLINE_CHECK_INTERVAL` steps.
+    #[cfg(feature = "std")]
+    pub(crate) fn check_deadlines(&self) -> Result<(), RuntimeError> {
+        if self.deadlines.borrow().is_empty() {
+            return Ok(());
+        }
+
+        if self.steps.get().is_multiple_of(DEADLINE_CHECK_INTERVAL) && self.deadline_exceeded() {
+            Err(RuntimeError::with_kind(
+                ErrorKind::BudgetExhausted,
+                TIMED_OUT,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// If evaluation failed because `exit` was called, the status it was
+    /// called with (which is then forgotten, so the context can be used again)
+    #[cfg(feature = "process")]
+    pub fn take_exit_status(&self) -> Option<i32> {
+        self.exit_status.take()
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            plists: RefCell::new(HashMap::new()),
+            traced: RefCell::new(HashSet::new()),
+            output: RefCell::new(Box::new(Stdout)),
+            truthiness: Cell::new(Truthiness::default()),
+            closure_capture: Cell::new(ClosureCapture::default()),
+            strict_mode: Cell::new(StrictMode::default()),
+            builtins: RefCell::new(HashSet::new()),
+            parser: RefCell::new(Parser::new()),
+            warnings: RefCell::new(Vec::new()),
+            warning_handler: RefCell::new(None),
+            log_level: Cell::new(LogLevel::default()),
+            logger: RefCell::new(None),
+            special_forms: RefCell::new(HashMap::new()),
+            cancel: CancelHandle::default(),
+            #[cfg(feature = "std")]
+            deadlines: RefCell::new(Vec::new()),
+            steps: Cell::new(0),
+            top_level_start: Cell::new(0),
+            eval_depth: Cell::new(0),
+            step_limits: RefCell::new(Vec::new()),
+            trace_depth: Cell::new(0),
+            max_depth: Cell::new(None),
+            nesting: Cell::new(0),
+            resource_limits: Cell::new(ResourceLimits::default()),
+            allocated: Cell::new(Allocated::default()),
+            gensym_count: Cell::new(0),
+            native_names: RefCell::new(HashMap::new()),
+            foreign_ops: RefCell::new(HashMap::new()),
+            #[cfg(feature = "regex")]
+            regex_cache: RefCell::new(HashMap::new()),
+            #[cfg(feature = "process")]
+            exit_status: Cell::new(None),
+            #[cfg(feature = "gc")]
+            tracked_envs: RefCell::new(Vec::new()),
+            #[cfg(feature = "gc")]
+            gc_threshold: Cell::new(super::gc::MIN_THRESHOLD),
+        }
+    }
+}
+
+impl Debug for Context {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Context")
+            .field("plists", &self.plists)
+            .field("traced", &self.traced)
+            .field("truthiness", &self.truthiness)
+            .field("closure_capture", &self.closure_capture)
+            .field("strict_mode", &self.strict_mode)
+            .field("max_depth", &self.max_depth)
+            .field("resource_limits", &self.resource_limits)
+            .field("parser", &self.parser)
+            .field("warnings", &self.warnings)
+            .field("log_level", &self.log_level)
+            .field(
+                "special_forms",
+                &self.special_forms.borrow().keys().collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+/// How many steps of evaluation go by between reading the clock, while there's
+/// a deadline
+#[cfg(feature = "std")]
+const DEADLINE_CHECK_INTERVAL: u64 = 64;
+
+/// The error when a `with_timeout` deadline passes
+#[cfg(feature = "std")]
+pub(crate) const TIMED_OUT: &str = "Evaluation timed out";
+
+/// The error when a `with_step_limit` limit is gone over
+pub(crate) const STEP_LIMIT_EXCEEDED: &str = "Step limit exceeded";
+
+/// The error when evaluation nests deeper than the `max_depth`
+pub(crate) const DEPTH_LIMIT_EXCEEDED: &str = "Maximum evaluation depth exceeded";
+
+/// The error when evaluation is cancelled through a `CancelHandle`
+pub(crate) const CANCELLED: &str = "Evaluation was cancelled";
+
+/// A combination being evaluated, counted towards the `max_depth` until it's
+/// dropped
+pub(crate) struct Nesting(Rc<Context>);
+
+impl Drop for Nesting {
+    fn drop(&mut self) {
+        self.0.nesting.set(self.0.nesting.get().saturating_sub(1));
+    }
+}
+
+/// Where logged messages go when the host hasn't set a logger
+#[cfg(feature = "log")]
+fn default_log(level: LogLevel, msg: &str) {
+    let level = match level {
+        LogLevel::Debug => log::Level::Debug,
+        LogLevel::Info => log::Level::Info,
+        LogLevel::Warn => log::Level::Warn,
+        LogLevel::Error => log::Level::Error,
+    };
+
+    log::log!(target: "rust_lisp", level, "{}", msg);
+}
+
+#[cfg(all(feature = "std", not(feature = "log")))]
+fn default_log(level: LogLevel, msg: &str) {
+    std::eprintln!("[{}] {}", level, msg);
+}
+
+#[cfg(not(any(feature = "std", feature = "log")))]
+fn default_log(_level: LogLevel, _msg: &str) {}
+
+/// The default output
+struct Stdout;
+
+impl Write for Stdout {
+    #[cfg(feature = "std")]
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        use std::io::Write;
+
+        std::io::stdout()
+            .write_all(s.as_bytes())
+            .map_err(|_| core::fmt::Error)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn write_str(&mut self, _s: &str) -> core::fmt::Result {
+        Ok(())
+    }
+}
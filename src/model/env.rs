@@ -1,39 +1,128 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::{collections::HashMap, fmt::Debug};
+use crate::collections::{HashMap, HashSet};
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::cell::RefCell;
+use core::fmt::Debug;
 
-use super::{RuntimeError, Symbol, Value};
+use super::{Check, Context, RuntimeError, StrictMode, Symbol, Value};
+use crate::interpreter::is_special_form;
 
 /// An environment of symbol bindings. Used for the base environment, for
 /// closures, for `let` statements, for function arguments, etc.
 #[derive(Debug)]
 pub struct Env {
-    parent: Option<Rc<RefCell<Env>>>,
-    entries: HashMap<Symbol, Value>,
+    pub(super) parent: Option<Rc<RefCell<Env>>>,
+    pub(super) entries: HashMap<Symbol, Value>,
+    /// Names which have been declared here, but not defined yet
+    pub(super) unassigned: Vec<Symbol>,
+    /// Whether this is a scope made during evaluation (for a function call,
+    /// `let`, etc), rather than a global one
+    local: bool,
+    context: Rc<Context>,
 }
 
 impl Env {
-    /// Create a new, empty environment
+    /// Create a new, empty environment, with a fresh interpreter context
     pub fn new() -> Self {
         Self {
             parent: None,
             entries: HashMap::new(),
+            unassigned: Vec::new(),
+            local: false,
+            context: Rc::new(Context::new()),
         }
     }
 
-    /// Create a new environment extending the given environment
+    /// Create a new, empty environment which shares an existing interpreter
+    /// context
+    pub fn with_context(context: Rc<Context>) -> Self {
+        Self {
+            parent: None,
+            entries: HashMap::new(),
+            unassigned: Vec::new(),
+            local: false,
+            context,
+        }
+    }
+
+    /// Create a new environment extending the given environment (and sharing
+    /// its context)
     pub fn extend(parent: Rc<RefCell<Env>>) -> Self {
+        let context = parent.borrow().context.clone();
+
         Self {
             parent: Some(parent),
             entries: HashMap::new(),
+            unassigned: Vec::new(),
+            local: false,
+            context,
         }
     }
 
+    /// Create a local scope extending the given environment, for the
+    /// interpreter's own use (calling a function, `let`, etc)
+    pub(crate) fn extend_local(parent: Rc<RefCell<Env>>) -> Self {
+        Self {
+            local: true,
+            ..Self::extend(parent)
+        }
+    }
+
+    /// Wrap this environment up to be shared, as `eval()` and closures need
+    /// it. With the `gc` feature, its context keeps track of it from then on,
+    /// so that `Context::collect_garbage()` can free it if it ends up only
+    /// being kept alive by a reference cycle.
+    pub fn into_shared(self) -> Rc<RefCell<Env>> {
+        #[cfg(feature = "gc")]
+        let context = self.context.clone();
+        let env = Rc::new(RefCell::new(self));
+
+        #[cfg(feature = "gc")]
+        context.track(&env);
+
+        env
+    }
+
+    /// The environment this one extends, if any
+    pub(crate) fn parent(&self) -> Option<&Rc<RefCell<Env>>> {
+        self.parent.as_ref()
+    }
+
+    /// The interpreter-wide state shared by this environment and every
+    /// environment related to it
+    pub fn context(&self) -> &Rc<Context> {
+        &self.context
+    }
+
+    /// The number of bindings defined directly in this environment (not
+    /// counting its parents)
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no bindings defined directly in this environment
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The bindings defined directly in this environment (not counting its
+    /// parents), in no particular order
+    pub fn entries(&self) -> impl Iterator<Item = (&Symbol, &Value)> {
+        self.entries.iter()
+    }
+
+    /// Whether the key is defined in this environment itself (not counting
+    /// its parents)
+    pub fn defines(&self, key: &Symbol) -> bool {
+        self.entries.contains_key(key)
+    }
+
     /// Walks up the environment hierarchy until it finds the symbol's value or
-    /// runs out of environments.
+    /// runs out of environments (or finds it declared but not yet defined).
     pub fn get(&self, key: &Symbol) -> Option<Value> {
-        if let Some(val) = self.entries.get(&key) {
+        if let Some(val) = self.entries.get(key) {
             Some(val.clone()) // clone the Rc
+        } else if self.unassigned.contains(key) {
+            None
         } else if let Some(parent) = &self.parent {
             parent.borrow().get(key)
         } else {
@@ -41,23 +130,203 @@ impl Env {
         }
     }
 
+    /// Check a definition of `key` in this environment (by the special form
+    /// `keyword`) against the context's strict mode, recording a warning or
+    /// returning an error if it's flagged
+    pub(crate) fn check_definition(&self, keyword: &str, key: &Symbol) -> Result<(), RuntimeError> {
+        self.check_special_form(keyword, key)?;
+
+        let strict_mode = self.context.strict_mode.get();
+        if strict_mode == StrictMode::LENIENT {
+            return Ok(());
+        }
+
+        let (check, problem) = if self.context.builtins.borrow().contains(key) {
+            (strict_mode.redefine_builtin, "redefines the builtin")
+        } else if !self.local && self.entries.contains_key(key) {
+            (strict_mode.redefine_global, "redefines the global")
+        } else if self.local && self.is_defined_globally(key) {
+            (strict_mode.shadow_global, "shadows the global")
+        } else if self.local && self.is_defined_in_outer_local(key) {
+            (strict_mode.shadow_local, "shadows the local")
+        } else if let Some(similar) = self.similar_name(key) {
+            let msg = format!(
+                "\"{}\" defines \"{}\"; did you mean \"{}\"?",
+                keyword, key, similar
+            );
+            return self.flag(strict_mode.similar_name, msg);
+        } else {
+            return Ok(());
+        };
+
+        self.flag(check, format!("\"{}\" {} \"{}\"", keyword, problem, key))
+    }
+
+    /// Check a `set` of `key` (by the special form `keyword`): it can't be a
+    /// special form, and under the strict mode, a name that isn't defined but
+    /// is close to one that is gets a suggestion
+    pub(crate) fn check_set(&self, keyword: &str, key: &Symbol) -> Result<(), RuntimeError> {
+        self.check_special_form(keyword, key)?;
+
+        let check = self.context.strict_mode.get().similar_name;
+        if check == Check::Allow {
+            return Ok(());
+        }
+
+        match self.similar_name(key) {
+            Some(similar) => self.flag(
+                check,
+                format!(
+                    "\"{}\" sets \"{}\", which isn't defined; did you mean \"{}\"?",
+                    keyword, key, similar
+                ),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Handle a problem flagged by the strict mode
+    fn flag(&self, check: Check, msg: String) -> Result<(), RuntimeError> {
+        match check {
+            Check::Allow => Ok(()),
+            Check::Warn => {
+                self.context.warn(msg);
+                Ok(())
+            }
+            Check::Error => Err(RuntimeError::new(msg)),
+        }
+    }
+
+    /// Fail if `key` is the keyword of a special form (built in, or registered
+    /// on the context), which `keyword` can't define or set: the form would
+    /// still be used wherever it's called, so the definition would be ignored
+    pub(crate) fn check_special_form(
+        &self,
+        keyword: &str,
+        key: &Symbol,
+    ) -> Result<(), RuntimeError> {
+        if is_special_form(&key.0) || self.context.special_forms.borrow().contains_key(key) {
+            Err(RuntimeError::new(format!(
+                "\"{}\" can't redefine the special form \"{}\"",
+                keyword, key
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the key is defined in a global (not local) scope, here or
+    /// further up
+    fn is_defined_globally(&self, key: &Symbol) -> bool {
+        (!self.local && self.entries.contains_key(key))
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.borrow().is_defined_globally(key))
+    }
+
+    /// Whether the key is defined in a local scope above this one (not
+    /// counting this one)
+    fn is_defined_in_outer_local(&self, key: &Symbol) -> bool {
+        let mut env = self.parent.clone();
+        while let Some(current) = env {
+            let current_ref = current.borrow();
+            if !current_ref.local {
+                return false;
+            }
+            if current_ref.entries.contains_key(key) || current_ref.unassigned.contains(key) {
+                return true;
+            }
+            env = current_ref.parent.clone();
+        }
+
+        false
+    }
+
+    /// If the key isn't defined anywhere visible from here, the first (in
+    /// alphabetical order) of the names that are, and are one edit away from
+    /// it
+    fn similar_name(&self, key: &Symbol) -> Option<Symbol> {
+        // short names are close to lots of others without being misspellings
+        if key.0.chars().count() < MIN_SIMILAR_LENGTH
+            || self.get(key).is_some()
+            || self.is_unassigned(key)
+        {
+            return None;
+        }
+
+        let mut similar: Option<Symbol> = None;
+        let mut consider = |name: &Symbol| {
+            if one_edit_apart(&key.0, &name.0) && similar.as_ref().is_none_or(|s| name.0 < s.0) {
+                similar = Some(name.clone());
+            }
+        };
+
+        self.entries.keys().for_each(&mut consider);
+        let mut env = self.parent.clone();
+        while let Some(current) = env {
+            let current_ref = current.borrow();
+            current_ref.entries.keys().for_each(&mut consider);
+            env = current_ref.parent.clone();
+        }
+
+        similar
+    }
+
     /// Define a new key in the current environment
     pub fn define(&mut self, key: Symbol, value: Value) {
+        if !self.unassigned.is_empty() {
+            self.unassigned.retain(|name| *name != key);
+        }
+
+        if let Value::NativeFunc(func) = value {
+            self.context.name_native(func, &key);
+        }
+
         self.entries.insert(key, value);
     }
 
+    /// Declare a key in the current environment without giving it a value
+    /// yet. Until it's defined, it hides any definition further up, but
+    /// there's no value to `get()`.
+    pub fn declare(&mut self, key: Symbol) {
+        if !self.entries.contains_key(&key) && !self.unassigned.contains(&key) {
+            self.unassigned.push(key);
+        }
+    }
+
+    /// Whether the nearest (going upwards) declaration of this key hasn't been
+    /// defined yet
+    pub fn is_unassigned(&self, key: &Symbol) -> bool {
+        if self.entries.contains_key(key) {
+            false
+        } else if self.unassigned.contains(key) {
+            true
+        } else if let Some(parent) = &self.parent {
+            parent.borrow().is_unassigned(key)
+        } else {
+            false
+        }
+    }
+
     /// Find the environment where this key is defined, and update its value.
     /// Returns an Err if the symbol has not been defined anywhere in the hierarchy.
     pub fn set(&mut self, key: Symbol, value: Value) -> Result<(), RuntimeError> {
-        if self.entries.contains_key(&key) {
-            self.entries.insert(key, value);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            *entry = value;
             Ok(())
+        } else if self.unassigned.contains(&key) {
+            Err(RuntimeError::new(format!(
+                "Tried to set value of \"{}\" before it was defined",
+                key
+            )))
         } else if let Some(parent) = &self.parent {
             parent.borrow_mut().set(key, value)
         } else {
-            Err(RuntimeError {
-                msg: format!("Tried to set value of undefined symbol \"{}\"", key),
-            })
+            Err(RuntimeError::new(format!(
+                "Tried to set value of undefined symbol \"{}\"",
+                key
+            )))
         }
     }
 
@@ -65,11 +334,91 @@ impl Env {
     pub fn undefine(&mut self, key: &Symbol) {
         if self.entries.contains_key(key) {
             self.entries.remove(key);
+        } else if self.unassigned.contains(key) {
+            self.unassigned.retain(|name| name != key);
         } else if let Some(parent) = &self.parent {
             parent.borrow_mut().undefine(key);
         }
     }
 
+    /// The closure for a function made in `env` which refers to `names`, with
+    /// copies of the ones bound in local scopes, extending the nearest global
+    /// environment. None if one of them hasn't been defined yet, so there's
+    /// nothing to copy.
+    pub(crate) fn capture(
+        env: &Rc<RefCell<Env>>,
+        mut names: HashSet<Symbol>,
+    ) -> Option<Rc<RefCell<Env>>> {
+        let mut entries = HashMap::new();
+        let mut current = env.clone();
+
+        loop {
+            let parent = {
+                let scope = current.borrow();
+                if !scope.local {
+                    break;
+                }
+
+                for name in &scope.unassigned {
+                    if names.contains(name) {
+                        return None;
+                    }
+                }
+                names.retain(|name| match scope.entries.get(name) {
+                    Some(value) => {
+                        entries.insert(name.clone(), value.clone());
+                        false
+                    }
+                    None => true,
+                });
+
+                match &scope.parent {
+                    Some(parent) => parent.clone(),
+                    None => break,
+                }
+            };
+
+            current = parent;
+        }
+
+        if Rc::ptr_eq(&current, env) {
+            return Some(current);
+        }
+
+        let mut closure = Env::extend_local(current);
+        closure.entries = entries;
+
+        Some(closure.into_shared())
+    }
+
+    /// A name that `value` itself (not just an equal value) is bound to,
+    /// going upwards, and that isn't shadowed by a nearer binding. Names in
+    /// the same environment are tried in alphabetical order.
+    pub(crate) fn name_of(&self, value: &Value) -> Option<Symbol> {
+        let mut names = Vec::new();
+        self.find_names(value, &mut names);
+
+        names.into_iter().find(|name| {
+            self.get(name)
+                .is_some_and(|bound| bound.is_identical(value))
+        })
+    }
+
+    fn find_names(&self, value: &Value, names: &mut Vec<Symbol>) {
+        let start = names.len();
+        names.extend(
+            self.entries
+                .iter()
+                .filter(|(_, bound)| bound.is_identical(value))
+                .map(|(name, _)| name.clone()),
+        );
+        names[start..].sort_by(|a, b| a.0.cmp(&b.0));
+
+        if let Some(parent) = &self.parent {
+            parent.borrow().find_names(value, names);
+        }
+    }
+
     fn display_recursive(&self, output: &mut String, depth: i32) {
         let indent = &(0..depth).map(|_| "  ").collect::<String>();
 
@@ -94,8 +443,14 @@ impl Env {
     }
 }
 
-impl std::fmt::Display for Env {
-    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Display for Env {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         let mut output = String::new();
 
         output.push_str("Env: ");
@@ -104,3 +459,39 @@ impl std::fmt::Display for Env {
         write!(formatter, "{}", &output)
     }
 }
+
+/// How long a name has to be before the strict mode looks for one that it's a
+/// misspelling of
+const MIN_SIMILAR_LENGTH: usize = 4;
+
+/// Whether `b` can be made from `a` by inserting, deleting or replacing one
+/// character, or swapping two neighbouring ones
+fn one_edit_apart(a: &str, b: &str) -> bool {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+
+    if longer.len() - shorter.len() > 1 {
+        return false;
+    }
+
+    let prefix = shorter
+        .iter()
+        .zip(&longer)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix == longer.len() {
+        return false;
+    }
+
+    if shorter.len() < longer.len() {
+        return shorter[prefix..] == longer[prefix + 1..];
+    }
+
+    let swapped = prefix + 1 < longer.len()
+        && shorter[prefix] == longer[prefix + 1]
+        && shorter[prefix + 1] == longer[prefix]
+        && shorter[prefix + 2..] == longer[prefix + 2..];
+
+    swapped || shorter[prefix + 1..] == longer[prefix + 1..]
+}
@@ -0,0 +1,186 @@
+//! Freeing environments which are only kept alive by reference cycles, with
+//! the `gc` feature. Environments and values are reference counted, so a
+//! function defined in an environment (whose closure is that environment), or
+//! a local function that refers to itself, keeps its environment alive after
+//! everything else has let go of it.
+//!
+//! Each context keeps a weak reference to every environment made with
+//! `Env::into_shared()`. Collecting garbage counts how many references each
+//! of those gets from the others (through parents, closures, and values that
+//! nothing else can reach); an environment with more references than that is
+//! in use from outside, by the host or an evaluation in progress. Anything
+//! reachable from one that's in use is too, and the rest are cleared, which
+//! breaks their cycles. References that can't be seen into (like the ones
+//! captured by native closures, or in a suspended generator) are counted as
+//! being from outside, so nothing in use is ever freed, although some garbage
+//! might not be.
+
+use alloc::{
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::{cell::RefCell, mem};
+
+use crate::collections::HashMap;
+
+use super::{Context, Env, Promise, Value};
+
+/// The fewest environments there can be before collecting automatically
+pub(crate) const MIN_THRESHOLD: usize = 4096;
+
+impl Context {
+    /// Keep track of a new environment, collecting garbage first if enough
+    /// have been made since the last time
+    pub(crate) fn track(&self, env: &Rc<RefCell<Env>>) {
+        let count = self.tracked_envs.borrow().len();
+
+        if count >= self.gc_threshold.get() {
+            self.collect_garbage();
+
+            let live = self.tracked_envs.borrow().len();
+            self.gc_threshold.set((live * 2).max(MIN_THRESHOLD));
+        }
+
+        self.tracked_envs.borrow_mut().push(Rc::downgrade(env));
+    }
+
+    /// How many of the environments made with `Env::into_shared()` are still
+    /// alive, whether they're in use or garbage that hasn't been collected yet
+    pub fn live_envs(&self) -> usize {
+        self.tracked_envs
+            .borrow()
+            .iter()
+            .filter(|env| env.strong_count() > 0)
+            .count()
+    }
+
+    /// Free the environments which are only kept alive by reference cycles,
+    /// by clearing their bindings, and return how many there were. This also
+    /// happens automatically as more environments are made (each time the
+    /// number of them doubles).
+    ///
+    /// It's safe to call at any time, including from a native function during
+    /// an evaluation. A host can also use it to tear down an interpreter: after
+    /// dropping the root environment, collecting garbage through a copy of its
+    /// context frees everything that was defined in it.
+    pub fn collect_garbage(&self) -> usize {
+        let envs = self
+            .tracked_envs
+            .borrow()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+        let indices = envs
+            .iter()
+            .enumerate()
+            .map(|(index, env)| (Rc::as_ptr(env), index))
+            .collect::<HashMap<_, _>>();
+
+        // the references each environment gets from the others, and which
+        // ones each of them refers to
+        let mut internal = vec![0; envs.len()];
+        let mut edges = vec![Vec::new(); envs.len()];
+        let mut in_use = vec![false; envs.len()];
+
+        for (index, env) in envs.iter().enumerate() {
+            let Ok(env) = env.try_borrow() else {
+                in_use[index] = true;
+                continue;
+            };
+
+            for target in references(&env) {
+                if let Some(&target) = indices.get(&target) {
+                    internal[target] += 1;
+                    edges[index].push(target);
+                }
+            }
+        }
+
+        // (not counting the reference in `envs`)
+        let mut pending = (0..envs.len())
+            .filter(|&index| in_use[index] || Rc::strong_count(&envs[index]) - 1 > internal[index])
+            .collect::<Vec<_>>();
+        for &index in &pending {
+            in_use[index] = true;
+        }
+
+        while let Some(index) = pending.pop() {
+            for &target in &edges[index] {
+                if !in_use[target] {
+                    in_use[target] = true;
+                    pending.push(target);
+                }
+            }
+        }
+
+        // the bindings are only dropped once all of them have been taken out,
+        // in case dropping them leads to anything else
+        let mut freed = Vec::new();
+        for (env, _) in envs.iter().zip(&in_use).filter(|(_, in_use)| !**in_use) {
+            if let Ok(mut env) = env.try_borrow_mut() {
+                env.unassigned.clear();
+                freed.push((mem::take(&mut env.entries), env.parent.take()));
+            }
+        }
+
+        let count = freed.len();
+        drop(freed);
+        drop(envs);
+
+        self.tracked_envs
+            .borrow_mut()
+            .retain(|env| env.strong_count() > 0);
+
+        count
+    }
+}
+
+/// The environments that `env` refers to: its parent, and those in its
+/// bindings that only it can reach
+fn references(env: &Env) -> Vec<*const RefCell<Env>> {
+    let mut found = Vec::new();
+    found.extend(env.parent.as_ref().map(Rc::as_ptr));
+
+    // each of these is a copy, so anything it's the only other reference to
+    // only belongs to `env`
+    let mut pending = env.entries.values().cloned().collect::<Vec<Value>>();
+
+    while let Some(value) = pending.pop() {
+        match &value {
+            Value::Lambda(lambda) | Value::Macro(lambda) => {
+                found.push(Rc::as_ptr(&lambda.closure));
+            }
+            Value::Env(env) => found.push(Rc::as_ptr(env)),
+            Value::List(list) => pending.extend(list.unshared_items(2)),
+            Value::Values(values) if Rc::strong_count(values) == 2 => {
+                pending.extend(values.iter().cloned())
+            }
+            Value::HashMap(map) if Rc::strong_count(map) == 2 => {
+                if let Ok(map) = map.try_borrow() {
+                    for (key, value) in map.iter() {
+                        pending.push(key.clone());
+                        pending.push(value.clone());
+                    }
+                }
+            }
+            Value::Vector(items) if Rc::strong_count(items) == 2 => {
+                if let Ok(items) = items.try_borrow() {
+                    pending.extend(items.iter().cloned());
+                }
+            }
+            Value::Combinator(combinator) if Rc::strong_count(combinator) == 2 => {
+                pending.extend(combinator.values());
+            }
+            Value::Promise(promise) if Rc::strong_count(promise) == 2 => {
+                match promise.try_borrow().as_deref() {
+                    Ok(Promise::Delayed { env, .. }) => found.push(Rc::as_ptr(env)),
+                    Ok(Promise::Forced(Ok(value))) => pending.push(value.clone()),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    found
+}
@@ -0,0 +1,160 @@
+use alloc::{rc::Rc, vec::Vec};
+use core::cell::RefCell;
+use core::fmt::Debug;
+
+use super::{Env, List, Symbol, Value};
+
+/// A generator created from a lisp function by the `generator` builtin. Each
+/// call to `next` runs the function's body until it reaches a `yield`, and
+/// then suspends it there until the following `next`.
+///
+/// Because the main evaluator is recursive, the body is instead run by a
+/// stepwise evaluator which keeps its continuation in `frames`. Only code
+/// that this stepwise evaluator runs can `yield`: the generator function
+/// itself and any lisp functions it calls directly. Native functions (and
+/// therefore callbacks passed to `map`, etc) run normally and can't `yield`.
+#[derive(Debug)]
+pub struct Generator {
+    pub(crate) state: GeneratorState,
+}
+
+impl Generator {
+    pub(crate) fn new(frames: Vec<Frame>) -> Self {
+        Self {
+            state: GeneratorState::Suspended(frames),
+        }
+    }
+
+    /// Whether the generator's body has run to completion (or failed). Note
+    /// that this only becomes true once a `next` call finds there are no more
+    /// values to yield.
+    pub fn is_exhausted(&self) -> bool {
+        matches!(self.state, GeneratorState::Exhausted)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum GeneratorState {
+    Suspended(Vec<Frame>),
+    Running,
+    Exhausted,
+}
+
+/// A pending step of a suspended computation, waiting on the value of the
+/// expression that's currently being evaluated.
+#[derive(Debug)]
+pub(crate) enum Frame {
+    /// Evaluate the remaining expressions of a body in order
+    Block {
+        env: Rc<RefCell<Env>>,
+        rest: List,
+    },
+    If {
+        env: Rc<RefCell<Env>>,
+        then_expr: Value,
+        else_expr: Option<Value>,
+    },
+    Cond {
+        env: Rc<RefCell<Env>>,
+        body: List,
+        rest: List,
+    },
+    AndOr {
+        env: Rc<RefCell<Env>>,
+        is_or: bool,
+        rest: List,
+    },
+    Define {
+        env: Rc<RefCell<Env>>,
+        symbol: Symbol,
+        is_set: bool,
+    },
+    Let {
+        env: Rc<RefCell<Env>>,
+        symbol: Symbol,
+        declarations: List,
+        body: List,
+    },
+    /// Evaluate the function (if it's `None`) and then each of the arguments
+    Call {
+        env: Rc<RefCell<Env>>,
+        func: Option<Value>,
+        args: Vec<Value>,
+        rest: List,
+        /// Whether the argument being evaluated is spliced into `args`
+        splicing: bool,
+    },
+    /// A `let_values` binding the names of a clause to its values, and then
+    /// going on to the rest of the clauses
+    LetValues {
+        env: Rc<RefCell<Env>>,
+        names: List,
+        clauses: List,
+        body: List,
+    },
+    /// A `for` going through the combinations of items from its clauses.
+    /// `levels` holds the environment each clause's list was evaluated in and
+    /// the items left in it, for the clauses bound so far.
+    For {
+        for_loop: Rc<ForLoop>,
+        levels: Vec<(Rc<RefCell<Env>>, List)>,
+        results: Vec<Value>,
+        stage: ForStage,
+    },
+    /// A `do` loop in `env`, with `values` holding the variables' initial or
+    /// next values evaluated so far
+    Do {
+        do_loop: Rc<DoLoop>,
+        env: Rc<RefCell<Env>>,
+        values: Vec<Value>,
+        stage: DoStage,
+    },
+    Yield,
+}
+
+/// A `for` form that's been read (see `Frame::For`)
+#[derive(Debug)]
+pub(crate) struct ForLoop {
+    /// The name bound to each item of each list, and the list's expression
+    pub(crate) clauses: Vec<(Symbol, Value)>,
+    /// The conditions from the `(when ...)` forms at the start of the body
+    pub(crate) guards: Vec<Value>,
+    pub(crate) body: List,
+}
+
+/// What a `Frame::For` is waiting on the value of
+#[derive(Debug)]
+pub(crate) enum ForStage {
+    /// The list of the next clause, in the environment binding the items of
+    /// the ones before it
+    Items(Rc<RefCell<Env>>),
+    /// The guard at this index, in the environment binding the current items
+    Guard(usize, Rc<RefCell<Env>>),
+    /// The body, whose value is collected
+    Body,
+}
+
+/// A `do` form that's been read (see `Frame::Do`)
+#[derive(Debug)]
+pub(crate) struct DoLoop {
+    /// Each variable, with its initial value and its step (if it has one)
+    pub(crate) clauses: Vec<(Symbol, Value, Option<Value>)>,
+    pub(crate) test: Value,
+    /// The forms evaluated once the test is true
+    pub(crate) exit: List,
+    pub(crate) body: List,
+}
+
+/// What a `Frame::Do` is waiting on the value of
+#[derive(Debug)]
+pub(crate) enum DoStage {
+    /// The initial value of the next variable
+    Init,
+    /// The test, in the environment of the current iteration
+    Test(Rc<RefCell<Env>>),
+    /// The body of the current iteration
+    Body(Rc<RefCell<Env>>),
+    /// The step of the next variable, in the environment of the current
+    /// iteration
+    Step(Rc<RefCell<Env>>),
+}
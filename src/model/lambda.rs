@@ -1,46 +1,172 @@
-use std::cell::RefCell;
-use std::fmt::Debug;
-use std::rc::Rc;
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::cell::RefCell;
+use core::fmt::Debug;
 
-use super::{Env, Symbol, Value};
+use super::{Env, List, Symbol, Value};
+use crate::{collections::HashSet, interpreter::internal_defines};
+
+/// How much of a function's body is shown when it's printed, in chars
+const SUMMARY_LENGTH: usize = 40;
 
 /// A Lisp function defined in Lisp.
 #[derive(Debug, Clone)]
 pub struct Lambda {
+    /// The name it was defined with (by `defun`, `defmacro`, or `define` of
+    /// a function that didn't have one yet), for printing
+    pub name: Option<Symbol>,
     pub closure: Rc<RefCell<Env>>,
     pub argnames: Vec<Symbol>,
-    pub body: Rc<Value>,
+    /// The default value expressions of any `&key` parameters that have one
+    pub key_defaults: Vec<(Symbol, Value)>,
+    /// The expressions of its body, which are evaluated in order
+    pub body: Rc<[Value]>,
+}
+
+impl Lambda {
+    /// The fewest arguments it takes, and the most, or None if it has a rest
+    /// parameter. Each `&key` parameter allows two more (the keyword and its
+    /// value).
+    pub fn arity(&self) -> (usize, Option<usize>) {
+        let is_marker = |name: &Symbol| matches!(&*name.0, "&rest" | "&key" | "...");
+
+        let required = self
+            .argnames
+            .iter()
+            .position(is_marker)
+            .unwrap_or(self.argnames.len());
+
+        if self
+            .argnames
+            .iter()
+            .any(|name| matches!(&*name.0, "&rest" | "..."))
+        {
+            return (required, None);
+        }
+
+        let keys = match self.argnames.iter().position(|name| &*name.0 == "&key") {
+            Some(index) => self.argnames.len() - index - 1,
+            None => 0,
+        };
+
+        (required, Some(required + 2 * keys))
+    }
+
+    /// Its parameter list as it was written, with `(name default)` for each
+    /// `&key` parameter that has a default
+    pub fn params(&self) -> List {
+        self.argnames
+            .iter()
+            .map(
+                |name| match self.key_defaults.iter().find(|(key, _)| key == name) {
+                    Some((key, default)) => Value::List(
+                        [Value::Symbol(key.clone()), default.clone()]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    None => Value::Symbol(name.clone()),
+                },
+            )
+            .collect()
+    }
+
+    /// Its documentation: a string at the start of its body, if there's more
+    /// after it
+    pub fn docstring(&self) -> Option<&str> {
+        match &*self.body {
+            [Value::String(doc), _, ..] => Some(doc),
+            _ => None,
+        }
+    }
+
+    /// Its body, without the docstring (if it has one)
+    pub fn code(&self) -> &[Value] {
+        match self.docstring() {
+            Some(_) => &self.body[1..],
+            None => &self.body,
+        }
+    }
+
+    /// Write it as `#<lambda name (params) body>`, with `kind` in place of
+    /// "lambda", and its body cut short if it's long
+    pub(crate) fn fmt_summary(
+        &self,
+        f: &mut core::fmt::Formatter<'_>,
+        kind: &str,
+    ) -> core::fmt::Result {
+        write!(f, "#<{}", kind)?;
+
+        if let Some(name) = &self.name {
+            write!(f, " {}", name)?;
+        }
+
+        let code = self
+            .code()
+            .iter()
+            .map(|expr| format!("{}", expr))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let params = self.params();
+
+        if params.is_empty() {
+            f.write_str(" ()")?;
+        } else {
+            write!(f, " {}", params)?;
+        }
+
+        if code.chars().count() > SUMMARY_LENGTH {
+            let cut = code.chars().take(SUMMARY_LENGTH).collect::<String>();
+            write!(f, " {}...>", cut.trim_end())
+        } else if code.is_empty() {
+            f.write_str(">")
+        } else {
+            write!(f, " {}>", code)
+        }
+    }
+
+    /// The symbols its parameter defaults and body refer to from outside of
+    /// the function (see `Value::referenced_symbols()`), not counting its
+    /// parameters or internal defines
+    pub fn free_variables(&self) -> HashSet<Symbol> {
+        let function = Value::List(
+            [
+                Value::Symbol(Symbol::from("lambda")),
+                Value::List(self.params()),
+            ]
+            .into_iter()
+            .chain(self.body.iter().cloned())
+            .collect(),
+        );
+
+        let mut free = function.referenced_symbols();
+        for name in internal_defines(self.body.iter()) {
+            free.remove(&name);
+        }
+
+        free
+    }
 }
 
 impl PartialEq for Lambda {
     fn eq(&self, other: &Self) -> bool {
         self.closure.as_ptr() == other.closure.as_ptr()
             && self.argnames == other.argnames
+            && self.key_defaults == other.key_defaults
             && self.body == other.body
     }
 }
 
-impl std::hash::Hash for Lambda {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl core::hash::Hash for Lambda {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         self.closure.as_ptr().hash(state);
         self.argnames.hash(state);
+        self.key_defaults.hash(state);
         self.body.hash(state);
     }
 }
 
-impl std::fmt::Display for Lambda {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let body_str = format!("{}", &self.body);
-
-        return write!(
-            f,
-            "({}) {}",
-            self.argnames
-                .iter()
-                .map(|sym| sym.0.as_str())
-                .collect::<Vec<&str>>()
-                .join(" "),
-            &body_str[1..body_str.chars().count() - 1]
-        );
+impl core::fmt::Display for Lambda {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.fmt_summary(f, "lambda")
     }
 }
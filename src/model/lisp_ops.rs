@@ -0,0 +1,157 @@
+use alloc::rc::Rc;
+use core::{
+    any::{Any, TypeId},
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+};
+
+use super::{Context, ErrorKind, Labels, RuntimeError, Value};
+
+/// How a `Value::Foreign` of some type works with the arithmetic, comparison
+/// and equality builtins, and how it's printed. Implement it for the type, and
+/// register it with `Context::register_lisp_ops()`.
+///
+/// `+`, `-`, `*` and `/` call the method on the first of their arguments
+/// (folding from left to right, like they do for numbers), or on the second
+/// if only that one is foreign, for `+` and `*`. Each operation that isn't
+/// implemented is an error naming both types.
+pub trait LispOps: Any {
+    /// The name of the type, for errors
+    fn type_name(&self) -> &str {
+        "foreign value"
+    }
+
+    fn add(&self, other: &Value) -> Result<Value, RuntimeError> {
+        Err(unsupported("+", self.type_name(), other))
+    }
+
+    fn sub(&self, other: &Value) -> Result<Value, RuntimeError> {
+        Err(unsupported("-", self.type_name(), other))
+    }
+
+    fn mul(&self, other: &Value) -> Result<Value, RuntimeError> {
+        Err(unsupported("*", self.type_name(), other))
+    }
+
+    fn div(&self, other: &Value) -> Result<Value, RuntimeError> {
+        Err(unsupported("/", self.type_name(), other))
+    }
+
+    /// `-` with just this value
+    fn neg(&self) -> Result<Value, RuntimeError> {
+        Err(RuntimeError::new(format!(
+            "Function \"-\" can't negate a {}",
+            self.type_name()
+        )))
+    }
+
+    /// How this compares to `other`, for `<`, `<=`, `>` and `>=`, or `None`
+    /// if they can't be compared
+    fn compare(&self, _other: &Value) -> Option<Ordering> {
+        None
+    }
+
+    /// Whether this is equal to `other`, for `==`, `equal` and `!=` (a value
+    /// is always equal to itself, without asking)
+    fn equals(&self, _other: &Value) -> bool {
+        false
+    }
+
+    /// How `print` (and the REPL) write this
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("<foreign_value>")
+    }
+}
+
+fn unsupported(func_name: &str, type_name: &str, other: &Value) -> RuntimeError {
+    mismatched_types(func_name, type_name, other.type_name())
+}
+
+/// The error for an operation on two values whose types it doesn't apply to
+pub(crate) fn mismatched_types(func_name: &str, this: &str, other: &str) -> RuntimeError {
+    RuntimeError::with_kind(
+        ErrorKind::TypeError,
+        format!(
+            "Function \"{}\" can't be applied to a {} and a {}",
+            func_name, this, other
+        ),
+    )
+}
+
+/// Gets the `LispOps` of a foreign value of the type it was registered for
+pub(crate) type OpsGetter = fn(&dyn Any) -> Option<&dyn LispOps>;
+
+impl Context {
+    /// Have foreign values of type `T` use its `LispOps`, so that `(+ a b)`
+    /// (and so on) works with them
+    pub fn register_lisp_ops<T: LispOps>(&self) {
+        let getter: OpsGetter = |value| value.downcast_ref::<T>().map(|value| value as _);
+
+        self.foreign_ops
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), getter);
+    }
+
+    /// The name of a value's type, from its `LispOps` if it has them
+    pub(crate) fn type_name<'a>(&self, value: &'a Value) -> &'a str {
+        match self.lisp_ops(value) {
+            Some(ops) => ops.type_name(),
+            None => value.type_name(),
+        }
+    }
+
+    /// The `LispOps` registered for the type of a foreign value, if any
+    pub(crate) fn lisp_ops<'a>(&self, value: &'a Value) -> Option<&'a dyn LispOps> {
+        let Value::Foreign(value) = value else {
+            return None;
+        };
+
+        let getter = *self.foreign_ops.borrow().get(&(**value).type_id())?;
+        getter(&**value)
+    }
+
+    /// Show a value the way `print` does: like its `Display`, except that
+    /// foreign values with `LispOps` are written with them, and native
+    /// functions with their names
+    pub fn display<'a>(&'a self, value: &'a Value) -> impl Display + 'a {
+        struct Displayed<'a>(&'a Context, &'a Value);
+
+        impl Display for Displayed<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                let Displayed(context, value) = *self;
+
+                value.fmt_visiting(f, &mut Default::default(), Some(context))
+            }
+        }
+
+        Displayed(self, value)
+    }
+
+    /// Show a value the way `write` does: with labels on its shared structure,
+    /// like `Value::labelled()`, and otherwise like `display()`
+    pub fn write<'a>(&'a self, value: &'a Value) -> impl Display + 'a {
+        struct Written<'a>(&'a Context, &'a Value);
+
+        impl Display for Written<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                let Written(context, value) = *self;
+
+                value.fmt_labelled(f, &mut Labels::of(value), Some(context))
+            }
+        }
+
+        Written(self, value)
+    }
+}
+
+/// Whether `a` and `b` are equal, by the `LispOps` of either if it has them
+pub(crate) fn ops_equal(context: &Context, a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Foreign(this), Value::Foreign(other)) if Rc::ptr_eq(this, other) => true,
+        _ => match (context.lisp_ops(a), context.lisp_ops(b)) {
+            (Some(ops), _) => ops.equals(b),
+            (None, Some(ops)) => ops.equals(a),
+            (None, None) => a == b,
+        },
+    }
+}
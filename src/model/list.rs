@@ -1,15 +1,18 @@
-use std::cell::RefCell;
-use std::fmt::Debug;
-use std::fmt::Display;
-use std::iter::FromIterator;
-use std::rc::Rc;
+use crate::collections::{HashMap, HashSet};
+use alloc::{boxed::Box, rc::Rc, string::String, vec::Vec};
+use cfg_if::cfg_if;
+use core::cell::{RefCell, RefMut};
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::iter::FromIterator;
+use core::mem;
 
-use super::{RuntimeError, Value};
+use super::{Context, RuntimeError, Symbol, Value};
 
 /**
  * A Lisp list, implemented as a linked-list
  */
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Clone)]
 pub struct List {
     head: Option<Rc<RefCell<ConsCell>>>,
 }
@@ -17,14 +20,74 @@ pub struct List {
 impl List {
     pub const NIL: List = List { head: None };
 
+    /// Create a list of the given values, in the same order
+    pub fn from_vec(values: Vec<Value>) -> List {
+        values.into_iter().collect()
+    }
+
+    /// The number of values in the list. Note that this has to walk the
+    /// whole list.
+    pub fn len(&self) -> usize {
+        self.into_iter().count()
+    }
+
+    /// The number of values in the list, or an error if it's circular (or
+    /// in use)
+    pub(crate) fn checked_len(&self) -> Result<usize, RuntimeError> {
+        self.cells().map(|cells| cells.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Get the value at `index`, if the list is long enough
+    pub fn get(&self, index: usize) -> Option<Value> {
+        self.into_iter().nth(index)
+    }
+
+    /// The items of the cons cells that only this list can reach: from the
+    /// start, up to the first cell that's also referred to from somewhere
+    /// else. The first cell is expected to have `head_refs` references (from
+    /// this `List` and any copies of it), or nothing is returned.
+    #[cfg(feature = "gc")]
+    pub(crate) fn unshared_items(&self, head_refs: usize) -> Vec<Value> {
+        let mut items = Vec::new();
+
+        let mut cell = match &self.head {
+            Some(head) if Rc::strong_count(head) == head_refs => head.clone(),
+            _ => return items,
+        };
+
+        loop {
+            let next = {
+                let cell = cell.borrow();
+                items.push(cell.car.clone());
+
+                match &cell.cdr {
+                    Some(next) if Rc::strong_count(next) == 1 => next.clone(),
+                    _ => break,
+                }
+            };
+
+            cell = next;
+        }
+
+        items
+    }
+
     pub fn car(&self) -> Result<Value, RuntimeError> {
         self.head
             .as_ref()
             .map(|rc| rc.borrow().car.clone())
-            .ok_or_else(|| RuntimeError {
-                msg: String::from("Attempted to apply car on nil"),
-            })
+            .ok_or_else(|| RuntimeError::new(String::from("Attempted to apply car on nil")))
     }
+    /// Look at the first value of this list without cloning it, if there is
+    /// one
+    pub(crate) fn with_car<R>(&self, f: impl FnOnce(&Value) -> R) -> Option<R> {
+        self.head.as_ref().map(|rc| f(&rc.borrow().car))
+    }
+
     #[must_use]
     pub fn cdr(&self) -> List {
         List {
@@ -35,65 +98,667 @@ impl List {
         }
     }
 
+    /// The rest of the list after its first value, as a value: like `cdr()`,
+    /// except that for the last cell of a dotted list like `(1 2 . 3)`, it's
+    /// what the list ends in (`3`) instead of NIL
+    pub fn cdr_value(&self) -> Value {
+        match &self.head {
+            Some(rc) => {
+                let cell = rc.borrow();
+
+                match (&cell.cdr, &cell.tail) {
+                    (None, Some(tail)) => (**tail).clone(),
+                    (cdr, _) => Value::List(List { head: cdr.clone() }),
+                }
+            }
+            None => Value::NIL,
+        }
+    }
+
+    /// What this list ends in instead of NIL, if it's a dotted list like
+    /// `(1 2 . 3)`. Its values are the ones before the dot, so iterating over
+    /// it (and its `len()`) leave this out.
+    pub fn dotted_tail(&self) -> Option<Value> {
+        let cells = self.cells().ok()?;
+        let last = cells.last()?.borrow();
+
+        last.tail.as_ref().map(|tail| (**tail).clone())
+    }
+
     #[must_use]
     pub fn cons(&self, val: Value) -> List {
         List {
-            head: Some(Rc::new(RefCell::new(ConsCell {
-                car: val,
-                cdr: self.head.clone(),
-            }))),
+            head: Some(new_cell(val, self.head.clone())),
         }
     }
+
+    /// Replace the first value of this list, in-place. Every other list
+    /// sharing this cons cell will see the change.
+    pub fn set_car(&self, val: Value) -> Result<(), RuntimeError> {
+        let head = self
+            .head
+            .as_ref()
+            .ok_or_else(|| RuntimeError::new(String::from("Attempted to apply set_car on nil")))?;
+
+        head.borrow_mut().car = val;
+
+        Ok(())
+    }
+
+    /// Replace everything after the first value of this list, in-place. Every
+    /// other list sharing this cons cell will see the change. This can be used
+    /// to create cyclic lists.
+    pub fn set_cdr(&self, cdr: &List) -> Result<(), RuntimeError> {
+        let head = self
+            .head
+            .as_ref()
+            .ok_or_else(|| RuntimeError::new(String::from("Attempted to apply set_cdr on nil")))?;
+
+        let mut head = head.borrow_mut();
+        head.cdr = cdr.head.clone();
+        head.tail = None;
+
+        Ok(())
+    }
+
+    /// Like `set_cdr()`, but `cdr` can be something other than a list, which
+    /// makes this the last cell of a dotted list
+    pub fn set_cdr_value(&self, cdr: Value) -> Result<(), RuntimeError> {
+        match cdr {
+            Value::List(cdr) => self.set_cdr(&cdr),
+            tail => {
+                let head = self.head.as_ref().ok_or_else(|| {
+                    RuntimeError::new(String::from("Attempted to apply set_cdr on nil"))
+                })?;
+
+                let mut head = head.borrow_mut();
+                head.cdr = None;
+                head.tail = Some(Box::new(tail));
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// `items` consed onto `rest`, in order: a list if `rest` is one, a dotted
+/// list like `(1 2 . 3)` if it isn't, or just `rest` if there are no items
+pub(crate) fn cons_all(items: &[Value], rest: Value) -> Value {
+    let Some((last, init)) = items.split_last() else {
+        return rest;
+    };
+
+    let list = match rest {
+        Value::List(rest) => rest.cons(last.clone()),
+        tail => {
+            let cell = new_cell(last.clone(), None);
+            cell.borrow_mut().tail = Some(Box::new(tail));
+
+            List { head: Some(cell) }
+        }
+    };
+
+    Value::List(
+        init.iter()
+            .rev()
+            .fold(list, |list, item| list.cons(item.clone())),
+    )
 }
 
-impl<'a> List {
-    pub fn into_iter(list: &'a List) -> ConsIterator {
+impl List {
+    pub fn into_iter(list: &List) -> ConsIterator {
         ConsIterator(list.head.clone())
     }
+
+    /// Whether both lists start at the same cons cell (or are both NIL)
+    pub fn ptr_eq(&self, other: &List) -> bool {
+        match (&self.head, &other.head) {
+            (Some(this), Some(other)) => Rc::ptr_eq(this, other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// The address of the first cons cell, to tell whether a list has been
+    /// seen before when walking a structure which may be cyclic
+    pub(crate) fn head_ptr(&self) -> Option<*const ()> {
+        self.head.as_ref().map(cell_ptr)
+    }
+}
+
+/// Destructive versions of the list operations, for big lists where copying
+/// is too expensive. These change the list's cons cells in-place (so every
+/// other list sharing them sees the change), but keep the same first cell
+/// wherever they can, so the original list sees the result.
+impl List {
+    /// Sort the values of the list, in-place
+    pub fn sort_in_place(&self) -> Result<(), RuntimeError> {
+        let cells = self.cells()?;
+        let mut values = borrow_all(&cells)?
+            .iter_mut()
+            .map(|cell| mem::replace(&mut cell.car, Value::NIL))
+            .collect::<Vec<Value>>();
+
+        // (comparing the values might look into the list itself, so it can't
+        // be borrowed in the meantime)
+        values.sort();
+
+        for (mut cell, value) in borrow_all(&cells)?.into_iter().zip(values) {
+            cell.car = value;
+        }
+
+        Ok(())
+    }
+
+    /// Reverse the order of the list's values, in-place
+    pub fn reverse_in_place(&self) -> Result<(), RuntimeError> {
+        let cells = self.cells()?;
+        let mut cells = borrow_all(&cells)?;
+
+        let middle = cells.len() / 2;
+        let (front, back) = cells.split_at_mut(middle);
+        for (a, b) in front.iter_mut().zip(back.iter_mut().rev()) {
+            mem::swap(&mut a.car, &mut b.car);
+        }
+
+        Ok(())
+    }
+
+    /// Attach `other` to the end of this list, in-place, and return the
+    /// result (which is just `other` if this list is empty). `other` isn't
+    /// copied, so changing it later changes this list too.
+    pub fn append_in_place(&self, other: &List) -> Result<List, RuntimeError> {
+        let Some(last) = self.cells()?.pop() else {
+            return Ok(other.clone());
+        };
+
+        if other.cells()?.iter().any(|cell| Rc::ptr_eq(cell, &last)) {
+            return Err(RuntimeError::new(String::from(
+                "Appending would make the list circular",
+            )));
+        }
+
+        let mut last = last.try_borrow_mut().map_err(|_| in_use())?;
+        last.cdr = other.head.clone();
+        last.tail = None;
+
+        Ok(self.clone())
+    }
+
+    /// Remove every value equal to `item` from the list, in-place, and return
+    /// the result. The values that are kept are moved up into the first
+    /// cells, so the original list sees the result unless nothing is left
+    /// (then it's unchanged, and NIL is returned).
+    pub fn delete_in_place(&self, item: &Value) -> Result<List, RuntimeError> {
+        let cells = self.cells()?;
+        let keep = cells
+            .iter()
+            .map(|cell| Ok(cell.try_borrow().map_err(|_| in_use())?.car != *item))
+            .collect::<Result<Vec<bool>, RuntimeError>>()?;
+        let mut cells = borrow_all(&cells)?;
+
+        let mut kept = 0;
+        for index in 0..cells.len() {
+            if keep[index] {
+                let car = mem::replace(&mut cells[index].car, Value::NIL);
+                cells[kept].car = car;
+                kept += 1;
+            }
+        }
+
+        if kept == 0 {
+            return Ok(List::NIL);
+        }
+
+        // the end of a dotted list stays at the end
+        let tail = cells.last_mut().and_then(|cell| cell.tail.take());
+        cells[kept - 1].cdr = None;
+        cells[kept - 1].tail = tail;
+
+        Ok(self.clone())
+    }
+
+    /// Every cons cell of the list, in order, or an error if it's circular
+    fn cells(&self) -> Result<Vec<Rc<RefCell<ConsCell>>>, RuntimeError> {
+        let mut cells: Vec<Rc<RefCell<ConsCell>>> = Vec::new();
+        let mut next = self.head.clone();
+
+        while let Some(cell) = next {
+            // comparing each cell with the one halfway back finds any cycle
+            // (once both are in it, and the distance is a multiple of its
+            // length)
+            if let Some(halfway) = cells.get(cells.len() / 2) {
+                if Rc::ptr_eq(halfway, &cell) {
+                    return Err(RuntimeError::new(String::from("The list is circular")));
+                }
+            }
+
+            next = cell.try_borrow().map_err(|_| in_use())?.cdr.clone();
+            cells.push(cell);
+        }
+
+        Ok(cells)
+    }
+}
+
+/// Borrow all of the cells mutably, or none of them if any are in use
+fn borrow_all(cells: &[Rc<RefCell<ConsCell>>]) -> Result<Vec<RefMut<'_, ConsCell>>, RuntimeError> {
+    cells
+        .iter()
+        .map(|cell| cell.try_borrow_mut().map_err(|_| in_use()))
+        .collect()
+}
+
+fn in_use() -> RuntimeError {
+    RuntimeError::new(String::from("The list is in use"))
 }
 
 /// A `ConsCell` is effectively a linked-list node, where the value in each node
 /// is a lisp `Value`. To be used as a true "list", the ConsCell must be wrapped
 /// in Value::List().
-#[derive(Debug, PartialEq, Eq)]
 struct ConsCell {
     pub car: Value,
     pub cdr: Option<Rc<RefCell<ConsCell>>>,
+    /// What a dotted list ends in instead of NIL, on its last cell (so `cdr`
+    /// is `None`). It's never a list, which would go in `cdr`.
+    pub tail: Option<Box<Value>>,
 }
 
-impl std::hash::Hash for ConsCell {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl core::hash::Hash for ConsCell {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         self.car.hash(state);
         self.cdr.as_ref().map(|rc| rc.as_ptr()).hash(state);
+        self.tail.hash(state);
     }
 }
 
+impl List {
+    /// Write the list, skipping any cons cell that's already being written
+    /// further up (in `visiting`) so that cyclic lists print as a `#<cycle>`
+    /// marker instead of forever
+    pub(crate) fn fmt_visiting(
+        &self,
+        formatter: &mut core::fmt::Formatter,
+        visiting: &mut HashSet<*const ()>,
+        context: Option<&Context>,
+    ) -> core::fmt::Result {
+        let head = match &self.head {
+            Some(head) => head,
+            None => return write!(formatter, "NIL"),
+        };
+
+        if visiting.contains(&cell_ptr(head)) {
+            return formatter.write_str("#<cycle>");
+        }
+
+        // `(quote x)` and `(comma x)` are written the way they're read, as
+        // `'x` and `,x` (but not with any other number of arguments)
+        if let Some(prefix) = reader_prefix(head) {
+            let quoted = match &head.borrow().cdr {
+                Some(rest) if is_last(rest) => Some(rest.borrow().car.clone()),
+                _ => None,
+            };
+
+            if let Some(quoted) = quoted {
+                visiting.insert(cell_ptr(head));
+                formatter.write_str(prefix)?;
+                let result = quoted.fmt_visiting(formatter, visiting, context);
+                visiting.remove(&cell_ptr(head));
+
+                return result;
+            }
+        }
+
+        formatter.write_str("(")?;
+
+        let mut added = Vec::new();
+        let mut next = Some(head.clone());
+
+        while let Some(cell) = next {
+            if !added.is_empty() {
+                if visiting.contains(&cell_ptr(&cell)) {
+                    formatter.write_str(" . #<cycle>")?;
+                    break;
+                }
+
+                formatter.write_str(" ")?;
+            }
+
+            visiting.insert(cell_ptr(&cell));
+            added.push(cell_ptr(&cell));
+
+            let cell = cell.borrow();
+
+            cell.car.fmt_visiting(formatter, visiting, context)?;
+
+            if let Some(tail) = &cell.tail {
+                formatter.write_str(" . ")?;
+                tail.fmt_visiting(formatter, visiting, context)?;
+            }
+
+            next = cell.cdr.clone();
+        }
+
+        for ptr in added {
+            visiting.remove(&ptr);
+        }
+
+        formatter.write_str(")")
+    }
+}
+
+/// The cons cells in a value that are reached more than once, and the labels
+/// given to the ones that have been written so far, for writing the value so
+/// that it reads back with the same structure
+#[derive(Default)]
+pub(crate) struct Labels {
+    shared: HashSet<*const ()>,
+    numbers: HashMap<*const (), usize>,
+}
+
+impl Labels {
+    /// Find the cells that are reached more than once in `value`, through
+    /// the items of its lists (and of the lists in those), or by a cycle
+    pub(crate) fn of(value: &Value) -> Self {
+        let mut seen = HashSet::new();
+        let mut shared = HashSet::new();
+        let mut pending = vec![value.clone()];
+
+        while let Some(value) = pending.pop() {
+            let Value::List(list) = value else {
+                continue;
+            };
+
+            let mut next = list.head.clone();
+            while let Some(cell) = next {
+                if !seen.insert(cell_ptr(&cell)) {
+                    shared.insert(cell_ptr(&cell));
+                    break;
+                }
+
+                let cell = cell.borrow();
+                pending.push(cell.car.clone());
+                pending.extend(cell.tail.as_deref().cloned());
+                next = cell.cdr.clone();
+            }
+        }
+
+        Labels {
+            shared,
+            numbers: HashMap::new(),
+        }
+    }
+
+    /// Write the label for a shared cell: `#N#` if it's been written already
+    /// (returning true, since there's nothing more to write), or `#N=` before
+    /// it's written the first time
+    fn write_label(
+        &mut self,
+        formatter: &mut core::fmt::Formatter,
+        cell: &Rc<RefCell<ConsCell>>,
+    ) -> Result<bool, core::fmt::Error> {
+        let ptr = cell_ptr(cell);
+        if !self.shared.contains(&ptr) {
+            return Ok(false);
+        }
+
+        if let Some(number) = self.numbers.get(&ptr) {
+            write!(formatter, "#{}#", number)?;
+            return Ok(true);
+        }
+
+        let number = self.numbers.len();
+        self.numbers.insert(ptr, number);
+        write!(formatter, "#{}=", number)?;
+
+        Ok(false)
+    }
+}
+
+impl List {
+    /// Write the list with `#N=` and `#N#` labels on its shared cells (see
+    /// `Value::labelled()`). A shared cell in the middle of a list starts a
+    /// dotted tail, like `(1 . #0=(2 3))`.
+    pub(crate) fn fmt_labelled(
+        &self,
+        formatter: &mut core::fmt::Formatter,
+        labels: &mut Labels,
+        context: Option<&Context>,
+    ) -> core::fmt::Result {
+        let head = match &self.head {
+            Some(head) => head,
+            None => return write!(formatter, "NIL"),
+        };
+
+        if labels.write_label(formatter, head)? {
+            return Ok(());
+        }
+
+        // the shorthand for `(quote x)` can't show a label on its second cell
+        if let Some(prefix) = reader_prefix(head) {
+            let quoted = match &head.borrow().cdr {
+                Some(rest) if is_last(rest) && !labels.shared.contains(&cell_ptr(rest)) => {
+                    Some(rest.borrow().car.clone())
+                }
+                _ => None,
+            };
+
+            if let Some(quoted) = quoted {
+                formatter.write_str(prefix)?;
+                return quoted.fmt_labelled(formatter, labels, context);
+            }
+        }
+
+        formatter.write_str("(")?;
+
+        let mut next = Some(head.clone());
+        let mut first = true;
+        while let Some(cell) = next {
+            if !mem::take(&mut first) {
+                if labels.shared.contains(&cell_ptr(&cell)) {
+                    formatter.write_str(" . ")?;
+                    List { head: Some(cell) }.fmt_labelled(formatter, labels, context)?;
+                    break;
+                }
+
+                formatter.write_str(" ")?;
+            }
+
+            let cell = cell.borrow();
+            cell.car.fmt_labelled(formatter, labels, context)?;
+
+            if let Some(tail) = &cell.tail {
+                formatter.write_str(" . ")?;
+                tail.fmt_labelled(formatter, labels, context)?;
+            }
+
+            next = cell.cdr.clone();
+        }
+
+        formatter.write_str(")")
+    }
+}
+
+/// Replace each reference to `placeholder` in `value` with `target`, both as
+/// an item and as the rest of a list. The parser reads a reference to a label
+/// whose value isn't finished yet (like the `#0#` in `#0=(1 . #0#)`) as a
+/// placeholder, which is replaced this way once it is. (Where the placeholder
+/// is the rest of a list, `target` contains that list, so it's a list too.)
+pub(crate) fn resolve_placeholder(value: &Value, placeholder: &List, target: &Value) {
+    let is_placeholder = |cell: &Option<Rc<RefCell<ConsCell>>>| {
+        cell.as_ref().map(cell_ptr) == placeholder.head_ptr()
+    };
+
+    let mut seen = HashSet::new();
+    let mut pending = vec![value.clone()];
+
+    while let Some(value) = pending.pop() {
+        let Value::List(list) = value else {
+            continue;
+        };
+
+        let mut next = list.head.clone();
+        while let Some(cell) = next {
+            if !seen.insert(cell_ptr(&cell)) {
+                break;
+            }
+
+            let mut cell = cell.borrow_mut();
+
+            match &cell.car {
+                Value::List(item) if is_placeholder(&item.head) => cell.car = target.clone(),
+                item => pending.push(item.clone()),
+            }
+            pending.extend(cell.tail.as_deref().cloned());
+
+            if is_placeholder(&cell.cdr) {
+                if let Value::List(target) = target {
+                    cell.cdr = target.head.clone();
+                }
+                break;
+            }
+
+            next = cell.cdr.clone();
+        }
+    }
+}
+
+/// Whether `cell` ends its list, with nothing after it (not even a dotted
+/// tail)
+fn is_last(cell: &Rc<RefCell<ConsCell>>) -> bool {
+    let cell = cell.borrow();
+
+    cell.cdr.is_none() && cell.tail.is_none()
+}
+
+/// The reader shorthand for a list's head symbol, if it has one
+fn reader_prefix(head: &Rc<RefCell<ConsCell>>) -> Option<&'static str> {
+    match &head.borrow().car {
+        Value::Symbol(Symbol(name)) => match &**name {
+            "quote" => Some("'"),
+            "comma" => Some(","),
+            "splice" => Some("@"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn cell_ptr(cell: &Rc<RefCell<ConsCell>>) -> *const () {
+    Rc::as_ptr(cell) as *const ()
+}
+
 impl Display for List {
-    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if let Some(head) = &self.head {
-            write!(formatter, "({})", head.as_ref().borrow())
-        } else {
-            write!(formatter, "NIL")
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.fmt_visiting(formatter, &mut HashSet::new(), None)
+    }
+}
+
+impl Debug for List {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(formatter, "List({})", self)
+    }
+}
+
+impl PartialEq for List {
+    // Compares cell by cell with a work-list instead of recursing, so long
+    // or deeply nested lists can't overflow the stack. Pairs of cells which
+    // are already being compared are skipped, so comparing cyclic lists
+    // terminates (they're equal if no difference is found along the cycle).
+    // That also keeps comparing shared structure linear: the same cell (or
+    // pair of cells) reached through many paths is only compared once.
+    fn eq(&self, other: &Self) -> bool {
+        // comparing against NIL, or a list against itself, is common enough
+        // to skip the setup for
+        if self.head.is_none() || other.head.is_none() || self.ptr_eq(other) {
+            return self.ptr_eq(other);
         }
+
+        let mut pending = vec![(self.head.clone(), other.head.clone())];
+        let mut compared = HashSet::new();
+
+        while let Some(pair) = pending.pop() {
+            match pair {
+                (None, None) => {}
+                (Some(this), Some(other)) => {
+                    if Rc::ptr_eq(&this, &other)
+                        || !compared.insert((cell_ptr(&this), cell_ptr(&other)))
+                    {
+                        continue;
+                    }
+
+                    let (this, other) = (this.borrow(), other.borrow());
+
+                    match (&this.car, &other.car) {
+                        (Value::List(this_car), Value::List(other_car)) => {
+                            pending.push((this_car.head.clone(), other_car.head.clone()))
+                        }
+                        (this_car, other_car) => {
+                            if this_car != other_car {
+                                return false;
+                            }
+                        }
+                    }
+
+                    if this.tail != other.tail {
+                        return false;
+                    }
+
+                    pending.push((this.cdr.clone(), other.cdr.clone()));
+                }
+                _ => return false,
+            }
+        }
+
+        true
     }
 }
 
-impl Display for ConsCell {
-    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self.cdr.as_ref() {
-            Some(cdr) => write!(formatter, "{} {}", self.car, cdr.borrow()),
-            None => write!(formatter, "{}", self.car),
+impl Eq for List {}
+
+impl Drop for ConsCell {
+    // Dropping the cells one by one recursively can overflow the stack for
+    // long (or deeply nested) lists, so any cells that this one solely owns
+    // are unlinked (and recycled) in a loop instead
+    fn drop(&mut self) {
+        let mut nested = Vec::new();
+        let mut next = self.cdr.take();
+
+        if let Value::List(list) = &mut self.car {
+            nested.extend(list.head.take());
+        }
+
+        while let Some(rc) = next.take().or_else(|| nested.pop()) {
+            if Rc::strong_count(&rc) == 1 {
+                let contents = {
+                    let mut cell = rc.borrow_mut();
+
+                    next = cell.cdr.take();
+
+                    if let Value::List(list) = &mut cell.car {
+                        nested.extend(list.head.take());
+                    }
+
+                    (
+                        core::mem::replace(&mut cell.car, Value::NIL),
+                        cell.tail.take(),
+                    )
+                };
+
+                recycle(rc);
+                drop(contents);
+            }
         }
     }
 }
 
-impl std::hash::Hash for List {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl core::hash::Hash for List {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         self.head.as_ref().map(|rc| rc.as_ptr()).hash(state);
     }
 }
 
-impl<'a> IntoIterator for &'a List {
+impl IntoIterator for &List {
     type Item = Value;
     type IntoIter = ConsIterator;
 
@@ -136,10 +801,7 @@ impl FromIterator<Value> for List {
 
         for val in iter {
             // The cons cell for the current value
-            let new_cons = Rc::new(RefCell::new(ConsCell {
-                car: val,
-                cdr: None,
-            }));
+            let new_cons = new_cell(val, None);
 
             // if this is the first cell, put it in the List
             if new_list.head.is_none() {
@@ -162,3 +824,83 @@ impl<'a> FromIterator<&'a Value> for List {
         iter.into_iter().cloned().collect()
     }
 }
+
+// The head cell of a list that's being dropped goes back to the pool too (the
+// rest are handled by `ConsCell`'s `drop()`)
+impl Drop for List {
+    fn drop(&mut self) {
+        if let Some(head) = self.head.take() {
+            if Rc::strong_count(&head) == 1 {
+                let contents = {
+                    let mut cell = head.borrow_mut();
+
+                    ConsCell {
+                        car: core::mem::replace(&mut cell.car, Value::NIL),
+                        cdr: cell.cdr.take(),
+                        tail: cell.tail.take(),
+                    }
+                };
+
+                recycle(head);
+                drop(contents);
+            }
+        }
+    }
+}
+
+// Most lists are short-lived, so instead of being freed, emptied cons cells
+// are kept in a per-thread pool (up to a limit) for new ones to reuse. This
+// saves an allocation and deallocation for each cell in code that builds lots
+// of temporary lists.
+cfg_if! {
+    if #[cfg(feature = "std")] {
+        /// How many free cells each thread keeps around
+        const MAX_FREE_CELLS: usize = 4096;
+
+        std::thread_local! {
+            static FREE_CELLS: RefCell<Vec<Rc<RefCell<ConsCell>>>> = const { RefCell::new(Vec::new()) };
+        }
+
+        fn new_cell(car: Value, cdr: Option<Rc<RefCell<ConsCell>>>) -> Rc<RefCell<ConsCell>> {
+            // (the pool isn't available while the thread is shutting down)
+            let free = FREE_CELLS
+                .try_with(|free| free.borrow_mut().pop())
+                .ok()
+                .flatten();
+
+            match free {
+                Some(mut rc) => {
+                    // cells only go in the pool when nothing else refers to them
+                    let cell = Rc::get_mut(&mut rc).unwrap().get_mut();
+                    cell.car = car;
+                    cell.cdr = cdr;
+                    cell.tail = None;
+
+                    rc
+                }
+                None => Rc::new(RefCell::new(ConsCell { car, cdr, tail: None })),
+            }
+        }
+
+        /// Put an emptied cell (which nothing else refers to) in the pool
+        fn recycle(rc: Rc<RefCell<ConsCell>>) {
+            if Rc::weak_count(&rc) > 0 {
+                return;
+            }
+
+            let _ = FREE_CELLS.try_with(|free| {
+                let mut free = free.borrow_mut();
+
+                if free.len() < MAX_FREE_CELLS {
+                    free.push(rc);
+                }
+            });
+        }
+    } else {
+        fn new_cell(car: Value, cdr: Option<Rc<RefCell<ConsCell>>>) -> Rc<RefCell<ConsCell>> {
+            Rc::new(RefCell::new(ConsCell { car, cdr, tail: None }))
+        }
+
+        fn recycle(_rc: Rc<RefCell<ConsCell>>) {}
+    }
+}
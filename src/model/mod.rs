@@ -25,16 +25,53 @@ cfg_if! {
     }
 }
 
+mod cancel;
+mod closure_capture;
+mod combinator;
+mod context;
 mod env;
+#[cfg(feature = "gc")]
+mod gc;
+mod generator;
 mod lambda;
+mod lisp_ops;
 mod list;
+mod log_level;
+mod promise;
+mod resource_limits;
 mod runtime_error;
+mod strict_mode;
 mod symbol;
+mod truthiness;
 mod value;
+mod walk;
 
+pub use cancel::CancelHandle;
+pub use closure_capture::ClosureCapture;
+pub use combinator::{Combinator, Function};
+#[cfg(feature = "std")]
+pub(crate) use context::TIMED_OUT;
+pub use context::{Context, Logger, SpecialFormHandler, WarningHandler};
+pub(crate) use context::{CANCELLED, STEP_LIMIT_EXCEEDED};
 pub use env::Env;
+pub use generator::Generator;
+pub(crate) use generator::{DoLoop, DoStage, ForLoop, ForStage, Frame, GeneratorState};
 pub use lambda::Lambda;
+pub use lisp_ops::LispOps;
+pub(crate) use lisp_ops::{mismatched_types, ops_equal, OpsGetter};
 pub use list::List;
-pub use runtime_error::RuntimeError;
+pub(crate) use list::{cons_all, resolve_placeholder, Labels};
+pub use log_level::LogLevel;
+pub use promise::Promise;
+pub(crate) use resource_limits::Allocated;
+pub use resource_limits::{Limit, ResourceLimits};
+pub use runtime_error::{ErrorKind, RuntimeError};
+pub use strict_mode::{Check, StrictMode};
 pub use symbol::Symbol;
-pub use value::{HashMapRc, NativeFunc, Value};
+pub use truthiness::Truthiness;
+pub(crate) use value::{
+    checked_div, float_to_scientific, int_from_i128, int_to_i128, int_to_string_radix,
+    int_to_usize, int_type_to_float_type, non_finite_float, parse_number_radix, sorted_members,
+    write_bytes_literal, CHAR_NAMES,
+};
+pub use value::{ArithmeticError, HashMapRc, NativeClosure, NativeFunc, SetRc, Value};
@@ -0,0 +1,14 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt::Debug;
+
+use super::{Env, RuntimeError, Value};
+
+/// An expression whose evaluation has been put off until it's needed, created
+/// by the `delay` special form. The first `force` evaluates it and remembers
+/// the result (or error), which every later `force` then returns.
+#[derive(Debug)]
+pub enum Promise {
+    Delayed { expr: Value, env: Rc<RefCell<Env>> },
+    Forced(Result<Value, RuntimeError>),
+}
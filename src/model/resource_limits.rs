@@ -0,0 +1,148 @@
+use alloc::string::String;
+
+use super::{Context, ErrorKind, RuntimeError};
+
+/// How much memory one top-level evaluation can allocate, for running code
+/// that isn't trusted. This is set per interpreter, on its
+/// [`Context`](super::Context), and is enforced by the builtins that make new
+/// lists, strings and collections; going over a limit is an error. With no
+/// limits (the default), nothing is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResourceLimits {
+    /// How many cons cells can be allocated, counting each item of each new
+    /// list
+    pub max_conses: Option<usize>,
+    /// How many bytes of new strings can be created, counting the whole of
+    /// each one (so appending to a string counts the string again)
+    pub max_string_bytes: Option<usize>,
+    /// How many items a single new list, vector, hash map, set or bytes can
+    /// have
+    pub max_collection_len: Option<usize>,
+}
+
+impl ResourceLimits {
+    /// Nothing is limited (the default)
+    pub const UNLIMITED: ResourceLimits = ResourceLimits {
+        max_conses: None,
+        max_string_bytes: None,
+        max_collection_len: None,
+    };
+}
+
+/// Which of the `ResourceLimits` was gone over, carried by an error of kind
+/// `ErrorKind::ResourceLimit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    MaxConses,
+    MaxStringBytes,
+    MaxCollectionLen,
+}
+
+impl Limit {
+    /// The name of the limit's field in `ResourceLimits`
+    pub fn name(self) -> &'static str {
+        match self {
+            Limit::MaxConses => "max_conses",
+            Limit::MaxStringBytes => "max_string_bytes",
+            Limit::MaxCollectionLen => "max_collection_len",
+        }
+    }
+}
+
+/// What's been allocated so far in the current top-level evaluation, while
+/// there are `ResourceLimits`
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Allocated {
+    conses: usize,
+    string_bytes: usize,
+}
+
+/// The start of the error when a resource limit is gone over, which goes on
+/// to say which one
+pub(crate) const RESOURCE_LIMIT_EXCEEDED: &str = "Resource limit exceeded: ";
+
+impl Context {
+    /// Count a new list of `len()` items towards the `max_conses`, and check
+    /// it against the `max_collection_len` (`len` is only called if there are
+    /// limits to check)
+    pub(crate) fn allocate_list(&self, len: impl FnOnce() -> usize) -> Result<(), RuntimeError> {
+        let limits = self.resource_limits.get();
+        if limits.max_conses.is_none() && limits.max_collection_len.is_none() {
+            return Ok(());
+        }
+
+        let len = len();
+        self.allocate_conses(len)?;
+        self.check_collection_len(len)
+    }
+
+    /// Count `count` new cons cells towards the `max_conses`
+    pub(crate) fn allocate_conses(&self, count: usize) -> Result<(), RuntimeError> {
+        let Some(max_conses) = self.resource_limits.get().max_conses else {
+            return Ok(());
+        };
+
+        let mut allocated = self.allocated.get();
+        allocated.conses = allocated.conses.saturating_add(count);
+        self.allocated.set(allocated);
+
+        if allocated.conses > max_conses {
+            Err(exceeded(
+                Limit::MaxConses,
+                format!("more than {} cons cells were allocated", max_conses),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Count a new string of `bytes` bytes towards the `max_string_bytes`
+    pub(crate) fn allocate_string(&self, bytes: usize) -> Result<(), RuntimeError> {
+        let Some(max_string_bytes) = self.resource_limits.get().max_string_bytes else {
+            return Ok(());
+        };
+
+        let mut allocated = self.allocated.get();
+        allocated.string_bytes = allocated.string_bytes.saturating_add(bytes);
+        self.allocated.set(allocated);
+
+        if allocated.string_bytes > max_string_bytes {
+            Err(exceeded(
+                Limit::MaxStringBytes,
+                format!(
+                    "more than {} bytes of strings were created",
+                    max_string_bytes
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check the length of a new (or grown) collection against the
+    /// `max_collection_len`
+    pub(crate) fn check_collection_len(&self, len: usize) -> Result<(), RuntimeError> {
+        match self.resource_limits.get().max_collection_len {
+            Some(max_len) if len > max_len => Err(exceeded(
+                Limit::MaxCollectionLen,
+                format!(
+                    "a collection of {} items is longer than the limit of {}",
+                    len, max_len
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start counting allocations again, for a new top-level evaluation
+    pub(crate) fn reset_allocated(&self) {
+        self.allocated.set(Allocated::default());
+    }
+}
+
+fn exceeded(limit: Limit, details: String) -> RuntimeError {
+    RuntimeError::with_kind(
+        ErrorKind::ResourceLimit(limit),
+        format!("{}{}", RESOURCE_LIMIT_EXCEEDED, details),
+    )
+}
@@ -1,17 +1,76 @@
-use std::fmt::Debug;
+use alloc::string::String;
+use core::fmt::Debug;
 
-/// An error that occurred while evaluating some lisp code
-#[derive(Debug, Clone, PartialEq, Eq)]
+use super::Limit;
+
+/// An error that occurred while evaluating some lisp code. Two errors are
+/// equal when their messages are, whatever their kinds.
+#[derive(Debug, Clone, Eq, Default)]
 pub struct RuntimeError {
     pub msg: String,
+    /// What went wrong, set by whatever raised the error (see `kind()`)
+    pub(crate) kind: ErrorKind,
+}
+
+/// What went wrong, for a [`RuntimeError`], so that the host can tell the
+/// interpreter's limits being reached from an ordinary error without looking
+/// at the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Anything else: an error raised by `error`, by a native function, etc
+    #[default]
+    UserError,
+    /// Evaluation nested deeper than the context's `max_depth`
+    DepthLimit,
+    /// A `with_step_limit` (or `with_timeout`) ran out
+    BudgetExhausted,
+    /// Code read at run time (by `eval_str`, `load`, etc) couldn't be parsed
+    ParseError,
+    /// A builtin was given an argument of the wrong type
+    TypeError,
+    /// Evaluation was cancelled through a `CancelHandle`. Nothing in Lisp
+    /// can catch this: the fallbacks of `with_step_limit` and `with_timeout`
+    /// only replace their own limit running out
+    Interrupted,
+    /// One of the context's `ResourceLimits` was gone over
+    ResourceLimit(Limit),
+}
+
+impl RuntimeError {
+    /// An ordinary error (a `UserError`) with the message `msg`
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self::with_kind(ErrorKind::UserError, msg)
+    }
+
+    /// An error of the given kind with the message `msg`
+    pub fn with_kind(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        Self {
+            msg: msg.into(),
+            kind,
+        }
+    }
+
+    /// What kind of error this is. It's a `UserError` unless the interpreter
+    /// raised it for one of the other reasons, whatever the message says.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl PartialEq for RuntimeError {
+    fn eq(&self, other: &Self) -> bool {
+        self.msg == other.msg
+    }
 }
 
-impl std::fmt::Display for RuntimeError {
-    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for RuntimeError {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(formatter, "Runtime error: {}", self.msg)
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for RuntimeError {
     fn description(&self) -> &str {
         &self.msg
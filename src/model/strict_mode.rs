@@ -0,0 +1,55 @@
+/// Which definitions are flagged as likely mistakes, and how. This is set per
+/// interpreter, on its [`Context`](super::Context), and is checked by
+/// `define`, `defun`, `defmacro` and `define_syntax`, and (for misspelled
+/// names) by `set`. Referring to, or `set`ting, a variable that isn't defined
+/// is always an error, as is naming a parameter after a special form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StrictMode {
+    /// Defining something with the same name as a builtin (one of the
+    /// functions defined by `default_env()`), anywhere
+    pub redefine_builtin: Check,
+    /// Defining something again in the global scope (outside of any function
+    /// or `let`)
+    pub redefine_global: Check,
+    /// Defining something locally (in a function or `let`) with the same name
+    /// as something in the global scope
+    pub shadow_global: Check,
+    /// Defining something locally with the same name as a local of an
+    /// enclosing function or `let`
+    pub shadow_local: Check,
+    /// Defining, or `set`ting, a name that isn't defined anywhere, but is one
+    /// letter off from one that is (`lenght` for `length`)
+    pub similar_name: Check,
+}
+
+/// What happens when a definition is flagged by the [`StrictMode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Check {
+    /// Nothing
+    #[default]
+    Allow,
+    /// A warning is recorded on the context, and the definition goes ahead
+    Warn,
+    /// The definition is an error
+    Error,
+}
+
+impl StrictMode {
+    /// Nothing is flagged (the default)
+    pub const LENIENT: StrictMode = StrictMode {
+        redefine_builtin: Check::Allow,
+        redefine_global: Check::Allow,
+        shadow_global: Check::Allow,
+        shadow_local: Check::Allow,
+        similar_name: Check::Allow,
+    };
+
+    /// Everything is an error
+    pub const STRICT: StrictMode = StrictMode {
+        redefine_builtin: Check::Error,
+        redefine_global: Check::Error,
+        shadow_global: Check::Error,
+        shadow_local: Check::Error,
+        similar_name: Check::Error,
+    };
+}
@@ -1,18 +1,35 @@
+use alloc::{rc::Rc, string::String};
+
 /**
- * A String [newtype](https://rust-unofficial.github.io/patterns/patterns/behavioural/newtype.html)
- * representing a lisp symbol (identifier)
+ * A string [newtype](https://rust-unofficial.github.io/patterns/patterns/behavioural/newtype.html)
+ * representing a lisp symbol (identifier). The name is shared, so copies of a
+ * symbol (which the interpreter makes a lot of) don't allocate.
  */
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Symbol(pub String);
+pub struct Symbol(pub Rc<str>);
+
+impl Symbol {
+    /// Whether this is a keyword, like `:width`. Keywords evaluate to
+    /// themselves, and are used to name keyword arguments.
+    pub fn is_keyword(&self) -> bool {
+        self.0.len() > 1 && self.0.starts_with(':')
+    }
+}
 
 impl From<&str> for Symbol {
     fn from(s: &str) -> Self {
-        Symbol(String::from(s))
+        Symbol(Rc::from(s))
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Self {
+        Symbol(Rc::from(s))
     }
 }
 
-impl std::fmt::Display for Symbol {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(&self.0, f)
+impl core::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.0, f)
     }
 }
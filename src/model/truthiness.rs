@@ -0,0 +1,51 @@
+use super::{IntType, List, Value};
+
+/// Which values count as false, for conditionals (`if`, `cond`, `and`, `or`,
+/// the guards of `for`, `filter`) and `not`. This is set per interpreter, on
+/// its [`Context`](super::Context). `F` is always false, and anything that
+/// isn't `F`, `NIL`, zero or an empty string is always true.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Truthiness {
+    /// `F` and `NIL` (the empty list) are false, and everything else is true
+    #[default]
+    Lisp,
+    /// Only `F` is false, like in Scheme
+    Scheme,
+    /// `F`, `NIL`, `0`, `0.0` and `""` are false, like in most scripting
+    /// languages
+    Loose,
+    /// Choose which of the values the other policies disagree about are false
+    Custom {
+        /// Whether `NIL` is false
+        empty_list_is_false: bool,
+        /// Whether `0` and `0.0` are false
+        zero_is_false: bool,
+        /// Whether `""` is false
+        empty_string_is_false: bool,
+    },
+}
+
+impl Truthiness {
+    pub fn is_truthy(self, value: &Value) -> bool {
+        let (empty_list_is_false, zero_is_false, empty_string_is_false) = match self {
+            Truthiness::Lisp => (true, false, false),
+            Truthiness::Scheme => (false, false, false),
+            Truthiness::Loose => (true, true, true),
+            Truthiness::Custom {
+                empty_list_is_false,
+                zero_is_false,
+                empty_string_is_false,
+            } => (empty_list_is_false, zero_is_false, empty_string_is_false),
+        };
+
+        match value {
+            Value::False => false,
+            Value::Values(_) => self.is_truthy(&value.clone().into_single()),
+            Value::List(list) if empty_list_is_false => *list != List::NIL,
+            Value::Int(int) if zero_is_false => *int != IntType::from(0i8),
+            Value::Float(float) if zero_is_false => *float != 0.0,
+            Value::String(string) if empty_string_is_false => !string.is_empty(),
+            _ => true,
+        }
+    }
+}
@@ -1,19 +1,26 @@
+use crate::collections::{HashMap, HashSet};
+use alloc::{
+    borrow::ToOwned,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
 use cfg_if::cfg_if;
-use std::any::Any;
-use std::ops::{Add, Div, Mul, Sub};
-use std::rc::Rc;
-use std::{cell::RefCell, cmp::Ordering};
-use std::{collections::HashMap, fmt::Debug};
+use core::any::Any;
+use core::fmt::{Debug, Display};
+use core::ops::{Add, Div, Mul, Sub};
+use core::{cell::RefCell, cmp::Ordering};
 
 cfg_if! {
     if #[cfg(feature = "bigint")] {
-        use num_bigint::BigInt;
-        use num_traits::ToPrimitive;
+        use num_traits::{Num, ToPrimitive};
     }
 }
 
-use super::{Env, FloatType, IntType, Lambda, List, RuntimeError, Symbol};
-use crate::lisp;
+use super::{
+    Combinator, Context, Env, ErrorKind, FloatType, Generator, IntType, Labels, Lambda, List,
+    Promise, RuntimeError, Symbol, Truthiness,
+};
 
 /// `Value` encompasses all possible Lisp values, including atoms, lists, and
 /// others.
@@ -24,18 +31,27 @@ pub enum Value {
     Int(IntType),
     Float(FloatType),
     String(String),
+
+    /// A single Unicode scalar value, written like `#\a` or `#\space`
+    Char(char),
+
     Symbol(Symbol),
     List(List),
     HashMap(HashMapRc),
 
+    /// An unordered collection of distinct atoms (numbers, strings, symbols,
+    /// etc), from `make_set`
+    Set(SetRc),
+
     /// A native Rust function that can be called from lisp code
     NativeFunc(NativeFunc),
 
     /// A native Rust closure that can be called from lisp code (the closure
     /// can capture things from its Rust environment)
-    NativeClosure(
-        Rc<RefCell<dyn FnMut(Rc<RefCell<Env>>, Vec<Value>) -> Result<Value, RuntimeError>>>,
-    ),
+    NativeClosure(Rc<RefCell<NativeClosure>>),
+
+    /// A function made by `partial`, `compose`, etc, out of other values
+    Combinator(Rc<Combinator>),
 
     /// A lisp function defined in lisp
     Lambda(Lambda),
@@ -46,6 +62,32 @@ pub enum Value {
     /// A reference to a foreign value (struct, enum, etc)
     Foreign(Rc<dyn Any>),
 
+    /// A suspendable lisp function, created with `generator`
+    Generator(Rc<RefCell<Generator>>),
+
+    /// A delayed expression, created with `delay`
+    Promise(Rc<RefCell<Promise>>),
+
+    /// An environment, from `current_env` or `make_env`
+    Env(Rc<RefCell<Env>>),
+
+    /// A string that can be appended to in place, from `string_builder`
+    StringBuilder(Rc<RefCell<String>>),
+
+    /// A buffer of bytes that can be changed in place, written like
+    /// `#u8(1 2 255)`
+    Bytes(Rc<RefCell<Vec<u8>>>),
+
+    /// A fixed number of values, each of which can be changed in place,
+    /// written like `#(1 2 3)`
+    Vector(Rc<RefCell<Vec<Value>>>),
+
+    /// The results of `(values ...)`, when there are more or less than one.
+    /// Anything but `call_with_values` or `let_values` that's given this
+    /// (as an argument, a variable's value, or a condition) gets only the
+    /// first of them, or NIL if there are none.
+    Values(Rc<[Value]>),
+
     /// A tail-call that has yet to be executed. Internal use only!
     TailCall {
         func: Rc<Value>,
@@ -53,12 +95,27 @@ pub enum Value {
     },
 }
 
+/// The chars that are written by name (like `#\space`) rather than as
+/// themselves, since they'd be hard to see or to tell apart
+pub(crate) const CHAR_NAMES: [(&str, char); 4] = [
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+];
+
 /// A Rust function that is to be called from lisp code
 pub type NativeFunc = fn(env: Rc<RefCell<Env>>, args: Vec<Value>) -> Result<Value, RuntimeError>;
 
+/// A Rust closure that is to be called from lisp code
+pub type NativeClosure = dyn FnMut(Rc<RefCell<Env>>, Vec<Value>) -> Result<Value, RuntimeError>;
+
 /// Alias for the contents of Value::HashMap
 pub type HashMapRc = Rc<RefCell<HashMap<Value, Value>>>;
 
+/// Alias for the contents of Value::Set
+pub type SetRc = Rc<RefCell<HashSet<Value>>>;
+
 impl Value {
     pub const NIL: Value = Value::List(List::NIL);
 
@@ -66,21 +123,75 @@ impl Value {
         match self {
             Value::NativeFunc(_) => "function",
             Value::NativeClosure(_) => "function",
+            Value::Combinator(_) => "function",
             Value::Lambda(_) => "function",
             Value::Macro(_) => "macro",
             Value::True => "T",
             Value::False => "F",
             Value::String(_) => "string",
-            Value::List(List::NIL) => "nil",
+            Value::Char(_) => "char",
+            Value::List(list) if *list == List::NIL => "nil",
             Value::List(_) => "list",
             Value::HashMap(_) => "hash map",
+            Value::Set(_) => "set",
             Value::Int(_) => "integer",
             Value::Float(_) => "float",
             Value::Symbol(_) => "symbol",
             Value::Foreign(_) => "foreign value",
+            Value::Generator(_) => "generator",
+            Value::Promise(_) => "promise",
+            Value::Env(_) => "environment",
+            Value::StringBuilder(_) => "string builder",
+            Value::Bytes(_) => "bytes",
+            Value::Vector(_) => "vector",
+            Value::Values(_) => "multiple values",
             Value::TailCall { func: _, args: _ } => "tail call",
         }
     }
+
+    /// The value itself, or if it's multiple values, the first of them (or
+    /// NIL if there are none), for anywhere that only takes one value
+    pub fn into_single(self) -> Value {
+        match self {
+            Value::Values(values) => values.first().cloned().unwrap_or(Value::NIL),
+            value => value,
+        }
+    }
+
+    /// The value, with `name` attached if it's a function that doesn't have
+    /// a name yet, for when it's defined as `name`
+    pub(crate) fn named(self, name: &Symbol) -> Value {
+        match self {
+            Value::Lambda(mut lambda) if lambda.name.is_none() => {
+                lambda.name = Some(name.clone());
+                Value::Lambda(lambda)
+            }
+            value => value,
+        }
+    }
+
+    /// Whether two values are the same object, rather than just equal (like
+    /// Scheme's `eq?`). Lists, hash maps, functions and the like are
+    /// identical only if they're the very same instance. Numbers, symbols,
+    /// chars, booleans and strings have no identity of their own (strings are
+    /// copied rather than shared), so they're identical when they're equal.
+    pub fn is_identical(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::List(this), Value::List(other)) => this.ptr_eq(other),
+            (Value::Lambda(this), Value::Lambda(other))
+            | (Value::Macro(this), Value::Macro(other)) => {
+                Rc::ptr_eq(&this.closure, &other.closure) && Rc::ptr_eq(&this.body, &other.body)
+            }
+            (Value::NativeFunc(this), Value::NativeFunc(other)) => {
+                core::ptr::fn_addr_eq(*this, *other)
+            }
+            (Value::NativeClosure(this), Value::NativeClosure(other)) => Rc::ptr_eq(this, other),
+            (Value::Bytes(this), Value::Bytes(other)) => Rc::ptr_eq(this, other),
+            (Value::Vector(this), Value::Vector(other)) => Rc::ptr_eq(this, other),
+            (Value::TailCall { .. }, Value::TailCall { .. }) => false,
+            _ => self == other,
+        }
+    }
 }
 
 impl From<bool> for Value {
@@ -98,21 +209,26 @@ impl From<Value> for bool {
     }
 }
 
+/// Whether the value is true under the default [`Truthiness`]. The
+/// interpreter uses the truthiness set on its `Context` instead.
 impl From<&Value> for bool {
     fn from(value: &Value) -> Self {
-        value != &Value::List(List::NIL) && value != &Value::False
+        Truthiness::default().is_truthy(value)
     }
 }
 
 impl TryFrom<&Value> for IntType {
     type Error = RuntimeError;
 
+    // `IntType` isn't `Copy` with the `bigint` feature
+    #[allow(clippy::clone_on_copy)]
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         match value {
             Value::Int(this) => Ok(this.clone()),
-            _ => Err(RuntimeError {
-                msg: format!("Expected int, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected int, got a {}", value),
+            )),
         }
     }
 }
@@ -123,15 +239,24 @@ impl From<IntType> for Value {
     }
 }
 
+// so that plain integer literals can be converted regardless of `IntType`
+#[cfg(any(feature = "bigint", feature = "i128", feature = "i64"))]
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::Int(i.into())
+    }
+}
+
 impl TryFrom<&Value> for FloatType {
     type Error = RuntimeError;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         match value {
             Value::Float(this) => Ok(*this),
-            _ => Err(RuntimeError {
-                msg: format!("Expected float, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected float, got a {}", value),
+            )),
         }
     }
 }
@@ -148,9 +273,10 @@ impl<'a> TryFrom<&'a Value> for &'a String {
     fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
         match value {
             Value::String(this) => Ok(this),
-            _ => Err(RuntimeError {
-                msg: format!("Expected string, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected string, got a {}", value),
+            )),
         }
     }
 }
@@ -161,15 +287,42 @@ impl From<String> for Value {
     }
 }
 
+impl From<&str> for Value {
+    fn from(i: &str) -> Self {
+        Value::String(i.to_owned())
+    }
+}
+
+impl TryFrom<&Value> for char {
+    type Error = RuntimeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Char(this) => Ok(*this),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected char, got a {}", value),
+            )),
+        }
+    }
+}
+
+impl From<char> for Value {
+    fn from(i: char) -> Self {
+        Value::Char(i)
+    }
+}
+
 impl<'a> TryFrom<&'a Value> for &'a Symbol {
     type Error = RuntimeError;
 
     fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
         match value {
             Value::Symbol(this) => Ok(this),
-            _ => Err(RuntimeError {
-                msg: format!("Expected symbol, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected symbol, got a {}", value),
+            )),
         }
     }
 }
@@ -186,9 +339,10 @@ impl<'a> TryFrom<&'a Value> for &'a List {
     fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
         match value {
             Value::List(this) => Ok(this),
-            _ => Err(RuntimeError {
-                msg: format!("Expected list, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected list, got a {}", value),
+            )),
         }
     }
 }
@@ -205,9 +359,10 @@ impl<'a> TryFrom<&'a Value> for &'a Lambda {
     fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
         match value {
             Value::Lambda(this) => Ok(this),
-            _ => Err(RuntimeError {
-                msg: format!("Expected function, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected function, got a {}", value),
+            )),
         }
     }
 }
@@ -224,9 +379,10 @@ impl<'a> TryFrom<&'a Value> for &'a HashMapRc {
     fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
         match value {
             Value::HashMap(this) => Ok(this),
-            _ => Err(RuntimeError {
-                msg: format!("Expected hash map, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected hash map, got a {}", value),
+            )),
         }
     }
 }
@@ -249,47 +405,167 @@ impl<'a> TryFrom<&'a Value> for &'a Rc<dyn Any> {
     fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
         match value {
             Value::Foreign(this) => Ok(this),
-            _ => Err(RuntimeError {
-                msg: format!("Expected foreign value, got a {}", value),
-            }),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected foreign value, got a {}", value),
+            )),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a Rc<RefCell<Generator>> {
+    type Error = RuntimeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Generator(this) => Ok(this),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected generator, got a {}", value),
+            )),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a SetRc {
+    type Error = RuntimeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Set(this) => Ok(this),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected set, got a {}", value),
+            )),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a Rc<RefCell<Env>> {
+    type Error = RuntimeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Env(this) => Ok(this),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected environment, got a {}", value),
+            )),
         }
     }
 }
 
+impl From<Rc<RefCell<Env>>> for Value {
+    fn from(i: Rc<RefCell<Env>>) -> Self {
+        Value::Env(i)
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a Rc<RefCell<String>> {
+    type Error = RuntimeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::StringBuilder(this) => Ok(this),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected string builder, got a {}", value),
+            )),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a Rc<RefCell<Vec<u8>>> {
+    type Error = RuntimeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bytes(this) => Ok(this),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected bytes, got a {}", value),
+            )),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(bytes: Vec<u8>) -> Self {
+        Value::Bytes(Rc::new(RefCell::new(bytes)))
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a Rc<RefCell<Vec<Value>>> {
+    type Error = RuntimeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Vector(this) => Ok(this),
+            _ => Err(RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!("Expected vector, got a {}", value),
+            )),
+        }
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::Vector(Rc::new(RefCell::new(items)))
+    }
+}
+
 impl From<Rc<dyn Any>> for Value {
     fn from(i: Rc<dyn Any>) -> Self {
         Value::Foreign(i)
     }
 }
 
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
-            Value::NativeFunc(_) => f.write_str("<native_function>"),
+            Value::NativeFunc(_) => f.write_str("#<native>"),
             Value::NativeClosure(_) => f.write_str("<closure_function>"),
+            Value::Combinator(this) => write!(f, "{}", this),
             Value::True => f.write_str("T"),
             Value::False => f.write_str("F"),
-            Value::Lambda(this) => write!(f, "<func:(lambda {})>", this),
-            Value::Macro(this) => write!(f, "(macro {})", this),
+            Value::Lambda(this) => write!(f, "{}", this),
+            Value::Macro(this) => this.fmt_summary(f, "macro"),
             Value::String(this) => write!(f, "\"{}\"", this),
-            Value::List(this) => write!(f, "{}", this),
-            Value::HashMap(this) => {
-                let borrowed = this.borrow();
-                let entries = std::iter::once(lisp! { hash }).chain(
-                    borrowed
-                        .iter()
-                        .map(|(key, value)| [key.clone(), value.clone()].into_iter())
-                        .flatten(),
-                );
+            Value::Char(this) => match CHAR_NAMES.iter().find(|(_, ch)| ch == this) {
+                Some((name, _)) => write!(f, "#\\{}", name),
+                None => write!(f, "#\\{}", this),
+            },
+            Value::List(_) | Value::HashMap(_) | Value::Vector(_) => {
+                self.fmt_visiting(f, &mut HashSet::new(), None)
+            }
+            Value::Set(this) => {
+                f.write_str("(make_set")?;
 
-                let list = Value::List(entries.collect());
+                for member in sorted_members(&this.borrow()) {
+                    write!(f, " {}", member)?;
+                }
+
+                f.write_str(")")
+            }
+            Value::Values(this) => {
+                f.write_str("(values")?;
+
+                for value in this.iter() {
+                    write!(f, " {}", value)?;
+                }
 
-                write!(f, "{}", list)
+                f.write_str(")")
             }
             Value::Int(this) => write!(f, "{}", this),
-            Value::Float(this) => write!(f, "{}", this),
+            Value::Float(this) => fmt_float(*this, f),
             Value::Symbol(Symbol(this)) => write!(f, "{}", this),
             Value::Foreign(_) => f.write_str("<foreign_value>"),
+            Value::Generator(_) => f.write_str("<generator>"),
+            Value::Promise(_) => f.write_str("<promise>"),
+            Value::Env(this) => fmt_env(this, f),
+            Value::StringBuilder(this) => fmt_string_builder(this, f),
+            Value::Bytes(this) => fmt_bytes(this, f),
             Value::TailCall { func, args } => {
                 write!(f, "<tail-call: {:?} with {:?} >", func, args)
             }
@@ -297,22 +573,159 @@ impl std::fmt::Display for Value {
     }
 }
 
+impl Value {
+    /// Write a value which may contain itself (through lists or hash maps
+    /// that have been mutated), using `visiting` to keep track of the
+    /// containers that are already being written further up. Foreign values
+    /// are written with their `LispOps`, if they're registered on `context`,
+    /// and native functions with the name they were defined with there.
+    pub(crate) fn fmt_visiting(
+        &self,
+        f: &mut core::fmt::Formatter,
+        visiting: &mut HashSet<*const ()>,
+        context: Option<&Context>,
+    ) -> core::fmt::Result {
+        match self {
+            Value::List(this) => this.fmt_visiting(f, visiting, context),
+            Value::Foreign(_) => match context.and_then(|context| context.lisp_ops(self)) {
+                Some(ops) => ops.fmt(f),
+                None => write!(f, "{}", self),
+            },
+            Value::NativeFunc(func) => match context.and_then(|context| context.native_name(*func))
+            {
+                Some(name) => write!(f, "#<native {}>", name),
+                None => write!(f, "{}", self),
+            },
+            Value::HashMap(this) => {
+                let ptr = Rc::as_ptr(this) as *const ();
+
+                if !visiting.insert(ptr) {
+                    return f.write_str("#<cycle>");
+                }
+
+                f.write_str("(hash")?;
+
+                for (key, value) in this.borrow().iter() {
+                    f.write_str(" ")?;
+                    key.fmt_visiting(f, visiting, context)?;
+                    f.write_str(" ")?;
+                    value.fmt_visiting(f, visiting, context)?;
+                }
+
+                visiting.remove(&ptr);
+
+                f.write_str(")")
+            }
+            Value::Vector(this) => {
+                let ptr = Rc::as_ptr(this) as *const ();
+
+                let Ok(items) = this.try_borrow() else {
+                    return f.write_str("#<vector>");
+                };
+                if !visiting.insert(ptr) {
+                    return f.write_str("#<cycle>");
+                }
+
+                f.write_str("#(")?;
+
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    item.fmt_visiting(f, visiting, context)?;
+                }
+
+                visiting.remove(&ptr);
+
+                f.write_str(")")
+            }
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+impl Value {
+    /// Show the value so that parsing it gives back the same structure: each
+    /// cons cell that's reached more than once (from two places, or by a
+    /// cycle) is labelled with `#N=` where it's first written, and written as
+    /// `#N#` after that. Other values are written like `Display` does.
+    ///
+    /// ```
+    /// use rust_lisp::{lisp, model::{List, Value}, parser::parse};
+    ///
+    /// let shared = lisp! { (1 2) };
+    /// let value = Value::List([shared.clone(), shared].into_iter().collect());
+    ///
+    /// assert_eq!(value.to_string(), "((1 2) (1 2))");
+    /// assert_eq!(value.labelled().to_string(), "(#0=(1 2) #0#)");
+    /// ```
+    pub fn labelled(&self) -> impl Display + '_ {
+        struct Labelled<'a>(&'a Value);
+
+        impl Display for Labelled<'_> {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                self.0.fmt_labelled(f, &mut Labels::of(self.0), None)
+            }
+        }
+
+        Labelled(self)
+    }
+
+    /// Write the value with labels on its shared cells, which are found
+    /// beforehand (see `labelled()`)
+    pub(crate) fn fmt_labelled(
+        &self,
+        f: &mut core::fmt::Formatter,
+        labels: &mut Labels,
+        context: Option<&Context>,
+    ) -> core::fmt::Result {
+        match self {
+            Value::List(this) => this.fmt_labelled(f, labels, context),
+            _ => self.fmt_visiting(f, &mut HashSet::new(), context),
+        }
+    }
+}
+
 impl Debug for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             Value::NativeFunc(_) => f.write_str("<native_function>"),
             Value::NativeClosure(_) => f.write_str("<closure_function>"),
+            Value::Combinator(this) => write!(f, "Value::Combinator({:?})", this),
             Value::True => f.write_str("Value::True"),
             Value::False => f.write_str("Value::False"),
             Value::Lambda(this) => write!(f, "Value::Lambda({:?})", this),
             Value::Macro(this) => write!(f, "Value::Macro({:?})", this),
             Value::String(this) => write!(f, "Value::String({:?})", this),
+            Value::Char(this) => write!(f, "Value::Char({:?})", this),
             Value::List(this) => write!(f, "Value::List({:?})", this),
             Value::HashMap(this) => write!(f, "Value::HashMap({:?})", this),
+            Value::Set(this) => write!(f, "Value::Set({:?})", sorted_members(&this.borrow())),
+            Value::Values(this) => write!(f, "Value::Values({:?})", this),
             Value::Int(this) => write!(f, "Value::Int({:?})", this),
             Value::Float(this) => write!(f, "Value::Float({:?})", this),
             Value::Symbol(Symbol(this)) => write!(f, "Value::Symbol({:?})", this),
             Value::Foreign(_) => f.write_str("<foreign_value>"),
+            Value::Generator(_) => f.write_str("<generator>"),
+            Value::Promise(_) => f.write_str("<promise>"),
+            Value::Env(this) => {
+                f.write_str("Value::Env(")?;
+                fmt_env(this, f)?;
+                f.write_str(")")
+            }
+            Value::StringBuilder(this) => {
+                f.write_str("Value::StringBuilder(")?;
+                fmt_string_builder(this, f)?;
+                f.write_str(")")
+            }
+            Value::Bytes(this) => match this.try_borrow() {
+                Ok(bytes) => write!(f, "Value::Bytes({:?})", bytes),
+                Err(_) => f.write_str("Value::Bytes(<borrowed>)"),
+            },
+            Value::Vector(this) => match this.try_borrow() {
+                Ok(items) => write!(f, "Value::Vector({:?})", items),
+                Err(_) => f.write_str("Value::Vector(<borrowed>)"),
+            },
             Value::TailCall { func, args } => write!(
                 f,
                 "Value::TailCall {{ func: {:?}, args: {:?} }}",
@@ -322,6 +735,92 @@ impl Debug for Value {
     }
 }
 
+// Only a summary, because environments are usually full of functions whose
+// closures lead back to the same environment
+fn fmt_env(env: &Rc<RefCell<Env>>, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    match env.try_borrow() {
+        Ok(env) => write!(f, "#<env {} bindings>", env.len()),
+        // it's being modified (printing from inside `define`, etc)
+        Err(_) => f.write_str("#<env>"),
+    }
+}
+
+// Only the length, because builders are for strings too big to print
+fn fmt_string_builder(
+    builder: &Rc<RefCell<String>>,
+    f: &mut core::fmt::Formatter,
+) -> core::fmt::Result {
+    match builder.try_borrow() {
+        Ok(builder) => write!(f, "#<string_builder {} chars>", builder.chars().count()),
+        Err(_) => f.write_str("#<string_builder>"),
+    }
+}
+
+/// The members of a set in a stable order: numbers, chars, strings, symbols,
+/// booleans and then NIL, each sorted among themselves
+// a set's members are hashed by identity where they're mutable
+#[allow(clippy::mutable_key_type)]
+pub(crate) fn sorted_members(set: &HashSet<Value>) -> Vec<Value> {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Int(_) | Value::Float(_) => 0,
+            Value::Char(_) => 1,
+            Value::String(_) => 2,
+            Value::Symbol(_) => 3,
+            Value::True | Value::False => 4,
+            _ => 5,
+        }
+    }
+
+    let mut members = set.iter().cloned().collect::<Vec<_>>();
+    members.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+    members
+}
+
+/// The most bytes that are written out in full; bigger buffers are summarized
+const BYTES_SHOWN: usize = 64;
+
+/// How many bytes of a bigger buffer are shown in its summary
+const BYTES_SUMMARIZED: usize = 16;
+
+// Short buffers are written as literals, and longer ones as their length and
+// a hex dump of the start
+fn fmt_bytes(bytes: &Rc<RefCell<Vec<u8>>>, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let Ok(bytes) = bytes.try_borrow() else {
+        return f.write_str("#<bytes>");
+    };
+
+    if bytes.len() <= BYTES_SHOWN {
+        return write_bytes_literal(&bytes, f);
+    }
+
+    write!(f, "#<bytes {}:", bytes.len())?;
+
+    for byte in &bytes[..BYTES_SUMMARIZED] {
+        write!(f, " {:02x}", byte)?;
+    }
+
+    f.write_str(" ...>")
+}
+
+/// Write `bytes` as a `#u8(...)` literal, however long it is
+pub(crate) fn write_bytes_literal(
+    bytes: &[u8],
+    f: &mut impl core::fmt::Write,
+) -> core::fmt::Result {
+    f.write_str("#u8(")?;
+
+    for (index, byte) in bytes.iter().enumerate() {
+        if index > 0 {
+            f.write_str(" ")?;
+        }
+
+        write!(f, "{}", byte)?;
+    }
+
+    f.write_str(")")
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -330,12 +829,22 @@ impl PartialEq for Value {
             (Value::Lambda(this), Value::Lambda(other)) => this == other,
             (Value::Macro(this), Value::Macro(other)) => this == other,
             (Value::String(this), Value::String(other)) => this == other,
+            (Value::Char(this), Value::Char(other)) => this == other,
             (Value::List(this), Value::List(other)) => this == other,
             (Value::Int(this), Value::Int(other)) => this == other,
             (Value::Float(this), Value::Float(other)) => this.to_bits() == other.to_bits(),
             (Value::Symbol(this), Value::Symbol(other)) => this == other,
             (Value::HashMap(this), Value::HashMap(other)) => Rc::ptr_eq(this, other),
+            (Value::Set(this), Value::Set(other)) => this == other,
             (Value::Foreign(this), Value::Foreign(other)) => Rc::ptr_eq(this, other),
+            (Value::Generator(this), Value::Generator(other)) => Rc::ptr_eq(this, other),
+            (Value::Promise(this), Value::Promise(other)) => Rc::ptr_eq(this, other),
+            (Value::Env(this), Value::Env(other)) => Rc::ptr_eq(this, other),
+            (Value::StringBuilder(this), Value::StringBuilder(other)) => Rc::ptr_eq(this, other),
+            (Value::Bytes(this), Value::Bytes(other)) => this == other,
+            (Value::Vector(this), Value::Vector(other)) => Rc::ptr_eq(this, other) || this == other,
+            (Value::Values(this), Value::Values(other)) => this == other,
+            (Value::Combinator(this), Value::Combinator(other)) => Rc::ptr_eq(this, other),
             (
                 Value::TailCall {
                     func: this_func,
@@ -354,6 +863,9 @@ impl PartialEq for Value {
 
 impl Eq for Value {}
 
+// Incomparable values have no ordering for the comparison operators, but
+// `Ord` falls back to comparing their debug strings so lists can be sorted
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
         if self == other {
@@ -364,6 +876,7 @@ impl PartialOrd for Value {
             (Value::True, Value::False) => Some(Ordering::Less),
             (Value::False, Value::True) => Some(Ordering::Greater),
             (Value::String(this), Value::String(other)) => this.partial_cmp(other),
+            (Value::Char(this), Value::Char(other)) => this.partial_cmp(other),
             (Value::Symbol(Symbol(this)), Value::Symbol(Symbol(other))) => this.partial_cmp(other),
             (Value::Int(this), Value::Int(other)) => this.partial_cmp(other),
             (Value::Float(this), Value::Float(other)) => this.partial_cmp(other),
@@ -378,22 +891,33 @@ impl PartialOrd for Value {
     }
 }
 
+/// Why an arithmetic operation on two `Value`s couldn't be performed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+    /// The operation doesn't apply to values of these types
+    WrongTypes,
+    /// The result is too big (or small) to fit in an `IntType`
+    Overflow,
+    /// Division by zero (of either integers or floats)
+    DivideByZero,
+}
+
 impl Add<&Value> for &Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn add(self, other: &Value) -> Self::Output {
         match (self, other) {
             // same type
-            (Value::Int(this), Value::Int(other)) => Ok(Value::from(this + other)),
+            (Value::Int(this), Value::Int(other)) => checked_add(this, other).map(Value::from),
             (Value::Float(this), Value::Float(other)) => Ok(Value::from(this + other)),
             (Value::String(this), Value::String(other)) => Ok(Value::from(this.clone() + other)),
 
             // different numeric types
             (Value::Int(this), Value::Float(other)) => {
-                Ok(Value::from(int_type_to_float_type(&this) + other))
+                Ok(Value::from(int_type_to_float_type(this) + other))
             }
             (Value::Float(this), Value::Int(other)) => {
-                Ok(Value::from(this + int_type_to_float_type(&other)))
+                Ok(Value::from(this + int_type_to_float_type(other)))
             }
 
             // non-string + string
@@ -406,13 +930,13 @@ impl Add<&Value> for &Value {
             (Value::Int(this), Value::String(other)) => Ok(Value::from(this.to_string() + other)),
             (Value::Float(this), Value::String(other)) => Ok(Value::from(this.to_string() + other)),
 
-            _ => Err(()),
+            _ => Err(ArithmeticError::WrongTypes),
         }
     }
 }
 
 impl Add<Value> for Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn add(self, other: Value) -> Self::Output {
         &self + &other
@@ -420,27 +944,27 @@ impl Add<Value> for Value {
 }
 
 impl Sub<&Value> for &Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn sub(self, other: &Value) -> Self::Output {
         match (self, other) {
-            (Value::Int(this), Value::Int(other)) => Ok(Value::from(this - other)),
+            (Value::Int(this), Value::Int(other)) => checked_sub(this, other).map(Value::from),
             (Value::Float(this), Value::Float(other)) => Ok(Value::from(this - other)),
 
             (Value::Int(this), Value::Float(other)) => {
-                Ok(Value::from(int_type_to_float_type(&this) - other))
+                Ok(Value::from(int_type_to_float_type(this) - other))
             }
             (Value::Float(this), Value::Int(other)) => {
-                Ok(Value::from(this - int_type_to_float_type(&other)))
+                Ok(Value::from(this - int_type_to_float_type(other)))
             }
 
-            _ => Err(()),
+            _ => Err(ArithmeticError::WrongTypes),
         }
     }
 }
 
 impl Sub<Value> for Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn sub(self, other: Value) -> Self::Output {
         &self - &other
@@ -448,27 +972,27 @@ impl Sub<Value> for Value {
 }
 
 impl Mul<&Value> for &Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn mul(self, other: &Value) -> Self::Output {
         match (self, other) {
-            (Value::Int(this), Value::Int(other)) => Ok(Value::from(this * other)),
+            (Value::Int(this), Value::Int(other)) => checked_mul(this, other).map(Value::from),
             (Value::Float(this), Value::Float(other)) => Ok(Value::from(this * other)),
 
             (Value::Int(this), Value::Float(other)) => {
-                Ok(Value::from(int_type_to_float_type(&this) * other))
+                Ok(Value::from(int_type_to_float_type(this) * other))
             }
             (Value::Float(this), Value::Int(other)) => {
-                Ok(Value::from(this * int_type_to_float_type(&other)))
+                Ok(Value::from(this * int_type_to_float_type(other)))
             }
 
-            _ => Err(()),
+            _ => Err(ArithmeticError::WrongTypes),
         }
     }
 }
 
 impl Mul<Value> for Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn mul(self, other: Value) -> Self::Output {
         &self * &other
@@ -476,46 +1000,253 @@ impl Mul<Value> for Value {
 }
 
 impl Div<&Value> for &Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn div(self, other: &Value) -> Self::Output {
+        // unlike IEEE float division, dividing a float by zero is an error
+        // rather than infinity/NaN, for consistency with integers
+        let divisor_is_zero = match other {
+            Value::Int(other) => *other == IntType::from(0i8),
+            Value::Float(other) => *other == 0.0,
+            _ => false,
+        };
+        if divisor_is_zero {
+            return Err(ArithmeticError::DivideByZero);
+        }
+
         match (self, other) {
-            (Value::Int(this), Value::Int(other)) => Ok(Value::from(this / other)),
+            (Value::Int(this), Value::Int(other)) => checked_div(this, other).map(Value::from),
             (Value::Float(this), Value::Float(other)) => Ok(Value::from(this / other)),
 
             (Value::Int(this), Value::Float(other)) => {
-                Ok(Value::from(int_type_to_float_type(&this) / other))
+                Ok(Value::from(int_type_to_float_type(this) / other))
             }
             (Value::Float(this), Value::Int(other)) => {
-                Ok(Value::from(this / int_type_to_float_type(&other)))
+                Ok(Value::from(this / int_type_to_float_type(other)))
             }
 
-            _ => Err(()),
+            _ => Err(ArithmeticError::WrongTypes),
         }
     }
 }
 
 impl Div<Value> for Value {
-    type Output = Result<Value, ()>;
+    type Output = Result<Value, ArithmeticError>;
 
     fn div(self, other: Value) -> Self::Output {
         &self / &other
     }
 }
 
+// Integer arithmetic which errs when the result doesn't fit in `IntType`,
+// instead of panicking (in debug builds) or silently wrapping around
+
+fn checked_add(a: &IntType, b: &IntType) -> Result<IntType, ArithmeticError> {
+    cfg_if! {
+        if #[cfg(feature = "bigint")] {
+            Ok(a + b)
+        } else {
+            a.checked_add(*b).ok_or(ArithmeticError::Overflow)
+        }
+    }
+}
+
+fn checked_sub(a: &IntType, b: &IntType) -> Result<IntType, ArithmeticError> {
+    cfg_if! {
+        if #[cfg(feature = "bigint")] {
+            Ok(a - b)
+        } else {
+            a.checked_sub(*b).ok_or(ArithmeticError::Overflow)
+        }
+    }
+}
+
+fn checked_mul(a: &IntType, b: &IntType) -> Result<IntType, ArithmeticError> {
+    cfg_if! {
+        if #[cfg(feature = "bigint")] {
+            Ok(a * b)
+        } else {
+            a.checked_mul(*b).ok_or(ArithmeticError::Overflow)
+        }
+    }
+}
+
+pub(crate) fn checked_div(a: &IntType, b: &IntType) -> Result<IntType, ArithmeticError> {
+    if *b == IntType::from(0i8) {
+        return Err(ArithmeticError::DivideByZero);
+    }
+
+    cfg_if! {
+        if #[cfg(feature = "bigint")] {
+            Ok(a / b)
+        } else {
+            // dividing the smallest value by -1
+            a.checked_div(*b).ok_or(ArithmeticError::Overflow)
+        }
+    }
+}
+
+/// Write an int in base `radix` (which must be 2, 8, 10 or 16), with a `-` in
+/// front of it if it's negative
+pub(crate) fn int_to_string_radix(int: &IntType, radix: u32) -> String {
+    cfg_if! {
+        if #[cfg(feature = "bigint")] {
+            let magnitude = int.magnitude();
+        } else {
+            let magnitude = int.unsigned_abs();
+        }
+    }
+
+    let digits = match radix {
+        2 => format!("{:b}", magnitude),
+        8 => format!("{:o}", magnitude),
+        16 => format!("{:x}", magnitude),
+        _ => format!("{}", magnitude),
+    };
+
+    if *int < IntType::from(0i8) {
+        format!("-{}", digits)
+    } else {
+        digits
+    }
+}
+
+/// An int as an `i128`, or None if it doesn't fit in one
+// `IntType` may already be `i128`
+#[allow(clippy::useless_conversion)]
+pub(crate) fn int_to_i128(int: &IntType) -> Option<i128> {
+    cfg_if! {
+        if #[cfg(feature = "bigint")] {
+            int.to_i128()
+        } else {
+            Some(i128::from(*int))
+        }
+    }
+}
+
+/// An int as a `usize` (for an index or a size), or None if it isn't one
+pub(crate) fn int_to_usize(int: &IntType) -> Option<usize> {
+    int_to_i128(int).and_then(|int| usize::try_from(int).ok())
+}
+
+/// An `i128` as an int, or None if it doesn't fit in `IntType`
+pub(crate) fn int_from_i128(int: i128) -> Option<IntType> {
+    cfg_if! {
+        if #[cfg(feature = "bigint")] {
+            Some(IntType::from(int))
+        } else {
+            // this can't fail with the `i128` feature
+            #[allow(clippy::unnecessary_fallible_conversions)]
+            let int = IntType::try_from(int);
+
+            int.ok()
+        }
+    }
+}
+
+/// Read a number written in base `radix` (which must be 2, 8, 10 or 16), or
+/// None if `text` isn't one. Only a base 10 number can be a float, written
+/// with a decimal point or an exponent. An int that doesn't fit in `IntType`
+/// is an overflow.
+pub(crate) fn parse_number_radix(text: &str, radix: u32) -> Result<Option<Value>, ArithmeticError> {
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+
+    if !digits.is_empty() && digits.chars().all(|ch| ch.is_digit(radix)) {
+        return match IntType::from_str_radix(text, radix) {
+            Ok(int) => Ok(Some(Value::Int(int))),
+            Err(_) => Err(ArithmeticError::Overflow),
+        };
+    }
+
+    if let Some(float) = non_finite_float(text).filter(|_| radix == 10) {
+        return Ok(Some(Value::Float(float)));
+    }
+
+    // without the check, `parse` would also accept things like "inf" and "NaN"
+    let is_float = radix == 10
+        && digits.contains(|ch: char| ch.is_ascii_digit())
+        && digits
+            .chars()
+            .all(|ch| ch.is_ascii_digit() || matches!(ch, '.' | 'e' | 'E' | '-' | '+'));
+
+    Ok(is_float
+        .then(|| text.parse::<FloatType>().ok())
+        .flatten()
+        .map(Value::Float))
+}
+
+/// Write a float so that reading it back gives the same float: as the
+/// shortest decimal that does, with a `.0` if it would otherwise read as an
+/// int, and in scientific notation if it's very large or very small. NaN and
+/// the infinities are written `+nan.0`, `+inf.0` and `-inf.0`.
+fn fmt_float(float: FloatType, f: &mut impl core::fmt::Write) -> core::fmt::Result {
+    if float.is_nan() {
+        return f.write_str("+nan.0");
+    }
+    if float.is_infinite() {
+        return f.write_str(if float > 0.0 { "+inf.0" } else { "-inf.0" });
+    }
+
+    let magnitude = float.abs();
+    if magnitude != 0.0 && !(SMALLEST_PLAIN_FLOAT..LARGEST_PLAIN_FLOAT).contains(&magnitude) {
+        return f.write_str(&float_to_scientific(float, None));
+    }
+
+    let plain = format!("{}", float);
+    f.write_str(&plain)?;
+    if !plain.contains('.') {
+        f.write_str(".0")?;
+    }
+
+    Ok(())
+}
+
+/// Write a finite float in scientific notation, with `places` digits after
+/// the point, or as few as will read back the same
+pub(crate) fn float_to_scientific(float: FloatType, places: Option<usize>) -> String {
+    let scientific = match places {
+        Some(places) => format!("{:.*e}", places, float),
+        None => format!("{:e}", float),
+    };
+
+    // `1e30` would read back the same, but `1.0e30` looks like a float
+    match scientific.split_once('e') {
+        Some((mantissa, exponent)) if places.is_none() && !mantissa.contains('.') => {
+            format!("{}.0e{}", mantissa, exponent)
+        }
+        _ => scientific,
+    }
+}
+
+/// The float written as `token`, if it's NaN or an infinity (see
+/// `fmt_float()`)
+pub(crate) fn non_finite_float(token: &str) -> Option<FloatType> {
+    match token {
+        "+nan.0" | "-nan.0" => Some(FloatType::NAN),
+        "+inf.0" => Some(FloatType::INFINITY),
+        "-inf.0" => Some(FloatType::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+/// Floats with magnitudes from this up to `LARGEST_PLAIN_FLOAT` are written
+/// without an exponent
+const SMALLEST_PLAIN_FLOAT: FloatType = 1e-7;
+const LARGEST_PLAIN_FLOAT: FloatType = 1e16;
+
 /// Convert whatever int type we're using to whatever float type we're using
-fn int_type_to_float_type(i: &IntType) -> FloatType {
+pub(crate) fn int_type_to_float_type(i: &IntType) -> FloatType {
     cfg_if! {
         if #[cfg(feature = "bigint")] {
             cfg_if! {
                 if #[cfg(feature = "f64")] {
-                    return i.to_f64().unwrap_or(f64::NAN);
+                    i.to_f64().unwrap_or(f64::NAN)
                 } else {
-                    return i.to_f32().unwrap_or(f32::NAN);
+                    i.to_f32().unwrap_or(f32::NAN)
                 }
             }
         } else {
-            return *i as FloatType;
+            *i as FloatType
         }
     }
 }
@@ -529,8 +1260,8 @@ impl Ord for Value {
     }
 }
 
-impl std::hash::Hash for Value {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl core::hash::Hash for Value {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         // core::mem::discriminant(self).hash(state);
         match self {
             Value::False => false.hash(state),
@@ -538,14 +1269,26 @@ impl std::hash::Hash for Value {
             Value::Int(x) => x.hash(state),
             Value::Float(x) => x.to_bits().hash(state),
             Value::String(x) => x.hash(state),
+            Value::Char(x) => x.hash(state),
             Value::Symbol(x) => x.hash(state),
             Value::List(x) => x.hash(state),
             Value::HashMap(x) => x.as_ptr().hash(state),
-            Value::NativeFunc(x) => std::ptr::hash(x, state),
-            Value::NativeClosure(x) => std::ptr::hash(x, state),
+            // (equal sets can have their members in different orders)
+            Value::Set(x) => x.borrow().len().hash(state),
+            Value::Values(x) => x.hash(state),
+            Value::NativeFunc(x) => core::ptr::hash(x, state),
+            Value::NativeClosure(x) => core::ptr::hash(x, state),
+            Value::Combinator(x) => core::ptr::hash(x, state),
             Value::Lambda(x) => x.hash(state),
             Value::Macro(x) => x.hash(state),
-            Value::Foreign(x) => std::ptr::hash(x, state),
+            Value::Foreign(x) => core::ptr::hash(x, state),
+            Value::Generator(x) => x.as_ptr().hash(state),
+            Value::Promise(x) => x.as_ptr().hash(state),
+            Value::Env(x) => x.as_ptr().hash(state),
+            Value::StringBuilder(x) => x.as_ptr().hash(state),
+            Value::Bytes(x) => x.borrow().hash(state),
+            // (a vector can contain itself)
+            Value::Vector(x) => x.borrow().len().hash(state),
             Value::TailCall { func, args } => {
                 func.hash(state);
                 args.hash(state);
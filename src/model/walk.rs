@@ -0,0 +1,525 @@
+//! Traversing expressions as trees, for tools which analyze or rewrite code
+//! before it's evaluated. Everything here uses an explicit stack instead of
+//! recursion, so deeply nested expressions can't overflow the real one. Only
+//! lists are descended into. `walk()` and `map_tree()` are for code, and walk
+//! a list which contains itself (through `set_cdr`, or a datum label) forever;
+//! `walk_graph()` and `map_graph()` look at each cons cell once, for data
+//! which may share structure or contain itself.
+
+use crate::{
+    collections::{HashMap, HashSet},
+    interpreter::is_special_form,
+};
+use alloc::{rc::Rc, vec::Vec};
+
+use super::{cons_all, List, Symbol, Value};
+
+impl Value {
+    /// Call `visit` with this value and then, if it's a list, each of its items
+    /// in turn (and so on, depth-first).
+    pub fn walk(&self, visit: &mut impl FnMut(&Value)) {
+        let mut pending = vec![self.clone()];
+
+        while let Some(value) = pending.pop() {
+            visit(&value);
+
+            if let Value::List(list) = &value {
+                let start = pending.len();
+                pending.extend(list);
+                pending[start..].reverse();
+            }
+        }
+    }
+
+    /// Rewrite this value from the bottom up: `rewrite` is called with each
+    /// value after its items (if it's a list) have been rewritten, and returns
+    /// a replacement or `None` to keep it. Lists where nothing was replaced are
+    /// kept as they were rather than copied, and so is everything after the
+    /// last replaced item of a list.
+    pub fn map_tree(&self, rewrite: impl Fn(&Value) -> Option<Value>) -> Value {
+        // a list whose items are being rewritten
+        struct Frame {
+            list: List,
+            remaining: Vec<Value>,
+            rewritten: Vec<Value>,
+            last_changed: Option<usize>,
+        }
+
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut next = self.clone();
+
+        loop {
+            // go down to the first item that isn't a (non-empty) list
+            let mut done = loop {
+                match &next {
+                    Value::List(list) if !list.is_empty() => {
+                        let mut remaining = list.into_iter().collect::<Vec<Value>>();
+                        remaining.reverse();
+                        let first = remaining.pop().unwrap_or(Value::NIL);
+
+                        frames.push(Frame {
+                            list: list.clone(),
+                            remaining,
+                            rewritten: Vec::new(),
+                            last_changed: None,
+                        });
+                        next = first;
+                    }
+                    _ => break replace(&rewrite, next.clone(), false),
+                }
+            };
+
+            // then back up through every list that's now finished
+            loop {
+                let Some(frame) = frames.last_mut() else {
+                    return done.0;
+                };
+
+                if done.1 {
+                    frame.last_changed = Some(frame.rewritten.len());
+                }
+                frame.rewritten.push(done.0);
+
+                if let Some(item) = frame.remaining.pop() {
+                    next = item;
+                    break;
+                }
+
+                let Some(frame) = frames.pop() else {
+                    unreachable!()
+                };
+
+                done = match frame.last_changed {
+                    None => replace(&rewrite, Value::List(frame.list), false),
+                    Some(last) => {
+                        let mut tail = frame.list;
+                        for _ in 0..last {
+                            tail = tail.cdr();
+                        }
+
+                        let list = cons_all(&frame.rewritten[..=last], tail.cdr_value());
+
+                        replace(&rewrite, list, true)
+                    }
+                };
+            }
+        }
+    }
+
+    /// Like `walk()`, but the items of each cons cell are only visited once,
+    /// so this finishes on lists which share structure or contain themselves.
+    pub(crate) fn walk_graph(&self, visit: &mut impl FnMut(&Value)) {
+        let mut seen = HashSet::new();
+        let mut pending = vec![self.clone()];
+
+        while let Some(value) = pending.pop() {
+            visit(&value);
+
+            if let Value::List(list) = &value {
+                let start = pending.len();
+                pending.extend(unseen_items(list, &mut seen));
+                pending[start..].reverse();
+            }
+        }
+    }
+
+    /// A copy of this value's lists, with values replaced as `rewrite` says.
+    /// It's called with each value (first to last, depth-first) before any of
+    /// its items, which aren't looked at if it's replaced. Each cons cell is
+    /// copied once, so the copy shares structure where the original does, and
+    /// contains itself where the original does. The first error from
+    /// `rewrite` stops the copy.
+    pub(crate) fn map_graph<E>(
+        &self,
+        mut rewrite: impl FnMut(&Value) -> Result<Option<Value>, E>,
+    ) -> Result<Value, E> {
+        // the copy of each cell (as the list starting there) by the address of
+        // the original
+        type Copies = HashMap<*const (), List>;
+
+        // copy the cells of `list` that haven't been yet, leaving their items
+        // to be filled in from `pending`
+        fn copy_cells(list: &List, copies: &mut Copies, pending: &mut Vec<(List, Value)>) -> List {
+            let start = pending.len();
+            let mut first = None;
+            let mut last: Option<List> = None;
+            let mut rest = list.clone();
+
+            let tail = loop {
+                let Some(cell) = rest.head_ptr() else {
+                    break Value::NIL;
+                };
+                if let Some(copy) = copies.get(&cell) {
+                    break Value::List(copy.clone());
+                }
+
+                let copy = List::NIL.cons(Value::NIL);
+                copies.insert(cell, copy.clone());
+                pending.push((copy.clone(), rest.car().unwrap_or(Value::NIL)));
+
+                // a new cell can't be NIL, so setting its parts can't fail
+                match &last {
+                    Some(last) => {
+                        let _ = last.set_cdr(&copy);
+                    }
+                    None => first = Some(copy.clone()),
+                }
+
+                last = Some(copy);
+                rest = match rest.cdr_value() {
+                    Value::List(rest) => rest,
+                    // the end of a dotted list, which is kept as it is
+                    tail => break tail,
+                };
+            };
+
+            if let Some(last) = &last {
+                let _ = last.set_cdr_value(tail.clone());
+            }
+
+            // so the items are filled in first to last
+            pending[start..].reverse();
+
+            match (first, tail) {
+                (Some(first), _) => first,
+                (None, Value::List(tail)) => tail,
+                (None, _) => List::NIL,
+            }
+        }
+
+        if let Some(replacement) = rewrite(self)? {
+            return Ok(replacement);
+        }
+
+        let Value::List(list) = self else {
+            return Ok(self.clone());
+        };
+
+        let mut copies = Copies::new();
+        let mut pending = Vec::new();
+        let copy = copy_cells(list, &mut copies, &mut pending);
+
+        while let Some((cell, item)) = pending.pop() {
+            let item = match rewrite(&item)? {
+                Some(replacement) => replacement,
+                None => match &item {
+                    Value::List(list) => Value::List(copy_cells(list, &mut copies, &mut pending)),
+                    _ => item,
+                },
+            };
+
+            let _ = cell.set_car(item);
+        }
+
+        Ok(Value::List(copy))
+    }
+
+    /// The symbols which this expression refers to from outside of itself:
+    /// those it uses as variables or calls as functions, not counting the
+    /// names it binds (as the parameters of a `lambda`, `defun`, etc, or in a
+    /// `let`, `for` or `do`) where they're in scope, the names of special
+    /// forms, or keywords. Quoted data isn't included, except for any `comma`
+    /// forms in it. The names being defined by `define` and `defun` aren't
+    /// included either (unless they're used too).
+    pub fn referenced_symbols(&self) -> HashSet<Symbol> {
+        let mut found = HashSet::new();
+        // the cells of quoted data, which may contain itself
+        let mut seen = HashSet::new();
+        let mut pending = vec![(self.clone(), None, false)];
+
+        while let Some((value, scope, quoted)) = pending.pop() {
+            let list = match value {
+                Value::Symbol(symbol) if !quoted => {
+                    if !symbol.is_keyword() && !Scope::binds(&scope, &symbol) {
+                        found.insert(symbol);
+                    }
+                    continue;
+                }
+                Value::List(list) if !list.is_empty() => list,
+                _ => continue,
+            };
+
+            let keyword = match list.car() {
+                Ok(Value::Symbol(Symbol(keyword))) if quoted => {
+                    if &*keyword == "comma" {
+                        pending.extend(
+                            list.cdr()
+                                .into_iter()
+                                .map(|arg| (arg, scope.clone(), false)),
+                        );
+                        continue;
+                    }
+                    None
+                }
+                Ok(Value::Symbol(Symbol(keyword))) if is_special_form(&keyword) => Some(keyword),
+                _ => None,
+            };
+
+            let args = list.cdr();
+
+            match keyword.as_deref() {
+                // data, and syntax_rules patterns and templates
+                Some("quote") => {
+                    pending.extend(args.into_iter().map(|arg| (arg, scope.clone(), true)))
+                }
+                Some("define_syntax") => {}
+
+                Some("lambda") => add_function(&mut pending, &scope, &args),
+                Some("defun" | "defmacro") => add_function(&mut pending, &scope, &args.cdr()),
+                Some("case_lambda") => {
+                    for clause in &args {
+                        if let Value::List(clause) = clause {
+                            add_function(&mut pending, &scope, &clause);
+                        }
+                    }
+                }
+
+                // each binding is in scope for those after it, and the body
+                Some("let" | "for") => {
+                    let mut scope = scope.clone();
+                    let bindings = match args.car() {
+                        Ok(Value::List(bindings)) => bindings,
+                        _ => List::NIL,
+                    };
+
+                    for binding in &bindings {
+                        match binding {
+                            Value::List(binding) => {
+                                pending.extend(
+                                    binding
+                                        .cdr()
+                                        .into_iter()
+                                        .map(|expr| (expr, scope.clone(), false)),
+                                );
+
+                                if let Ok(Value::Symbol(name)) = binding.car() {
+                                    scope = Scope::extend(scope, vec![name]);
+                                }
+                            }
+                            other => pending.push((other, scope.clone(), false)),
+                        }
+                    }
+
+                    for form in &args.cdr() {
+                        match &form {
+                            // only the condition of a `for` guard is evaluated
+                            Value::List(guard)
+                                if keyword.as_deref() == Some("for")
+                                    && matches!(guard.car(), Ok(Value::Symbol(Symbol(head))) if &*head == "when") =>
+                            {
+                                pending.extend(
+                                    guard
+                                        .cdr()
+                                        .into_iter()
+                                        .map(|expr| (expr, scope.clone(), false)),
+                                );
+                            }
+                            _ => pending.push((form, scope.clone(), false)),
+                        }
+                    }
+                }
+
+                // like `let`, but each clause binds a list of names
+                Some("let_values") => {
+                    let mut scope = scope.clone();
+                    let clauses = match args.car() {
+                        Ok(Value::List(clauses)) => clauses,
+                        _ => List::NIL,
+                    };
+
+                    for clause in &clauses {
+                        let Value::List(clause) = clause else {
+                            pending.push((clause, scope.clone(), false));
+                            continue;
+                        };
+
+                        pending.extend(
+                            clause
+                                .cdr()
+                                .into_iter()
+                                .map(|expr| (expr, scope.clone(), false)),
+                        );
+
+                        if let Ok(Value::List(names)) = clause.car() {
+                            let names = names
+                                .into_iter()
+                                .filter_map(|name| match name {
+                                    Value::Symbol(name) if &*name.0 != "&rest" => Some(name),
+                                    _ => None,
+                                })
+                                .collect();
+                            scope = Scope::extend(scope, names);
+                        }
+                    }
+
+                    pending.extend(
+                        args.cdr()
+                            .into_iter()
+                            .map(|form| (form, scope.clone(), false)),
+                    );
+                }
+
+                // the initial values are outside of the loop, and everything
+                // else (including the steps) is inside it
+                Some("do") => {
+                    let clauses = match args.car() {
+                        Ok(Value::List(clauses)) => clauses,
+                        _ => List::NIL,
+                    };
+                    let mut names = Vec::new();
+                    let mut inside = Vec::new();
+
+                    for clause in &clauses {
+                        match clause {
+                            Value::List(clause) => {
+                                let mut parts = clause.into_iter();
+
+                                match parts.next() {
+                                    Some(Value::Symbol(name)) => names.push(name),
+                                    Some(other) => pending.push((other, scope.clone(), false)),
+                                    None => {}
+                                }
+                                if let Some(init) = parts.next() {
+                                    pending.push((init, scope.clone(), false));
+                                }
+                                inside.extend(parts);
+                            }
+                            other => pending.push((other, scope.clone(), false)),
+                        }
+                    }
+
+                    // the (test result...) clause, and then the body
+                    match args.cdr().car() {
+                        Ok(Value::List(exit)) => inside.extend(&exit),
+                        Ok(other) => inside.push(other),
+                        Err(_) => {}
+                    }
+                    inside.extend(&args.cdr().cdr());
+
+                    let scope = Scope::extend(scope, names);
+                    pending.extend(inside.into_iter().map(|expr| (expr, scope.clone(), false)));
+                }
+
+                Some("define") => pending.extend(
+                    args.cdr()
+                        .into_iter()
+                        .map(|arg| (arg, scope.clone(), false)),
+                ),
+                Some(_) => pending.extend(args.into_iter().map(|arg| (arg, scope.clone(), false))),
+                None if quoted => pending.extend(
+                    unseen_items(&list, &mut seen)
+                        .into_iter()
+                        .map(|item| (item, scope.clone(), true)),
+                ),
+                None => pending.extend(list.into_iter().map(|item| (item, scope.clone(), false))),
+            }
+        }
+
+        found
+    }
+}
+
+/// The items of the list's cons cells up to the first one in `seen` (adding
+/// them to it), so a list which contains itself is only gone through once
+fn unseen_items(list: &List, seen: &mut HashSet<*const ()>) -> Vec<Value> {
+    let mut items = Vec::new();
+    let mut rest = list.clone();
+
+    while let Some(cell) = rest.head_ptr() {
+        if !seen.insert(cell) {
+            break;
+        }
+
+        items.push(rest.car().unwrap_or(Value::NIL));
+        rest = rest.cdr();
+    }
+
+    items
+}
+
+/// Call `rewrite` on a value, keeping it if there's no replacement, along with
+/// whether it's been changed (which it already has if `changed`)
+fn replace(
+    rewrite: &impl Fn(&Value) -> Option<Value>,
+    value: Value,
+    changed: bool,
+) -> (Value, bool) {
+    match rewrite(&value) {
+        Some(replacement) => (replacement, true),
+        None => (value, changed),
+    }
+}
+
+/// The names bound around an expression, innermost first
+struct Scope {
+    names: Vec<Symbol>,
+    outer: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    fn extend(outer: Option<Rc<Scope>>, names: Vec<Symbol>) -> Option<Rc<Scope>> {
+        Some(Rc::new(Scope { names, outer }))
+    }
+
+    fn binds(mut scope: &Option<Rc<Scope>>, name: &Symbol) -> bool {
+        while let Some(inner) = scope {
+            if inner.names.contains(name) {
+                return true;
+            }
+            scope = &inner.outer;
+        }
+
+        false
+    }
+}
+
+impl Drop for Scope {
+    // a long chain of nested scopes would be dropped recursively otherwise
+    fn drop(&mut self) {
+        let mut outer = self.outer.take();
+
+        while let Some(scope) = outer {
+            outer = match Rc::try_unwrap(scope) {
+                Ok(mut scope) => scope.outer.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+/// Queue up the parts of a `(params body...)` function definition: any
+/// `&key` defaults, and the body, with the parameters in scope
+fn add_function(
+    pending: &mut Vec<(Value, Option<Rc<Scope>>, bool)>,
+    scope: &Option<Rc<Scope>>,
+    definition: &List,
+) {
+    let mut names = Vec::new();
+    let mut defaults = Vec::new();
+
+    if let Ok(Value::List(params)) = definition.car() {
+        for param in &params {
+            match param {
+                Value::Symbol(name) if !matches!(&*name.0, "&rest" | "&key" | "...") => {
+                    names.push(name)
+                }
+                Value::List(pair) => {
+                    if let Ok(Value::Symbol(name)) = pair.car() {
+                        names.push(name);
+                    }
+                    defaults.extend(&pair.cdr());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let scope = Scope::extend(scope.clone(), names);
+
+    pending.extend(
+        defaults
+            .into_iter()
+            .chain(&definition.cdr())
+            .map(|expr| (expr, scope.clone(), false)),
+    );
+}
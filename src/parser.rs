@@ -1,177 +1,997 @@
+//! Reading source code into `Value`s, without evaluating it. This is what
+//! `eval()` is given, and it's also meant for tools that work on the code
+//! itself (formatters, linters, etc).
+//!
+//! [`parse()`] reads one top-level form at a time:
+//!
+//! ```
+//! use rust_lisp::{lisp, parser::parse};
+//!
+//! let source = "
+//!   ;; comments are skipped
+//!   (define x 12)
+//!   'quoted
+//!   (print (+ x 1))";
+//!
+//! let forms = parse(source).collect::<Result<Vec<_>, _>>().unwrap();
+//!
+//! assert_eq!(
+//!     forms,
+//!     vec![
+//!         lisp! { (define x 12) },
+//!         lisp! { (quote quoted) },
+//!         lisp! { (print (+ x 1)) },
+//!     ]
+//! );
+//! ```
+//!
+//! The forms before an error are still returned, and then the error (which
+//! has the span of source where it happened), after which the stream ends:
+//!
+//! ```
+//! use rust_lisp::{lisp, parser::{parse, render_error}};
+//!
+//! let source = "(define x 12)\n(print \"unclosed)\n(never reached)";
+//! let mut forms = parse(source);
+//!
+//! assert_eq!(forms.next(), Some(Ok(lisp! { (define x 12) })));
+//!
+//! let err = forms.next().unwrap().unwrap_err();
+//! assert_eq!(err.msg, "Unclosed string at index 46");
+//! assert_eq!(&source[err.span.clone()], "\"unclosed)\n(never reached)");
+//!
+//! assert_eq!(
+//!     render_error(source, err.span.clone(), &err),
+//!     "\
+//! Parse error: Unclosed string at index 46
+//!   |
+//! 2 | (print \"unclosed)
+//!   |        ^^^^^^^^^^
+//! "
+//! );
+//!
+//! assert_eq!(forms.next(), None);
+//! ```
+
 use crate::{
-    lisp,
-    model::{FloatType, IntType, List, Symbol, Value},
+    collections::HashMap,
+    lisp_list,
+    model::{
+        cons_all, non_finite_float, resolve_placeholder, FloatType, IntType, List, Symbol, Value,
+        CHAR_NAMES,
+    },
+    sym,
 };
+use alloc::{borrow::ToOwned, rc::Rc, string::String, vec::Vec};
 
-use std::fmt::Display;
+use core::{
+    borrow::Borrow,
+    cell::RefCell,
+    fmt::{Debug, Display},
+    ops::Range,
+};
 
-/// Parse a string of Lisp code into a series of s-expressions. There
-/// are more than one expressions when the base string has more than one
-/// independent parenthesized lists at its root.
+/// Parse a string of Lisp code into a series of s-expressions, one for each
+/// top-level form in it (skipping whitespace and comments). Nothing is
+/// evaluated. The iterator ends after the first error, since there's no
+/// telling where the next expression starts. An unmatched `)` is an error, but
+/// the iterator also ends (without one, for now) at anything else that can't
+/// start an expression.
 pub fn parse(code: &str) -> impl Iterator<Item = Result<Value, ParseError>> + '_ {
+    parse_forms(Parser::new(), code)
+}
+
+/// A parser with extensions to the syntax, registered from Rust. Without any,
+/// it reads code the same way [`parse()`] does.
+///
+/// ```
+/// use rust_lisp::{lisp, model::{List, Value}, parser::Parser, sym};
+///
+/// let mut parser = Parser::new();
+///
+/// // #p"/etc/hosts" reads as (path "/etc/hosts")
+/// parser.register_dispatch('p', |reader| match reader.read()? {
+///     Value::String(path) => Ok(lisp! { (path { Value::String(path) }) }),
+///     _ => Err(reader.error("Expected a string after #p")),
+/// });
+///
+/// // [1 2] reads as (list 1 2)
+/// parser.register_char_macro('[', |reader| {
+///     let items = reader.read_until(']')?;
+///     Ok(Value::List(List::from_vec(items).cons(sym!("list"))))
+/// });
+///
+/// let forms = parser.parse("(open #p\"/etc/hosts\" [1 2])").collect::<Vec<_>>();
+/// assert_eq!(forms, vec![Ok(lisp! { (open (path "/etc/hosts") (list 1 2)) })]);
+///
+/// let err = parser.parse("(open #p12)").next().unwrap().unwrap_err();
+/// assert_eq!(err.msg, "Expected a string after #p at index 6");
+/// assert_eq!(err.span, 6..10);
+/// ```
+///
+/// An interpreter's `Context` has the parser that `load` and the REPL use.
+#[derive(Clone, Default)]
+pub struct Parser {
+    dispatch_macros: Vec<(char, Rc<ReaderMacro>)>,
+    char_macros: Vec<(char, Rc<ReaderMacro>)>,
+    brackets: Brackets,
+}
+
+/// How `[]` and `{}` are read, as set with `Parser::set_brackets()`
+///
+/// ```
+/// use rust_lisp::{lisp, parser::{Brackets, Parser}};
+///
+/// let mut parser = Parser::new();
+///
+/// parser.set_brackets(Brackets::Grouping);
+/// assert_eq!(parser.parse("[+ 1 2]").next(), Some(Ok(lisp! { (+ 1 2) })));
+///
+/// parser.set_brackets(Brackets::Collections);
+/// assert_eq!(
+///     parser.parse("[1 2] {a 1}").collect::<Vec<_>>(),
+///     vec![Ok(lisp! { (list 1 2) }), Ok(lisp! { (hash a 1) })]
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Brackets {
+    /// They're ordinary chars, which can be part of symbols (the default)
+    #[default]
+    Ordinary,
+    /// They group a list, just like `()`
+    Grouping,
+    /// `[1 2]` reads as `(list 1 2)`, and `{a 1}` as `(hash a 1)`
+    Collections,
+}
+
+impl Brackets {
+    /// The list that `open` starts, if it's an opening bracket: what closes
+    /// it, and the symbol that's read at its head (if any)
+    fn open(self, open: char) -> Option<(char, Option<&'static str>)> {
+        match (open, self) {
+            ('(', _) => Some((')', None)),
+            ('[', Brackets::Grouping) => Some((']', None)),
+            ('{', Brackets::Grouping) => Some(('}', None)),
+            ('[', Brackets::Collections) => Some((']', Some("list"))),
+            ('{', Brackets::Collections) => Some(('}', Some("hash"))),
+            _ => None,
+        }
+    }
+
+    /// The chars that close a list
+    fn closing(self) -> &'static [char] {
+        match self {
+            Brackets::Ordinary => &[')'],
+            Brackets::Grouping | Brackets::Collections => &[')', ']', '}'],
+        }
+    }
+}
+
+/// A function which reads an extension to the syntax, starting from just
+/// after the chars that mark it. It reads whatever follows through the
+/// [`Reader`], and returns the value it stands for (often a `Value::Foreign`,
+/// or a list tagged with a symbol), or an error from `Reader::error()` or
+/// from reading.
+pub type ReaderMacro = dyn Fn(&mut Reader) -> Result<Value, ParseError>;
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read `#` followed by `ch` with `handler`, like `#d"2024-01-01"` with
+    /// `'d'`. This takes precedence over the built-in `#t`, `#f` and `#\`,
+    /// and registering a char again replaces its handler.
+    pub fn register_dispatch(
+        &mut self,
+        ch: char,
+        handler: impl Fn(&mut Reader) -> Result<Value, ParseError> + 'static,
+    ) {
+        register(&mut self.dispatch_macros, ch, Rc::new(handler));
+    }
+
+    /// Read `ch` with `handler` wherever an expression can start, like `[`
+    /// for a vector literal. The char also ends any token before it, so it
+    /// doesn't need whitespace around it. A closing char can be registered
+    /// with a handler that returns an error, to report it when it's stray.
+    pub fn register_char_macro(
+        &mut self,
+        ch: char,
+        handler: impl Fn(&mut Reader) -> Result<Value, ParseError> + 'static,
+    ) {
+        register(&mut self.char_macros, ch, Rc::new(handler));
+    }
+
+    /// Choose how `[]` and `{}` are read. Unless they're `Brackets::Ordinary`
+    /// (the default), they also end any token before them, and a list has to
+    /// be closed by the same kind of bracket that opened it.
+    pub fn set_brackets(&mut self, brackets: Brackets) {
+        self.brackets = brackets;
+    }
+
+    /// Parse `code` like [`parse()`] does, with this parser's extensions
+    pub fn parse<'a>(
+        &'a self,
+        code: &'a str,
+    ) -> impl Iterator<Item = Result<Value, ParseError>> + 'a {
+        parse_forms(self, code)
+    }
+}
+
+fn register(macros: &mut Vec<(char, Rc<ReaderMacro>)>, ch: char, handler: Rc<ReaderMacro>) {
+    macros.retain(|(registered, _)| *registered != ch);
+    macros.push((ch, handler));
+}
+
+impl Debug for Parser {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let chars = |macros: &[(char, Rc<ReaderMacro>)]| {
+            macros.iter().map(|(ch, _)| *ch).collect::<Vec<_>>()
+        };
+
+        f.debug_struct("Parser")
+            .field("dispatch_macros", &chars(&self.dispatch_macros))
+            .field("char_macros", &chars(&self.char_macros))
+            .field("brackets", &self.brackets)
+            .finish()
+    }
+}
+
+/// The code being parsed, as a [`ReaderMacro`] sees it: reading carries on
+/// from just after the chars that marked the macro
+pub struct Reader<'a> {
+    syntax: Syntax<'a>,
+    code: &'a str,
+    start: usize,
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// The index in the code that reading has got to
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Read the next expression, with the same syntax (including extensions),
+    /// skipping any whitespace and comments before it
+    pub fn read(&mut self) -> Result<Value, ParseError> {
+        self.index = consume_whitespace_and_comments(self.code, self.index);
+
+        match parse_expression(&self.syntax, self.code, self.index) {
+            Some(Ok(res)) => {
+                self.index = res.index;
+                Ok(res.parsed)
+            }
+            Some(Err(err)) => Err(err),
+            None => Err(ParseError {
+                msg: format!("Expected an expression at index {}", self.index),
+                span: self.index..self.index + 1,
+            }),
+        }
+    }
+
+    /// Read expressions until `close`, like the `]` after a `[`, and skip
+    /// past it. While they're read, `close` ends tokens as well, so `[1 2]`
+    /// doesn't read `2]` as a symbol.
+    pub fn read_until(&mut self, close: char) -> Result<Vec<Value>, ParseError> {
+        let mut syntax = self.syntax.clone();
+        syntax.breaks.push(close);
+
+        let mut items = Vec::new();
+
+        loop {
+            self.index = consume_whitespace_and_comments(self.code, self.index);
+
+            if self.code[self.index..].starts_with(close) {
+                self.index += close.len_utf8();
+                return Ok(items);
+            }
+
+            match parse_expression(&syntax, self.code, self.index) {
+                Some(Ok(res)) => {
+                    self.index = res.index;
+                    items.push(res.parsed);
+                }
+                Some(Err(err)) => return Err(err),
+                None => {
+                    return Err(ParseError {
+                        msg: format!("Expected {} at index {}", close, self.index),
+                        span: self.start..self.index + 1,
+                    })
+                }
+            }
+        }
+    }
+
+    /// The raw text from here up to the next whitespace or token-ending char,
+    /// without reading it as anything
+    pub fn read_token(&mut self) -> &'a str {
+        let start = self.index;
+        self.index = consume_while(self.code, start, |(_, ch)| {
+            is_symbolic(ch, &self.syntax.breaks)
+        })
+        .map_or(start, |(last_index, last_char)| {
+            last_index + last_char.len_utf8()
+        });
+
+        &self.code[start..self.index]
+    }
+
+    /// An error in this macro, spanning from where it started to where
+    /// reading has got to
+    pub fn error(&self, msg: impl Display) -> ParseError {
+        ParseError {
+            msg: format!("{} at index {}", msg, self.start),
+            span: self.start..self.index.max(self.start + 1),
+        }
+    }
+}
+
+/// Parse each top-level form of `code` in turn, with `parser`
+fn parse_forms<'a>(
+    parser: impl Borrow<Parser> + 'a,
+    code: &'a str,
+) -> impl Iterator<Item = Result<Value, ParseError>> + 'a {
     let mut index = 0;
     index = consume_whitespace_and_comments(code, index);
+    let mut failed = false;
+
+    core::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+
+        let syntax = Syntax::new(parser.borrow());
 
-    std::iter::from_fn(move || {
-        if let Some(res) = parse_expression(code, index) {
+        if let Some(res) = parse_expression(&syntax, code, index) {
             if let Ok(res) = res {
                 index = res.index;
                 index = consume_whitespace_and_comments(code, index);
 
-                Some(Ok(res.parsed.into_value()))
+                Some(Ok(res.parsed))
             } else {
+                failed = true;
                 Some(Err(res.unwrap_err()))
             }
+        } else if let Some(close) = closing_bracket(&syntax, code, index) {
+            failed = true;
+            Some(Err(ParseError {
+                msg: format!("Unexpected {} at index {}", close, index),
+                span: index..index + 1,
+            }))
         } else {
             None // TODO: Err if we don't parse the whole input?
         }
     })
 }
 
-/// A slightly more convenient data structure for building the parse tree, before
-/// eventually converting it into proper s-expressions.
-#[derive(Debug, Clone)]
-enum ParseTree {
-    Atom(Value),
-    List(Vec<ParseTree>),
-    Quoted(Box<ParseTree>),
-    Comma(Box<ParseTree>),
-}
-
-impl ParseTree {
-    pub fn into_value(self) -> Value {
-        match self {
-            ParseTree::Atom(value) => value,
-            ParseTree::List(vec) => Value::List(
-                vec.into_iter()
-                    .map(|parse_tree| parse_tree.into_value())
-                    .collect::<List>(),
-            ),
-            ParseTree::Quoted(inner) => lisp! { (quote {inner.into_value()}) },
-            ParseTree::Comma(inner) => lisp! { (comma {inner.into_value()}) },
-        }
-    }
-}
-
 /**
  * An error that occurred while parsing a string as lisp code
  */
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseError {
+    /// What went wrong, including the index where it happened
     pub msg: String,
-    // pub line: i32,
+
+    /// Where in the source the error is, as a range of byte indices. This
+    /// ends past the end of the source when something's missing at the end.
+    /// An unclosed string or list starts at its opening `"` or `(`.
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    /// Show the error the way rustc does, with the file, line and column it
+    /// happened at, the line itself with carets under the span (so an unclosed
+    /// list points back at its opening `(`), and a hint for common mistakes:
+    ///
+    /// ```text
+    /// Parse error: Unclosed list at index 27
+    ///  --> main.lisp:2:1
+    ///   |
+    /// 2 | (print (+ x 1)
+    ///   | ^
+    ///   |
+    ///   = hint: this `(` is never closed; add a `)` where the list ends
+    /// ```
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        let location = Location::of(source, self.span.start);
+        let gutter = " ".repeat(location.line_number.len());
+
+        let mut rendered = format!(
+            "{}\n{}--> {}:{}:{}\n{}",
+            self,
+            gutter,
+            filename,
+            location.line_number,
+            location.column + 1,
+            location.excerpt(source, &self.span)
+        );
+
+        if let Some(hint) = self.hint() {
+            rendered += &format!("{} |\n{} = hint: {}\n", gutter, gutter, hint);
+        }
+
+        rendered
+    }
+
+    /// Advice for fixing the common kinds of error
+    fn hint(&self) -> Option<&'static str> {
+        if self.msg.starts_with("Unclosed string") {
+            Some("add a `\"` where the string ends (strings can't contain `\"`)")
+        } else if self.msg.starts_with("Unclosed list") {
+            Some("this `(` is never closed; add a `)` where the list ends")
+        } else if self.msg.starts_with("Unexpected )") {
+            Some("this `)` has no matching `(`; remove it, or add a `(` where the list starts")
+        } else if self.msg.starts_with("Mismatched") {
+            Some("a list has to be closed by the same kind of bracket that opened it")
+        } else {
+            None
+        }
+    }
 }
 
 impl Display for ParseError {
-    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        return write!(formatter, "Parse error: {}", self.msg);
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(formatter, "Parse error: {}", self.msg)
+    }
+}
+
+/// Show an error along with the line of `source` that it happened on, with
+/// carets under the `span` (byte indices) where it happened:
+///
+/// ```text
+/// Parse error: Unclosed string at index 13
+///   |
+/// 1 | (print "hello)
+///   |        ^^^^^^^
+/// ```
+///
+/// A span covering more than one line is only underlined up to the end of its
+/// first line. Currently only a `ParseError` knows its span.
+pub fn render_error(source: &str, span: Range<usize>, err: &impl Display) -> String {
+    let location = Location::of(source, span.start);
+
+    format!("{}\n{}", err, location.excerpt(source, &span))
+}
+
+/// Where an index is in the source, for showing it to people
+struct Location {
+    start: usize,
+    line_start: usize,
+    line_end: usize,
+    line_number: String,
+    /// In characters, from zero
+    column: usize,
+}
+
+impl Location {
+    fn of(source: &str, index: usize) -> Self {
+        let mut start = index.min(source.len());
+        while !source.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+
+        Location {
+            start,
+            line_start,
+            line_end,
+            line_number: format!("{}", source[..line_start].matches('\n').count() + 1),
+            column: source[line_start..start].chars().count(),
+        }
+    }
+
+    /// The line, with carets under as much of `span` as is on it
+    fn excerpt(&self, source: &str, span: &Range<usize>) -> String {
+        let line = source[self.line_start..self.line_end].trim_end_matches('\r');
+        let gutter = " ".repeat(self.line_number.len());
+        let width = source
+            .get(self.start..span.end.clamp(self.start, self.line_end))
+            .map_or(0, |underlined| underlined.chars().count())
+            .max(1);
+
+        format!(
+            "{} |\n{} | {}\n{} | {}{}\n",
+            gutter,
+            self.line_number,
+            line,
+            gutter,
+            " ".repeat(self.column),
+            "^".repeat(width)
+        )
     }
 }
 
 #[derive(Clone, Debug)]
 struct ParsedAndIndex {
-    pub parsed: ParseTree,
+    pub parsed: Value,
     pub index: usize,
 }
 
 type ParseResult = Option<Result<ParsedAndIndex, ParseError>>;
 type ConsumeResult = Option<usize>;
 
-fn parse_expression(code: &str, index: usize) -> ParseResult {
-    for func in [parse_list, parse_atom] {
-        let res = func(code, index);
+/// What parsing needs besides the code: the parser's extensions, the chars
+/// that end a token (besides whitespace and the special tokens), and the
+/// datum labels (`#0=`) in the top-level form being read
+#[derive(Clone)]
+struct Syntax<'a> {
+    parser: &'a Parser,
+    breaks: Vec<char>,
+    labels: Rc<RefCell<HashMap<usize, Label>>>,
+}
+
+/// A datum label, like the `0` of `#0=(1 2)`
+enum Label {
+    /// The labelled value is still being read, so references to it (like in
+    /// `#0=(1 . #0#)`) are read as this placeholder, and replaced at the end
+    Reading(List),
+    Read(Value),
+}
+
+impl<'a> Syntax<'a> {
+    fn new(parser: &'a Parser) -> Self {
+        let mut breaks = parser
+            .char_macros
+            .iter()
+            .map(|(ch, _)| *ch)
+            .collect::<Vec<_>>();
+        if parser.brackets != Brackets::Ordinary {
+            breaks.extend(['[', ']', '{', '}']);
+        }
 
-        if res.is_some() {
-            return res;
+        Syntax {
+            parser,
+            breaks,
+            labels: Rc::default(),
         }
     }
 
-    None
-}
+    /// The value of a reference to a label, like `#0#`
+    fn label_reference(
+        &self,
+        label: usize,
+        index: usize,
+        next: usize,
+    ) -> Result<Value, ParseError> {
+        match RefCell::borrow(&self.labels).get(&label) {
+            Some(Label::Read(value)) => Ok(value.clone()),
+            Some(Label::Reading(placeholder)) => Ok(Value::List(placeholder.clone())),
+            None => Err(ParseError {
+                msg: format!("Undefined label #{}# at index {}", label, index),
+                span: index..next,
+            }),
+        }
+    }
 
-fn parse_list(code: &str, index: usize) -> ParseResult {
-    let mut index = consume(code, index, "(")?;
-    let mut members = vec![];
+    /// Start reading the value of a label, like `#0=`
+    fn start_label(&self, label: usize, index: usize, next: usize) -> Result<(), ParseError> {
+        let mut labels = self.labels.borrow_mut();
+        if labels.contains_key(&label) {
+            return Err(ParseError {
+                msg: format!("Label #{}= is defined twice at index {}", label, index),
+                span: index..next,
+            });
+        }
 
-    index = consume_whitespace_and_comments(code, index);
+        let placeholder = List::NIL.cons(Value::NIL);
+        labels.insert(label, Label::Reading(placeholder));
 
-    while let Some(res) = parse_expression(code, index) {
-        if let Ok(res) = res {
-            index = res.index;
-            members.push(res.parsed);
-            index = consume_whitespace_and_comments(code, index);
-        } else {
-            return Some(res);
+        Ok(())
+    }
+
+    /// Finish reading the value of a label, replacing any references to it
+    /// from inside the value
+    fn finish_label(&self, label: usize, index: usize, value: &Value) -> Result<(), ParseError> {
+        let mut labels = self.labels.borrow_mut();
+        let Some(Label::Reading(placeholder)) = labels.remove(&label) else {
+            unreachable!("label #{}= was started", label);
+        };
+
+        if matches!(value, Value::List(list) if list.ptr_eq(&placeholder)) {
+            return Err(ParseError {
+                msg: format!(
+                    "Label #{}= refers to nothing but itself at index {}",
+                    label, index
+                ),
+                span: index..index + 1,
+            });
         }
+        resolve_placeholder(value, &placeholder, value);
+
+        labels.insert(label, Label::Read(value.clone()));
+
+        Ok(())
     }
 
-    if let Some(index) = consume(code, index, ")") {
-        Some(Ok(ParsedAndIndex {
-            parsed: ParseTree::List(members),
+    /// Run the macro `handler`, which was marked by the chars from `start` up
+    /// to `index`
+    fn expand(
+        &self,
+        handler: &Rc<ReaderMacro>,
+        code: &str,
+        start: usize,
+        index: usize,
+    ) -> ParseResult {
+        let mut reader = Reader {
+            syntax: self.clone(),
+            code,
+            start,
             index,
-        }))
-    } else {
-        Some(Err(ParseError {
-            msg: format!("Unclosed list at index {}", index),
+        };
+
+        Some(handler(&mut reader).map(|parsed| ParsedAndIndex {
+            parsed,
+            index: reader.index,
         }))
     }
 }
 
-fn parse_atom(code: &str, index: usize) -> ParseResult {
-    for func in [
-        parse_quoted,
-        parse_comma,
-        parse_nil,
-        parse_false,
-        parse_true,
-        parse_number,
-        parse_string,
-        parse_symbol,
-    ] {
-        let res = func(code, index);
+/// A list, vector, quote or label that has been started, but not yet
+/// finished, while parsing an expression
+enum Pending {
+    List {
+        members: Vec<Value>,
+        start: usize,
+        /// The bracket that closes it, and the symbol read at its head
+        close: char,
+        tag: Option<&'static str>,
+        /// Where the `.` is, in a dotted list like `(1 2 . rest)`, and then
+        /// the rest of the list that comes after it (which isn't necessarily
+        /// a list, as in `(1 2 . 3)`)
+        dot: Option<usize>,
+        tail: Option<Value>,
+    },
+    /// A `#(...)` literal, whose items aren't evaluated
+    Vector {
+        members: Vec<Value>,
+        start: usize,
+    },
+    Quoted {
+        index: usize,
+    },
+    Comma {
+        index: usize,
+    },
+    Splice {
+        index: usize,
+    },
+    Label {
+        label: usize,
+        index: usize,
+    },
+}
+
+/// Parses one expression. Lists and quotes are handled with an explicit stack
+/// of the ones that are still open (instead of recursively), so that deeply
+/// nested input can't overflow the call stack.
+fn parse_expression(syntax: &Syntax, code: &str, index: usize) -> ParseResult {
+    let mut stack: Vec<Pending> = vec![];
+    let mut index = index;
 
-        if res.is_some() {
-            return res;
+    loop {
+        // nothing can come after the rest of a dotted list but its end
+        if let Some(&Pending::List {
+            tail: Some(_),
+            close,
+            ..
+        }) = stack.last()
+        {
+            if index < code.len() && !code[index..].starts_with(close) {
+                return Some(Err(ParseError {
+                    msg: format!(
+                        "Expected {} after the rest of a dotted list at index {}",
+                        close, index
+                    ),
+                    span: index..index + 1,
+                }));
+            }
         }
-    }
 
-    None
+        let char_macro = code.get(index..).and_then(|rest| {
+            let next = rest.chars().next()?;
+
+            syntax
+                .parser
+                .char_macros
+                .iter()
+                .find(|(ch, _)| *ch == next)
+                .map(|(ch, handler)| (index + ch.len_utf8(), handler))
+        });
+
+        // try to start an expression at `index`
+        let parsed = if let Some((next, handler)) = char_macro {
+            match syntax.expand(handler, code, index, next) {
+                Some(Ok(res)) => {
+                    index = res.index;
+                    Some(res.parsed)
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => None,
+            }
+        } else if let Some((close, tag)) = code
+            .get(index..)
+            .and_then(|rest| rest.chars().next())
+            .and_then(|open| syntax.parser.brackets.open(open))
+        {
+            stack.push(Pending::List {
+                members: vec![],
+                start: index,
+                close,
+                tag,
+                dot: None,
+                tail: None,
+            });
+            index = consume_whitespace_and_comments(code, index + 1);
+            continue;
+        } else if let Some(next) = consume(code, index, "#(") {
+            stack.push(Pending::Vector {
+                members: vec![],
+                start: index,
+            });
+            index = consume_whitespace_and_comments(code, next);
+            continue;
+        } else if let Some((label, defining, next)) = parse_label(code, index) {
+            if defining {
+                if let Err(err) = syntax.start_label(label, index, next) {
+                    return Some(Err(err));
+                }
+
+                stack.push(Pending::Label { label, index });
+                index = next;
+                continue;
+            }
+
+            match syntax.label_reference(label, index, next) {
+                Ok(value) => {
+                    index = next;
+                    Some(value)
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        } else if let Some(next) = dot_in_list(&stack, code, index, &syntax.breaks) {
+            if let Some(Pending::List { dot, .. }) = stack.last_mut() {
+                *dot = Some(index);
+            }
+            index = consume_whitespace_and_comments(code, next);
+            continue;
+        } else if let Some(next) = consume(code, index, "'") {
+            stack.push(Pending::Quoted { index });
+            index = next;
+            continue;
+        } else if let Some(next) = consume(code, index, ",") {
+            stack.push(Pending::Comma { index });
+            index = next;
+            continue;
+        } else if let Some(next) = consume(code, index, "@") {
+            stack.push(Pending::Splice { index });
+            index = next;
+            continue;
+        } else {
+            match parse_atom(syntax, code, index) {
+                Some(Ok(res)) => {
+                    index = res.index;
+                    Some(res.parsed)
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => None,
+            }
+        };
+
+        let mut parsed = match parsed {
+            Some(parsed) => parsed,
+
+            // no expression here; that's only okay if it's the end of a list
+            None => loop {
+                match stack.pop() {
+                    None => return None,
+
+                    // a quote or label that isn't followed by anything isn't
+                    // an expression either
+                    Some(
+                        Pending::Quoted { index: start }
+                        | Pending::Comma { index: start }
+                        | Pending::Splice { index: start }
+                        | Pending::Label { index: start, .. },
+                    ) => {
+                        index = start;
+                    }
+
+                    Some(Pending::List {
+                        mut members,
+                        start,
+                        close,
+                        tag,
+                        dot,
+                        tail,
+                    }) => match (closing_bracket(syntax, code, index), dot, tail) {
+                        (Some(found), _, _) if found != close => {
+                            return Some(Err(ParseError {
+                                msg: format!(
+                                    "Mismatched {} at index {}: expected {} to close the {} at index {}",
+                                    found,
+                                    index,
+                                    close,
+                                    &code[start..start + 1],
+                                    start
+                                ),
+                                span: start..index + 1,
+                            }))
+                        }
+                        (Some(_), Some(dot), None) => return Some(Err(missing_tail(dot, index))),
+                        (Some(_), _, tail) => {
+                            index += 1;
+                            if let Some(tag) = tag {
+                                members.insert(0, sym!(tag));
+                            }
+                            break cons_all(&members, tail.unwrap_or(Value::NIL));
+                        }
+                        (None, _, _) => {
+                            return Some(Err(ParseError {
+                                msg: format!("Unclosed list at index {}", index),
+                                span: start..index + 1,
+                            }))
+                        }
+                    },
+
+                    Some(Pending::Vector { members, start }) => {
+                        match closing_bracket(syntax, code, index) {
+                            Some(')') => {
+                                index += 1;
+                                break Value::from(members);
+                            }
+                            Some(found) => {
+                                return Some(Err(ParseError {
+                                    msg: format!(
+                                        "Mismatched {} at index {}: expected ) to close the #( at index {}",
+                                        found, index, start
+                                    ),
+                                    span: start..index + 1,
+                                }))
+                            }
+                            None => {
+                                return Some(Err(ParseError {
+                                    msg: format!("Unclosed vector at index {}", index),
+                                    span: start..index + 1,
+                                }))
+                            }
+                        }
+                    }
+                }
+            },
+        };
+
+        // finish off any quotes around the expression, and then add it to
+        // the list it's in (if any)
+        loop {
+            match stack.last_mut() {
+                None => return Some(Ok(ParsedAndIndex { parsed, index })),
+                Some(Pending::Quoted { .. }) => {
+                    stack.pop();
+                    parsed = lisp_list![sym!("quote"), parsed];
+                }
+                Some(Pending::Comma { .. }) => {
+                    stack.pop();
+                    parsed = lisp_list![sym!("comma"), parsed];
+                }
+                Some(Pending::Splice { .. }) => {
+                    stack.pop();
+                    parsed = lisp_list![sym!("splice"), parsed];
+                }
+                Some(&mut Pending::Label {
+                    label,
+                    index: start,
+                }) => {
+                    stack.pop();
+                    if let Err(err) = syntax.finish_label(label, start, &parsed) {
+                        return Some(Err(err));
+                    }
+                }
+                Some(Pending::List {
+                    members, dot, tail, ..
+                }) => {
+                    match (*dot, parsed) {
+                        (None, parsed) => members.push(parsed),
+                        (Some(_), rest) => *tail = Some(rest),
+                    }
+                    index = consume_whitespace_and_comments(code, index);
+                    break;
+                }
+                Some(Pending::Vector { members, .. }) => {
+                    members.push(parsed);
+                    index = consume_whitespace_and_comments(code, index);
+                    break;
+                }
+            }
+        }
+    }
 }
 
-fn parse_quoted(code: &str, index: usize) -> ParseResult {
-    let index = consume(code, index, "'")?;
-    let res = parse_expression(code, index)?;
+/// A datum label, like `#0=` (which defines the label, and is true) or `#0#`
+/// (which refers to it), and the index after it
+fn parse_label(code: &str, index: usize) -> Option<(usize, bool, usize)> {
+    let start = consume(code, index, "#")?;
+    let digits = code[start..].len()
+        - code[start..]
+            .trim_start_matches(|ch: char| ch.is_ascii_digit())
+            .len();
+    let label = code[start..start + digits].parse().ok()?;
 
-    if let Ok(ParsedAndIndex { parsed, index }) = res {
-        Some(Ok(ParsedAndIndex {
-            parsed: ParseTree::Quoted(Box::new(parsed)),
-            index,
-        }))
-    } else {
-        Some(res)
+    match code[start + digits..].chars().next()? {
+        '=' => Some((label, true, start + digits + 1)),
+        '#' => Some((label, false, start + digits + 1)),
+        _ => None,
     }
 }
 
-fn parse_comma(code: &str, index: usize) -> ParseResult {
-    let index = consume(code, index, ",")?;
-    let res = parse_expression(code, index)?;
+/// The `.` of a dotted list, like `(1 2 . rest)`, and the index after it. It
+/// has to be on its own, after at least one item.
+fn dot_in_list(stack: &[Pending], code: &str, index: usize, breaks: &[char]) -> ConsumeResult {
+    match stack.last() {
+        Some(Pending::List {
+            members, dot: None, ..
+        }) if !members.is_empty() => {
+            let next = consume(code, index, ".")?;
+            next_char_is_break(code, next, breaks).then_some(next)
+        }
+        _ => None,
+    }
+}
 
-    if let Ok(ParsedAndIndex { parsed, index }) = res {
-        Some(Ok(ParsedAndIndex {
-            parsed: ParseTree::Comma(Box::new(parsed)),
-            index,
-        }))
-    } else {
-        Some(res)
+/// The bracket at `index`, if it's one that closes a list
+fn closing_bracket(syntax: &Syntax, code: &str, index: usize) -> Option<char> {
+    let ch = code.get(index..)?.chars().next()?;
+
+    syntax.parser.brackets.closing().contains(&ch).then_some(ch)
+}
+
+/// The error for a `.` in a list that isn't followed by the rest of the list
+fn missing_tail(dot: usize, index: usize) -> ParseError {
+    ParseError {
+        msg: format!("Expected a value after the . at index {}", dot),
+        span: dot..index + 1,
     }
 }
 
-fn parse_nil(code: &str, index: usize) -> ParseResult {
+fn parse_atom(syntax: &Syntax, code: &str, index: usize) -> ParseResult {
+    let breaks = &syntax.breaks;
+
+    parse_dispatch(syntax, code, index)
+        .or_else(|| parse_nil(code, index, breaks))
+        .or_else(|| parse_false(code, index, breaks))
+        .or_else(|| parse_true(code, index, breaks))
+        .or_else(|| parse_float_notation(code, index, breaks))
+        .or_else(|| parse_number(code, index, breaks))
+        .or_else(|| parse_string(code, index))
+        .or_else(|| parse_char(code, index, breaks))
+        .or_else(|| parse_bytes(code, index, breaks))
+        .or_else(|| parse_symbol(code, index, breaks))
+}
+
+/// `#` followed by a char that a dispatch macro has been registered for
+fn parse_dispatch(syntax: &Syntax, code: &str, index: usize) -> ParseResult {
+    let start = consume(code, index, "#")?;
+    let next = code.get(start..)?.chars().next()?;
+
+    let (_, handler) = syntax
+        .parser
+        .dispatch_macros
+        .iter()
+        .find(|(ch, _)| *ch == next)?;
+
+    syntax.expand(handler, code, index, start + next.len_utf8())
+}
+
+fn parse_nil(code: &str, index: usize, breaks: &[char]) -> ParseResult {
     let index = consume(code, index, "nil")?;
 
-    if next_char_is_break(code, index) {
+    if next_char_is_break(code, index, breaks) {
         Some(Ok(ParsedAndIndex {
-            parsed: ParseTree::Atom(Value::NIL),
+            parsed: Value::NIL,
             index,
         }))
     } else {
@@ -179,12 +999,13 @@ fn parse_nil(code: &str, index: usize) -> ParseResult {
     }
 }
 
-fn parse_false(code: &str, index: usize) -> ParseResult {
-    let index = consume(code, index, "f")?;
+fn parse_false(code: &str, index: usize, breaks: &[char]) -> ParseResult {
+    // Scheme's #f is accepted too
+    let index = consume(code, index, "f").or_else(|| consume(code, index, "#f"))?;
 
-    if next_char_is_break(code, index) {
+    if next_char_is_break(code, index, breaks) {
         Some(Ok(ParsedAndIndex {
-            parsed: ParseTree::Atom(Value::False),
+            parsed: Value::False,
             index,
         }))
     } else {
@@ -192,12 +1013,13 @@ fn parse_false(code: &str, index: usize) -> ParseResult {
     }
 }
 
-fn parse_true(code: &str, index: usize) -> ParseResult {
-    let index = consume(code, index, "t")?;
+fn parse_true(code: &str, index: usize, breaks: &[char]) -> ParseResult {
+    // Scheme's #t is accepted too
+    let index = consume(code, index, "t").or_else(|| consume(code, index, "#t"))?;
 
-    if next_char_is_break(code, index) {
+    if next_char_is_break(code, index, breaks) {
         Some(Ok(ParsedAndIndex {
-            parsed: ParseTree::Atom(Value::True),
+            parsed: Value::True,
             index,
         }))
     } else {
@@ -205,7 +1027,7 @@ fn parse_true(code: &str, index: usize) -> ParseResult {
     }
 }
 
-fn parse_number(code: &str, index: usize) -> ParseResult {
+fn parse_number(code: &str, index: usize, breaks: &[char]) -> ParseResult {
     let (front_last_index, front_last_char) = consume_while(code, index, |(index, ch)| {
         (index == 0 && ch == '-') || ch.is_numeric()
     })?;
@@ -221,14 +1043,14 @@ fn parse_number(code: &str, index: usize) -> ParseResult {
             let back_last_index = back_last_index + 1;
 
             if back_last_index >= front_last_index + 2 {
-                if next_char_is_break(code, back_last_index) {
+                if next_char_is_break(code, back_last_index, breaks) {
                     if let Ok(float) = code
                         .get(index..back_last_index)
                         .unwrap_or("")
                         .parse::<FloatType>()
                     {
                         return Some(Ok(ParsedAndIndex {
-                            parsed: ParseTree::Atom(Value::Float(float)),
+                            parsed: Value::Float(float),
                             index: back_last_index,
                         }));
                     }
@@ -239,20 +1061,38 @@ fn parse_number(code: &str, index: usize) -> ParseResult {
                         "Expected decimal value after '.' at index {}",
                         back_last_index - 1
                     ),
+                    span: back_last_index - 1..back_last_index,
                 }));
             }
         }
 
-        if next_char_is_break(code, front_last_index) {
-            if let Ok(int) = code
-                .get(index..front_last_index)
-                .unwrap_or("")
-                .parse::<IntType>()
-            {
-                return Some(Ok(ParsedAndIndex {
-                    parsed: ParseTree::Atom(Value::Int(int)),
-                    index: front_last_index,
-                }));
+        if next_char_is_break(code, front_last_index, breaks) {
+            let literal = code.get(index..front_last_index).unwrap_or("");
+
+            match literal.parse::<IntType>() {
+                Ok(int) => {
+                    return Some(Ok(ParsedAndIndex {
+                        parsed: Value::Int(int),
+                        index: front_last_index,
+                    }))
+                }
+
+                // plain ascii digits can only fail to parse if they don't fit
+                Err(_)
+                    if literal
+                        .trim_start_matches('-')
+                        .bytes()
+                        .all(|b| b.is_ascii_digit()) =>
+                {
+                    return Some(Err(ParseError {
+                        msg: format!(
+                            "Integer literal {} is out of range at index {}",
+                            literal, index
+                        ),
+                        span: index..front_last_index,
+                    }))
+                }
+                Err(_) => {}
             }
         }
     }
@@ -260,40 +1100,154 @@ fn parse_number(code: &str, index: usize) -> ParseResult {
     None
 }
 
+/// A float in scientific notation, like `1.5e-7`, or NaN or an infinity,
+/// written `+nan.0`, `+inf.0` or `-inf.0` (which is how floats are printed)
+fn parse_float_notation(code: &str, index: usize, breaks: &[char]) -> ParseResult {
+    let end = consume_while(code, index, |(_, ch)| is_symbolic(ch, breaks))
+        .map(|(last_index, last_char)| last_index + last_char.len_utf8())?;
+    let token = &code[index..end];
+
+    let float = match non_finite_float(token) {
+        Some(float) => float,
+        None if is_scientific(token) => token.parse().ok()?,
+        None => return None,
+    };
+
+    Some(Ok(ParsedAndIndex {
+        parsed: Value::Float(float),
+        index: end,
+    }))
+}
+
+/// Whether `token` is a number with an exponent, like `-1.5e10` or `2E-3`
+fn is_scientific(token: &str) -> bool {
+    let Some((mantissa, exponent)) = token.split_once(['e', 'E']) else {
+        return false;
+    };
+    let mantissa = mantissa.strip_prefix(['-', '+']).unwrap_or(mantissa);
+    let exponent = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, "0"));
+
+    [whole, fraction, exponent]
+        .iter()
+        .all(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+}
+
 fn parse_string(code: &str, index: usize) -> ParseResult {
-    let (last_index, _) = consume_while(code, index, |(index, ch)| {
+    let (last_index, last_char) = consume_while(code, index, |(index, ch)| {
         (index == 0 && ch == '"') || (index > 0 && ch != '"')
     })?;
+    let end = last_index + last_char.len_utf8();
 
-    if last_index > index {
-        if code.as_bytes().get(last_index + 1) == Some(&b'"') {
-            Some(Ok(ParsedAndIndex {
-                parsed: ParseTree::Atom(Value::String(
-                    code.get(index + 1..last_index + 1).unwrap_or("").to_owned(),
-                )),
-                index: last_index + 2,
-            }))
-        } else {
-            Some(Err(ParseError {
-                msg: format!("Unclosed string at index {}", last_index),
-            }))
-        }
+    if code.as_bytes().get(end) == Some(&b'"') {
+        Some(Ok(ParsedAndIndex {
+            parsed: Value::String(code.get(index + 1..end).unwrap_or("").to_owned()),
+            index: end + 1,
+        }))
     } else {
-        None
+        Some(Err(ParseError {
+            msg: format!("Unclosed string at index {}", last_index),
+            span: index..end,
+        }))
+    }
+}
+
+/// A char is `#\` followed by either the char itself, or its name (`#\space`,
+/// etc). Anything symbolic straight after it is part of the name, so `#\(`
+/// and `#\)` are chars, but `#\ab` is an error.
+fn parse_char(code: &str, index: usize, breaks: &[char]) -> ParseResult {
+    let start = consume(code, index, "#\\")?;
+
+    let Some(first) = code.get(start..).and_then(|rest| rest.chars().next()) else {
+        return Some(Err(ParseError {
+            msg: format!("Expected a char after #\\ at index {}", start),
+            span: index..start,
+        }));
+    };
+    let end = consume_while(code, start + first.len_utf8(), |(_, ch)| {
+        is_symbolic(ch, breaks)
+    })
+    .map_or(start + first.len_utf8(), |(last_index, last_char)| {
+        last_index + last_char.len_utf8()
+    });
+    let name = code.get(start..end).unwrap_or("");
+
+    let parsed = if name.len() == first.len_utf8() {
+        first
+    } else {
+        match CHAR_NAMES.iter().find(|(known, _)| *known == name) {
+            Some((_, ch)) => *ch,
+            None => {
+                return Some(Err(ParseError {
+                    msg: format!("Unknown char name #\\{} at index {}", name, index),
+                    span: index..end,
+                }))
+            }
+        }
+    };
+
+    Some(Ok(ParsedAndIndex {
+        parsed: Value::Char(parsed),
+        index: end,
+    }))
+}
+
+/// A bytes literal is `#u8(` followed by ints from 0 to 255, and then `)`
+fn parse_bytes(code: &str, index: usize, breaks: &[char]) -> ParseResult {
+    let mut current = consume(code, index, "#u8(")?;
+    let mut bytes = Vec::new();
+
+    loop {
+        current = consume_whitespace_and_comments(code, current);
+
+        match code.get(current..).and_then(|rest| rest.chars().next()) {
+            Some(')') => break,
+            Some(_) => {}
+            None => {
+                return Some(Err(ParseError {
+                    msg: format!("Unclosed bytes literal at index {}", index),
+                    span: index..current,
+                }))
+            }
+        }
+
+        let end = consume_while(code, current, |(_, ch)| is_symbolic(ch, breaks))
+            .map_or(current + 1, |(last_index, last_char)| {
+                last_index + last_char.len_utf8()
+            });
+        let literal = code.get(current..end).unwrap_or("");
+
+        match literal.parse::<u8>() {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => {
+                return Some(Err(ParseError {
+                    msg: format!(
+                        "Expected a byte (0 to 255) in bytes literal, got {} at index {}",
+                        literal, current
+                    ),
+                    span: current..end,
+                }))
+            }
+        }
+
+        current = end;
     }
+
+    Some(Ok(ParsedAndIndex {
+        parsed: Value::from(bytes),
+        index: current + 1,
+    }))
 }
 
-fn parse_symbol(code: &str, index: usize) -> ParseResult {
-    let (last_index, _) = consume_while(code, index, |(index, ch)| {
-        (index == 0 && is_symbol_start(ch)) || (index > 0 && is_symbolic(ch))
+fn parse_symbol(code: &str, index: usize, breaks: &[char]) -> ParseResult {
+    let (last_index, last_char) = consume_while(code, index, |(index, ch)| {
+        (index == 0 && is_symbol_start(ch, breaks)) || (index > 0 && is_symbolic(ch, breaks))
     })?;
-    let last_index = last_index + 1;
+    let last_index = last_index + last_char.len_utf8();
 
     if last_index > index {
         Some(Ok(ParsedAndIndex {
-            parsed: ParseTree::Atom(Value::Symbol(Symbol(
-                code.get(index..last_index).unwrap_or("").to_owned(),
-            ))),
+            parsed: Value::Symbol(Symbol::from(code.get(index..last_index).unwrap_or(""))),
             index: last_index,
         }))
     } else {
@@ -308,11 +1262,11 @@ fn consume(code: &str, index: usize, s: &str) -> ConsumeResult {
         && slice
             .chars()
             .zip(s.chars())
-            .all(|(a, b)| a.to_ascii_lowercase() == b.to_ascii_lowercase())
+            .all(|(a, b)| a.eq_ignore_ascii_case(&b))
     {
-        return Some(index + s.len());
+        Some(index + s.len())
     } else {
-        return None;
+        None
     }
 }
 
@@ -326,12 +1280,14 @@ fn consume_whitespace_and_comments(code: &str, index: usize) -> usize {
             semicolons = 0;
         }
 
-        return ch.is_whitespace() || ch == ';' || semicolons >= 2;
+        ch.is_whitespace() || ch == ';' || semicolons >= 2
     })
-    .map(|(index, _)| index + 1)
+    .map(|(index, ch)| index + ch.len_utf8())
     .unwrap_or(index)
 }
 
+/// Consume characters from `index` for as long as `pred` holds, and return the
+/// (byte) index of the last one consumed, and what it was
 fn consume_while<F: FnMut((usize, char)) -> bool>(
     code: &str,
     index: usize,
@@ -345,19 +1301,18 @@ fn consume_while<F: FnMut((usize, char)) -> bool>(
         .map(|(last_index, ch)| (last_index + index, ch))
 }
 
-fn is_symbol_start(c: char) -> bool {
-    !c.is_numeric() && is_symbolic(c)
+fn is_symbol_start(c: char, breaks: &[char]) -> bool {
+    !c.is_numeric() && is_symbolic(c, breaks)
 }
 
-fn is_symbolic(c: char) -> bool {
-    !c.is_whitespace() && !SPECIAL_TOKENS.iter().any(|t| *t == c)
+fn is_symbolic(c: char, breaks: &[char]) -> bool {
+    !c.is_whitespace() && !SPECIAL_TOKENS.contains(&c) && !breaks.contains(&c)
 }
 
-fn next_char_is_break(code: &str, index: usize) -> bool {
+fn next_char_is_break(code: &str, index: usize, breaks: &[char]) -> bool {
     code.get(index..)
-        .map(|s| s.chars().next())
-        .flatten()
-        .map(|ch| ch.is_whitespace() || SPECIAL_TOKENS.iter().any(|t| *t == ch))
+        .and_then(|s| s.chars().next())
+        .map(|ch| !is_symbolic(ch, breaks))
         .unwrap_or(true)
 }
 
@@ -0,0 +1,13 @@
+;; The part of the standard library that's written in Lisp. It's evaluated
+;; into every `default_env()`, after the builtins are defined.
+
+;; accessors for nested lists
+(defun caar (list) (car (car list)))
+(defun cadr (list) (car (cdr list)))
+(defun cdar (list) (cdr (car list)))
+(defun cddr (list) (cdr (cdr list)))
+(defun caddr (list) (car (cddr list)))
+(defun cdddr (list) (cdr (cddr list)))
+
+(defun second (list) (cadr list))
+(defun third (list) (caddr list))
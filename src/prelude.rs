@@ -0,0 +1,54 @@
+use alloc::{borrow::ToOwned, vec::Vec};
+
+use crate::{
+    interpreter::eval_block,
+    model::{Env, ErrorKind, RuntimeError},
+    parser::parse,
+};
+
+/// The Lisp source of the functions that `default_env()` defines in Lisp
+/// rather than in Rust
+pub(crate) const PRELUDE: &str = include_str!("prelude.lisp");
+
+impl Env {
+    /// Evaluate `source` (all of it, if it parses) in this environment, and
+    /// return a new environment extending it, for evaluating everything else
+    /// in. Functions defined in `source` see the bindings in this environment
+    /// and each other, but not ones defined later in the new environment. This
+    /// is how `default_env()` defines the functions that are written in Lisp,
+    /// and it can be used again to layer more on top:
+    ///
+    /// ```
+    /// use rust_lisp::{default_env, interpreter::eval, lisp};
+    ///
+    /// let env = default_env()
+    ///     .eval_prelude("(defun square (x) (* x x))")
+    ///     .unwrap()
+    ///     .into_shared();
+    ///
+    /// assert_eq!(eval(env, &lisp! { (square (second (list 1 2 3))) }), Ok(lisp! { 4 }));
+    /// ```
+    ///
+    /// Returns a RuntimeError with the rendered parse error if `source`
+    /// doesn't parse, or with the first error it runs into while evaluating.
+    pub fn eval_prelude(self, source: &str) -> Result<Env, RuntimeError> {
+        let exprs = parse(source)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                RuntimeError::with_kind(
+                    ErrorKind::ParseError,
+                    err.render(source, "<prelude>").trim_end().to_owned(),
+                )
+            })?;
+
+        let env = self.into_shared();
+        eval_block(env.clone(), exprs.into_iter()).map_err(|err| {
+            RuntimeError::with_kind(
+                err.kind,
+                format!("Evaluating the prelude failed: {}", err.msg),
+            )
+        })?;
+
+        Ok(Env::extend(env))
+    }
+}
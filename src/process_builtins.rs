@@ -0,0 +1,208 @@
+use crate::{
+    interpreter::is_truthy,
+    model::{int_to_i128, Env, ErrorKind, IntType, List, RuntimeError, Symbol, Value},
+    utils::{keyword_args, require_typed_arg},
+};
+use std::{cell::RefCell, process::Command, rc::Rc};
+
+/// Add the builtins which reach the operating system's process environment to
+/// `env`. `run_process` runs a program directly with a list of arguments,
+/// which are passed to it as-is; only `shell` interprets a command line, using
+/// `sh -c` (or `cmd /C` on Windows).
+///
+/// Both return `((status code) (stdout out) (stderr err))`, where `status` is
+/// NIL if the process was ended by a signal. The output is decoded as UTF-8
+/// lossily (with `�` in place of any invalid bytes), or with `:bytes T` it's
+/// given as lists of bytes instead.
+///
+/// `exit` doesn't end the host program itself: it fails with an error which
+/// unwinds all the way out of the evaluation, after which the host can get the
+/// status from `Context::take_exit_status()` and decide what to do.
+pub(crate) fn define_process_builtins(env: &mut Env) {
+    env.define(
+        Symbol::from("getenv"),
+        Value::NativeFunc(|_env, args| {
+            let name = require_typed_arg::<&String>("getenv", &args, 0)?;
+
+            Ok(std::env::var_os(name)
+                .map(|value| Value::String(value.to_string_lossy().into()))
+                .unwrap_or(Value::NIL))
+        }),
+    );
+
+    env.define(
+        Symbol::from("setenv"),
+        Value::NativeFunc(|_env, args| {
+            let name = require_typed_arg::<&String>("setenv", &args, 0)?;
+            let value = require_typed_arg::<&String>("setenv", &args, 1)?;
+
+            if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+                return Err(RuntimeError::new(format!(
+                    "\"setenv\" can't set the environment variable {:?} to {:?}",
+                    name, value
+                )));
+            }
+
+            std::env::set_var(name, value);
+
+            Ok(Value::NIL)
+        }),
+    );
+
+    env.define(
+        Symbol::from("exit"),
+        Value::NativeFunc(|env, args| {
+            let status = match args.first() {
+                None => 0,
+                Some(Value::Int(int)) => {
+                    let status = int_to_i128(int).and_then(|int| i32::try_from(int).ok());
+
+                    status.ok_or_else(|| {
+                        RuntimeError::new(format!(
+                            "\"exit\" was given an out of range status {}",
+                            int
+                        ))
+                    })?
+                }
+                Some(other) => {
+                    return Err(RuntimeError::with_kind(
+                        ErrorKind::TypeError,
+                        format!("\"exit\" requires argument 1 to be an int; got {}", other),
+                    ))
+                }
+            };
+
+            env.borrow().context().exit_status.set(Some(status));
+
+            Err(RuntimeError::new(format!(
+                "\"exit\" was called with status {}",
+                status
+            )))
+        }),
+    );
+
+    env.define(
+        Symbol::from("run_process"),
+        Value::NativeFunc(|env, args| {
+            let program = require_typed_arg::<&String>("run_process", &args, 0)?;
+
+            // the argument list can be left out
+            let (arguments, keywords_index) = match args.get(1) {
+                Some(Value::List(arguments)) => (arguments.clone(), 2),
+                _ => (List::NIL, 1),
+            };
+            let arguments = arguments
+                .into_iter()
+                .map(|argument| match argument {
+                    Value::String(argument) => Ok(argument),
+                    other => Err(RuntimeError::new(format!(
+                        "\"run_process\" requires its arguments to be strings; got {}",
+                        other
+                    ))),
+                })
+                .collect::<Result<Vec<String>, _>>()?;
+
+            let mut command = Command::new(program);
+            command.args(arguments);
+
+            run("run_process", &env, command, &args, keywords_index)
+        }),
+    );
+
+    env.define(
+        Symbol::from("shell"),
+        Value::NativeFunc(|env, args| {
+            let command_line = require_typed_arg::<&String>("shell", &args, 0)?;
+
+            let mut command = if cfg!(windows) {
+                Command::new("cmd")
+            } else {
+                Command::new("sh")
+            };
+            command
+                .arg(if cfg!(windows) { "/C" } else { "-c" })
+                .arg(command_line);
+
+            run("shell", &env, command, &args, 1)
+        }),
+    );
+}
+
+/// Run `command` to completion, and return its status and output (with the
+/// `:bytes` keyword argument starting at `keywords_index` of `args`)
+fn run(
+    func_name: &str,
+    env: &Rc<RefCell<Env>>,
+    mut command: Command,
+    args: &[Value],
+    keywords_index: usize,
+) -> Result<Value, RuntimeError> {
+    let keywords = keyword_args(func_name, args, keywords_index, &["bytes"])?;
+    let as_bytes = keywords
+        .get("bytes")
+        .is_some_and(|bytes| is_truthy(env, bytes));
+
+    let output = command.output().map_err(|err| {
+        RuntimeError::new(format!(
+            "\"{}\" failed to run {:?}: {}",
+            func_name,
+            command.get_program(),
+            err
+        ))
+    })?;
+
+    // this can only fail with the `i8` feature
+    #[allow(clippy::unnecessary_fallible_conversions)]
+    let status = output
+        .status
+        .code()
+        .and_then(|code| IntType::try_from(code).ok())
+        .map(Value::Int)
+        .unwrap_or(Value::NIL);
+
+    let entry = |key: &str, value: Value| {
+        Value::List([Value::from(Symbol::from(key)), value].iter().collect())
+    };
+
+    Ok(Value::List(
+        [
+            entry("status", status),
+            entry(
+                "stdout",
+                output_to_value(func_name, output.stdout, as_bytes)?,
+            ),
+            entry(
+                "stderr",
+                output_to_value(func_name, output.stderr, as_bytes)?,
+            ),
+        ]
+        .iter()
+        .collect(),
+    ))
+}
+
+/// Some output from a process, as a string (decoded lossily) or a list of
+/// bytes
+fn output_to_value(
+    func_name: &str,
+    output: Vec<u8>,
+    as_bytes: bool,
+) -> Result<Value, RuntimeError> {
+    if !as_bytes {
+        return Ok(Value::String(String::from_utf8_lossy(&output).into_owned()));
+    }
+
+    // this can only fail with the `i8` feature
+    #[allow(clippy::unnecessary_fallible_conversions)]
+    let ints = output
+        .into_iter()
+        .map(|byte| IntType::try_from(byte).map(Value::Int))
+        .collect::<Result<List, _>>();
+
+    ints.map(Value::List).map_err(|_| {
+        RuntimeError::new(format!(
+            "\"{}\" can't represent the bytes of its output with this integer type",
+            func_name
+        ))
+    })
+}
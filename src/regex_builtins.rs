@@ -0,0 +1,126 @@
+use crate::{
+    model::{Env, List, RuntimeError, Symbol, Value},
+    utils::require_typed_arg,
+};
+use regex::{Captures, Regex};
+use std::{cell::RefCell, rc::Rc};
+
+/// Past this many cached patterns, the cache is cleared instead of growing
+/// (for programs that build lots of one-off patterns)
+const MAX_CACHED_PATTERNS: usize = 256;
+
+/// Add the regular expression builtins to `env`. Patterns are given as
+/// strings, and use the `regex` crate's syntax (which is Unicode-aware by
+/// default).
+pub(crate) fn define_regex_builtins(env: &mut Env) {
+    env.define(
+        Symbol::from("is_re_match"),
+        Value::NativeFunc(|env, args| {
+            let regex = require_regex(&env, "is_re_match", &args)?;
+            let text = require_typed_arg::<&String>("is_re_match", &args, 1)?;
+
+            Ok(Value::from(regex.is_match(text)))
+        }),
+    );
+
+    env.define(
+        Symbol::from("re_find"),
+        Value::NativeFunc(|env, args| {
+            let regex = require_regex(&env, "re_find", &args)?;
+            let text = require_typed_arg::<&String>("re_find", &args, 1)?;
+
+            Ok(regex
+                .captures(text)
+                .map(|captures| captures_to_list(&captures))
+                .unwrap_or(Value::NIL))
+        }),
+    );
+
+    env.define(
+        Symbol::from("re_find_all"),
+        Value::NativeFunc(|env, args| {
+            let regex = require_regex(&env, "re_find_all", &args)?;
+            let text = require_typed_arg::<&String>("re_find_all", &args, 1)?;
+
+            Ok(Value::List(
+                regex
+                    .captures_iter(text)
+                    .map(|captures| captures_to_list(&captures))
+                    .collect(),
+            ))
+        }),
+    );
+
+    env.define(
+        Symbol::from("re_replace"),
+        Value::NativeFunc(|env, args| {
+            let regex = require_regex(&env, "re_replace", &args)?;
+            let text = require_typed_arg::<&String>("re_replace", &args, 1)?;
+            let replacement = require_typed_arg::<&String>("re_replace", &args, 2)?;
+
+            Ok(Value::String(
+                regex.replace_all(text, replacement.as_str()).into_owned(),
+            ))
+        }),
+    );
+
+    env.define(
+        Symbol::from("re_split"),
+        Value::NativeFunc(|env, args| {
+            let regex = require_regex(&env, "re_split", &args)?;
+            let text = require_typed_arg::<&String>("re_split", &args, 1)?;
+
+            Ok(Value::List(
+                regex
+                    .split(text)
+                    .map(|piece| Value::String(piece.to_owned()))
+                    .collect(),
+            ))
+        }),
+    );
+}
+
+/// Compile the pattern that's the first of `args`, or get it from the cache if
+/// it's been compiled before. RuntimeError if it isn't a valid pattern.
+fn require_regex(
+    env: &Rc<RefCell<Env>>,
+    func_name: &str,
+    args: &[Value],
+) -> Result<Regex, RuntimeError> {
+    let pattern = require_typed_arg::<&String>(func_name, args, 0)?;
+
+    let env = env.borrow();
+    let mut cache = env.context().regex_cache.borrow_mut();
+
+    if let Some(regex) = cache.get(pattern) {
+        return Ok(regex.clone());
+    }
+
+    let regex = Regex::new(pattern).map_err(|err| {
+        RuntimeError::new(format!(
+            "\"{}\" was given an invalid regex: {}",
+            func_name, err
+        ))
+    })?;
+
+    if cache.len() >= MAX_CACHED_PATTERNS {
+        cache.clear();
+    }
+    cache.insert(pattern.clone(), regex.clone());
+
+    Ok(regex)
+}
+
+/// The whole match followed by each capture group, with NIL for the groups
+/// that didn't participate in the match
+fn captures_to_list(captures: &Captures) -> Value {
+    Value::List(
+        captures
+            .iter()
+            .map(|group| match group {
+                Some(group) => Value::String(group.as_str().to_owned()),
+                None => Value::NIL,
+            })
+            .collect::<List>(),
+    )
+}
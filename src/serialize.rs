@@ -0,0 +1,363 @@
+//! Writing the bindings of an environment out as Lisp source, so that they can
+//! be saved and restored later (in a fresh environment) by evaluating it.
+//!
+//! Data is written as literals, or `quote`d where it needs to be. Functions and
+//! macros are written as `lambda` and `defmacro` forms built from their
+//! parameters and body, so they're only serializable when they were defined at
+//! the top level of the environment: a function returned from another one (or
+//! defined inside a `let`) closes over local variables whose values aren't part
+//! of its source, and is an error to serialize. So is anything which only
+//! exists at runtime, like generators, promises, environments, string builders
+//! and foreign values.
+//!
+//! For saving a REPL session, `serialize_session()` is more forgiving: it
+//! leaves out whatever can't be written, and writes functions which close over
+//! local variables anyway (those variables are lost, so they're only restored
+//! properly if they don't use them), returning warnings about each. Its output
+//! is read back with `restore_session()`.
+
+use alloc::{borrow::ToOwned, rc::Rc, string::String, vec::Vec};
+use core::cell::RefCell;
+
+use crate::{
+    collections::HashSet,
+    interpreter::eval,
+    model::{
+        sorted_members, write_bytes_literal, Env, ErrorKind, Lambda, RuntimeError, Symbol, Value,
+    },
+    parser::parse,
+};
+
+/// Write the bindings defined directly in `env` as a sequence of `define` (and
+/// `defmacro`) forms, one per line and sorted by name, which recreate them when
+/// evaluated. Native functions (which includes all of the builtins, and the
+/// functions made from them by `partial`, `compose`, etc) are skipped, since
+/// they come from the host program rather than from Lisp.
+///
+/// Returns a RuntimeError naming the binding if any value can't be written as
+/// source (see the module documentation).
+pub fn serialize_env(env: &Env) -> Result<String, RuntimeError> {
+    let mut output = String::new();
+
+    for (symbol, value) in sorted_entries(env) {
+        if is_native(value) {
+            continue;
+        }
+
+        let form = match closes_over(env, value) {
+            Some(reason) => Err(Reason::from(reason)),
+            None => write_binding(symbol, value),
+        };
+
+        let form = form.map_err(|reason| {
+            RuntimeError::new(format!("Can't serialize \"{}\": {}", symbol, reason))
+        })?;
+
+        output.push_str(&form);
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Like `serialize_env()`, but for saving a session (as `save_session` does)
+/// as well as it can, rather than exactly: the bindings that can't be written
+/// as source (including native functions) are left out, and functions which
+/// close over local variables are written anyway, which only works if they
+/// don't refer to those variables. Returns the source, and a warning for each
+/// binding that was left out or approximated.
+pub fn serialize_session(env: &Env) -> (String, Vec<String>) {
+    let mut output = String::new();
+    let mut warnings = Vec::new();
+
+    for (symbol, value) in sorted_entries(env) {
+        let form = match is_native(value) {
+            true => Err(Reason::from("it's a function written in Rust")),
+            false => write_binding(symbol, value),
+        };
+
+        match form {
+            Ok(form) => {
+                if let Some(reason) = closes_over(env, value) {
+                    warnings.push(format!(
+                        "\"{}\" was saved, but {}, which weren't",
+                        symbol, reason
+                    ));
+                }
+
+                output.push_str(&form);
+                output.push('\n');
+            }
+            Err(reason) => warnings.push(format!("Skipped \"{}\": {}", symbol, reason)),
+        }
+    }
+
+    (output, warnings)
+}
+
+/// Evaluate a session written by `serialize_session()` (or `serialize_env()`)
+/// in `env`, as `load_session` does. Bindings `env` already has (directly,
+/// not in a parent) are replaced if `overwrite`, and otherwise kept, with the
+/// saved one skipped. Restored functions close over `env`. Returns the names
+/// that were restored, in order.
+pub fn restore_session(
+    env: Rc<RefCell<Env>>,
+    source: &str,
+    overwrite: bool,
+) -> Result<Vec<Symbol>, RuntimeError> {
+    let forms = parse(source)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| {
+            RuntimeError::with_kind(
+                ErrorKind::ParseError,
+                err.render(source, "<session>").trim_end().to_owned(),
+            )
+        })?;
+
+    let mut restored = Vec::new();
+
+    for form in forms {
+        let name = match &form {
+            Value::List(list) => match list.cdr().car() {
+                Ok(Value::Symbol(name)) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(name) = &name {
+            if !overwrite && env.borrow().defines(name) {
+                continue;
+            }
+        }
+
+        eval(env.clone(), &form)?;
+        restored.extend(name);
+    }
+
+    Ok(restored)
+}
+
+/// A failed serialization, explaining why the value can't be written
+type Reason = String;
+
+/// The bindings defined directly in `env`, sorted by name
+fn sorted_entries(env: &Env) -> Vec<(&Symbol, &Value)> {
+    let mut entries = env.entries().collect::<Vec<_>>();
+    entries.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+
+    entries
+}
+
+/// Whether `value` is a function which comes from the host program
+fn is_native(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::NativeFunc(_) | Value::NativeClosure(_) | Value::Combinator(_)
+    )
+}
+
+/// Why `value` can't be written as source exactly, if it's a function or macro
+/// that wasn't defined at the top level of `env`
+fn closes_over(env: &Env, value: &Value) -> Option<&'static str> {
+    match value {
+        Value::Lambda(lambda) | Value::Macro(lambda)
+            if !core::ptr::eq(lambda.closure.as_ptr(), env) =>
+        {
+            Some("it closes over local variables")
+        }
+        _ => None,
+    }
+}
+
+/// The form which defines `symbol` as `value`
+fn write_binding(symbol: &Symbol, value: &Value) -> Result<String, Reason> {
+    let name = write_symbol(symbol)?;
+
+    match value {
+        Value::Lambda(lambda) => {
+            write_lambda(lambda).map(|lambda| format!("(define {} (lambda {}))", name, lambda))
+        }
+        Value::Macro(lambda) => {
+            write_lambda(lambda).map(|lambda| format!("(defmacro {} {})", name, lambda))
+        }
+        _ => write_expression(value).map(|expr| format!("(define {} {})", name, expr)),
+    }
+}
+
+/// The parameter list and body of a function or macro
+fn write_lambda(lambda: &Lambda) -> Result<String, Reason> {
+    let params = lambda
+        .argnames
+        .iter()
+        .map(
+            |name| match lambda.key_defaults.iter().find(|(key, _)| key == name) {
+                Some((key, default)) => Ok(format!("({} {})", key, write_code(default)?)),
+                None => write_symbol(name),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut output = format!("({})", params.join(" "));
+
+    for form in lambda.body.iter() {
+        output.push(' ');
+        output.push_str(&write_code(form)?);
+    }
+
+    Ok(output)
+}
+
+/// An expression which evaluates to `value`
+fn write_expression(value: &Value) -> Result<String, Reason> {
+    match value {
+        Value::Symbol(_) | Value::List(_) => Ok(format!(
+            "(quote {})",
+            write_datum(value, true, &mut HashSet::new())?
+        )),
+        Value::HashMap(hash) => {
+            let mut output = String::from("(hash");
+
+            for (key, value) in hash.borrow().iter() {
+                output.push(' ');
+                output.push_str(&write_expression(key)?);
+                output.push(' ');
+                output.push_str(&write_expression(value)?);
+            }
+
+            output.push(')');
+
+            Ok(output)
+        }
+        Value::Set(set) => {
+            let mut output = String::from("(make_set");
+
+            for member in sorted_members(&set.borrow()) {
+                output.push(' ');
+                output.push_str(&write_expression(&member)?);
+            }
+
+            output.push(')');
+
+            Ok(output)
+        }
+        _ => write_datum(value, false, &mut HashSet::new()),
+    }
+}
+
+/// Source code, as it was parsed (for the bodies of functions)
+fn write_code(value: &Value) -> Result<String, Reason> {
+    write_datum(value, false, &mut HashSet::new())
+}
+
+/// Write `value` as it would be parsed. Inside a `quote`, hash maps and sets
+/// can be spliced in with `comma`, but a list which itself starts with `comma`
+/// can't be written. `visiting` holds the lists being written further up, to
+/// catch cycles.
+fn write_datum(
+    value: &Value,
+    quoted: bool,
+    visiting: &mut HashSet<*const ()>,
+) -> Result<String, Reason> {
+    match value {
+        Value::True => Ok("T".into()),
+        Value::False => Ok("F".into()),
+        Value::Int(int) => Ok(format!("{}", int)),
+        // including NaN and the infinities, which are written `+nan.0`, etc
+        Value::Float(_) => Ok(format!("{}", value)),
+        Value::String(string) if !string.contains('"') => Ok(format!("\"{}\"", string)),
+        Value::Char(_) => Ok(format!("{}", value)),
+        Value::Bytes(bytes) => {
+            // in full, since long ones display as a summary
+            let mut literal = String::new();
+            let _ = write_bytes_literal(&bytes.borrow(), &mut literal);
+            Ok(literal)
+        }
+        Value::Symbol(symbol) => write_symbol(symbol),
+        Value::List(list) if quoted && list.with_car(is_comma) == Some(true) => {
+            Err(format!("the comma form {} can't be quoted", list))
+        }
+        Value::List(list) => {
+            let mut added = Vec::new();
+            let mut rest = list.clone();
+            let mut items = Vec::new();
+
+            let result = loop {
+                let Some(ptr) = rest.head_ptr() else {
+                    break Ok(());
+                };
+
+                if !visiting.insert(ptr) {
+                    break Err(Reason::from("it contains a cyclic list"));
+                }
+                added.push(ptr);
+
+                match write_datum(&rest.car().unwrap_or(Value::NIL), quoted, visiting) {
+                    Ok(item) => items.push(item),
+                    Err(reason) => break Err(reason),
+                }
+
+                rest = match rest.cdr_value() {
+                    Value::List(rest) => rest,
+                    // the end of a dotted list
+                    tail => match write_datum(&tail, quoted, visiting) {
+                        Ok(tail) => {
+                            items.extend([".".into(), tail]);
+                            break Ok(());
+                        }
+                        Err(reason) => break Err(reason),
+                    },
+                };
+            };
+
+            for ptr in added {
+                visiting.remove(&ptr);
+            }
+
+            result.map(|_| format!("({})", items.join(" ")))
+        }
+        Value::Vector(items) => {
+            let ptr = Rc::as_ptr(items) as *const ();
+            if !visiting.insert(ptr) {
+                return Err(Reason::from("it contains a cyclic vector"));
+            }
+
+            // the items of a vector literal are never evaluated
+            let items = items
+                .borrow()
+                .iter()
+                .map(|item| write_datum(item, false, visiting))
+                .collect::<Result<Vec<_>, _>>();
+            visiting.remove(&ptr);
+
+            Ok(format!("#({})", items?.join(" ")))
+        }
+        Value::HashMap(_) | Value::Set(_) if quoted => {
+            Ok(format!("(comma {})", write_expression(value)?))
+        }
+        _ => Err(format!(
+            "the {} {} can't be written as source",
+            value.type_name(),
+            value
+        )),
+    }
+}
+
+/// A symbol's name, if it would be parsed back as the same symbol
+fn write_symbol(symbol: &Symbol) -> Result<String, Reason> {
+    let mut parsed = parse(&symbol.0);
+
+    match (parsed.next(), parsed.next()) {
+        (Some(Ok(Value::Symbol(parsed))), None) if parsed == *symbol => {
+            Ok(String::from(&*symbol.0))
+        }
+        _ => Err(format!(
+            "the symbol {:?} can't be written as source",
+            symbol.0
+        )),
+    }
+}
+
+fn is_comma(value: &Value) -> bool {
+    matches!(value, Value::Symbol(Symbol(name)) if &**name == "comma")
+}
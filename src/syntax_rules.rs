@@ -0,0 +1,413 @@
+//! Macros defined with `syntax_rules`, as a list of patterns to match calls
+//! against and templates to expand them into, instead of as code which
+//! builds the expansion:
+//!
+//! ```lisp
+//! (define_syntax swap
+//!   (syntax_rules ()
+//!     ((_ a b) (let ((tmp a)) (set a b) (set b tmp)))))
+//! ```
+//!
+//! A pattern variable followed by `...` matches any number of items, and a
+//! template item followed by `...` is repeated once for each of them.
+//!
+//! Variables which a template binds itself (`tmp` above) are renamed in each
+//! expansion, so they can't capture a variable of the same name passed in by
+//! the caller.
+
+use crate::collections::HashMap;
+use crate::model::{Env, Lambda, List, RuntimeError, Symbol, Value};
+use alloc::{rc::Rc, vec::Vec};
+use core::cell::RefCell;
+
+const ELLIPSIS: &str = "...";
+const WILDCARD: &str = "_";
+
+/// The forms in a template which bind variables, and which of their arguments
+/// lists them (either as a list of names, or of `(name value)` clauses)
+const BINDING_FORMS: [(&str, usize); 6] = [
+    ("lambda", 0),
+    ("let", 0),
+    ("for", 0),
+    ("do", 0),
+    ("defun", 1),
+    ("defmacro", 1),
+];
+
+/// Create the macro for `(define_syntax name (syntax_rules (literals...)
+/// (pattern template)...))`, where `rules` is the `syntax_rules` form
+pub(crate) fn make_syntax_rules(
+    env: Rc<RefCell<Env>>,
+    name: &Symbol,
+    rules: &Value,
+) -> Result<Value, RuntimeError> {
+    let syntax_error = || {
+        RuntimeError::new(format!(
+            "Expected (syntax_rules (literals...) (pattern template)...) for {}, found {}",
+            name, rules
+        ))
+    };
+
+    let rules = match rules {
+        Value::List(list) if list.car() == Ok(Value::Symbol(Symbol::from("syntax_rules"))) => {
+            list.cdr()
+        }
+        _ => return Err(syntax_error()),
+    };
+
+    let literals = match rules.car() {
+        Ok(Value::List(literals)) => literals
+            .into_iter()
+            .map(|literal| match literal {
+                Value::Symbol(symbol) => Ok(symbol),
+                _ => Err(syntax_error()),
+            })
+            .collect::<Result<Vec<Symbol>, RuntimeError>>()?,
+        _ => return Err(syntax_error()),
+    };
+
+    let rules = rules
+        .cdr()
+        .into_iter()
+        .map(|rule| match &rule {
+            Value::List(rule) if rule.cdr().cdr().cdr() == List::NIL => {
+                match (rule.car(), rule.cdr().car()) {
+                    // the first item of the pattern is the macro's name
+                    (Ok(Value::List(pattern)), Ok(template)) => Ok(Rule {
+                        pattern: pattern.cdr(),
+                        template,
+                    }),
+                    _ => Err(syntax_error()),
+                }
+            }
+            _ => Err(syntax_error()),
+        })
+        .collect::<Result<Vec<Rule>, RuntimeError>>()?;
+
+    let syntax = SyntaxRules {
+        name: name.clone(),
+        literals,
+        rules,
+    };
+
+    // The expansion is done natively, but wrapping it in an ordinary macro
+    // (which passes its arguments, unevaluated, to the native function) means
+    // it's expanded everywhere that macros are
+    let expand = Value::NativeClosure(Rc::new(RefCell::new(
+        move |env: Rc<RefCell<Env>>, args: Vec<Value>| {
+            let form = match args.first() {
+                Some(Value::List(form)) => form,
+                _ => return Ok(Value::NIL),
+            };
+
+            syntax.expand(&env, form)
+        },
+    )));
+    let form = Symbol::from("form");
+
+    Ok(Value::Macro(Lambda {
+        name: Some(name.clone()),
+        closure: env,
+        argnames: vec![Symbol::from("&rest"), form.clone()],
+        key_defaults: Vec::new(),
+        body: Rc::new([Value::List([expand, Value::Symbol(form)].iter().collect())]),
+    }))
+}
+
+struct SyntaxRules {
+    name: Symbol,
+    literals: Vec<Symbol>,
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    pattern: List,
+    template: Value,
+}
+
+/// What a pattern variable matched
+#[derive(Clone)]
+enum Binding {
+    One(Value),
+    /// One binding for each repetition of the `...` that the variable is under
+    Many(Vec<Binding>),
+}
+
+type Bindings = HashMap<Symbol, Binding>;
+
+impl SyntaxRules {
+    /// Expand a call to the macro, given its (unevaluated) arguments
+    fn expand(&self, env: &Rc<RefCell<Env>>, args: &List) -> Result<Value, RuntimeError> {
+        for rule in &self.rules {
+            let mut bindings = Bindings::new();
+
+            if self.match_list(&rule.pattern, args, &mut bindings) {
+                let mut renames = HashMap::new();
+                for symbol in self.introduced_bindings(&rule.template, &bindings) {
+                    let count = env.borrow().context().next_gensym();
+
+                    renames.insert(
+                        symbol.clone(),
+                        Symbol::from(format!("{}#{}", symbol, count)),
+                    );
+                }
+
+                return self.expand_template(&rule.template, &bindings, &renames);
+            }
+        }
+
+        Err(RuntimeError::new(format!(
+            "No syntax_rules pattern of {} matched {}",
+            self.name,
+            Value::List(args.cons(Value::Symbol(self.name.clone())))
+        )))
+    }
+
+    fn match_pattern(&self, pattern: &Value, form: &Value, bindings: &mut Bindings) -> bool {
+        match pattern {
+            Value::Symbol(symbol) if &*symbol.0 == WILDCARD => true,
+            Value::Symbol(symbol) if self.literals.contains(symbol) => {
+                matches!(form, Value::Symbol(other) if other == symbol)
+            }
+            Value::Symbol(symbol) => {
+                bindings.insert(symbol.clone(), Binding::One(form.clone()));
+                true
+            }
+            Value::List(pattern) => match form {
+                Value::List(form) => self.match_list(pattern, form, bindings),
+                _ => false,
+            },
+            _ => pattern == form,
+        }
+    }
+
+    fn match_list(&self, pattern: &List, form: &List, bindings: &mut Bindings) -> bool {
+        let patterns = pattern.into_iter().collect::<Vec<Value>>();
+        let forms = form.into_iter().collect::<Vec<Value>>();
+
+        let ellipsis = patterns.iter().position(is_ellipsis);
+
+        let Some(ellipsis) = ellipsis.filter(|index| *index > 0) else {
+            return patterns.len() == forms.len()
+                && patterns
+                    .iter()
+                    .zip(&forms)
+                    .all(|(pattern, form)| self.match_pattern(pattern, form, bindings));
+        };
+
+        let before = &patterns[..ellipsis - 1];
+        let repeated = &patterns[ellipsis - 1];
+        let after = &patterns[ellipsis + 1..];
+
+        if forms.len() < before.len() + after.len() {
+            return false;
+        }
+
+        let repetitions = &forms[before.len()..forms.len() - after.len()];
+
+        let ends_match = before
+            .iter()
+            .zip(&forms)
+            .chain(after.iter().zip(&forms[forms.len() - after.len()..]))
+            .all(|(pattern, form)| self.match_pattern(pattern, form, bindings));
+        if !ends_match {
+            return false;
+        }
+
+        let mut matches = Vec::new();
+        for form in repetitions {
+            let mut repetition = Bindings::new();
+            if !self.match_pattern(repeated, form, &mut repetition) {
+                return false;
+            }
+
+            matches.push(repetition);
+        }
+
+        for var in self.pattern_vars(repeated) {
+            let each = matches
+                .iter_mut()
+                .map(|repetition| repetition.remove(&var).unwrap())
+                .collect();
+
+            bindings.insert(var, Binding::Many(each));
+        }
+
+        true
+    }
+
+    fn pattern_vars(&self, pattern: &Value) -> Vec<Symbol> {
+        match pattern {
+            Value::Symbol(symbol)
+                if &*symbol.0 != WILDCARD
+                    && &*symbol.0 != ELLIPSIS
+                    && !self.literals.contains(symbol) =>
+            {
+                vec![symbol.clone()]
+            }
+            Value::List(list) => list
+                .into_iter()
+                .flat_map(|item| self.pattern_vars(&item))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The variables which `template` binds itself, rather than getting
+    /// from the macro's arguments
+    fn introduced_bindings(&self, template: &Value, bindings: &Bindings) -> Vec<Symbol> {
+        let mut bound = Vec::new();
+        collect_bound(template, &mut bound);
+
+        let mut introduced = Vec::new();
+        for symbol in bound {
+            if !bindings.contains_key(&symbol)
+                && &*symbol.0 != ELLIPSIS
+                // `&rest`, etc
+                && !symbol.0.starts_with('&')
+                && !introduced.contains(&symbol)
+            {
+                introduced.push(symbol);
+            }
+        }
+
+        introduced
+    }
+
+    fn expand_template(
+        &self,
+        template: &Value,
+        bindings: &Bindings,
+        renames: &HashMap<Symbol, Symbol>,
+    ) -> Result<Value, RuntimeError> {
+        match template {
+            Value::Symbol(symbol) => match bindings.get(symbol) {
+                Some(Binding::One(value)) => Ok(value.clone()),
+                Some(Binding::Many(_)) => Err(RuntimeError::new(format!(
+                        "In syntax_rules for {}, {} needs to be followed by ... in the template, like it is in the pattern",
+                        self.name, symbol
+                    ))),
+                None => Ok(Value::Symbol(
+                    renames.get(symbol).unwrap_or(symbol).clone(),
+                )),
+            },
+
+            Value::List(list) => {
+                let items = list.into_iter().collect::<Vec<Value>>();
+                let mut expanded = Vec::new();
+
+                let mut index = 0;
+                while index < items.len() {
+                    let item = &items[index];
+
+                    if items.get(index + 1).is_some_and(is_ellipsis) {
+                        for bindings in self.repetitions(item, bindings)? {
+                            expanded.push(self.expand_template(item, &bindings, renames)?);
+                        }
+
+                        index += 2;
+                    } else {
+                        expanded.push(self.expand_template(item, bindings, renames)?);
+
+                        index += 1;
+                    }
+                }
+
+                Ok(Value::List(List::from_vec(expanded)))
+            }
+
+            _ => Ok(template.clone()),
+        }
+    }
+
+    /// The bindings for each repetition of a template item that's followed by
+    /// `...`, one for each value matched by the repeated variables it uses
+    fn repetitions(
+        &self,
+        item: &Value,
+        bindings: &Bindings,
+    ) -> Result<Vec<Bindings>, RuntimeError> {
+        let repeated = self
+            .pattern_vars(item)
+            .into_iter()
+            .filter_map(|var| match bindings.get(&var) {
+                Some(Binding::Many(each)) => Some((var, each)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let count = match repeated.first() {
+            Some((_, each)) => each.len(),
+            None => {
+                return Err(RuntimeError::new(format!(
+                        "In syntax_rules for {}, {} is followed by ... in the template, but doesn't contain any variables that were followed by ... in the pattern",
+                        self.name, item
+                    )))
+            }
+        };
+
+        if repeated.iter().any(|(_, each)| each.len() != count) {
+            return Err(RuntimeError::new(format!(
+                    "In syntax_rules for {}, the variables repeated together in {} matched different numbers of items",
+                    self.name, item
+                )));
+        }
+
+        Ok((0..count)
+            .map(|index| {
+                let mut bindings = bindings.clone();
+                for (var, each) in &repeated {
+                    bindings.insert(var.clone(), each[index].clone());
+                }
+
+                bindings
+            })
+            .collect())
+    }
+}
+
+fn is_ellipsis(value: &Value) -> bool {
+    matches!(value, Value::Symbol(Symbol(name)) if &**name == ELLIPSIS)
+}
+
+/// Collect the variables bound by the binding forms in `template` (the
+/// parameters of functions, and the names declared by `let`s and `for`s)
+fn collect_bound(template: &Value, bound: &mut Vec<Symbol>) {
+    let list = match template {
+        Value::List(list) => list,
+        _ => return,
+    };
+
+    let head = match list.car() {
+        Ok(Value::Symbol(Symbol(head))) => head,
+        _ => Rc::from(""),
+    };
+
+    if &*head == "quote" {
+        return;
+    }
+
+    let binding_form = BINDING_FORMS.iter().find(|(keyword, _)| &*head == *keyword);
+
+    if let Some((_, index)) = binding_form {
+        if let Some(Value::List(declarations)) = list.into_iter().nth(index + 1) {
+            for declaration in declarations.into_iter() {
+                match declaration {
+                    // a lambda's parameter
+                    Value::Symbol(symbol) => bound.push(symbol),
+                    // a (name value) clause
+                    Value::List(clause) => {
+                        if let Ok(Value::Symbol(symbol)) = clause.car() {
+                            bound.push(symbol);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    for item in list.into_iter() {
+        collect_bound(&item, bound);
+    }
+}
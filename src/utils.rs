@@ -1,6 +1,11 @@
-use std::{any::Any, rc::Rc};
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::{any::Any, cell::RefCell};
 
-use crate::model::{FloatType, HashMapRc, IntType, List, RuntimeError, Symbol, Value};
+use crate::collections::HashMap;
+use crate::model::{
+    Env, ErrorKind, FloatType, Generator, HashMapRc, IntType, Lambda, List, RuntimeError, SetRc,
+    Symbol, Value,
+};
 
 /// Given a `Value` assumed to be a `Value::List()`, grab the item at `index`
 /// and err if there isn't one.
@@ -9,12 +14,12 @@ pub fn require_arg<'a>(
     args: &'a [Value],
     index: usize,
 ) -> Result<&'a Value, RuntimeError> {
-    args.get(index).ok_or_else(|| RuntimeError {
-        msg: format!(
+    args.get(index).ok_or_else(|| {
+        RuntimeError::new(format!(
             "\"{}\" requires an argument {}",
             func_or_form_name,
             index + 1
-        ),
+        ))
     })
 }
 
@@ -31,17 +36,71 @@ where
 {
     require_arg(func_or_form_name, args, index)?
         .try_into()
-        .map_err(|_| RuntimeError {
-            msg: format!(
-                "\"{}\" requires argument {} to be a {}; got {}",
-                func_or_form_name,
-                index + 1,
-                T::get_name(),
-                args.get(index).unwrap_or(&Value::NIL)
-            ),
+        .map_err(|_| {
+            RuntimeError::with_kind(
+                ErrorKind::TypeError,
+                format!(
+                    "\"{}\" requires argument {} to be a {}; got {}",
+                    func_or_form_name,
+                    index + 1,
+                    T::get_name(),
+                    args.get(index).unwrap_or(&Value::NIL)
+                ),
+            )
         })
 }
 
+/// Read the `:keyword value` pairs in `args`, starting at `index`, for a
+/// function that takes keyword arguments. `accepted` are the names of the
+/// keywords (without the colon), which the result is keyed by. If a keyword
+/// is given more than once, its first value is used. RuntimeError if there's
+/// an odd number of arguments, or one of the keywords isn't accepted.
+pub fn keyword_args<'a>(
+    func_or_form_name: &str,
+    args: &'a [Value],
+    index: usize,
+    accepted: &[&str],
+) -> Result<HashMap<&'a str, &'a Value>, RuntimeError> {
+    let mut values = HashMap::new();
+
+    for pair in args.get(index..).unwrap_or_default().chunks(2) {
+        let keyword = match &pair[0] {
+            Value::Symbol(symbol) if symbol.is_keyword() => symbol,
+            other => {
+                return Err(RuntimeError::new(format!(
+                    "\"{}\" expected a keyword argument, got {}",
+                    func_or_form_name, other
+                )))
+            }
+        };
+        let name = &keyword.0[1..];
+
+        if !accepted.contains(&name) {
+            return Err(RuntimeError::new(format!(
+                "\"{}\" got an unexpected keyword argument {}; expected one of {}",
+                func_or_form_name,
+                keyword,
+                accepted
+                    .iter()
+                    .map(|name| format!(":{}", name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )));
+        }
+
+        let value = pair.get(1).ok_or_else(|| {
+            RuntimeError::new(format!(
+                "\"{}\" requires a value after the keyword argument {}",
+                func_or_form_name, keyword
+            ))
+        })?;
+
+        values.entry(name).or_insert(value);
+    }
+
+    Ok(values)
+}
+
 pub trait TypeName {
     fn get_name() -> &'static str;
 }
@@ -64,6 +123,12 @@ impl TypeName for &String {
     }
 }
 
+impl TypeName for char {
+    fn get_name() -> &'static str {
+        "char"
+    }
+}
+
 impl TypeName for &Symbol {
     fn get_name() -> &'static str {
         "symbol"
@@ -82,8 +147,50 @@ impl TypeName for &HashMapRc {
     }
 }
 
+impl TypeName for &SetRc {
+    fn get_name() -> &'static str {
+        "set"
+    }
+}
+
 impl TypeName for &Rc<dyn Any> {
     fn get_name() -> &'static str {
         "foreign value"
     }
 }
+
+impl TypeName for &Lambda {
+    fn get_name() -> &'static str {
+        "lambda"
+    }
+}
+
+impl TypeName for &Rc<RefCell<Generator>> {
+    fn get_name() -> &'static str {
+        "generator"
+    }
+}
+
+impl TypeName for &Rc<RefCell<Env>> {
+    fn get_name() -> &'static str {
+        "environment"
+    }
+}
+
+impl TypeName for &Rc<RefCell<String>> {
+    fn get_name() -> &'static str {
+        "string builder"
+    }
+}
+
+impl TypeName for &Rc<RefCell<Vec<u8>>> {
+    fn get_name() -> &'static str {
+        "bytes"
+    }
+}
+
+impl TypeName for &Rc<RefCell<Vec<Value>>> {
+    fn get_name() -> &'static str {
+        "vector"
+    }
+}
@@ -0,0 +1,85 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    model::{Symbol, Value},
+    parser::parse,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::{Cell, RefCell},
+    hint::black_box,
+    rc::Rc,
+};
+
+// counts the allocations made by each thread, so tests running in parallel
+// don't see each other's
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn cloning_symbols_doesnt_allocate() {
+    let symbol = Value::Symbol(Symbol::from("a_rather_long_symbol_name"));
+
+    let count = allocations_during(|| {
+        for _ in 0..100 {
+            black_box(symbol.clone());
+        }
+    });
+
+    assert_eq!(count, 0);
+}
+
+// (with the `bigint` feature, ints are heap allocated)
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn cloning_ints_doesnt_allocate() {
+    let int = Value::Int(100i8.into());
+
+    let count = allocations_during(|| {
+        for _ in 0..100 {
+            black_box(int.clone());
+        }
+    });
+
+    assert_eq!(count, 0);
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn tight_loop_allocations() {
+    let env = Rc::new(RefCell::new(default_env()));
+    let exprs = parse("(define i 0) (do () ((== i 1000) i) (set i (+ i 1)))")
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let count = allocations_during(|| {
+        eval_block(env, exprs.into_iter()).unwrap();
+    });
+
+    // each iteration only allocates for the arguments of the calls it makes,
+    // and for `set`'s, not for copies of the symbols in them
+    assert!(count < 5 * 1000, "{} allocations", count);
+}
@@ -0,0 +1,114 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{CancelHandle, ErrorKind, RuntimeError},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc, sync::mpsc, thread, time::Duration};
+
+#[test]
+fn cancel_from_another_thread() {
+    for source in [
+        // a loop through tail calls
+        "(defun spin (n) (spin (+ n 1))) (spin 0)",
+        // a loop whose test and steps don't call anything
+        "(do ((i 0 i)) (F))",
+        // deep inside a native function calling back into lisp
+        "(map (lambda (x) (defun spin () (spin)) (spin)) (list 1 2 3))",
+    ] {
+        let result = eval_and_cancel(source, Duration::from_millis(50));
+
+        assert_eq!(
+            result,
+            Err(RuntimeError::with_kind(
+                ErrorKind::Interrupted,
+                "Evaluation was cancelled"
+            )),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn cancelled_until_reset() {
+    let env = Rc::new(RefCell::new(default_env()));
+    let handle = env.borrow().context().cancel_handle();
+
+    // cancelling before evaluation starts stops it straight away
+    handle.cancel();
+    assert!(handle.is_cancelled());
+    assert_eq!(
+        eval_block(env.clone(), vec![lisp! { (+ 1 2) }].into_iter()),
+        Err(RuntimeError::with_kind(
+            ErrorKind::Interrupted,
+            "Evaluation was cancelled"
+        ))
+    );
+
+    handle.reset();
+    assert!(!handle.is_cancelled());
+    assert_eq!(
+        eval_block(env, vec![lisp! { (+ 1 2) }].into_iter()),
+        Ok(lisp! { 3 })
+    );
+}
+
+#[test]
+fn fallbacks_dont_catch_cancelling() {
+    for source in [
+        "(defun spin () (spin)) (with_step_limit 2000000000 (spin) 'fallback)",
+        "(defun spin () (spin)) (with_timeout 1000 (spin) 'fallback)",
+    ] {
+        let result = eval_and_cancel(source, Duration::from_millis(50));
+
+        assert_eq!(
+            result.map_err(|err| err.kind()),
+            Err(ErrorKind::Interrupted),
+            "{}",
+            source
+        );
+    }
+
+    // and an error that only says it was cancelled is an ordinary one
+    let env = Rc::new(RefCell::new(default_env()));
+    let err = eval_block(
+        env,
+        parse("(error \"Evaluation was cancelled\")").map(|expr| expr.unwrap()),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UserError);
+}
+
+/// Evaluate `source` on another thread, and cancel it from this one after
+/// `delay`, returning the result (printed, since values can't be sent between
+/// threads)
+#[cfg(test)]
+fn eval_and_cancel(source: &str, delay: Duration) -> Result<String, RuntimeError> {
+    let source = source.to_owned();
+    let (handle_sender, handle_receiver) = mpsc::channel();
+    let (result_sender, result_receiver) = mpsc::channel();
+
+    // the environment isn't `Send`, so it's made on the thread that uses it,
+    // and only the handle is sent back
+    thread::spawn(move || {
+        let env = Rc::new(RefCell::new(default_env()));
+        handle_sender
+            .send(env.borrow().context().cancel_handle())
+            .unwrap();
+
+        let result = eval_block(env, parse(&source).map(|expr| expr.unwrap()));
+        result_sender
+            .send(result.map(|value| value.to_string()))
+            .unwrap();
+    });
+
+    let handle: CancelHandle = handle_receiver.recv().unwrap();
+    thread::sleep(delay);
+    handle.cancel();
+
+    result_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .expect("the evaluation wasn't cancelled")
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "catch_panics")]
+
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn native_panics_become_errors() {
+    let env = Rc::new(RefCell::new(default_env()));
+    env.borrow_mut().define(
+        "explode".into(),
+        Value::NativeFunc(|_env, args| {
+            let index = args.len() + 1;
+            Ok(args[index].clone())
+        }),
+    );
+    env.borrow_mut().define(
+        "refuse".into(),
+        Value::NativeFunc(|_env, _args| panic!("not today")),
+    );
+
+    let eval_str = |source: &str| eval_block(env.clone(), parse(source).map(|expr| expr.unwrap()));
+
+    assert!(matches!(
+        eval_str("(explode 1 2)"),
+        Err(RuntimeError { msg }) if msg.starts_with("native function panicked: index out of bounds")
+    ));
+    assert_eq!(
+        eval_str("(+ 1 (refuse))"),
+        Err(RuntimeError::new(
+            "native function panicked: not today".to_owned()
+        ))
+    );
+
+    // and the interpreter carries on afterwards
+    assert_eq!(eval_str("(define x 2) (* x 3)"), Ok(lisp! { 6 }));
+}
@@ -0,0 +1,119 @@
+use rust_lisp::{
+    default_env,
+    interpreter::{call_function, eval_block},
+    lisp,
+    model::{ClosureCapture, Env, Lambda, RuntimeError, Symbol, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn free_variables() {
+    let env = env_with(ClosureCapture::ByReference);
+    let source = "
+    (lambda (a &rest b &key (c d))
+      (define e 1)
+      (let ((f a) (g f))
+        (+ a b c e f g h (quote i) (lambda (j) (list j k)))))";
+    let Ok(Value::Lambda(lambda)) = eval_str_in(env, source) else {
+        panic!("expected a lambda");
+    };
+
+    assert_eq!(names(&lambda), ["+", "d", "h", "k", "list"]);
+}
+
+#[test]
+fn later_sets_arent_seen() {
+    let source = "
+    (defun make_getter ()
+      (define x 1)
+      (define get (lambda () x))
+      (set x 2)
+      (get))
+
+    (make_getter)";
+
+    assert_eq!(
+        eval_str_in(env_with(ClosureCapture::ByReference), source),
+        Ok(lisp! { 2 })
+    );
+    assert_eq!(
+        eval_str_in(env_with(ClosureCapture::Minimal), source),
+        Ok(lisp! { 1 })
+    );
+}
+
+#[test]
+fn minimal_closures_still_work() {
+    let env = env_with(ClosureCapture::Minimal);
+    let source = "
+    (define offset 100)
+
+    (defun make_counter (start)
+      (define n start)
+      (lambda () (set n (+ n 1)) (+ n offset)))
+
+    (defun countdown (n)
+      (defun go (n acc) (if (== n 0) acc (go (- n 1) (cons n acc))))
+      (go n (list)))
+
+    (define counter (make_counter 0))
+    (counter)
+    (set offset 200)
+    (list (counter) (counter) (countdown 3))";
+
+    // each function keeps its own copy of the local variables it refers to,
+    // but globals are shared, and local functions can still call themselves
+    assert_eq!(eval_str_in(env, source), Ok(lisp! { (202 203 (1 2 3)) }));
+}
+
+#[test]
+fn unrelated_bindings_are_freed() {
+    for (capture, kept) in [
+        (ClosureCapture::ByReference, true),
+        (ClosureCapture::Minimal, false),
+    ] {
+        let env = env_with(capture);
+        let make_adder = eval_str_in(
+            env.clone(),
+            "(defun make_adder (n big) (lambda (x) (+ x n))) make_adder",
+        )
+        .unwrap();
+
+        let big = Rc::new(RefCell::new(vec![0u8; 1_000_000]));
+        let weak = Rc::downgrade(&big);
+        let add = call_function(
+            env.clone(),
+            &make_adder,
+            vec![lisp! { 1 }, Value::Bytes(big)],
+        )
+        .unwrap();
+
+        assert_eq!(weak.upgrade().is_some(), kept, "{:?}", capture);
+        assert_eq!(call_function(env, &add, vec![lisp! { 2 }]), Ok(lisp! { 3 }));
+    }
+}
+
+#[cfg(test)]
+fn names(lambda: &Lambda) -> Vec<String> {
+    let mut names = lambda
+        .free_variables()
+        .into_iter()
+        .map(|Symbol(name)| String::from(&*name))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+fn env_with(capture: ClosureCapture) -> Rc<RefCell<Env>> {
+    let env = default_env();
+    env.context().closure_capture.set(capture);
+
+    env.into_shared()
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
@@ -0,0 +1,131 @@
+// `IntType` only differs from the literal type under some features
+#![allow(clippy::useless_conversion)]
+
+use rust_lisp::{
+    compiler::compile, default_env, interpreter::eval, lisp, model::Value, parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn matches_eval() {
+    let sources = [
+        "12",
+        "\"foo\"",
+        "(list)",
+        "()",
+        "(nil 1 2)",
+        "(define if 1)",
+        "(set cond 1)",
+        "undefined_symbol",
+        "(+ 1 2)",
+        "(define x 10)",
+        "(set x (* x 2))",
+        "(set y 1)",
+        "(let ((a 1) (b (+ a 1))) (list a b x))",
+        "(let ((a 1) 12) a)",
+        "(let ((a 1)))",
+        "(let_values (((a b) (values 1 2))) (list a b x))",
+        "(let 12)",
+        "(let ((a 1) (a (car (list)))) a)",
+        "(let () (define p (lambda () q)) (define q 2) (p))",
+        "(let () (define r x) (define x 1) r)",
+        "(begin (define z 3) (+ z 1))",
+        "(begin)",
+        "(cond ((== x 0) \"zero\") ((> x 0) \"positive\") (T \"negative\"))",
+        "(cond (F 1) 12)",
+        "(cond (T 1) 12)",
+        "(cond (F 1))",
+        "(cond (F 1) (T))",
+        "(cond (T (define w 1) (+ w 1)))",
+        "(if (> x 5) \"big\" \"small\")",
+        "(if F 1)",
+        "(if T)",
+        "(and 1 2 3)",
+        "(and 1 F (car (list)))",
+        "(or F NIL 3)",
+        "(or)",
+        "(and)",
+        "(quote (1 2 (comma (+ 1 2))))",
+        "(defun fib (n) (if (<= n 1) n (+ (fib (- n 1)) (fib (- n 2)))))",
+        "(fib 15)",
+        "(defmacro unless (c e) (list (quote if) c NIL e))",
+        "(unless F (+ x 1))",
+        "(map (lambda (n) (* n n)) (range 0 5))",
+        "((lambda (a b) (- a b)) 5 3)",
+        "((if (> x 5) car cdr) (list 1 2))",
+        "(defun pick_op (n) (if (> n 0) - +))",
+        "((pick_op x) 5 1)",
+        "(list 1 @(list 2 x) @(range 0 2) 9)",
+        "(list @x)",
+        "(for ((a (range 0 3)) (b (list x))) (when (> a 0)) (* a b))",
+        "(do ((i 0 (+ i 1)) (a 0 b) (b 1 (+ a b))) ((== i x) a))",
+        "(trace fib)",
+        "(untrace)",
+        "(yield 1)",
+        "(splice (list 1))",
+        "(car (list))",
+        "(12 1 2)",
+        "(define)",
+        "(set 1 2)",
+    ];
+
+    let eval_env = Rc::new(RefCell::new(default_env()));
+    let compile_env = Rc::new(RefCell::new(default_env()));
+
+    for source in sources {
+        let expr = parse(source).next().unwrap().unwrap();
+
+        assert_eq!(
+            compile(&expr).eval(compile_env.clone()),
+            eval(eval_env.clone(), &expr),
+            "for {}",
+            source
+        );
+    }
+}
+
+#[test]
+fn evaluate_repeatedly() {
+    let env = Rc::new(RefCell::new(default_env()));
+    let predicate = compile(&lisp! {
+        (and (> n 10) (< n 20) (== (/ n 2) 7))
+    });
+
+    let matches = (0..30)
+        .filter(|n| {
+            env.borrow_mut().define("n".into(), Value::Int((*n).into()));
+
+            predicate.eval(env.clone()) == Ok(Value::True)
+        })
+        .collect::<Vec<i32>>();
+
+    assert_eq!(matches, vec![14, 15]);
+}
+
+#[test]
+fn compiled_quote_is_fresh() {
+    // the compiled expression must not hand out a shared (mutable) list
+    let env = Rc::new(RefCell::new(default_env()));
+    let expr = compile(&lisp! { (quote (1 2)) });
+
+    if let Value::List(first) = expr.eval(env.clone()).unwrap() {
+        first.set_car(lisp! { 3 }).unwrap();
+    }
+
+    assert_eq!(expr.eval(env), Ok(lisp! { (1 2) }));
+}
+
+#[test]
+fn registered_special_forms() {
+    let env = default_env().into_shared();
+    let expr = compile(&lisp! { (list (first_arg (undefined) 2) (if T 1 2)) });
+
+    // forms registered after compiling are still seen
+    let context = env.borrow().context().clone();
+    context
+        .register_special_form("first_arg", |_, args| Ok(args[0].clone()))
+        .unwrap();
+    context.override_special_form("if", |_, _| Ok(Value::NIL));
+
+    assert_eq!(expr.eval(env), Ok(lisp! { ((undefined) NIL) }));
+}
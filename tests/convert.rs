@@ -0,0 +1,171 @@
+// `IntType` only differs from the literal type under some features
+#![allow(clippy::useless_conversion)]
+
+use rust_lisp::{
+    convert::{AlistBuilder, AlistReader, FromLisp, IntoLisp},
+    default_env,
+    interpreter::eval,
+    lisp,
+    model::{FloatType, IntType, RuntimeError, Value},
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Database {
+    url: String,
+    pool_size: IntType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Config {
+    name: String,
+    verbose: bool,
+    ratio: FloatType,
+    database: Database,
+    replicas: Vec<Database>,
+    tags: Vec<String>,
+    timeout: Option<IntType>,
+    retries: Option<IntType>,
+}
+
+impl IntoLisp for Database {
+    fn into_lisp(self) -> Value {
+        AlistBuilder::new()
+            .field("url", self.url)
+            .field("pool_size", self.pool_size)
+            .build()
+    }
+}
+
+impl FromLisp for Database {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        let mut fields = AlistReader::new("Database", value)?;
+        let url = fields.field("url");
+        let pool_size = fields.field("pool_size");
+        fields.finish()?;
+
+        Ok(Database {
+            url: url.unwrap(),
+            pool_size: pool_size.unwrap(),
+        })
+    }
+}
+
+impl IntoLisp for Config {
+    fn into_lisp(self) -> Value {
+        AlistBuilder::new()
+            .field("name", self.name)
+            .field("verbose", self.verbose)
+            .field("ratio", self.ratio)
+            .field("database", self.database)
+            .field("replicas", self.replicas)
+            .field("tags", self.tags)
+            .field("timeout", self.timeout)
+            .field("retries", self.retries)
+            .build()
+    }
+}
+
+impl FromLisp for Config {
+    fn from_lisp(value: &Value) -> Result<Self, RuntimeError> {
+        let mut fields = AlistReader::new("Config", value)?;
+        let name = fields.field("name");
+        let verbose = fields.field("verbose");
+        let ratio = fields.field("ratio");
+        let database = fields.field("database");
+        let replicas = fields.field("replicas");
+        let tags = fields.field("tags");
+        let timeout = fields.field("timeout");
+        let retries = fields.field("retries");
+        fields.finish()?;
+
+        Ok(Config {
+            name: name.unwrap(),
+            verbose: verbose.unwrap(),
+            ratio: ratio.unwrap(),
+            database: database.unwrap(),
+            replicas: replicas.unwrap(),
+            tags: tags.unwrap(),
+            timeout: timeout.unwrap(),
+            retries: retries.unwrap(),
+        })
+    }
+}
+
+fn database(url: &str) -> Database {
+    Database {
+        url: url.to_owned(),
+        pool_size: 4.into(),
+    }
+}
+
+fn config() -> Config {
+    Config {
+        name: "app".to_owned(),
+        verbose: true,
+        ratio: 0.5,
+        database: database("db://main"),
+        replicas: vec![database("db://a"), database("db://b")],
+        tags: vec!["x".to_owned(), "y".to_owned()],
+        timeout: Some(30.into()),
+        retries: None,
+    }
+}
+
+#[test]
+fn round_trip() {
+    let value = config().into_lisp();
+
+    assert_eq!(Config::from_lisp(&value), Ok(config()));
+}
+
+#[test]
+fn round_trip_through_lisp() {
+    let env = Rc::new(RefCell::new(default_env()));
+    env.borrow_mut()
+        .define("config".into(), config().into_lisp());
+
+    // read a nested field from lisp, and pass the whole thing back out
+    let port = eval(
+        env.clone(),
+        &lisp! { (nth 1 (nth 0 (nth 1 (nth 3 config)))) },
+    );
+    assert_eq!(port, Ok(Value::String("db://main".to_owned())));
+
+    let value = eval(env, &lisp! { config }).unwrap();
+    assert_eq!(Config::from_lisp(&value), Ok(config()));
+}
+
+#[test]
+fn missing_and_wrong_fields() {
+    let value = AlistBuilder::new()
+        .field("name", IntType::from(12))
+        .field("verbose", true)
+        .field(
+            "database",
+            AlistBuilder::new().field("url", "db://main").build(),
+        )
+        .build();
+
+    assert_eq!(
+        Config::from_lisp(&value),
+        Err(RuntimeError::new(
+            "Couldn't read Config: field \"name\": expected a string, got 12; \
+                missing field \"ratio\"; \
+                field \"database\": Couldn't read Database: missing field \"pool_size\"; \
+                missing field \"replicas\"; missing field \"tags\"; \
+                missing field \"timeout\"; missing field \"retries\""
+                .to_owned()
+        ))
+    );
+}
+
+#[test]
+fn not_an_alist() {
+    assert_eq!(
+        Database::from_lisp(&lisp! { (1 2) }),
+        Err(RuntimeError::new(
+            "Couldn't read Database: expected a (name value) entry, got 1".to_owned()
+        ))
+    );
+}
@@ -1,8 +1,13 @@
+// `IntType` only differs from the literal type under some features
+#![allow(clippy::useless_conversion)]
+
 use rust_lisp::{
     default_env,
     interpreter::eval,
-    lisp,
-    model::{IntType, Value},
+    lisp, lisp_list,
+    model::{IntType, RuntimeError, Symbol, Value},
+    parser::parse,
+    sym,
 };
 use std::{cell::RefCell, rc::Rc};
 
@@ -16,6 +21,14 @@ fn range() {
             (list 0 1 2 3 4 5 6 7 8 9)
         })
     );
+
+    // an empty or backwards range has nothing in it
+    assert_eq!(
+        eval_ast(lisp! {
+            (list (range 3 3) (range 5 2))
+        }),
+        lisp! { (NIL NIL) }
+    );
 }
 
 #[test]
@@ -42,6 +55,34 @@ fn map() {
     );
 }
 
+#[test]
+fn mapcat() {
+    assert_eq!(
+        eval_ast(lisp! {
+            (mapcat (lambda (n) (list n (* n n))) (range 1 4))
+        }),
+        lisp! { (1 1 2 4 3 9) }
+    );
+
+    // an empty result just leaves nothing for that item
+    assert_eq!(
+        eval_ast(lisp! {
+            (mapcat
+                (lambda (n) (if (== n 2) (list) (list n)))
+                (range 0 7))
+        }),
+        lisp! { (0 1 3 4 5 6) }
+    );
+
+    let env = Rc::new(RefCell::new(default_env()));
+    assert_eq!(
+        eval(env, &lisp! { (mapcat (lambda (n) n) (list 1)) }),
+        Err(RuntimeError::new(
+            "\"mapcat\" requires the function to return lists; got 1".to_owned()
+        ))
+    );
+}
+
 #[test]
 fn reverse() {
     assert_eq!(
@@ -54,6 +95,42 @@ fn reverse() {
     );
 }
 
+#[test]
+fn list_star() {
+    assert_eq!(
+        eval_ast(lisp! { (list_star 1 2 (list 3 4)) }),
+        lisp! { (1 2 3 4) }
+    );
+    assert_eq!(eval_ast(lisp! { (cons_star 1 (list)) }), lisp! { (1) });
+
+    // a single argument is returned as-is, even if it isn't a list
+    assert_eq!(eval_ast(lisp! { (list_star (list 1 2)) }), lisp! { (1 2) });
+    assert_eq!(eval_ast(lisp! { (list_star 3) }), lisp! { 3 });
+
+    // a last argument that isn't a list makes a dotted list
+    let dotted = eval_ast(lisp! { (list_star 1 2 3) });
+    assert_eq!(dotted.to_string(), "(1 2 . 3)");
+    assert_eq!(dotted, parse("(1 2 . 3)").next().unwrap().unwrap());
+    assert_eq!(
+        eval_ast(lisp! { (list (length (cons_star 1 2 3)) (cdr (cdr (cons_star 1 2 3)))) }),
+        lisp! { (2 3) }
+    );
+
+    // errors name the function that was called
+    for name in ["list_star", "cons_star"] {
+        assert_eq!(
+            eval(
+                Rc::new(RefCell::new(default_env())),
+                &lisp_list![sym!(name)]
+            ),
+            Err(RuntimeError::new(format!(
+                "\"{}\" requires an argument 1",
+                name
+            )))
+        );
+    }
+}
+
 #[test]
 fn sort() {
     assert_eq!(
@@ -80,6 +157,244 @@ fn filter() {
     );
 }
 
+#[test]
+fn every_some_count() {
+    let result = eval_ast(lisp! {
+        (list
+            (every (lambda (x) (> x 0)) (list 1 2 3))
+            (every (lambda (x) (> x 1)) (list 1 2 3))
+            (some (lambda (x) (if (> x 1) (* x 10) F)) (list 1 2 3))
+            (some (lambda (x) (> x 5)) (list 1 2 3))
+            (count (lambda (x) (> x 1)) (list 1 2 3)))
+    });
+
+    assert_eq!(result, lisp! { (T F 20 F 2) });
+}
+
+#[test]
+fn every_some_count_empty() {
+    let result = eval_ast(lisp! {
+        (list
+            (every car (list))
+            (some car (list))
+            (count car (list)))
+    });
+
+    assert_eq!(result, lisp! { (T F 0) });
+}
+
+#[test]
+fn every_some_short_circuit() {
+    // (car (list)) would be an error, if it got that far
+    let result = eval_ast(lisp! {
+        (list
+            (every (lambda (x) (> (car x) 0)) (list (list 1) (list 0) (list)))
+            (some (lambda (x) (car x)) (list (list F) (list 2) (list))))
+    });
+
+    assert_eq!(result, lisp! { (F 2) });
+
+    assert_eq!(
+        eval(
+            Rc::new(RefCell::new(default_env())),
+            &lisp! { (count (lambda (x) (car x)) (list (list 1) (list))) }
+        ),
+        Err(RuntimeError::new(
+            "Attempted to apply car on nil".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn every_some_count_multiple_lists() {
+    // the lists are stepped through together, until the shortest runs out
+    let result = eval_ast(lisp! {
+        (list
+            (every < (list 1 2) (list 2 3 0))
+            (some (lambda (a b) (if (== a b) a F)) (list 1 2 3) (list 3 2 1))
+            (count == (list 1 2 3) (list 1 0 3)))
+    });
+
+    assert_eq!(result, lisp! { (T 2 2) });
+}
+
+#[test]
+fn zip() {
+    let result = eval_ast(lisp! {
+        (list
+            (zip (list 1 2 3) (list (quote a) (quote b)) (list "x" "y" "z" "w"))
+            (zip (list 1 2))
+            (zip (list 1 2) (list)))
+    });
+
+    assert_eq!(result, lisp! { (((1 a "x") (2 b "y")) ((1) (2)) ()) });
+}
+
+#[test]
+fn unzip() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define columns (list (list 1 2 3) (list "a" "b" "c")))
+            (define rows (zip (nth 0 columns) (nth 1 columns)))
+            (list
+                (unzip rows)
+                (equal (zip (nth 0 (unzip rows)) (nth 1 (unzip rows))) rows)
+                (unzip (list))))
+    });
+
+    assert_eq!(result, lisp! { (((1 2 3) ("a" "b" "c")) T ()) });
+
+    assert_eq!(
+        eval(
+            Rc::new(RefCell::new(default_env())),
+            &lisp! { (unzip (list (list 1 2) (list 3 4) (list 5))) }
+        ),
+        Err(RuntimeError::new(
+            "\"unzip\" requires lists of the same length, but list 3 has 1 items instead of 2"
+                .to_owned()
+        ))
+    );
+}
+
+#[test]
+fn min_by_max_by() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define size (lambda (w) (length (string_to_list w))))
+            (define words (list "pear" "fig" "banana" "kiwi" "plum"))
+            (list
+                (min_by size words)
+                (max_by size words)
+                (max_by size (list "kiwi" "pear"))
+                (min_by (lambda (n) (* (- n 3) (- n 3))) (list 1 4 2))
+                (max_by (lambda (n) n) (list 1.5 2 (- 1)))))
+    });
+
+    // ties go to the first
+    assert_eq!(result, lisp! { ("fig" "banana" "kiwi" 4 2) });
+
+    for func in ["min_by", "max_by"] {
+        let ast = parse(&format!("({} car (list))", func))
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            eval(Rc::new(RefCell::new(default_env())), &ast),
+            Err(RuntimeError::new(format!(
+                "\"{}\" requires a non-empty list",
+                func
+            )))
+        );
+    }
+}
+
+#[test]
+fn group_by() {
+    let result = eval_ast(lisp! {
+        (list
+            (group_by (lambda (n) (if (== (- n (* 2 (/ n 2))) 0) (quote even) (quote odd)))
+                (list 1 2 3 4 5 7))
+            (group_by (lambda (w) (length (string_to_list w))) (list "a" "bb" "c" "dd" "eee"))
+            (group_by car (list)))
+    });
+
+    assert_eq!(
+        result,
+        lisp! { (((odd 1 3 5 7) (even 2 4)) ((1 "a" "c") (2 "bb" "dd") (3 "eee")) ()) }
+    );
+}
+
+#[test]
+fn partition() {
+    let result = eval_ast(lisp! {
+        (list
+            (partition (lambda (n) (> n 2)) (list 1 4 2 5 3))
+            (partition (lambda (n) F) (list 1 2))
+            (partition car (list)))
+    });
+
+    assert_eq!(result, lisp! { (((4 5 3) (1 2)) (() (1 2)) (() ())) });
+}
+
+#[test]
+fn enumerate_and_count_if() {
+    let result = eval_ast(lisp! {
+        (list
+            (enumerate (list (quote a) (quote b) (quote c)))
+            (enumerate (list))
+            (count_if (lambda (n) (> n 1)) (list 1 2 3))
+            (count_if car (list)))
+    });
+
+    assert_eq!(result, lisp! { (((0 a) (1 b) (2 c)) () 2 0) });
+}
+
+#[test]
+fn list_utilities_stop_at_errors() {
+    // the callback fails on the second item, and isn't called again after
+    for func in ["min_by", "max_by", "group_by", "partition", "count_if"] {
+        let env = Rc::new(RefCell::new(default_env()));
+        let source = format!(
+            "(begin
+               (define calls 0)
+               (list
+                 ({} (lambda (x) (set calls (+ calls 1)) (car x)) (list (list 1) (list) (list 2)))))",
+            func
+        );
+        let ast = parse(&source).next().unwrap().unwrap();
+
+        assert_eq!(
+            eval(env.clone(), &ast),
+            Err(RuntimeError::new(
+                "Attempted to apply car on nil".to_owned()
+            )),
+            "{}",
+            func
+        );
+        assert_eq!(
+            eval(env, &Value::from(Symbol::from("calls"))),
+            Ok(Value::from(Into::<IntType>::into(2))),
+            "{}",
+            func
+        );
+    }
+}
+
+#[test]
+fn map_long_list() {
+    assert_eq!(
+        eval_ast(lisp! {
+            (length (map (lambda (n) (+ n 1)) (range 0 1000000)))
+        }),
+        Value::from(Into::<IntType>::into(1000000))
+    );
+}
+
+#[test]
+fn filter_long_list() {
+    assert_eq!(
+        eval_ast(lisp! {
+            (length (filter (lambda (n) (< n 10)) (range 0 100000)))
+        }),
+        Value::from(Into::<IntType>::into(10))
+    );
+}
+
+#[test]
+fn for_each() {
+    assert_eq!(
+        eval_ast(lisp! {
+            (begin
+                (define total 0)
+                (list
+                    (for_each (lambda (n) (set total (+ total n))) (range 0 5))
+                    total))
+        }),
+        lisp! { (NIL 10) }
+    );
+}
+
 #[test]
 fn map_list_of_lists() {
     let result = eval_ast(lisp! {
@@ -119,31 +434,1212 @@ fn hash_map() {
 }
 
 #[test]
-fn number_cast_comparisons() {
+fn member() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 1 "two" (list 3) 4))
+            (list
+                (member "two" lst)
+                (member (list 3) lst)
+                (member 5 lst)
+                (member 1 (list))
+                // the result shares its cells with the original list
+                (== (cdr (member 1 lst)) (cdr lst))))
+    });
+
+    assert_eq!(result, lisp! { (("two" (3) 4) ((3) 4) NIL NIL T) });
+}
+
+#[test]
+fn memq() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define inner (list 3))
+            (define lst (list (quote a) 2 inner (list 3)))
+            (list
+                (memq (list 3) lst)
+                (length (memq inner lst))
+                (length (member (list 3) lst))
+                (memq (quote a) lst)
+                (memq 2 lst)))
+    });
+
+    assert_eq!(result, lisp! { (NIL 2 2 (a 2 (3) (3)) (2 (3) (3))) });
+}
+
+#[test]
+fn remove() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 1 (list 2) 3 (list 2) 1))
+            (list
+                (remove 1 lst)
+                (remove (list 2) lst)
+                (remove 4 lst)
+                (remove 1 (list))
+                lst))
+    });
+
     assert_eq!(
-        eval_ast(lisp! {
-            (< 0 0.1)
-        }),
-        lisp! { T }
+        result,
+        lisp! { (((2) 3 (2)) (1 3 1) (1 (2) 3 (2) 1) NIL (1 (2) 3 (2) 1)) }
     );
+}
+
+#[test]
+fn list_set() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 1 2 3))
+            (list
+                (list_set lst 1 20)
+                (list_set lst 2 (lambda (n) (* n 10)))
+                lst))
+    });
+
+    assert_eq!(result, lisp! { ((1 20 3) (1 2 30) (1 2 3)) });
+}
+
+#[test]
+fn nested_updates() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define grid (list (list 1 2) (list 3 4)))
+            (define config (hash
+                "server" (list (list (quote host) "localhost") (list (quote port) 8000))))
+
+            (hash_update config "server"
+                (lambda (server)
+                    (alist_update server (quote port) (lambda (port) (+ port 80)))))
+
+            (list
+                (list_set grid 1 (lambda (row) (list_set row 0 30)))
+                (hash_get config "server")))
+    });
 
     assert_eq!(
-        eval_ast(lisp! {
-            (< { Value::Int((-2).into()) } 1)
-        }),
-        lisp! { T }
+        result,
+        lisp! { (((1 2) (30 4)) ((host "localhost") (port 8080))) }
     );
+}
+
+#[test]
+fn update_errors() {
+    let env = Rc::new(RefCell::new(default_env()));
 
     assert_eq!(
-        eval_ast(lisp! {
-            (>= { Value::Float(-2.1) } 1)
-        }),
-        lisp! { F }
+        eval(env.clone(), &lisp! { (list_set (list 1 2 3) 3 0) }),
+        Err(RuntimeError::new(
+            "\"list_set\" index 3 is out of range for a list of length 3".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(env.clone(), &lisp! { (list_set (list 1 2 3) (- 1) 0) }),
+        Err(RuntimeError::new(
+            "\"list_set\" index -1 is out of range for a list of length 3".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(
+            env.clone(),
+            &lisp! { (alist_update (list (list (quote a) 1)) (quote b) 2) }
+        ),
+        Err(RuntimeError::new(
+            "\"alist_update\" found no entry for the key b".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(env.clone(), &lisp! { (alist_update (list 1) (quote b) 2) }),
+        Err(RuntimeError::new(
+            "\"alist_update\" expected a (key value) entry, got 1".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(env, &lisp! { (hash_update (hash "a" 1) "b" 2) }),
+        Err(RuntimeError::new(
+            "\"hash_update\" found no entry for the key \"b\"".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn hash_merge_precedence() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define defaults (hash "host" "localhost" "port" 80))
+            (define overrides (hash "port" 8080 "debug" T))
+            (define merged (hash_merge defaults overrides (hash "debug" F)))
+            (list
+                (hash_get merged "host")
+                (hash_get merged "port")
+                (hash_get merged "debug")
+                (hash_count merged)
+                // the maps that were merged are left as they were
+                (hash_get defaults "port")
+                (hash_count overrides)
+                (hash_count (hash_merge))))
+    });
+
+    assert_eq!(result, lisp! { ("localhost" 8080 F 3 80 2 0) });
+}
+
+#[test]
+fn hash_assoc_is_functional() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define original (hash "a" 1))
+            (define updated (hash_assoc (hash_assoc original "b" 2) "a" 10))
+            (list
+                (hash_get original "a")
+                (hash_get original "b")
+                (hash_count original)
+                (hash_get updated "a")
+                (hash_get updated "b")
+                (hash_count updated)))
+    });
+
+    assert_eq!(result, lisp! { (1 NIL 1 10 2 2) });
+}
+
+#[test]
+fn hash_iteration() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define prices (hash "apple" 3 "pear" 5))
+            (define doubled (hash_map (lambda (name price) (* price 2)) prices))
+            (define total 0)
+            (define names (list))
+            (hash_for_each
+                (lambda (name price)
+                    (set total (+ total price))
+                    (set names (cons name names)))
+                prices)
+            (list
+                (hash_get doubled "apple")
+                (hash_get doubled "pear")
+                (hash_get prices "pear")
+                total
+                (length names)))
+    });
+
+    assert_eq!(result, lisp! { (6 10 5 8 2) });
+}
+
+#[test]
+fn sets() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define s (make_set 1 "one" (quote one) 1 2.5))
+            (set_add s {Value::Char('a')} "one" 3)
+            (set_remove s 2.5 "missing")
+            (list
+                (set_contains s 1)
+                (set_contains s "one")
+                (set_contains s (quote one))
+                (set_contains s 2.5)
+                (length (set_to_list s))
+                (== s (list_to_set (list 3 {Value::Char('a')} (quote one) "one" 1)))
+                (== s (make_set 1 3))
+                (is_set s)
+                (is_set (list 1))))
+    });
+
+    assert_eq!(result, lisp! { (T T T F 5 T F T F) });
+
+    // printed (and listed) in a stable order
+    let result = eval_ast(lisp! { (list_to_set (list "b" 2 (quote c) 1 "a" 2)) });
+    assert_eq!(format!("{}", result), "(make_set 1 2 \"a\" \"b\" c)");
+}
+
+#[test]
+fn set_algebra() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define a (make_set 1 2 3))
+            (define b (make_set 2 3 4))
+            (define c (make_set 3 4 5))
+            (define empty (make_set))
+            (list
+                (set_to_list (set_union a b))
+                (set_to_list (set_intersection a b))
+                (set_to_list (set_difference a b))
+                (set_to_list (set_difference a b c))
+                // commutative
+                (== (set_union a b) (set_union b a))
+                (== (set_intersection a b) (set_intersection b a))
+                // associative
+                (== (set_union (set_union a b) c) (set_union a (set_union b c)))
+                (== (set_intersection (set_intersection a b) c)
+                    (set_intersection a (set_intersection b c)))
+                // identities
+                (== (set_union a empty) a)
+                (== (set_intersection a empty) empty)
+                (== (set_union a a) a)
+                (== (set_intersection a a) a)
+                (== (set_difference a a) empty)
+                // distributive
+                (== (set_intersection a (set_union b c))
+                    (set_union (set_intersection a b) (set_intersection a c)))
+                // the originals are unchanged
+                (set_to_list a)))
+    });
+
+    assert_eq!(
+        result,
+        lisp! { ((1 2 3 4) (2 3) (1) (1) T T T T T T T T T T (1 2 3)) }
+    );
+}
+
+#[test]
+fn set_errors() {
+    let cases = [
+        (
+            lisp! { (make_set 1 (list 2)) },
+            "\"make_set\" can only put atoms (numbers, strings, chars, symbols and booleans) in a set; got (2)",
+        ),
+        (
+            lisp! { (set_add (make_set) (make_set)) },
+            "\"set_add\" can only put atoms (numbers, strings, chars, symbols and booleans) in a set; got (make_set)",
+        ),
+        (
+            lisp! { (list_to_set (list (lambda (x) x))) },
+            "\"list_to_set\" can only put atoms (numbers, strings, chars, symbols and booleans) in a set; got #<lambda (x) x>",
+        ),
+        (
+            lisp! { (set_union (make_set 1) (list 1)) },
+            "\"set_union\" requires argument 2 to be a set; got (1)",
+        ),
+        (
+            lisp! { (set_difference) },
+            "\"set_difference\" requires an argument 1",
+        ),
+    ];
+
+    for (ast, msg) in cases {
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(eval(env, &ast), Err(RuntimeError::new(msg.to_owned())));
+    }
+}
+
+#[test]
+fn hash_iteration_while_changing() {
+    // each call goes over the entries as they were when it started, even if
+    // the function changes the map
+    let result = eval_ast(lisp! {
+        (begin
+            (define counts (hash "a" 1 "b" 2))
+            (define visited 0)
+            (hash_for_each
+                (lambda (key value)
+                    (set visited (+ visited 1))
+                    (hash_set counts (string_append key key) value))
+                counts)
+            (list visited (hash_count counts)))
+    });
+
+    assert_eq!(result, lisp! { (2 4) });
+}
+
+#[test]
+fn property_lists() {
+    let result = eval_ast(lisp! {
+        (begin
+            (put (quote apple) (quote color) "red")
+            (define before (get (quote apple) (quote color)))
+            (put (quote apple) (quote color) "green")
+            (list
+                before
+                (get (quote apple) (quote color))
+                (remprop (quote apple) (quote color))
+                (get (quote apple) (quote color))))
+    });
+
+    assert_eq!(result, lisp! { ("red" "green" "green" NIL) });
+}
+
+#[test]
+fn property_lists_absent() {
+    let result = eval_ast(lisp! {
+        (begin
+            (put (quote apple) (quote color) "red")
+            (list
+                (get (quote apple) (quote size))
+                (get (quote pear) (quote color))
+                (remprop (quote pear) (quote color))))
+    });
+
+    assert_eq!(result, lisp! { (NIL NIL NIL) });
+}
+
+#[test]
+fn property_lists_shared_across_scopes() {
+    let result = eval_ast(lisp! {
+        (begin
+            (defun tag (sym) (put sym (quote tagged) T))
+            (tag (quote apple))
+            (get (quote apple) (quote tagged)))
+    });
+
+    assert_eq!(result, lisp! { T });
+}
+
+#[test]
+fn set_car_and_cdr() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 1 2 3))
+            (define tail (cdr lst))
+            (set_car tail 4)
+            (set_cdr tail (list 5 6))
+            lst)
+    });
+
+    assert_eq!(result, lisp! { (1 4 5 6) });
+}
+
+#[test]
+fn in_place_list_operations() {
+    // the original list sees the new order
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 3 1 2))
+            (define same lst)
+            (sort_in_place lst)
+            (define sorted (reverse (reverse same)))
+            (reverse_in_place lst)
+            (list sorted same))
+    });
+
+    assert_eq!(result, lisp! { ((1 2 3) (3 2 1)) });
+
+    let result = eval_ast(lisp! {
+        (begin
+            (define a (list 1 2))
+            (define b (list 3))
+            (append_in_place a (list) b (list 4 5))
+            (set_car b 6)
+            a)
+    });
+
+    assert_eq!(result, lisp! { (1 2 6 4 5) });
+
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 1 2 1 3 1))
+            (list
+                (delete_in_place 1 lst)
+                lst
+                (delete_in_place 2 (list 2 2))))
+    });
+
+    assert_eq!(result, lisp! { ((2 3) (2 3) NIL) });
+
+    let env = Rc::new(RefCell::new(default_env()));
+    assert_eq!(
+        eval(
+            env.clone(),
+            &lisp! {
+                (begin
+                    (define lst (list 1 2))
+                    (append_in_place lst lst))
+            }
+        ),
+        Err(RuntimeError::new(
+            "Appending would make the list circular".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(
+            env,
+            &lisp! {
+                (begin
+                    (set_cdr (cdr lst) lst)
+                    (sort_in_place lst))
+            }
+        ),
+        Err(RuntimeError::new("The list is circular".to_owned()))
+    );
+}
+
+#[test]
+fn cyclic_list_display() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 1 2 3))
+            (set_cdr (cdr (cdr lst)) lst)
+            lst)
+    });
+
+    assert_eq!(format!("{}", result), "(1 2 3 . #<cycle>)");
+
+    let result = eval_ast(lisp! {
+        (begin
+            (define lst (list 1 2))
+            (set_car lst lst))
+    });
+
+    assert_eq!(format!("{}", result), "(#<cycle> 2)");
+
+    let result = eval_ast(lisp! {
+        (begin
+            (define shared (list 1))
+            (list shared shared))
+    });
+
+    assert_eq!(format!("{}", result), "((1) (1))");
+}
+
+#[test]
+fn cyclic_hash_display() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define my_hash (hash))
+            (hash_set my_hash "self" my_hash))
+    });
+
+    assert_eq!(format!("{}", result), "(hash \"self\" #<cycle>)");
+}
+
+#[test]
+fn cyclic_list_equality() {
+    let result = eval_ast(lisp! {
+        (begin
+            (defun cycle (a b)
+                (define lst (list a b))
+                (set_cdr (cdr lst) lst))
+            (list
+                (equal (cycle 1 2) (cycle 1 2))
+                (equal (cycle 1 2) (cycle 1 3))
+                (equal (cycle 1 2) (list 1 2 1 2))
+                (== (cycle 1 2) (cycle 1 2))))
+    });
+
+    assert_eq!(result, lisp! { (T F F T) });
+}
+
+#[test]
+fn shared_list_equality() {
+    // each level refers to the one below twice, so walking the whole tree
+    // would visit the bottom 2^100 times
+    let result = eval_ast(lisp! {
+        (begin
+            (defun tower (n below)
+                (if (== n 0)
+                    below
+                    (tower (- n 1) (list below below))))
+            (define a (tower 100 (list 1)))
+            (define b a)
+            (list
+                (equal a b)
+                (equal a (tower 100 (list 1)))
+                (equal a (tower 99 (list 1)))
+                (equal (list a 1) (list (tower 100 (list 1)) 2))))
+    });
+
+    assert_eq!(result, lisp! { (T T F F) });
+}
+
+#[test]
+fn partial_native_function() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define add5 (partial + 2 3))
+            (list (add5 10) (add5) (map (partial * 2) (list 1 2 3))))
+    });
+
+    assert_eq!(result, lisp! { (15 5 (2 4 6)) });
+}
+
+#[test]
+fn partial_lambda() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define join3 (lambda (a b c) (list a b c)))
+            (define join_ab (partial (partial join3 "a") "b"))
+            (join_ab "c"))
+    });
+
+    assert_eq!(result, lisp! { ("a" "b" "c") });
+}
+
+#[test]
+fn recursion_through_partial() {
+    let result = eval_ast(lisp! {
+        (begin
+            (defun depth (step n)
+                (if (<= n 0)
+                    0
+                    (+ 1 (count_down (- n step)))))
+            (define count_down (partial depth 2))
+            (count_down 10))
+    });
+
+    assert_eq!(result, lisp! { 5 });
+}
+
+#[test]
+fn compose_two_functions() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define add_one_then_double (compose (partial * 2) (partial + 1)))
+            (list (add_one_then_double 3) ((compose car reverse) (list 1 2 3))))
+    });
+
+    assert_eq!(result, lisp! { (8 3) });
+}
+
+#[test]
+fn compose_three_functions() {
+    let result = eval_ast(lisp! {
+        (begin
+            (defun square (n) (* n n))
+            // the rightmost function gets all of the arguments
+            ((compose square (partial + 1) +) 1 2 3))
+    });
+
+    assert_eq!(result, lisp! { 49 });
+}
+
+#[test]
+fn compose_identity() {
+    let result = eval_ast(lisp! {
+        (list ((compose) 5) ((compose) (list 1 2)) ((compose car) (list 3 4)))
+    });
+
+    assert_eq!(result, lisp! { (5 (1 2) 3) });
+}
+
+#[test]
+fn compose_errors() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    assert_eq!(
+        eval(env.clone(), &lisp! { ((compose (partial + 1) car) (list)) }),
+        Err(RuntimeError::new(
+            "Attempted to apply car on nil".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(env, &lisp! { (compose car 1) }),
+        Err(RuntimeError::new(
+            "\"compose\" requires argument 2 to be a function; got 1".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn recursion_through_compose() {
+    let result = eval_ast(lisp! {
+        (begin
+            (defun halve (n) (/ n 2))
+            (defun depth (n)
+                (if (<= n 1)
+                    0
+                    (+ 1 (halve_depth n))))
+            (define halve_depth (compose depth halve))
+            (halve_depth 16))
+    });
+
+    assert_eq!(result, lisp! { 3 });
+}
+
+#[test]
+fn combinators() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define always_one (const 1))
+            (list
+                ((partial + 1) 2 3)
+                ((compose car cdr) (quote (1 2 3)))
+                ((curry list 1 2) 3)
+                (always_one)
+                (always_one 2 3)
+                ((flip -) 1 10)
+                ((flip list) 1 2 3)
+                (is_procedure (partial + 1))
+                (identity 4)
+                ((complement is_null) (list))
+                ((complement <) 1 2)))
+    });
+
+    assert_eq!(result, lisp! { (6 2 (1 2 3) 1 1 9 (2 1 3) T 4 F F) });
+}
+
+#[test]
+fn combinators_with_higher_order_functions() {
+    let result = eval_ast(lisp! {
+        (begin
+            (defun is_even (n) (== (* 2 (/ n 2)) n))
+            (list
+                (filter (complement is_even) (list 1 2 3 4 5))
+                (filter (complement (partial < 2)) (list 1 2 3 4))
+                (map (const 0) (list 1 2 3))
+                (map (compose (const (quote x)) identity) (list 1 2))
+                (map identity (list 1 2))))
+    });
+
+    assert_eq!(result, lisp! { ((1 3 5) (1 2) (0 0 0) (x x) (1 2)) });
+}
+
+#[test]
+fn printing_combinators() {
+    let env = Rc::new(RefCell::new(default_env()));
+    let printed = |expr: Value| format!("{}", eval(env.clone(), &expr).unwrap());
+
+    // functions are shown by the names they had where they were combined
+    assert_eq!(printed(lisp! { (partial + 1) }), "#<partial + (1)>");
+    assert_eq!(
+        printed(lisp! { (curry list "a" (quote b)) }),
+        "#<curry list (\"a\" b)>"
+    );
+    assert_eq!(printed(lisp! { (compose car cdr) }), "#<compose car cdr>");
+    assert_eq!(printed(lisp! { (const (list 1 2)) }), "#<const (1 2)>");
+    assert_eq!(printed(lisp! { (let ((sub -)) (flip sub)) }), "#<flip sub>");
+    assert_eq!(
+        printed(lisp! { (complement is_null) }),
+        "#<complement is_null>"
+    );
+    assert_eq!(
+        printed(lisp! {
+            (begin
+                (defun double (x) (* x 2))
+                (compose double (partial + 1)))
+        }),
+        "#<compose double #<partial + (1)>>"
+    );
+    assert_eq!(
+        printed(lisp! { (partial (lambda (x y) x) 1) }),
+        "#<partial #<lambda (x y) x> (1)>"
+    );
+}
+
+#[test]
+fn combinator_tail_calls() {
+    // a long chain of calls through combinators doesn't use up the stack
+    let result = eval_ast(lisp! {
+        (begin
+            (defun count_down (step n)
+                (if (<= n 0)
+                    n
+                    (next_step step n)))
+            (define next_step (flip (compose (partial count_down 1) -)))
+            (count_down 1 100000))
+    });
+
+    assert_eq!(result, lisp! { 0 });
+}
+
+#[test]
+fn multiple_values() {
+    let source = "
+    (begin
+      (defun divmod (a b) (values (/ a b) (- a (* b (/ a b)))))
+      (defun last_divmod (n) (if (== n 0) (divmod 17 5) (last_divmod (- n 1))))
+      (define q (divmod 7 2))
+      (list
+        (let_values (((quotient remainder) (divmod 17 5))) (list quotient remainder))
+        (let_values (((a &rest more) (values 1 2 3)) ((b) (+ a 1))) (list a more b))
+        (call_with_values (lambda () (divmod 9 4)) list)
+        (call_with_values (lambda () (values)) list)
+        (let_values (((x y) (last_divmod 10000))) (list x y))
+        (+ (divmod 7 2) 1)
+        q
+        (if (values F T) 1 2)
+        (values 1)
+        (map (lambda (n) (divmod n 2)) (list 5 8))))";
+
+    let result = eval_ast(parse(source).next().unwrap().unwrap());
+
+    assert_eq!(
+        format!("{}", result),
+        "((3 2) (1 (2 3) 2) (2 1) NIL (3 2) 4 3 2 1 (2 4))"
+    );
+
+    let env = Rc::new(RefCell::new(default_env()));
+    assert_eq!(
+        eval(env.clone(), &lisp! { (values 1 2) }).map(|values| format!("{}", values)),
+        Ok("(values 1 2)".to_owned())
+    );
+
+    let expr = parse("(let_values (((a b) (values 1 2 3))) a)")
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        eval(env, &expr),
+        Err(RuntimeError::new(
+            "\"let_values\" expected 2 values for (a b), got 3".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn arity_and_params() {
+    let source = "
+    (begin
+      (defun pair (a b) (list a b))
+      (defun log (level &rest parts) parts)
+      (defun draw (shape &key (width 1) height) shape)
+      (defmacro unless (test body) (list 'if test () body))
+      (list
+        (arity pair) (params pair)
+        (arity log) (params log)
+        (arity draw) (params draw)
+        (arity (lambda () 1)) (params (lambda () 1))
+        (arity unless)
+        (arity car) (params car)
+        (arity (partial pair 1)) (params (partial pair 1))))";
+
+    let result = eval_ast(parse(source).next().unwrap().unwrap());
+
+    assert_eq!(
+        format!("{}", result),
+        "(2 (a b) (1 T) (level &rest parts) (1 5) (shape &key (width 1) height) 0 NIL 2 NIL NIL NIL NIL)"
+    );
+
+    let env = Rc::new(RefCell::new(default_env()));
+    assert_eq!(
+        eval(env, &lisp! { (arity 1) }),
+        Err(RuntimeError::new(
+            "\"arity\" requires argument 1 to be a function; got 1".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn combinator_errors() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    assert_eq!(
+        eval(env.clone(), &lisp! { ((flip -) 1) }),
+        Err(RuntimeError::new(
+            "#<flip -> requires at least 2 arguments, but got 1".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(env.clone(), &lisp! { ((partial (lambda (a b c) a) 1) 2) }),
+        Err(RuntimeError::new(
+            "Expected 3 arguments, but got 2".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(env.clone(), &lisp! { (flip 1) }),
+        Err(RuntimeError::new(
+            "\"flip\" requires argument 1 to be a function; got 1".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval(env, &lisp! { (const) }),
+        Err(RuntimeError::new(
+            "\"const\" requires an argument 1".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn number_cast_comparisons() {
+    assert_eq!(
+        eval_ast(lisp! {
+            (< 0 0.1)
+        }),
+        lisp! { T }
+    );
+
+    assert_eq!(
+        eval_ast(lisp! {
+            (< { Value::Int((-2).into()) } 1)
+        }),
+        lisp! { T }
+    );
+
+    assert_eq!(
+        eval_ast(lisp! {
+            (>= { Value::Float(-2.1) } 1)
+        }),
+        lisp! { F }
+    );
+}
+
+#[test]
+fn string_append() {
+    let result = eval_ast(lisp! {
+        (list
+            (string_append "a" 1 "b" 2.5)
+            (string_append)
+            (+ "a" "b" 1 "c"))
+    });
+
+    assert_eq!(result, lisp! { ("a1b2.5" "" "ab1c") });
+
+    let env = Rc::new(RefCell::new(default_env()));
+    assert_eq!(
+        eval(env, &lisp! { (+ "a" (list 1)) }),
+        Err(RuntimeError::new(
+            "Function \"+\" requires arguments to be numbers or strings; found (1)".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn string_builder() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define sb (string_builder))
+            (sb_append sb "one" 2)
+            (sb_append (sb_append sb) "three" 4.5)
+            (list (sb_to_string sb) sb))
+    });
+
+    assert_eq!(
+        format!("{}", result),
+        "(\"one2three4.5\" #<string_builder 12 chars>)"
+    );
+}
+
+#[test]
+fn string_builder_appends_in_place() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define sb (string_builder))
+            (defun fill (n)
+                (if (> n 0)
+                    (begin
+                        (sb_append sb "x")
+                        (fill (- n 1)))
+                    sb))
+            (sb_to_string (fill 100000)))
+    });
+
+    assert_eq!(result, Value::String("x".repeat(100_000)));
+}
+
+#[test]
+fn string_chars() {
+    // indices count chars, not bytes
+    let result = eval_ast(lisp! {
+        (begin
+            (define s "héllo 東京")
+            (define chars (list))
+            (string_for_each (lambda (ch) (set chars (cons ch chars))) s)
+            (list
+                (string_ref s 1)
+                (string_ref s 7)
+                (length (string_to_list s))
+                (list_to_string (reverse chars))
+                (== (list_to_string (string_to_list s)) s)
+                (list_to_string (list))
+                (string_append "a" (string_ref s 6) 1)
+                (is_char (string_ref s 0))
+                (is_char "h")))
+    });
+
+    assert_eq!(
+        result,
+        Value::List(
+            [
+                Value::Char('é'),
+                Value::Char('京'),
+                Value::from(Into::<IntType>::into(8)),
+                Value::from("héllo 東京"),
+                Value::True,
+                Value::from(""),
+                Value::from("a東1"),
+                Value::True,
+                Value::False,
+            ]
+            .into_iter()
+            .collect()
+        )
+    );
+}
+
+#[test]
+fn string_char_errors() {
+    let cases = [
+        (
+            lisp! { (string_ref "hé" 2) },
+            "\"string_ref\" index 2 is out of range for a string of length 2",
+        ),
+        (
+            lisp! { (string_ref "abc" (- 1)) },
+            "\"string_ref\" index -1 is out of range for a string of length 3",
+        ),
+        (
+            lisp! { (list_to_string (list "a" "b")) },
+            "\"list_to_string\" requires a list to only contain chars; got (\"a\" \"b\")",
+        ),
+    ];
+
+    for (ast, msg) in cases {
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(eval(env, &ast), Err(RuntimeError::new(msg.to_owned())));
+    }
+}
+
+#[test]
+fn bytes() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define b (bytes 1 2 255))
+            (bytes_set b 0 104)
+            (list
+                b
+                (bytes_length b)
+                (bytes_ref b 2)
+                (bytes_slice b 1)
+                (bytes_slice b 0 1)
+                (bytes_append b (bytes) (string_to_utf8 "é"))
+                (utf8_to_string (bytes 104 105))
+                (== (bytes 1 2) (bytes 1 2))
+                (is_bytes b)
+                (is_bytes (list 1 2))))
+    });
+
+    assert_eq!(
+        format!("{}", result),
+        "(#u8(104 2 255) 3 255 #u8(2 255) #u8(104) #u8(104 2 255 195 169) \"hi\" T T F)"
+    );
+}
+
+#[test]
+fn vectors() {
+    // the items of a literal aren't evaluated, so `foo` is just a symbol
+    let source = "
+    (begin
+      (define v (vector 1 (+ 1 1) \"three\"))
+      (vector_set v 0 'one)
+      (list
+        v
+        #(foo (bar) #(1 2))
+        (vector_length v)
+        (vector_ref v 1)
+        (vector_ref #(foo) 0)
+        (vector_to_list v)
+        (list_to_vector (list 1 2))
+        (== (vector 1 2) #(1 2))
+        (is_vector v)
+        (is_vector (list 1 2))))";
+    let result = eval_ast(parse(source).next().unwrap().unwrap());
+
+    assert_eq!(
+        format!("{}", result),
+        "(#(one 2 \"three\") #(foo (bar) #(1 2)) 3 2 foo (one 2 \"three\") #(1 2) T T F)"
+    );
+
+    let env = Rc::new(RefCell::new(default_env()));
+    assert_eq!(
+        eval(env, &lisp! { (vector_ref (vector 1 2) 2) }),
+        Err(RuntimeError::new(
+            "\"vector_ref\" index 2 is out of range for a vector of length 2".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn printing_large_bytes() {
+    let result = eval_ast(lisp! {
+        (bytes_append
+            (string_to_utf8 "0123456789abcdef0123456789abcdef")
+            (string_to_utf8 "0123456789abcdef0123456789abcdef")
+            (bytes 255))
+    });
+
+    assert_eq!(
+        format!("{}", result),
+        "#<bytes 65: 30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66 ...>"
+    );
+}
+
+#[test]
+fn bytes_packing() {
+    let big = Value::Symbol(Symbol::from(":big"));
+    let little = Value::Symbol(Symbol::from(":little"));
+    let signed = Value::Symbol(Symbol::from(":signed"));
+
+    let result = eval_ast(lisp! {
+        (list
+            (int_to_bytes 258 2 {big.clone()})
+            (int_to_bytes 258 4 {little.clone()})
+            (int_to_bytes (- 2) 2 {big.clone()} {signed.clone()} T)
+            (bytes_to_int (bytes 1 2) {big.clone()})
+            (bytes_to_int (bytes 1 2) {little.clone()})
+            (bytes_to_int (bytes 255 254) {big.clone()})
+            (bytes_to_int (bytes 255 254) {big.clone()} {signed.clone()} T)
+            (bytes_to_int (int_to_bytes (- 300) 3 {little.clone()} {signed.clone()} T)
+                {little} {signed} T))
+    });
+
+    assert_eq!(
+        format!("{}", result),
+        "(#u8(1 2) #u8(2 1 0 0) #u8(255 254) 258 513 65534 -2 -300)"
+    );
+}
+
+#[test]
+fn bytes_errors() {
+    let big = Value::Symbol(Symbol::from(":big"));
+
+    let cases = [
+        (
+            lisp! { (bytes 1 256) },
+            "\"bytes\" requires argument 2 to be a byte (an integer from 0 to 255); got 256",
+        ),
+        (
+            lisp! { (bytes_ref (bytes 1 2) 2) },
+            "\"bytes_ref\" index 2 is out of range for bytes of length 2",
+        ),
+        (
+            lisp! { (bytes_set (bytes 1 2) (- 1) 0) },
+            "\"bytes_set\" index -1 is out of range for bytes of length 2",
+        ),
+        (
+            lisp! { (bytes_set (bytes 1 2) 0 (- 1)) },
+            "\"bytes_set\" requires argument 3 to be a byte (an integer from 0 to 255); got -1",
+        ),
+        (
+            lisp! { (bytes_slice (bytes 1 2) 1 3) },
+            "\"bytes_slice\" range 1 to 3 is out of range for bytes of length 2",
+        ),
+        (
+            lisp! { (utf8_to_string (bytes 104 105 255 104)) },
+            "\"utf8_to_string\" found invalid UTF-8 at byte 2",
+        ),
+        (
+            lisp! { (int_to_bytes 256 1 {big.clone()}) },
+            "\"int_to_bytes\" 256 doesn't fit in a 1-byte unsigned int",
+        ),
+        (
+            lisp! { (int_to_bytes (- 1) 1 {big.clone()}) },
+            "\"int_to_bytes\" -1 doesn't fit in a 1-byte unsigned int",
+        ),
+        (
+            lisp! { (int_to_bytes 1 9 {big.clone()}) },
+            "\"int_to_bytes\" requires a size of 1 to 8 bytes; got 9",
+        ),
+        (
+            lisp! { (bytes_to_int (bytes) {big}) },
+            "\"bytes_to_int\" can only read ints of 1 to 8 bytes; got 0 bytes",
+        ),
+        (
+            lisp! { (bytes_to_int (bytes 1) (quote middle)) },
+            "\"bytes_to_int\" requires argument 2 to be :big or :little; got middle",
+        ),
+    ];
+
+    for (ast, msg) in cases {
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(eval(env, &ast), Err(RuntimeError::new(msg.to_owned())));
+    }
+}
+
+#[test]
+fn prelude_accessors() {
+    let result = eval_ast(lisp! {
+        (begin
+            (define nested (list (list 1 2) 3 4 5))
+            (list
+                (caar nested)
+                (cadr nested)
+                (cdar nested)
+                (cddr nested)
+                (caddr nested)
+                (cdddr nested)
+                (second nested)
+                (third nested)))
+    });
+
+    assert_eq!(result, lisp! { (1 3 (2) (4 5) 4 (5) 3 4) });
+}
+
+#[test]
+fn prelude_functions() {
+    let result = eval_ast(lisp! {
+        (begin
+            (list
+                (map second (list (list 1 2) (list 3 4)))
+                ((compose second cdr) (list 1 2 3))))
+    });
+
+    assert_eq!(result, lisp! { ((2 4) 3) });
+}
+
+#[test]
+fn prelude_is_shadowed_by_definitions() {
+    // redefining a prelude function at the top level doesn't change the ones
+    // in the prelude that use it
+    let result = eval_ast(lisp! {
+        (begin
+            (defun cadr (list) "mine")
+            (list (cadr (list 1 2)) (second (list 1 2))))
+    });
+
+    assert_eq!(result, lisp! { ("mine" 2) });
+}
+
+#[test]
+fn layered_prelude() {
+    let env = default_env()
+        .eval_prelude(
+            "
+    (defun square (n) (* n n))
+    (define squares (map square (list 1 2 3)))",
+        )
+        .unwrap()
+        .eval_prelude("(defun last_square () (third squares))")
+        .unwrap();
+
+    let result = eval(
+        Rc::new(RefCell::new(env)),
+        &lisp! { (list (square 5) (last_square)) },
+    );
+
+    assert_eq!(result, Ok(lisp! { (25 9) }));
+}
+
+#[test]
+fn prelude_errors() {
+    assert_eq!(
+        default_env()
+            .eval_prelude("(defun broken (x)\n  (+ x 1)")
+            .err(),
+        Some(RuntimeError::new(
+            "\
+Parse error: Unclosed list at index 27
+ --> <prelude>:1:1
+  |
+1 | (defun broken (x)
+  | ^^^^^^^^^^^^^^^^^
+  |
+  = hint: this `(` is never closed; add a `)` where the list ends"
+                .to_owned()
+        ))
+    );
+
+    assert_eq!(
+        default_env().eval_prelude("(define x 1) (car x)").err(),
+        Some(RuntimeError::new(
+            "Evaluating the prelude failed: \"car\" requires argument 1 to be a list; got 1"
+                .to_owned()
+        ))
+    );
+}
+
+#[test]
+fn io_functions_need_std() {
+    let env = default_env();
+
+    assert_eq!(
+        env.get(&Symbol::from("print")).is_some(),
+        cfg!(feature = "std")
     );
+    assert!(env.get(&Symbol::from("car")).is_some());
 }
 
 #[cfg(test)]
 fn eval_ast(ast: Value) -> Value {
     let env = Rc::new(RefCell::new(default_env()));
-    return eval(env, &ast).unwrap();
+    eval(env, &ast).unwrap()
 }
@@ -0,0 +1,192 @@
+use rust_lisp::{default_env, eval_str, model::Env};
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+#[test]
+fn function_display() {
+    let (env, _) = env_with_captured_output();
+
+    let printed = |source: &str| {
+        let value = eval_str(env.clone(), source).unwrap();
+        let printed = env.borrow().context().display(&value).to_string();
+
+        printed
+    };
+
+    assert_eq!(printed("(lambda (x) x)"), "#<lambda (x) x>");
+    assert_eq!(printed("(lambda () 1 2)"), "#<lambda () 1 2>");
+    assert_eq!(
+        printed("(defun add (a b) \"Adds them\" (+ a b)) add"),
+        "#<lambda add (a b) (+ a b)>"
+    );
+
+    // `define` names a function that doesn't have a name yet
+    assert_eq!(
+        printed("(define sub (lambda (a b) (- a b))) (list sub (define also_add add))"),
+        "(#<lambda sub (a b) (- a b)> #<lambda add (a b) (+ a b)>)"
+    );
+
+    // long bodies are cut short
+    assert_eq!(
+        printed("(lambda (items) (map (lambda (item) (* item item item)) items))"),
+        "#<lambda (items) (map (lambda (item) (* item item item))...>"
+    );
+
+    assert_eq!(
+        printed("(defmacro unless (test body) (list 'if test () body)) unless"),
+        "#<macro unless (test body) (list 'if test NIL body)>"
+    );
+
+    // native functions are shown with the name they were defined with
+    assert_eq!(printed("cons"), "#<native cons>");
+    assert_eq!(printed("(let ((first car)) first)"), "#<native car>");
+    assert_eq!(eval_str(env, "car").unwrap().to_string(), "#<native>");
+}
+
+#[test]
+fn describe_lambda() {
+    let (env, output) = env_with_captured_output();
+
+    eval_str(
+        env,
+        "
+    (defun scale (items &key (factor 2))
+      \"Multiplies each of the items by the factor\"
+      (define scaled (map (lambda (item) (* item factor)) items))
+      scaled)
+    (describe scale)",
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+#<lambda scale (items &key (factor 2)) (define scaled (map (lambda (item) (* it...>
+  type: function
+  name: scale
+  parameters: (items &key (factor 2))
+  documentation: Multiplies each of the items by the factor
+  body:
+    (define scaled (map (lambda (item) (* item factor)) items))
+    scaled
+"
+    );
+}
+
+#[test]
+fn describe_string() {
+    let (env, output) = env_with_captured_output();
+
+    eval_str(env, "(describe \"naïve\")").unwrap();
+
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+\"naïve\"
+  type: string
+  length: 5 chars, 6 bytes
+"
+    );
+}
+
+#[test]
+fn describe_other_values() {
+    let (env, output) = env_with_captured_output();
+
+    eval_str(
+        env,
+        "
+    (describe car)
+    (describe (list 1 2 3))
+    (define circle (list 1 2))
+    (set_cdr (cdr circle) circle)
+    (describe circle)
+    (describe 1.5)",
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+#<native car>
+  type: function
+  name: car
+(1 2 3)
+  type: list
+  length: 3
+(1 2 . #<cycle>)
+  type: list
+  length: unknown (The list is circular)
+1.5
+  type: float
+"
+    );
+}
+
+#[test]
+fn describe_env() {
+    let (env, output) = env_with_captured_output();
+
+    eval_str(
+        env.clone(),
+        "
+    (define total 0)
+    (defun add (n) (set total (+ total n)))
+    (define names (list \"a\" \"b\"))
+    (describe_env)",
+    )
+    .unwrap();
+
+    // only what's defined in the scope itself, not the builtins
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+add: function
+names: list
+total: integer
+"
+    );
+
+    // inside a function, that's its locals
+    output.borrow_mut().clear();
+    eval_str(
+        env.clone(),
+        "
+    (defun g (x)
+      (define doubled (* x 2.0))
+      (describe_env))
+    (g 1)",
+    )
+    .unwrap();
+
+    assert_eq!(output.borrow().as_str(), "doubled: float\nx: integer\n");
+
+    // and other environments can be described too
+    output.borrow_mut().clear();
+    eval_str(
+        env,
+        "
+    (define other (make_env))
+    (env_define other 'greeting \"hi\")
+    (describe_env other)",
+    )
+    .unwrap();
+
+    assert_eq!(output.borrow().as_str(), "greeting: string\n");
+}
+
+struct Capture(Rc<RefCell<String>>);
+
+impl Write for Capture {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0.borrow_mut().push_str(s);
+        Ok(())
+    }
+}
+
+fn env_with_captured_output() -> (Rc<RefCell<Env>>, Rc<RefCell<String>>) {
+    let env = default_env();
+    let output = Rc::new(RefCell::new(String::new()));
+    env.context().set_output(Capture(output.clone()));
+
+    (env.into_shared(), output)
+}
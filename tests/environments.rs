@@ -0,0 +1,134 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{Env, RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn current_env_is_the_call_site() {
+    let result = eval_str(
+        "
+    (defun g (x) (current_env))
+
+    (env_get (g 5) 'x)",
+    );
+
+    assert_eq!(result, Ok(lisp! { 5 }));
+}
+
+#[test]
+fn define_and_eval_in_env() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    let result = eval_str_in(
+        env.clone(),
+        "
+    (define e (make_env))
+    (env_define e 'a 10)
+
+    (eval 'a e)",
+    );
+
+    assert_eq!(result, Ok(lisp! { 10 }));
+
+    // nothing leaked into the environment that created it
+    assert_eq!(
+        eval_str_in(env, "a"),
+        Err(RuntimeError::new("\"a\" is not defined".to_owned()))
+    );
+}
+
+#[test]
+fn env_with_parent() {
+    let result = eval_str(
+        "
+    (define e (make_env (current_env)))
+    (env_define e 'x 1)
+    (define y 2)
+
+    (list (eval '(+ x y) e) (env_get e 'y))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (3 2) }));
+}
+
+#[test]
+fn empty_env_cant_see_globals() {
+    assert_eq!(
+        eval_str(
+            "
+    (define secret 42)
+
+    (eval 'secret (make_env))"
+        ),
+        Err(RuntimeError::new("\"secret\" is not defined".to_owned()))
+    );
+
+    assert_eq!(
+        eval_str("(eval '(car (list 1 2)) (make_env))"),
+        Err(RuntimeError::new("\"car\" is not defined".to_owned()))
+    );
+
+    assert_eq!(
+        eval_str("(env_get (make_env) 'car)"),
+        Err(RuntimeError::new("\"car\" is not defined".to_owned()))
+    );
+}
+
+#[test]
+fn env_display() {
+    let result = eval_str(
+        "
+    (define e (make_env))
+    (env_define e 'self e)
+    (env_define e 'get_e (lambda () e))
+
+    e",
+    )
+    .unwrap();
+
+    assert_eq!(result.to_string(), "#<env 2 bindings>");
+    assert_eq!(format!("{:?}", result), "Value::Env(#<env 2 bindings>)");
+
+    let env = Rc::new(RefCell::new(Env::new()));
+    assert_eq!(Value::Env(env).to_string(), "#<env 0 bindings>");
+}
+
+#[test]
+fn env_equality() {
+    let result = eval_str(
+        "
+    (define e (make_env))
+
+    (list
+      (== e e)
+      (== (current_env) (current_env))
+      (== (make_env) (make_env))
+      (== e (make_env e)))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (T T F F) }));
+}
+
+#[test]
+fn wrong_arg_types() {
+    assert_eq!(
+        eval_str("(eval 1 2)"),
+        Err(RuntimeError::new(
+            "\"eval\" requires argument 2 to be a environment; got 2".to_owned()
+        ))
+    );
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    eval_str_in(Rc::new(RefCell::new(default_env())), source)
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
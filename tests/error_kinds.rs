@@ -0,0 +1,107 @@
+use rust_lisp::{
+    default_env, eval_str, lisp,
+    model::{ErrorKind, RuntimeError},
+};
+
+const DEPTH: &str = "
+(defun depth (n) (if (== n 0) 0 (+ 1 (depth (- n 1)))))
+(defun count_down (n) (if (== n 0) 'done (count_down (- n 1))))";
+
+#[test]
+fn depth_limit() {
+    let env = default_env();
+    env.context().max_depth.set(Some(30));
+    let env = env.into_shared();
+    eval_str(env.clone(), DEPTH).unwrap();
+
+    let err = eval_str(env.clone(), "(depth 100)").unwrap_err();
+    assert_eq!(err.msg, "Maximum evaluation depth exceeded");
+    assert_eq!(err.kind(), ErrorKind::DepthLimit);
+
+    // shallow recursion is fine, and so is any amount of tail recursion, and
+    // the depth is unwound after an error
+    assert_eq!(
+        eval_str(env, "(list (depth 5) (count_down 10000))"),
+        Ok(lisp! { (5 done) })
+    );
+}
+
+#[test]
+fn budget_exhausted() {
+    let env = default_env().into_shared();
+    eval_str(env.clone(), DEPTH).unwrap();
+
+    let err = eval_str(env.clone(), "(with_step_limit 100 (count_down 1000))").unwrap_err();
+    assert_eq!(
+        err.msg,
+        "\"with_step_limit\" went over its limit of 100 steps"
+    );
+    assert_eq!(err.kind(), ErrorKind::BudgetExhausted);
+
+    // an outer limit is the same kind of error, seen from inside
+    let err = eval_str(
+        env,
+        "(with_step_limit 100 (list (with_step_limit 1000 (count_down 1000) 'inner)))",
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BudgetExhausted);
+}
+
+#[test]
+fn user_errors() {
+    let env = default_env().into_shared();
+
+    let err = eval_str(env.clone(), "(error \"no ~a for ~s\" 'entry \"key\")").unwrap_err();
+    assert_eq!(err, RuntimeError::new("no entry for \"key\""));
+    assert_eq!(err.kind(), ErrorKind::UserError);
+
+    assert_eq!(
+        eval_str(env.clone(), "(undefined_function)")
+            .unwrap_err()
+            .kind(),
+        ErrorKind::UserError
+    );
+
+    // the kind is whatever raised the error, not what its message says
+    for source in [
+        "(error \"Maximum evaluation depth exceeded\")",
+        "(error \"Step limit exceeded\")",
+        "(error \"~s went over its limit of 100 steps\" \"with_step_limit\")",
+        "(error \"Parse error: Unclosed list at index 0\")",
+        "(error \"~s requires argument 1 to be a list; got 1\" \"car\")",
+    ] {
+        let err = eval_str(env.clone(), source).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UserError, "{}", err.msg);
+    }
+
+    // and a fallback only replaces the budget running out, not an error
+    // that says it has
+    let err = eval_str(
+        env,
+        "(with_step_limit 100 (error \"Step limit exceeded\") 'fallback)",
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UserError);
+}
+
+#[test]
+fn other_kinds() {
+    let env = default_env().into_shared();
+
+    for source in [
+        "(car 1)",
+        "(+ \"a\" 'b)",
+        "(- \"a\" 1)",
+        "(vector_ref (list) 0)",
+    ] {
+        let err = eval_str(env.clone(), source).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TypeError, "{}", err.msg);
+    }
+
+    let err = eval_str(env.clone(), "(list 1").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ParseError);
+
+    env.borrow().context().cancel_handle().cancel();
+    let err = eval_str(env, "(list 1)").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Interrupted);
+}
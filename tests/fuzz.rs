@@ -0,0 +1,59 @@
+#![cfg(feature = "fuzzing")]
+
+use proptest::prelude::*;
+use rust_lisp::{
+    default_env,
+    fuzzing::program,
+    interpreter::eval,
+    model::{RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    // a panic fails the test, so this checks that evaluation never panics too
+    #[test]
+    fn evaluation_is_deterministic(source in program()) {
+        prop_assert_eq!(eval_forms(&source), eval_forms(&source), "{}", source);
+    }
+
+    #[test]
+    fn values_round_trip_through_printing(value in any::<Value>()) {
+        let printed = format!("{}", value);
+        let parsed = parse(&printed).collect::<Vec<_>>();
+
+        prop_assert_eq!(parsed, vec![Ok(value)], "{}", printed);
+    }
+}
+
+#[test]
+fn seed_corpus() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fuzz_corpus");
+    let mut seeds = 0;
+
+    for entry in fs::read_dir(corpus).unwrap() {
+        let path = entry.unwrap().path();
+        let source = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(eval_forms(&source), eval_forms(&source), "{:?}", path);
+        seeds += 1;
+    }
+
+    assert!(seeds > 0);
+}
+
+/// Evaluate each top-level form of `source` in turn, in a fresh default
+/// environment, carrying on after any errors
+#[cfg(test)]
+fn eval_forms(source: &str) -> Vec<Result<Value, RuntimeError>> {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    parse(source)
+        .map(|expr| match expr {
+            Ok(expr) => eval(env.clone(), &expr),
+            Err(err) => Err(RuntimeError::new(err.msg)),
+        })
+        .collect()
+}
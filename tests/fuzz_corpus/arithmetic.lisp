@@ -0,0 +1,8 @@
+;; mixed int and float arithmetic, and the ways it can fail
+(list (+ 1 2.5) (- 3) (* -4 0.25) (/ 7 2) (/ 7.0 2))
+(/ 1 0)
+(/ 1.5 0.0)
+(* 2147483647 2147483647)
+(- -2147483648 1)
+(+ "a" 1 2.5)
+(< 1 "a")
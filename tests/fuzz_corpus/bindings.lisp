@@ -0,0 +1,8 @@
+;; scoping through let, lambda and shadowing, and unbound variables
+(let ((a 1)) (let ((a (+ a 1))) ((lambda (a) (* a 10)) a)))
+((lambda (b) (let ((a b)) (list a b))) 3)
+(let ((a c)) a)
+((lambda (a) a) 1 2)
+((lambda (a) a))
+((lambda (a b &rest c) c) 1)
+(if a b c)
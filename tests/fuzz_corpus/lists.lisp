@@ -0,0 +1,11 @@
+;; list operations on empty, short and mistyped lists
+(list (car '(1 2)) (cdr '(1)) (cons 1 NIL) (length '()) (reverse '(1 2 3)))
+(car '())
+(cdr NIL)
+(nth -1 '(1 2 3))
+(nth 10 '(1 2 3))
+(nth 1 2)
+(cons 1 2)
+(length 5)
+(map (lambda (a) (* a a)) '(1 2 3))
+(map (lambda (a) (car a)) '(1 2 3))
@@ -0,0 +1,4 @@
+;; deeply nested expressions
+(list (list (list (list (list (list (list (list (list (list 1))))))))))
+(if (if (if T F T) NIL T) (car (cdr (cdr '(1 2 3)))) (car '()))
+(+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 (+ 1 1))))))))))
@@ -0,0 +1,5 @@
+;; deep tail recursion, which mustn't grow the stack
+(defun count_down (a b) (if (<= a 0) b (count_down (- a 1) (+ b 1))))
+(count_down 100000 0)
+(count_down 10 (count_down 10 '()))
+(count_down "a" 0)
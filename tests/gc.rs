@@ -0,0 +1,126 @@
+#![cfg(feature = "gc")]
+
+use rust_lisp::{
+    default_env,
+    interpreter::{call_function, eval_block},
+    lisp,
+    model::{Env, RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn abandoned_cycles_are_collected() {
+    let env = default_env().into_shared();
+    let context = env.borrow().context().clone();
+
+    // each call leaves behind an environment which refers to itself, through
+    // the closure of the local function defined in it
+    let result = eval_str_in(
+        env.clone(),
+        "
+    (defun make_cycle (n)
+      (define self (lambda () self))
+      n)
+
+    (defun churn (n)
+      (if (== n 0)
+        0
+        (churn (- (make_cycle n) 1))))
+
+    (churn 1000000)",
+    );
+
+    assert_eq!(result, Ok(lisp! { 0 }));
+
+    // they're collected automatically as they pile up
+    assert!(context.live_envs() < 10_000, "{}", context.live_envs());
+
+    // and the rest can be collected on demand, leaving the root environment
+    // and the prelude's
+    assert!(context.collect_garbage() > 0);
+    assert_eq!(context.live_envs(), 2);
+    assert_eq!(context.collect_garbage(), 0);
+}
+
+#[test]
+fn values_in_use_are_kept() {
+    let env = default_env().into_shared();
+    let context = env.borrow().context().clone();
+
+    let counter = eval_str_in(
+        env.clone(),
+        "
+    (defun make_counter ()
+      (define n 0)
+      (define count (lambda () (set n (+ n 1)) n))
+      count)
+
+    (define kept (list (make_counter) (current_env)))
+    (define counter (make_counter))
+    ((car kept))
+    (make_counter)
+    (make_counter)",
+    )
+    .unwrap();
+
+    // only the counter nothing refers to any more is garbage: the others are
+    // held by the root environment, or by the host
+    assert_eq!(context.collect_garbage(), 1);
+
+    assert_eq!(
+        call_function(env.clone(), &counter, vec![]),
+        Ok(lisp! { 1 })
+    );
+    assert_eq!(
+        eval_str_in(env.clone(), "(list (counter) (counter) ((car kept)))"),
+        Ok(lisp! { (1 2 2) })
+    );
+
+    // and a local environment that's still being evaluated in is in use
+    let result = eval_str_in(
+        env.clone(),
+        "
+    (let ((a 1))
+      (define b (lambda () a))
+      (set_gc)
+      (b))",
+    );
+    assert_eq!(result, Ok(lisp! { 1 }));
+}
+
+#[test]
+fn teardown() {
+    let env = default_env().into_shared();
+    let context = env.borrow().context().clone();
+
+    eval_str_in(
+        env.clone(),
+        "
+    (defun loop (n) (if (== n 0) n (loop (- n 1))))
+    (define env (current_env))",
+    )
+    .unwrap();
+
+    // the root environment (and the prelude's, which it extends) is kept alive
+    // by the functions defined in it, even after the host lets go of it
+    drop(env);
+    assert_eq!(context.live_envs(), 2);
+
+    assert_eq!(context.collect_garbage(), 2);
+    assert_eq!(context.live_envs(), 0);
+    assert_eq!(Rc::strong_count(&context), 1);
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    env.borrow_mut().define(
+        "set_gc".into(),
+        Value::NativeFunc(|env, _args| {
+            env.borrow().context().collect_garbage();
+            Ok(Value::NIL)
+        }),
+    );
+
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
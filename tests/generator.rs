@@ -0,0 +1,353 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn yields_values_in_order() {
+    let result = eval_str(
+        "
+    (define gen
+      (generator (lambda ()
+        (yield 1)
+        (yield 2)
+        (yield 3))))
+
+    (list (next gen) (next gen) (next gen))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 2 3) }));
+}
+
+#[test]
+fn suspends_between_calls() {
+    let result = eval_str(
+        "
+    (define log (list))
+
+    (define gen
+      (generator (lambda ()
+        (set log (cons \"started\" log))
+        (yield 1)
+        (set log (cons \"resumed\" log))
+        (yield 2))))
+
+    (list
+      (length log)
+      (next gen)
+      log
+      (next gen)
+      log)",
+    );
+
+    assert_eq!(
+        result,
+        Ok(lisp! { (0 1 ("started") 2 ("resumed" "started")) })
+    );
+}
+
+#[test]
+fn next_after_exhaustion() {
+    let result = eval_str(
+        "
+    (define gen
+      (generator (lambda ()
+        (yield 1))))
+
+    (list
+      (next gen)
+      (is_exhausted gen)
+      (next gen)
+      (is_exhausted gen)
+      (next gen))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 F NIL T NIL) }));
+}
+
+#[test]
+fn infinite_generator_with_local_loop() {
+    let result = eval_str(
+        "
+    (define naturals
+      (generator (lambda ()
+        (defun loop (n)
+          (yield n)
+          (loop (+ n 1)))
+        (loop 0))))
+
+    (map (lambda (_) (next naturals)) (range 0 5))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (0 1 2 3 4) }));
+}
+
+#[test]
+fn long_running_generator() {
+    let result = eval_str(
+        "
+    (define counter
+      (generator (lambda (limit)
+        (defun loop (n)
+          (if (< n limit)
+            (begin
+              (yield n)
+              (loop (+ n 1)))))
+        (loop 0))
+        1000))
+
+    (length (gen_to_list counter))",
+    );
+
+    assert_eq!(result, Ok(lisp! { 1000 }));
+}
+
+#[test]
+fn yield_inside_special_forms() {
+    let result = eval_str(
+        "
+    (define gen
+      (generator (lambda ()
+        (let ((a (yield 1)))
+          (cond
+            ((yield 2) (yield 3))
+            (T (if (or F (yield 4)) (yield 5)))))
+        (yield 6))))
+
+    (gen_to_list gen)",
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 2 4 6) }));
+}
+
+#[test]
+fn yield_inside_loops_and_bindings() {
+    let result = eval_str(
+        "
+    (define gen
+      (generator (lambda ()
+        (let_values (((a b) (values (yield 1) 3))
+                     ((c) (yield b)))
+          (yield (for ((x (list 1 2 3)) (y (list (yield (* x 10)))))
+                   (when (not (yield x)))
+                   (list x y)))))))
+
+    (gen_to_list gen)",
+    );
+
+    assert_eq!(
+        result,
+        Ok(lisp! { (1 3 10 1 20 2 30 3 ((1 NIL) (2 NIL) (3 NIL))) })
+    );
+}
+
+#[test]
+fn yield_inside_do() {
+    let result = eval_str(
+        "
+    (define gen
+      (generator (lambda ()
+        (yield
+          (do ((i 0 (+ i 1))
+               (seen (list) (cons (yield i) seen))
+               (last 0))
+              ((== i 3) (yield last) (length seen))
+            (set last (* i 10)))))))
+
+    (gen_to_list gen)",
+    );
+
+    assert_eq!(result, Ok(lisp! { (0 1 2 20 3) }));
+}
+
+#[test]
+fn yield_inside_macros_and_case_lambdas() {
+    let result = eval_str(
+        "
+    (defmacro twice (expr) '(begin ,expr ,expr))
+
+    (define either
+      (case_lambda
+        ((x) (yield x))
+        ((x y) (yield x) (yield y))))
+
+    (define gen
+      (generator (lambda ()
+        (twice (yield 1))
+        (either 2)
+        (either 3 4)
+        (yield ,5))))
+
+    (gen_to_list gen)",
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 1 2 3 4 5) }));
+}
+
+#[test]
+fn yield_inside_quote_or_promise() {
+    assert_eq!(
+        eval_str("(next (generator (lambda () (yield '(1 ,(yield 2))))))"),
+        Err(RuntimeError::new(
+            "\"yield\" can't be used in a comma inside a quote".to_owned()
+        ))
+    );
+
+    assert_eq!(
+        eval_str("(next (generator (lambda () (force (delay (yield 1))))))"),
+        Err(RuntimeError::new(
+            "\"yield\" can only be used inside a generator".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn next_tells_nil_from_exhaustion() {
+    let result = eval_str(
+        "
+    (define gen
+      (generator (lambda ()
+        (yield NIL))))
+
+    (defun next_list (gen)
+      (let_values (((item ok) (next gen)))
+        (list item ok)))
+
+    (list (next_list gen) (next_list gen))",
+    );
+
+    assert_eq!(result, Ok(lisp! { ((NIL T) (NIL F)) }));
+}
+
+#[test]
+fn spread_arguments_inside_generator() {
+    // the generator evaluates calls a step at a time, so it splices them itself
+    let result = eval_str(
+        "
+    (define gen
+      (generator (lambda ()
+        (define xs (list 2 3))
+        (yield (list 1 @xs @(list (yield 4)) 5)))))
+
+    (list (next gen) (next gen))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (4 (1 2 3 NIL 5)) }));
+
+    assert_eq!(
+        eval_str(
+            "
+    (define gen (generator (lambda () (yield (list 6 @7)))))
+    (next gen)"
+        ),
+        Err(RuntimeError::new(
+            "\"splice\" requires argument 1 to be a list; got 7".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn empty_bodies_inside_generator() {
+    let result = eval_str(
+        "
+    (defun nothing ())
+
+    (define gen
+      (generator (lambda ()
+        (yield (begin))
+        (yield (nothing))
+        (yield (let ((x 1))))
+        (yield (cond (T)))
+        (yield (cond (T (yield 1) 2))))))
+
+    (gen_to_list gen)",
+    );
+
+    assert_eq!(result, Ok(lisp! { (NIL NIL NIL NIL 1 2) }));
+}
+
+#[test]
+fn gen_to_list() {
+    let result = eval_str(
+        "
+    (defun squares (lst)
+      (generator (lambda ()
+        (map (lambda (n) (* n n)) lst)
+        (yield (car lst))
+        (yield (* (car lst) (car lst))))))
+
+    (gen_to_list (squares (list 3 4)))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (3 9) }));
+}
+
+#[test]
+fn error_inside_generator() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    let result = eval_str_in(
+        env.clone(),
+        "
+    (define gen
+      (generator (lambda ()
+        (yield 1)
+        (car (list))
+        (yield 2))))
+
+    (next gen)
+    (next gen)",
+    );
+
+    assert_eq!(
+        result,
+        Err(RuntimeError::new(
+            "Attempted to apply car on nil".to_owned()
+        ))
+    );
+
+    assert_eq!(
+        eval_str_in(env, "(list (is_exhausted gen) (next gen))"),
+        Ok(lisp! { (T NIL) })
+    );
+}
+
+#[test]
+fn yield_outside_generator() {
+    assert_eq!(
+        eval_str("(yield 1)"),
+        Err(RuntimeError::new(
+            "\"yield\" can only be used inside a generator".to_owned()
+        ))
+    );
+
+    // callbacks run by native functions aren't suspendable
+    assert_eq!(
+        eval_str(
+            "
+    (gen_to_list
+      (generator (lambda ()
+        (map (lambda (x) (yield x)) (list 1 2)))))"
+        ),
+        Err(RuntimeError::new(
+            "\"yield\" can only be used inside a generator".to_owned()
+        ))
+    );
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    eval_str_in(Rc::new(RefCell::new(default_env())), source)
+}
+
+#[cfg(test)]
+fn eval_str_in(
+    env: Rc<RefCell<rust_lisp::model::Env>>,
+    source: &str,
+) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
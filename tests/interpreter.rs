@@ -1,9 +1,10 @@
 use rust_lisp::{
     default_env,
-    interpreter::eval,
-    lisp,
-    model::{FloatType, IntType, RuntimeError, Symbol, Value},
+    interpreter::{eval, eval_block, eval_each},
+    lisp, lisp_list,
+    model::{FloatType, IntType, List, RuntimeError, Symbol, Value},
     parser::parse,
+    sym,
 };
 use std::{cell::RefCell, rc::Rc};
 
@@ -42,6 +43,36 @@ fn eval_nil() {
     assert_eq!(result, Value::from(true));
 }
 
+#[test]
+fn eval_empty_combination() {
+    // `()` isn't a call, it's NIL
+    assert_eq!(eval_str("()"), Value::NIL);
+    assert_eq!(
+        eval_str("(list () (begin ()) ((lambda () ())))"),
+        lisp! { (() () ()) }
+    );
+}
+
+#[test]
+fn call_nil() {
+    for source in [
+        "(nil 1 2)",
+        "(() 1)",
+        "((car (list ())) 1)",
+        "(let ((g ())) (g))",
+    ] {
+        let ast = parse(source).next().unwrap().unwrap();
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(
+            eval(env, &ast),
+            Err(RuntimeError::new(String::from("NIL is not callable"))),
+            "{}",
+            source
+        );
+    }
+}
+
 #[test]
 fn eval_quote_1() {
     let result = eval_str("(quote \"stuff\")");
@@ -74,9 +105,10 @@ fn eval_quote_tick_atom() {
 fn eval_quote_tick_symbol() {
     let result = eval_str("(nth 0 (list 'foo))");
 
-    assert_eq!(result, Value::Symbol(Symbol(String::from("foo"))));
+    assert_eq!(result, Value::Symbol(Symbol::from("foo")));
 }
 
+#[cfg(feature = "std")] // uses `print`
 #[test]
 fn eval_let() {
     let result = eval_str(
@@ -207,6 +239,7 @@ fn eval_merge_sort() {
     assert_eq!(result, lisp! { (0 1 2 5 5 7) });
 }
 
+#[cfg(feature = "std")] // uses `print`
 #[test]
 fn tail_call_test() {
     let result = eval_str(
@@ -226,6 +259,111 @@ fn tail_call_test() {
     assert_eq!(result, Value::from(Into::<IntType>::into(0)));
 }
 
+#[test]
+fn tail_call_through_and_or() {
+    let result = eval_str(
+        "
+    (begin
+      (defun count_down (n)
+        (or (== n 0)
+            (and (> n 0) (count_down (- n 1)))))
+
+      (count_down 100000))",
+    );
+
+    assert_eq!(result, Value::True);
+}
+
+#[test]
+fn tail_call_through_computed_function() {
+    // the `if` picking the function isn't in the tail position, but the call
+    // of whatever it picks is
+    let result = eval_str(
+        "
+    (begin
+      (defun finish (n total) total)
+      (defun count_down (n total)
+        ((if (== n 0) finish count_down) (- n 1) (+ total 1)))
+
+      (count_down 100000 0))",
+    );
+
+    assert_eq!(result, Value::from(Into::<IntType>::into(100001)));
+}
+
+#[test]
+fn tail_call_through_function_returned_by_call() {
+    // the call that picks the function runs to completion, rather than being
+    // handed back as a tail call in place of the function
+    let result = eval_str(
+        "
+    (begin
+      (defun is_even (n) (if (== n 0) T ((pick n) (- n 1))))
+      (defun is_odd (n) (if (== n 0) F ((cond ((> n 0) (pick_even)) (T car)) (- n 1))))
+      (defun pick (n) (if (> n 0) is_odd car))
+      (defun pick_even () is_even)
+
+      (list (is_even 100000) (is_even 7) (is_odd 7)))",
+    );
+
+    assert_eq!(result, lisp! { (T F T) });
+}
+
+#[test]
+fn spread_arguments() {
+    let result = eval_str(
+        "
+    (begin
+      (define xs (list 4 5))
+      (list 1 @(list 2 3) @xs @() 9))",
+    );
+
+    assert_eq!(result, lisp! { (1 2 3 4 5 9) });
+}
+
+#[test]
+fn spread_arguments_in_tail_calls() {
+    // the tail call is handed back with its arguments already spliced
+    let result = eval_str(
+        "
+    (begin
+      (defun count_down (n total)
+        (if (== n 0)
+          total
+          (count_down @(list (- n 1)) @(list (+ total 1)))))
+
+      (defun g (a b c d) (list a b c d))
+      (defun h (xs) (g 0 @xs 3))
+
+      (list (count_down 100000 0) (h (list 1 2))))",
+    );
+
+    assert_eq!(result, lisp! { (100000 (0 1 2 3)) });
+}
+
+#[test]
+fn spread_argument_errors() {
+    for (source, msg) in [
+        (
+            "(list 1 @2)",
+            "\"splice\" requires argument 1 to be a list; got 2",
+        ),
+        (
+            "((lambda (xs) (list 0 @xs)) 3)",
+            "\"splice\" requires argument 1 to be a list; got 3",
+        ),
+        (
+            "@(list 1)",
+            "\"splice\" can only be used as an argument of a function call",
+        ),
+    ] {
+        let ast = parse(source).next().unwrap().unwrap();
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(eval(env, &ast), Err(RuntimeError::new(msg.to_owned())));
+    }
+}
+
 #[test]
 fn rest_parameters_test() {
     let result = eval_str(
@@ -240,6 +378,299 @@ fn rest_parameters_test() {
     assert_eq!(result, lisp! { (3 4 5) });
 }
 
+#[test]
+fn rest_parameter_after_fixed_ones() {
+    let result = eval_str(
+        "
+    (begin
+      (defun foo (a b ...)
+        (list a b ...))
+
+      (list (foo 1 2) (foo 1 2 3 4)))",
+    );
+
+    assert_eq!(result, lisp! { ((1 2 ()) (1 2 (3 4))) });
+
+    // every fixed parameter needs an argument
+    let ast = parse("((lambda (a b ...) ...) 1)").next().unwrap().unwrap();
+    let env = Rc::new(RefCell::new(default_env()));
+
+    assert_eq!(
+        eval(env, &ast),
+        Err(RuntimeError::new(String::from(
+            "Expected at least 2 arguments, but got 1"
+        )))
+    );
+}
+
+#[test]
+fn named_rest_parameter() {
+    let result = eval_str(
+        "
+    (begin
+      (defun foo (a &rest others)
+        (list a others))
+
+      (list (foo 1 2 3) (foo 1) ((lambda (&rest all) all) 4 5)))",
+    );
+
+    assert_eq!(result, lisp! { ((1 (2 3)) (1 ()) (4 5)) });
+}
+
+#[test]
+fn rest_parameter_needs_one_name() {
+    for source in ["(defun foo (a &rest) a)", "(lambda (&rest b c) b)"] {
+        let ast = parse(source).next().unwrap().unwrap();
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(
+            eval(env, &ast),
+            Err(RuntimeError::new(String::from(
+                "Expected exactly one arg name after &rest"
+            )))
+        );
+    }
+}
+
+#[test]
+fn too_few_arguments() {
+    let cases = [
+        ("((lambda (a) a))", "Expected 1 arguments, but got 0"),
+        (
+            "((lambda (a b &rest c) c) 1)",
+            "Expected at least 2 arguments, but got 1",
+        ),
+        (
+            "((lambda (a b ...) a))",
+            "Expected at least 2 arguments, but got 0",
+        ),
+    ];
+
+    for (source, msg) in cases {
+        let ast = parse(source).next().unwrap().unwrap();
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(eval(env, &ast), Err(RuntimeError::new(String::from(msg))));
+    }
+}
+
+#[test]
+fn case_lambda() {
+    let result = eval_str(
+        "
+    (begin
+      (define area
+        (case_lambda
+          ((side) (* side side))
+          ((width height) (* width height))
+          ((width height &rest more) (list width height more))))
+
+      (list (area 3) (area 2 5) (area 1 2 3 4)))",
+    );
+
+    assert_eq!(result, lisp! { (9 10 (1 2 (3 4))) });
+}
+
+#[test]
+fn case_lambda_prefers_exact_arity() {
+    let result = eval_str(
+        "
+    (begin
+      (define pick
+        (case_lambda
+          ((&rest all) (list (quote rest) all))
+          ((x) (list (quote one) x))))
+
+      (list (pick) (pick 1) (pick 1 2)))",
+    );
+
+    assert_eq!(result, lisp! { ((rest ()) (one 1) (rest (1 2))) });
+}
+
+#[test]
+fn case_lambda_recursion() {
+    // the clauses call each other (and themselves) as tail calls
+    let result = eval_str(
+        "
+    (begin
+      (define count
+        (case_lambda
+          ((n) (count n 0))
+          ((n total) (if (== n 0) total (count (- n 1) (+ total 1))))))
+
+      (count 100000))",
+    );
+
+    assert_eq!(result, Value::from(Into::<IntType>::into(100_000)));
+}
+
+#[test]
+fn case_lambda_errors() {
+    let cases = [
+        (
+            "((case_lambda ((a) a) ((a b c) a)) 1 2)",
+            "No case_lambda clause takes 2 arguments",
+        ),
+        (
+            "(case_lambda (a a))",
+            "Expected a (params body...) clause in case_lambda, found (a a)",
+        ),
+        ("(case_lambda ((a a) a))", "Duplicate parameter names: a"),
+    ];
+
+    for (source, msg) in cases {
+        let ast = parse(source).next().unwrap().unwrap();
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(
+            eval(env, &ast),
+            Err(RuntimeError::new(String::from(msg))),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn for_single_clause() {
+    let result = eval_str("(for ((x (list 1 2 3))) (* x x))");
+
+    assert_eq!(result, lisp! { (1 4 9) });
+}
+
+#[test]
+fn for_nested_clauses() {
+    let result = eval_str(
+        "
+    (for ((x (list 1 2)) (y (list \"a\" \"b\" \"c\")))
+      (list x y))",
+    );
+
+    assert_eq!(
+        result,
+        lisp! { ((1 "a") (1 "b") (1 "c") (2 "a") (2 "b") (2 "c")) }
+    );
+
+    // later clauses can use the earlier ones' variables
+    let result = eval_str("(for ((x (range 1 4)) (y (range 0 x))) (list x y))");
+
+    assert_eq!(result, lisp! { ((1 0) (2 0) (2 1) (3 0) (3 1) (3 2)) });
+}
+
+#[test]
+fn for_guard_clauses() {
+    let result = eval_str(
+        "
+    (for ((x (list -1 0 1 2)) (y (range 0 3)))
+      (when (> x 0))
+      (when (!= x y))
+      (* x y))",
+    );
+
+    assert_eq!(result, lisp! { (0 2 0 2) });
+}
+
+#[test]
+fn for_empty_clause() {
+    assert_eq!(eval_str("(for ((x (list))) (* x x))"), lisp! { () });
+    assert_eq!(
+        eval_str("(for ((x (list 1 2)) (y (list))) (* x y))"),
+        lisp! { () }
+    );
+}
+
+#[test]
+fn for_binds_each_item_separately() {
+    let result = eval_str(
+        "
+    (map
+      (lambda (get) (get))
+      (for ((x (list 1 2 3))) (lambda () x)))",
+    );
+
+    assert_eq!(result, lisp! { (1 2 3) });
+}
+
+#[test]
+fn for_requires_lists() {
+    let ast = parse("(for ((x 12)) x)").next().unwrap().unwrap();
+    let env = Rc::new(RefCell::new(default_env()));
+
+    assert_eq!(
+        eval(env, &ast),
+        Err(RuntimeError::new(String::from(
+            "\"for\" requires the value for x to be a list; got 12"
+        )))
+    );
+}
+
+#[test]
+fn do_fibonacci() {
+    // `a` and `b` are stepped at the same time, so (+ a b) sees the old `a`
+    let result = eval_str(
+        "
+    (do ((i 0 (+ i 1))
+         (a 0 b)
+         (b 1 (+ a b)))
+        ((== i 10) (list i a b)))",
+    );
+
+    assert_eq!(result, lisp! { (10 55 89) });
+}
+
+#[test]
+fn do_body_and_unstepped_variables() {
+    let result = eval_str(
+        "
+    (do ((i 0 (+ i 1))
+         (is_even T (not is_even))
+         (evens (list))
+         (closures (list) (cons (lambda () i) closures)))
+        ((== i 5) (list evens (map (lambda (get) (get)) closures)))
+      (if is_even
+        (set evens (cons i evens))))",
+    );
+
+    // each closure keeps the `i` from its own iteration
+    assert_eq!(result, lisp! { ((4 2 0) (4 3 2 1 0)) });
+
+    // without any result forms, it's NIL
+    assert_eq!(eval_str("(do ((i 0 (+ i 1))) ((> i 3)))"), lisp! { () });
+}
+
+#[test]
+fn do_many_iterations() {
+    let result = eval_str("(do ((i 0 (+ i 1)) (total 0 (+ total 2))) ((== i 100000) total))");
+
+    assert_eq!(result, Value::from(Into::<IntType>::into(200_000)));
+}
+
+#[test]
+fn do_errors() {
+    let cases = [
+        (
+            "(do (i) (T))",
+            "Expected (name init step) clause in do, found i",
+        ),
+        (
+            "(do ((i 0 1 2)) (T))",
+            "Expected (name init step) clause in do, found (i 0 1 2)",
+        ),
+        ("(do ((i 0) (i 1)) (T))", "Duplicate do variable names: i"),
+        (
+            "(do ((i 0)) ())",
+            "Expected (test result...) clause in do, found NIL",
+        ),
+    ];
+
+    for (source, msg) in cases {
+        let ast = parse(source).next().unwrap().unwrap();
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(eval(env, &ast), Err(RuntimeError::new(String::from(msg))));
+    }
+}
+
 #[test]
 fn calling_empty_fun() {
     let result = eval_str(
@@ -264,6 +695,190 @@ fn closure() {
     assert_eq!(result, lisp! {(3 4 5 6 7)});
 }
 
+#[test]
+fn closure_counters() {
+    // each counter gets its own `n`, which persists between its calls
+    let result = eval_str(
+        "
+    (begin
+      (defun make_counter ()
+        (let ((n 0))
+          (lambda () (set n (+ n 1)) n)))
+
+      (define first (make_counter))
+      (define second (make_counter))
+
+      (list (first) (first) (second) (first) (second)))",
+    );
+
+    assert_eq!(result, lisp! { (1 2 1 3 2) });
+
+    // closures made in the same scope share it
+    let result = eval_str(
+        "
+    (begin
+      (define counter
+        (let ((n 0))
+          (list
+            (lambda () (set n (+ n 1)) n)
+            (lambda () n))))
+
+      ((car counter))
+      ((car counter))
+      ((car (cdr counter))))",
+    );
+
+    assert_eq!(result, Value::from(Into::<IntType>::into(2)));
+}
+
+#[test]
+fn internal_defines() {
+    // like `letrec*`, the defines at the start of a body can refer to each
+    // other, whatever order they're in
+    let result = eval_str(
+        "
+    (begin
+      (defun parity (n)
+        (define is_even (lambda (n) (if (== n 0) T (is_odd (- n 1)))))
+        (define is_odd (lambda (n) (if (== n 0) F (is_even (- n 1)))))
+        (list (is_even n) (is_odd n)))
+
+      (parity 7))",
+    );
+
+    assert_eq!(result, lisp! { (F T) });
+
+    // but using one before it's been defined is an error, rather than finding
+    // the outer definition
+    let env = Rc::new(RefCell::new(default_env()));
+    let results = parse(
+        "
+    (define limit 100)
+
+    (defun clamp (n)
+      (define low (- limit))
+      (define limit 10)
+      (max low (min n limit)))
+
+    (clamp 50)
+
+    (let ((n 50))
+      (define high limit)
+      (define limit 10)
+      high)
+
+    (let ()
+      (define old (set limit 1))
+      (define limit 10))
+
+    limit",
+    )
+    .map(|expr| eval(env.clone(), &expr.unwrap()))
+    .collect::<Vec<_>>();
+
+    let used_early = Err(RuntimeError::new(
+        "\"limit\" was used before it was defined".to_owned(),
+    ));
+
+    assert_eq!(results[2], used_early);
+    assert_eq!(results[3], used_early);
+    assert_eq!(
+        results[4],
+        Err(RuntimeError::new(
+            "Tried to set value of \"limit\" before it was defined".to_owned()
+        ))
+    );
+    assert_eq!(results[5], Ok(Value::from(Into::<IntType>::into(100))));
+}
+
+#[test]
+fn internal_defines_in_lambdas_and_lets() {
+    // the same goes for `lambda` and `let` bodies: a function can call one
+    // defined after it, and the names don't leak out of the body
+    let env = Rc::new(RefCell::new(default_env()));
+    let results = parse(
+        "
+    (define count_down
+      (lambda (n)
+        (define step (lambda (n acc) (if (== n 0) acc (again (- n 1) (cons n acc)))))
+        (define again (lambda (n acc) (step n acc)))
+        (step n (list))))
+
+    (count_down 3)
+
+    (let ((n 4))
+      (define is_even (lambda (n) (if (== n 0) T (is_odd (- n 1)))))
+      (define is_odd (lambda (n) (if (== n 0) F (is_even (- n 1)))))
+      (list (is_even n) (is_odd n)))
+
+    step
+
+    is_odd",
+    )
+    .map(|expr| eval(env.clone(), &expr.unwrap()))
+    .collect::<Vec<_>>();
+
+    assert_eq!(results[1], Ok(lisp! { (1 2 3) }));
+    assert_eq!(results[2], Ok(lisp! { (T F) }));
+    for (result, name) in results[3..].iter().zip(["step", "is_odd"]) {
+        assert_eq!(
+            result,
+            &Err(RuntimeError::new(format!("\"{}\" is not defined", name)))
+        );
+    }
+}
+
+#[test]
+fn special_forms_cant_be_redefined() {
+    // a definition would be ignored wherever the form is called, so it's an
+    // error rather than a surprise
+    let env = Rc::new(RefCell::new(default_env()));
+    let results = parse(
+        "
+    (define if 5)
+    (set cond (lambda (x) x))
+    (defun let (x) x)
+    (defmacro quote (x) x)
+    (define_syntax and (syntax_rules () ((_ a) a)))
+    (let () (define lambda 1) 2)
+    (if T 1 2)
+    if",
+    )
+    .map(|expr| eval(env.clone(), &expr.unwrap()))
+    .collect::<Vec<_>>();
+
+    let redefines = |keyword: &str, name: &str| {
+        Err(RuntimeError::new(format!(
+            "\"{}\" can't redefine the special form \"{}\"",
+            keyword, name
+        )))
+    };
+
+    assert_eq!(results[0], redefines("define", "if"));
+    assert_eq!(results[1], redefines("set", "cond"));
+    assert_eq!(results[2], redefines("defun", "let"));
+    assert_eq!(results[3], redefines("defmacro", "quote"));
+    assert_eq!(results[4], redefines("define_syntax", "and"));
+    assert_eq!(results[5], redefines("define", "lambda"));
+
+    // so `if` is still the special form when it's called, and isn't a value
+    assert_eq!(results[6], Ok(Value::from(Into::<IntType>::into(1))));
+    assert_eq!(
+        results[7],
+        Err(RuntimeError::new(
+            "\"if\" is a special form, which can only be called".to_owned()
+        ))
+    );
+
+    // special forms registered on the context can't be redefined either
+    env.borrow()
+        .context()
+        .register_special_form("unless", |_env, _args| Ok(Value::NIL))
+        .unwrap();
+    let ast = parse("(define unless 1)").next().unwrap().unwrap();
+    assert_eq!(eval(env, &ast), redefines("define", "unless"));
+}
+
 #[test]
 fn lambda_err() {
     let ast = parse(
@@ -278,12 +893,63 @@ fn lambda_err() {
 
     assert_eq!(
         result,
-        Err(RuntimeError {
-            msg: String::from("Expected list of arg names, but arg 0 is a F")
-        })
+        Err(RuntimeError::new(String::from(
+            "Expected a symbol for parameter 1, found F"
+        )))
     );
 }
 
+#[test]
+fn invalid_parameter_names() {
+    let cases = [
+        ("(lambda (x x) x)", "Duplicate parameter names: x"),
+        ("(defun g (a b a c b) a)", "Duplicate parameter names: a, b"),
+        ("(lambda (a &key (a 1)) a)", "Duplicate parameter names: a"),
+        (
+            "(defmacro m (quote if) quote)",
+            "Special forms can't be used as parameter names: quote, if",
+        ),
+        (
+            "(lambda (x \"y\") x)",
+            "Expected a symbol for parameter 2, found \"y\"",
+        ),
+        (
+            "(let ((x 1) (y 2) (x 3)) x)",
+            "Duplicate let binding names: x",
+        ),
+        (
+            "(let ((cond 1)) cond)",
+            "Special forms can't be used as let binding names: cond",
+        ),
+    ];
+
+    for (source, msg) in cases {
+        let ast = parse(source).next().unwrap().unwrap();
+        let env = Rc::new(RefCell::new(default_env()));
+
+        assert_eq!(
+            eval(env, &ast),
+            Err(RuntimeError::new(msg.to_owned())),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn shadowing_outer_bindings() {
+    let result = eval_str(
+        "
+    (begin
+      (define x 1)
+      (defun inner (x) (let ((x (* x 10))) x))
+
+      (list (inner 2) (let ((x 3)) ((lambda (x) x) 4)) x))",
+    );
+
+    assert_eq!(result, lisp! { (20 4 1) });
+}
+
 #[test]
 fn quote_comma() {
     let result = eval_str(
@@ -310,6 +976,75 @@ fn defmacro() {
     assert_eq!(result, lisp! { (3 3 3) })
 }
 
+#[test]
+fn defmacro_calling_function() {
+    let result = eval_str(
+        "
+    (begin
+      (defmacro foo (x)
+        (list 'quote x))
+      
+      (foo 3))
+  ",
+    );
+
+    assert_eq!(result, lisp! { 3 })
+}
+
+#[test]
+fn defmacro_auto_gensym() {
+    let result = eval_str(
+        "
+    (begin
+      (defmacro swap (a b)
+        '(let ((tmp# ,a))
+           (set ,a ,b)
+           (set ,b tmp#)))
+
+      (define tmp 1)
+      (define other 2)
+      (swap tmp other)
+      (list tmp other))
+  ",
+    );
+
+    assert_eq!(result, lisp! { (2 1) });
+
+    // the same within one expansion, and different in the next
+    let result = eval_str(
+        "
+    (begin
+      (defmacro names () ''(x# x# y#))
+      (define first (names))
+      (define second (names))
+      (list
+        (== (nth 0 first) (nth 1 first))
+        (== (nth 0 first) (nth 2 first))
+        (== (nth 0 first) (nth 0 second))))
+  ",
+    );
+
+    assert_eq!(result, lisp! { (T F F) });
+}
+
+#[test]
+fn defmacro_definition_env() {
+    // the caller's own `list` doesn't replace the one the macro expands into,
+    // but the arguments still mean what they do where it's called
+    let result = eval_str(
+        "
+    (begin
+      (defmacro pair (a b)
+        '(list ,a ,b))
+
+      (let ((list (lambda (a b) (+ a b))))
+        (cons (list 3 4) (pair 1 (list 1 1)))))
+  ",
+    );
+
+    assert_eq!(result, lisp! { (7 1 2) });
+}
+
 #[test]
 fn or_expressions() {
     let result = eval_str(
@@ -363,6 +1098,76 @@ fn short_circuit() {
     assert_eq!(result, lisp! { 0 })
 }
 
+#[test]
+fn empty_bodies() {
+    let result = eval_str(
+        "
+    (begin
+      (defun nothing ())
+
+      (list
+        (begin)
+        (nothing)
+        ((lambda (x)) 1)
+        (let ((x 1)))
+        (cond (F 1) (T))
+        (cond (F 1) (T 2 3))))",
+    );
+
+    assert_eq!(result, lisp! { (NIL NIL NIL NIL NIL 3) })
+}
+
+#[test]
+fn empty_programs() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    assert_eq!(eval_block(env.clone(), std::iter::empty()), Ok(Value::NIL));
+
+    for source in ["", "   \n\t ", ";; nothing but comments\n  ;; here\n"] {
+        let forms = parse(source).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(forms, vec![]);
+
+        assert_eq!(eval_block(env.clone(), forms.into_iter()), Ok(Value::NIL));
+    }
+}
+
+#[test]
+fn errors_stop_blocks() {
+    let env = Rc::new(RefCell::new(default_env()));
+    let forms = parse("(define x 1) (car 12) (define y 2)").map(|expr| expr.unwrap());
+
+    // the clauses before the error have already run, and the ones after it
+    // never do
+    assert!(eval_block(env.clone(), forms).is_err());
+    assert_eq!(env.borrow().get(&Symbol::from("x")), Some(Value::from(1)));
+    assert_eq!(env.borrow().get(&Symbol::from("y")), None);
+
+    // the same goes for the body of a function, and of a begin
+    let result = eval(
+        env.clone(),
+        &parse(
+            "(begin
+              (defun body ()
+                (set x 2)
+                (undefined_function)
+                (set x 3))
+              (body))",
+        )
+        .next()
+        .unwrap()
+        .unwrap(),
+    );
+    assert_eq!(
+        result.map_err(|err| err.msg),
+        Err("\"undefined_function\" is not defined".to_owned())
+    );
+    assert_eq!(env.borrow().get(&Symbol::from("x")), Some(Value::from(2)));
+
+    let result = eval(env.clone(), &lisp! { (begin (set x 4) (car 12) (set x 5)) });
+    assert!(result.is_err());
+    assert_eq!(env.borrow().get(&Symbol::from("x")), Some(Value::from(4)));
+}
+
 #[test]
 fn native_closure() {
     let my_state = Rc::new(RefCell::new(0));
@@ -393,11 +1198,180 @@ fn native_closure() {
     assert_eq!(*my_state.borrow(), 3);
 }
 
+#[test]
+fn build_list_from_rust() {
+    let list = List::from_vec(vec![Value::from("a"), sym!("b"), lisp_list![1, 2]]);
+
+    assert_eq!(list.len(), 3);
+    assert!(!list.is_empty());
+    assert!(List::NIL.is_empty());
+    assert_eq!(list.get(1), Some(sym!("b")));
+    assert_eq!(list.get(3), None);
+    assert_eq!(Value::List(list), lisp! { ("a" b (1 2)) });
+}
+
+#[test]
+fn from_vec_preserves_order() {
+    let values = (0..100_000)
+        .map(|n| Value::from(n.to_string()))
+        .collect::<Vec<Value>>();
+
+    let list = List::from_vec(values.clone());
+    assert_eq!(list.len(), values.len());
+    assert!(list.into_iter().eq(values.iter().cloned()));
+
+    let collected = values.iter().collect::<List>();
+    assert!(collected.into_iter().eq(values.into_iter()));
+
+    assert_eq!(List::from_vec(vec![]), List::NIL);
+}
+
+#[test]
+fn reused_cons_cells() {
+    let item = |n: usize| Value::from(n.to_string());
+
+    let kept_tails = (0..1000)
+        .map(|n| {
+            let list = List::from_vec((0..10).map(|m| item(n * 10 + m)).collect());
+            let tail = list.cdr().cdr();
+            drop(List::from_vec(vec![Value::List(list)]));
+
+            tail
+        })
+        .collect::<Vec<List>>();
+
+    // cells freed above get reused here, but none that are still in use
+    let fresh = (0..1000)
+        .map(|n| List::from_vec(vec![item(n)]).cons(sym!("head")))
+        .collect::<Vec<List>>();
+
+    for (n, tail) in kept_tails.iter().enumerate() {
+        assert!(tail.into_iter().eq((2..10).map(|m| item(n * 10 + m))));
+        assert!(fresh[n].into_iter().eq([sym!("head"), item(n)]));
+    }
+}
+
+#[test]
+fn eval_built_call() {
+    let env = Rc::new(RefCell::new(default_env()));
+    let expr = lisp_list![sym!("*"), 3, lisp_list![sym!("+"), 1, 3]];
+
+    assert_eq!(eval(env, &expr), Ok(Value::from(12)));
+}
+
+#[test]
+fn registered_special_forms() {
+    let env = default_env().into_shared();
+    let context = env.borrow().context().clone();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+
+    // a swap! that gets the names of the variables, rather than their values
+    let seen_in_form = seen.clone();
+    context
+        .register_special_form("swap", move |env, args| {
+            seen_in_form.borrow_mut().extend(args.iter().cloned());
+
+            let (Value::Symbol(a), Value::Symbol(b)) = (&args[0], &args[1]) else {
+                return Err(RuntimeError::new("\"swap\" requires two symbols"));
+            };
+            let old_a = eval(env.clone(), &args[0])?;
+            let old_b = eval(env.clone(), &args[1])?;
+            env.borrow_mut().set(a.clone(), old_b)?;
+            env.borrow_mut().set(b.clone(), old_a)?;
+
+            Ok(Value::NIL)
+        })
+        .unwrap();
+
+    let result = eval_block(
+        env.clone(),
+        parse("(define x 1) (define y 2) (swap x y) (list x y)").map(|expr| expr.unwrap()),
+    );
+    assert_eq!(result, Ok(lisp! { (2 1) }));
+    assert_eq!(*seen.borrow(), vec![sym!("x"), sym!("y")]);
+
+    // built-in forms still work around them, including tail calls
+    let result = eval_block(
+        env.clone(),
+        parse(
+            "(defun count_down (n) (if (== n 0) (quote done) (count_down (- n 1))))
+            (count_down 100000)",
+        )
+        .map(|expr| expr.unwrap()),
+    );
+    assert_eq!(result, Ok(sym!("done")));
+
+    // but they can only be replaced on purpose
+    assert!(context
+        .register_special_form("if", |_, _| Ok(Value::NIL))
+        .is_err());
+    assert_eq!(eval(env.clone(), &lisp! { (if T 1 2) }), Ok(lisp! { 1 }));
+
+    context.override_special_form("if", |_, _| Ok(Value::from("overridden")));
+    assert_eq!(
+        eval(env.clone(), &lisp! { (if T 1 2) }),
+        Ok(Value::from("overridden"))
+    );
+}
+
 #[cfg(test)]
+#[test]
+fn eval_each_form() {
+    let env = Rc::new(RefCell::new(default_env()));
+    let source = "(define x 1) (car x) (set x (+ x 1)) x";
+    let forms = || parse(source).map(|expr| expr.unwrap());
+
+    // stopping at the first error
+    let mut results = Vec::new();
+    for result in eval_each(env.clone(), forms()) {
+        let failed = result.is_err();
+        results.push(result);
+
+        if failed {
+            break;
+        }
+    }
+
+    assert_eq!(
+        results,
+        vec![
+            Ok(Value::from(1)),
+            Err(RuntimeError::new(
+                "\"car\" requires argument 1 to be a list; got 1".to_owned()
+            )),
+        ]
+    );
+    assert_eq!(eval(env.clone(), &sym!("x")), Ok(Value::from(1)));
+
+    // or carrying on past it
+    let results = eval_each(env, forms()).collect::<Vec<_>>();
+
+    assert_eq!(results.len(), 4);
+    assert!(results[1].is_err());
+    assert_eq!(results[2], Ok(Value::from(2)));
+    assert_eq!(results[3], Ok(Value::from(2)));
+}
+
+#[test]
+fn eval_each_tail_calls() {
+    // every form is in its own tail position, so a deep tail-recursive call
+    // in the middle of a block works like one at the end
+    let env = Rc::new(RefCell::new(default_env()));
+    let source = "
+    (defun count_down (n) (if (== n 0) 0 (count_down (- n 1))))
+    (count_down 100000)
+    (count_down 100000)";
+    let forms = parse(source).map(|expr| expr.unwrap());
+
+    let results = eval_each(env, forms).collect::<Vec<_>>();
+
+    assert_eq!(results[1..], [Ok(Value::from(0)), Ok(Value::from(0))]);
+}
+
 fn eval_str(source: &str) -> Value {
     let ast = parse(source).next().unwrap().unwrap();
     let env = Rc::new(RefCell::new(default_env()));
-    return eval(env, &ast).unwrap();
+    eval(env, &ast).unwrap()
 }
 
 // #[bench]
@@ -0,0 +1,266 @@
+#![cfg(feature = "io")]
+
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp, lisp_list,
+    model::{List, RuntimeError, Symbol, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, path::Path, rc::Rc};
+use tempfile::tempdir;
+
+#[test]
+fn write_and_read() {
+    let dir = tempdir().unwrap();
+
+    let result = eval_in(
+        dir.path(),
+        "
+    (define path (string_append dir \"/notes.txt\"))
+
+    (list
+      (file_exists path)
+      (write_file path \"first\")
+      (append_file path \", second\")
+      (read_file path)
+      (file_exists path)
+      (delete_file path)
+      (file_exists path))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (F NIL NIL "first, second" T NIL F) }));
+}
+
+#[test]
+fn append_creates_files() {
+    let dir = tempdir().unwrap();
+
+    eval_in(
+        dir.path(),
+        "(append_file (string_append dir \"/log\") \"one\")",
+    )
+    .unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("log")).unwrap(),
+        "one"
+    );
+}
+
+#[test]
+fn bytes() {
+    let dir = tempdir().unwrap();
+
+    let result = eval_in(
+        dir.path(),
+        "
+    (define path (string_append dir \"/data.bin\"))
+    (write_file path (list 0 104 105 255))
+    (append_file path (bytes_slice (read_file path :bytes T) 1 3))
+    (read_file path :bytes T)",
+    );
+
+    assert_eq!(result, Ok(Value::from(vec![0, 104, 105, 255, 104, 105])));
+    assert_eq!(
+        std::fs::read(dir.path().join("data.bin")).unwrap(),
+        vec![0, 104, 105, 255, 104, 105]
+    );
+
+    assert_eq!(
+        eval_in(dir.path(), "(write_file (string_append dir \"/x\") (list 1 256))"),
+        Err(RuntimeError::new("\"write_file\" requires a list to only contain bytes (integers from 0 to 255); got (1 256)".to_owned()))
+    );
+}
+
+#[test]
+fn directories() {
+    let dir = tempdir().unwrap();
+
+    let result = eval_in(
+        dir.path(),
+        "
+    (create_dir (string_append dir \"/sub/nested\"))
+    (create_dir (string_append dir \"/sub/nested\"))
+    (write_file (string_append dir \"/b.txt\") \"\")
+    (write_file (string_append dir \"/a.txt\") \"\")
+
+    (list (list_dir dir) (list_dir (string_append dir \"/sub\")))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (("a.txt" "b.txt" "sub") ("nested")) }));
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_file_names() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let dir = tempdir().unwrap();
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+
+    // not every filesystem allows names like this
+    if std::fs::write(dir.path().join(name), "").is_err() {
+        return;
+    }
+
+    assert_eq!(
+        eval_in(dir.path(), "(list_dir dir)"),
+        Ok(lisp_list!["caf\u{fffd}.txt"])
+    );
+}
+
+#[test]
+fn load() {
+    let dir = tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("lib.lisp"),
+        "(defun double (x) (* x 2))\n(define loaded (double 21))",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("broken.lisp"),
+        "(define before 1)\n(defun oops (x)\n  x\n",
+    )
+    .unwrap();
+
+    let result = eval_in(
+        dir.path(),
+        "
+    (list
+      (load (string_append dir \"/lib.lisp\"))
+      (double loaded))",
+    );
+    assert_eq!(result, Ok(lisp! { (42 84) }));
+
+    // a file that doesn't parse is reported with where it went wrong
+    let path = format!("{}/broken.lisp", dir.path().to_str().unwrap());
+    let result = eval_in(dir.path(), "(load (string_append dir \"/broken.lisp\"))");
+
+    assert_eq!(
+        result,
+        Err(RuntimeError::new(format!(
+            "\
+Parse error: Unclosed list at index 38
+ --> {}:2:1
+  |
+2 | (defun oops (x)
+  | ^^^^^^^^^^^^^^^
+  |
+  = hint: this `(` is never closed; add a `)` where the list ends",
+            path
+        )))
+    );
+}
+
+#[test]
+fn load_with_extended_syntax() {
+    let dir = tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("lists.lisp"),
+        "(define items [1 2 (+ 1 2)])",
+    )
+    .unwrap();
+
+    // `load` uses the parser from the interpreter's context
+    let env = default_env();
+    env.context()
+        .parser
+        .borrow_mut()
+        .register_char_macro('[', |reader| {
+            let items = reader.read_until(']')?;
+            Ok(Value::List(
+                List::from_vec(items).cons(Value::Symbol(Symbol::from("list"))),
+            ))
+        });
+
+    let path = format!("{}/lists.lisp", dir.path().to_str().unwrap());
+    let result = eval_block(
+        Rc::new(RefCell::new(env)),
+        vec![lisp_list![Symbol::from("load"), path], lisp! { items }].into_iter(),
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 2 3) }));
+}
+
+#[test]
+fn sessions() {
+    let dir = tempdir().unwrap();
+
+    let warnings = eval_in(
+        dir.path(),
+        "
+    (define scores (hash \"amy\" (list 3 4)))
+    (defun fact (n) (if (< n 2) 1 (* n (fact (- n 1)))))
+    (define show print)
+    (save_session (string_append dir \"/session.lisp\"))",
+    );
+
+    assert_eq!(
+        warnings,
+        Ok(Value::List(
+            [Value::String(
+                "Skipped \"show\": it's a function written in Rust".to_owned()
+            )]
+            .into_iter()
+            .collect()
+        ))
+    );
+
+    // restored into a fresh environment, whose own `dir` and `fact` are kept
+    // with `:overwrite F`
+    let result = eval_in(
+        dir.path(),
+        "
+    (define path (string_append dir \"/session.lisp\"))
+    (define fact 1)
+    (list
+      (load_session path :overwrite F)
+      fact
+      (hash_get scores \"amy\")
+      (load_session path)
+      (fact 5))",
+    );
+
+    assert_eq!(
+        result,
+        Ok(lisp! { ((scores) 1 (3 4) (dir fact scores) 120) })
+    );
+}
+
+#[test]
+fn errors_include_the_path() {
+    let dir = tempdir().unwrap();
+    let missing = format!("{}/missing.txt", dir.path().to_str().unwrap());
+
+    for func in ["load", "read_file", "delete_file", "list_dir"] {
+        let result = eval_in(
+            dir.path(),
+            &format!("({} (string_append dir \"/missing.txt\"))", func),
+        );
+        let prefix = format!("\"{}\" failed for {}: ", func, missing);
+
+        assert!(
+            matches!(
+                &result,
+                Err(RuntimeError { msg }) if msg.starts_with(&prefix) && msg.len() > prefix.len()
+            ),
+            "{:?}",
+            result
+        );
+    }
+}
+
+#[cfg(test)]
+fn eval_in(dir: &Path, source: &str) -> Result<Value, RuntimeError> {
+    let mut env = default_env();
+    env.define(
+        Symbol::from("dir"),
+        Value::String(dir.to_str().unwrap().to_owned()),
+    );
+
+    eval_block(
+        Rc::new(RefCell::new(env)),
+        parse(source).map(|expr| expr.unwrap()),
+    )
+}
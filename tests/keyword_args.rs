@@ -0,0 +1,184 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp, lisp_list,
+    model::{Env, RuntimeError, Symbol, Value},
+    parser::parse,
+    sym,
+    utils::keyword_args,
+};
+use std::{cell::RefCell, rc::Rc};
+
+const MAKE_WINDOW: &str = "
+    (defun make_window (&key width height (title \"untitled\"))
+      (list width height title))";
+
+#[test]
+fn keywords_evaluate_to_themselves() {
+    assert_eq!(
+        eval_str("(list :width ':height)"),
+        Ok(lisp_list![sym!(":width"), sym!(":height")])
+    );
+
+    // but a lone colon is still a normal symbol
+    assert_eq!(
+        eval_str(":"),
+        Err(RuntimeError::new("\":\" is not defined".to_owned()))
+    );
+}
+
+#[test]
+fn keyword_arguments_in_any_order() {
+    let result = eval_str(&format!(
+        "{}
+    (list
+      (make_window :width 800 :height 600 :title \"hi\")
+      (make_window :height 600 :width 800)
+      (make_window))",
+        MAKE_WINDOW
+    ));
+
+    assert_eq!(
+        result,
+        Ok(lisp! { ((800 600 "hi") (800 600 "untitled") (NIL NIL "untitled")) })
+    );
+}
+
+#[test]
+fn keyword_arguments_after_positional() {
+    let result = eval_str(
+        "
+    (defun g (a &key b (c (* a 2)))
+      (list a b c))
+
+    (list (g 1 :b 2) (g 3) ((lambda (&key x) x) :x 4))",
+    );
+
+    assert_eq!(result, Ok(lisp! { ((1 2 2) (3 NIL 6) 4) }));
+}
+
+#[test]
+fn bad_keyword_arguments() {
+    let cases = [
+        (
+            "(make_window :width 800 :depth 2)",
+            "\"make_window\" got an unexpected keyword argument :depth; expected one of :width, :height, :title",
+        ),
+        (
+            "(make_window :width)",
+            "\"make_window\" requires a value after the keyword argument :width",
+        ),
+        (
+            "(make_window 800 600)",
+            "\"make_window\" expected a keyword argument, got 800",
+        ),
+    ];
+
+    for (call, msg) in cases {
+        assert_eq!(
+            eval_str(&format!("{} {}", MAKE_WINDOW, call)),
+            Err(RuntimeError::new(msg.to_owned())),
+            "{}",
+            call
+        );
+    }
+
+    // an anonymous function is called "lambda"
+    assert_eq!(
+        eval_str("((lambda (&key x) x) :y 1)"),
+        Err(RuntimeError::new(
+            "\"lambda\" got an unexpected keyword argument :y; expected one of :x".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn rest_and_key() {
+    // like in Common Lisp, the rest parameter gets the keyword arguments too
+    let result = eval_str(
+        "
+    (defun g (a &rest others &key x)
+      (list a others x))
+
+    (list (g 1 :x 2) (g 1))",
+    );
+
+    assert_eq!(
+        result,
+        Ok(lisp_list![
+            lisp_list![1, lisp_list![sym!(":x"), 2], 2],
+            lisp_list![1, Value::NIL, Value::NIL]
+        ])
+    );
+
+    for (source, msg) in [
+        (
+            "(lambda (&key a &rest b) a)",
+            "Expected &rest to come before &key",
+        ),
+        ("(lambda (&key a &key b) a)", "Expected &key at most once"),
+        (
+            "(lambda (&rest &key a) a)",
+            "Expected exactly one arg name after &rest",
+        ),
+    ] {
+        assert_eq!(
+            eval_str(source),
+            Err(RuntimeError::new(msg.to_owned())),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn lambda_display_with_defaults() {
+    assert_eq!(
+        eval_str("(lambda (a &key b (c 3)) a)").unwrap().to_string(),
+        "#<lambda (a &key b (c 3)) a>"
+    );
+}
+
+#[test]
+fn native_keyword_arguments() {
+    let mut env = default_env();
+
+    env.define(
+        Symbol::from("area"),
+        Value::NativeFunc(|_env, args| {
+            let keys = keyword_args("area", &args, 0, &["width", "height"])?;
+            let width = keys.get("width").copied().cloned().unwrap_or(lisp! { 1 });
+            let height = keys.get("height").copied().cloned().unwrap_or(lisp! { 1 });
+
+            Ok((width * height).unwrap())
+        }),
+    );
+
+    let env = Rc::new(RefCell::new(env));
+
+    assert_eq!(
+        eval_str_in(env.clone(), "(area :height 3 :width 4)"),
+        Ok(lisp! { 12 })
+    );
+    assert_eq!(
+        eval_str_in(env.clone(), "(area :height 3 :height 5)"),
+        Ok(lisp! { 3 })
+    );
+    assert_eq!(
+        eval_str_in(env, "(area :size 3)"),
+        Err(RuntimeError::new(
+            "\"area\" got an unexpected keyword argument :size; expected one of :width, :height"
+                .to_owned()
+        ))
+    );
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    eval_str_in(Rc::new(RefCell::new(default_env())), source)
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
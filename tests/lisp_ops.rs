@@ -0,0 +1,213 @@
+#![cfg(feature = "std")]
+
+use rust_lisp::{
+    default_env, eval_str,
+    model::{Env, FloatType, IntType, LispOps, RuntimeError, Value},
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    cmp::Ordering,
+    fmt::{self, Formatter, Write},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vec2(FloatType, FloatType);
+
+impl Vec2 {
+    fn length(&self) -> FloatType {
+        (self.0 * self.0 + self.1 * self.1).sqrt()
+    }
+}
+
+impl LispOps for Vec2 {
+    fn type_name(&self) -> &str {
+        "vec2"
+    }
+
+    fn add(&self, other: &Value) -> Result<Value, RuntimeError> {
+        let other = as_vec2(other)
+            .ok_or_else(|| RuntimeError::new(format!("Can't add {} to a vec2", other)))?;
+
+        Ok(vec2(self.0 + other.0, self.1 + other.1))
+    }
+
+    fn mul(&self, other: &Value) -> Result<Value, RuntimeError> {
+        let factor = match other {
+            Value::Int(factor) => int_to_float(factor),
+            Value::Float(factor) => *factor,
+            _ => {
+                return Err(RuntimeError::new(format!(
+                    "Can't multiply a vec2 by {}",
+                    other
+                )))
+            }
+        };
+
+        Ok(vec2(self.0 * factor, self.1 * factor))
+    }
+
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        self.length().partial_cmp(&as_vec2(other)?.length())
+    }
+
+    fn equals(&self, other: &Value) -> bool {
+        as_vec2(other) == Some(*self)
+    }
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "#<vec2 {} {}>", self.0, self.1)
+    }
+}
+
+fn vec2(x: FloatType, y: FloatType) -> Value {
+    Value::Foreign(Rc::new(Vec2(x, y)))
+}
+
+fn as_vec2(value: &Value) -> Option<Vec2> {
+    match value {
+        Value::Foreign(value) => value.downcast_ref::<Vec2>().copied(),
+        _ => None,
+    }
+}
+
+#[test]
+fn operators() {
+    let (env, _) = env_with_vec2();
+
+    let result = eval_str(
+        env.clone(),
+        "
+    (list
+      (+ (vec2 1 2) (vec2 3 4) (vec2 0.5 0.5))
+      (* (vec2 1 2) 3)
+      (* 2 (vec2 1 2))
+      (== (vec2 1 2) (vec2 1 2))
+      (equal (vec2 1 2) (vec2 2 1))
+      (!= (vec2 1 2) 1)
+      (< (vec2 1 1) (vec2 2 2))
+      (>= (vec2 1 1) (vec2 2 2)))",
+    )
+    .unwrap();
+
+    let Value::List(items) = result else {
+        panic!("expected a list, got {}", result);
+    };
+    let items = items.into_iter().collect::<Vec<_>>();
+    assert_eq!(as_vec2(&items[0]), Some(Vec2(4.5, 6.5)));
+    assert_eq!(as_vec2(&items[1]), Some(Vec2(3.0, 6.0)));
+    assert_eq!(as_vec2(&items[2]), Some(Vec2(2.0, 4.0)));
+    assert_eq!(
+        &items[3..],
+        &[
+            Value::True,
+            Value::False,
+            Value::True,
+            Value::True,
+            Value::False
+        ]
+    );
+
+    // operations the type doesn't have name both types
+    assert_eq!(
+        eval_str(env.clone(), "(- (vec2 1 2) 1)"),
+        Err(RuntimeError::new(
+            "Function \"-\" can't be applied to a vec2 and a integer".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval_str(env.clone(), "(- 1 (vec2 1 2))"),
+        Err(RuntimeError::new(
+            "Function \"-\" can't be applied to a integer and a vec2".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval_str(env.clone(), "(< (vec2 1 2) \"a\")"),
+        Err(RuntimeError::new(
+            "Function \"<\" can't be applied to a vec2 and a string".to_owned()
+        ))
+    );
+
+    // and the type's own errors come through
+    assert_eq!(
+        eval_str(env, "(* (vec2 1 2) \"a\")"),
+        Err(RuntimeError::new(
+            "Can't multiply a vec2 by \"a\"".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn printing() {
+    let (env, output) = env_with_vec2();
+
+    eval_str(env, "(print (list (vec2 1 2) (+ (vec2 1 2) (vec2 1 2))))").unwrap();
+
+    assert_eq!(output.borrow().as_str(), "(#<vec2 1 2> #<vec2 2 4>)\n");
+}
+
+#[test]
+fn unregistered_types() {
+    struct Opaque;
+
+    let env = default_env().into_shared();
+    env.borrow_mut().define(
+        "opaque".into(),
+        Value::Foreign(Rc::new(Opaque) as Rc<dyn Any>),
+    );
+
+    assert_eq!(
+        eval_str(env.clone(), "(+ opaque 1)"),
+        Err(RuntimeError::new(
+            "Function \"+\" can't be applied to a foreign value and a integer".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval_str(env, "(list (== opaque opaque) (== opaque 1))"),
+        Ok(Value::List(
+            [Value::True, Value::False].into_iter().collect()
+        ))
+    );
+}
+
+struct Capture(Rc<RefCell<String>>);
+
+impl Write for Capture {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.borrow_mut().push_str(s);
+        Ok(())
+    }
+}
+
+fn env_with_vec2() -> (Rc<RefCell<Env>>, Rc<RefCell<String>>) {
+    let mut env = default_env();
+    let output = Rc::new(RefCell::new(String::new()));
+    env.context().set_output(Capture(output.clone()));
+    env.context().register_lisp_ops::<Vec2>();
+
+    env.define(
+        "vec2".into(),
+        Value::NativeFunc(|_env, args| {
+            let x = args.first().and_then(as_float).unwrap_or(0.0);
+            let y = args.get(1).and_then(as_float).unwrap_or(0.0);
+
+            Ok(vec2(x, y))
+        }),
+    );
+
+    (env.into_shared(), output)
+}
+
+fn as_float(value: &Value) -> Option<FloatType> {
+    match value {
+        Value::Int(value) => Some(int_to_float(value)),
+        Value::Float(value) => Some(*value),
+        _ => None,
+    }
+}
+
+// `IntType` isn't a primitive (that `as` can convert) with the `bigint` feature
+fn int_to_float(int: &IntType) -> FloatType {
+    int.to_string().parse().unwrap_or(FloatType::NAN)
+}
@@ -27,9 +27,9 @@ fn two() {
 fn three() {
     assert_eq!(
         eval_str("(car (list))"),
-        Err(RuntimeError {
-            msg: "Attempted to apply car on nil".to_owned()
-        })
+        Err(RuntimeError::new(
+            "Attempted to apply car on nil".to_owned()
+        ))
     );
 }
 
@@ -50,9 +50,9 @@ fn five() {
 fn six() {
     assert_eq!(
         eval_str("(cons (list 1 2 3) 4)"),
-        Err(RuntimeError {
-            msg: "\"cons\" requires argument 2 to be a list; got 4".to_owned()
-        })
+        Err(RuntimeError::new(
+            "\"cons\" requires argument 2 to be a list; got 4".to_owned()
+        ))
     );
 }
 
@@ -65,5 +65,5 @@ fn seven() {
 fn eval_str(source: &str) -> Result<Value, RuntimeError> {
     let ast = parse(source).next().unwrap().unwrap();
     let env = Rc::new(RefCell::new(default_env()));
-    return eval(env, &ast);
+    eval(env, &ast)
 }
@@ -0,0 +1,105 @@
+use rust_lisp::{
+    default_env, eval_str, lisp,
+    model::{Env, LogLevel, RuntimeError, Value},
+};
+use std::{cell::RefCell, rc::Rc};
+
+type Logged = Rc<RefCell<Vec<(LogLevel, String)>>>;
+
+#[test]
+fn levels_are_filtered() {
+    let (env, logged) = env_with_logger();
+
+    let result = eval_str(
+        env.clone(),
+        "
+    (log_debug \"dropped\")
+    (log_info \"starting ~a\" \"up\")
+    (define level_before (log_level))
+    (set_log_level 'warn)
+    (log_info \"dropped too\")
+    (log_warn \"low on ~s\" \"disk\")
+    (log_error \"failed after ~a tries: ~s~%\" 3 (list 1 'a))
+    (list level_before (log_level))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (info warn) }));
+    assert_eq!(
+        *logged.borrow(),
+        vec![
+            (LogLevel::Info, "starting up".to_owned()),
+            (LogLevel::Warn, "low on \"disk\"".to_owned()),
+            (LogLevel::Error, "failed after 3 tries: (1 a)\n".to_owned()),
+        ]
+    );
+
+    // everything is logged at the lowest level
+    env.borrow().context().log_level.set(LogLevel::Debug);
+    logged.borrow_mut().clear();
+    eval_str(env, "(log_debug \"~~~a\" 1)").unwrap();
+
+    assert_eq!(*logged.borrow(), vec![(LogLevel::Debug, "~1".to_owned())]);
+}
+
+#[test]
+fn dropped_messages_are_not_formatted() {
+    let (env, logged) = env_with_logger();
+
+    // the arguments are still evaluated, but a template that doesn't fit them
+    // isn't noticed if the message is dropped
+    let result = eval_str(
+        env,
+        "
+    (define calls 0)
+    (defun count () (set calls (+ calls 1)))
+    (log_debug \"~a ~a\" (count))
+    calls",
+    );
+
+    assert_eq!(result, Ok(lisp! { 1 }));
+    assert!(logged.borrow().is_empty());
+}
+
+#[test]
+fn format_templates() {
+    let (env, _) = env_with_logger();
+
+    for (source, msg) in [
+        (
+            "(format \"~a and ~a\" 1)",
+            "Function \"format\" has more directives in its template than arguments",
+        ),
+        (
+            "(log_info \"~a\" 1 2)",
+            "Function \"log_info\" has more arguments than directives in its template",
+        ),
+        (
+            "(format \"~d\" 1)",
+            "Function \"format\" doesn't know the directive ~d",
+        ),
+        (
+            "(set_log_level 'loud)",
+            "Function \"set_log_level\" requires a level of debug, info, warn or error; got loud",
+        ),
+    ] {
+        assert_eq!(
+            eval_str(env.clone(), source),
+            Err(RuntimeError::new(msg.to_owned()))
+        );
+    }
+
+    assert_eq!(
+        eval_str(env, "(format \"~a is ~s\" #\\x #\\x)"),
+        Ok(Value::String("x is #\\x".to_owned()))
+    );
+}
+
+fn env_with_logger() -> (Rc<RefCell<Env>>, Logged) {
+    let env = default_env();
+    let logged = Logged::default();
+    let sink = logged.clone();
+    env.context()
+        .set_logger(move |level, msg| sink.borrow_mut().push((level, msg.to_owned())));
+
+    (env.into_shared(), logged)
+}
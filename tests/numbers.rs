@@ -0,0 +1,446 @@
+// `IntType` only differs from the literal type under some features
+#![allow(clippy::useless_conversion)]
+
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    model::{FloatType, IntType, RuntimeError, Symbol, Value},
+    parser::{parse, ParseError},
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn division_by_zero() {
+    assert_eq!(
+        eval_str("(/ 1 0)"),
+        Err(RuntimeError::new("Division by zero in \"/\"".to_owned()))
+    );
+
+    assert_eq!(
+        eval_str("(truncate 1 0)"),
+        Err(RuntimeError::new(
+            "Division by zero in \"truncate\"".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn division_by_zero_in_fold() {
+    for source in [
+        "(/ 0)",
+        "(/ 8 0 2)",
+        "(/ 8 2 0)",
+        "(/ 8.0 2 0)",
+        "(/ 1 0.0)",
+    ] {
+        assert_eq!(
+            eval_str(source),
+            Err(RuntimeError::new("Division by zero in \"/\"".to_owned())),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn zero_arg_identities() {
+    assert_eq!(eval_str("(+)"), Ok(Value::Int(0i8.into())));
+    assert_eq!(eval_str("(*)"), Ok(Value::Int(1i8.into())));
+
+    assert!(eval_str("(-)").is_err());
+    assert!(eval_str("(/)").is_err());
+}
+
+#[test]
+fn unary_negate_and_reciprocal() {
+    assert_eq!(eval_str("(- 5)"), Ok(Value::Int((-5i8).into())));
+    assert_eq!(eval_str("(- 2.5)"), Ok(Value::Float(-2.5)));
+    assert_eq!(eval_str("(/ 4.0)"), Ok(Value::Float(0.25)));
+    assert_eq!(eval_str("(/ 1)"), Ok(Value::Int(1i8.into())));
+
+    // like any other integer division, this truncates
+    assert_eq!(eval_str("(/ 4)"), Ok(Value::Int(0i8.into())));
+
+    // and single arguments still get passed through
+    assert_eq!(eval_str("(+ 5)"), Ok(Value::Int(5i8.into())));
+    assert_eq!(eval_str("(* 5)"), Ok(Value::Int(5i8.into())));
+}
+
+#[test]
+fn left_fold() {
+    assert_eq!(eval_str("(+ 1 2 3)"), Ok(Value::Int(6i8.into())));
+    assert_eq!(eval_str("(- 10 1 2)"), Ok(Value::Int(7i8.into())));
+    assert_eq!(eval_str("(* 2 3 4)"), Ok(Value::Int(24i8.into())));
+    assert_eq!(eval_str("(/ 8 2 2)"), Ok(Value::Int(2i8.into())));
+    assert_eq!(eval_str("(/ 7 2 2)"), Ok(Value::Int(1i8.into())));
+    assert_eq!(eval_str("(- 10 1 2.5)"), Ok(Value::Float(6.5)));
+    assert_eq!(
+        eval_str("(+ \"a\" \"b\" \"c\")"),
+        Ok(Value::String("abc".to_owned()))
+    );
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn integer_overflow() {
+    let overflow = |func: &str| {
+        Err(RuntimeError::new(format!(
+            "Integer overflow in \"{}\"",
+            func
+        )))
+    };
+
+    let max = IntType::MAX.to_string();
+    let min = IntType::MIN.to_string();
+
+    assert_eq!(eval_str(&format!("(+ {} 1)", max)), overflow("+"));
+    assert_eq!(eval_str(&format!("(- {} 1)", min)), overflow("-"));
+    assert_eq!(eval_str(&format!("(- {})", min)), overflow("-"));
+    assert_eq!(eval_str(&format!("(+ 1 {} -1)", max)), overflow("+"));
+    assert_eq!(eval_str(&format!("(* {} 2)", max)), overflow("*"));
+    assert_eq!(eval_str(&format!("(/ {} -1)", min)), overflow("/"));
+    assert_eq!(
+        eval_str(&format!("(truncate {} -1)", min)),
+        overflow("truncate")
+    );
+
+    assert_eq!(
+        eval_str(&format!("(- {} 1)", max)),
+        Ok(Value::Int(IntType::MAX - 1))
+    );
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn integer_overflow() {
+    assert_eq!(
+        eval_str("(* 4000000000 4000000000 4000000000)"),
+        Ok(Value::Int(
+            "64000000000000000000000000000".parse::<IntType>().unwrap()
+        ))
+    );
+}
+
+#[cfg(not(any(feature = "i64", feature = "i128", feature = "bigint")))]
+#[test]
+fn literal_out_of_range() {
+    assert_eq!(
+        parse("(+ 1 3000000000)").next(),
+        Some(Err(ParseError {
+            msg: "Integer literal 3000000000 is out of range at index 5".to_owned(),
+            span: 5..15,
+        }))
+    );
+}
+
+#[cfg(any(feature = "i64", feature = "i128", feature = "bigint"))]
+#[test]
+fn literal_out_of_range() {
+    assert_eq!(
+        eval_str("(+ 1 3000000000)"),
+        Ok(Value::Int(3000000001i64.try_into().unwrap()))
+    );
+
+    assert!(matches!(
+        parse("100000000000000000000000000000000000000000").next(),
+        Some(Err(_)) | Some(Ok(Value::Int(_)))
+    ));
+}
+
+#[test]
+fn literal_limits() {
+    #[cfg(not(feature = "bigint"))]
+    {
+        let max = IntType::MAX.to_string();
+        let min = IntType::MIN.to_string();
+
+        assert_eq!(eval_str(&max), Ok(Value::Int(IntType::MAX)));
+        assert_eq!(eval_str(&min), Ok(Value::Int(IntType::MIN)));
+    }
+
+    assert_eq!(eval_str("-12"), Ok(Value::Int((-12i8).into())));
+}
+
+#[test]
+fn number_to_string() {
+    let cases = [
+        ("(number_to_string 255)", "255"),
+        ("(number_to_string 255 16)", "ff"),
+        ("(number_to_string -255 16)", "-ff"),
+        ("(number_to_string 10 2)", "1010"),
+        ("(number_to_string 64 8)", "100"),
+        ("(number_to_string 0 2)", "0"),
+        ("(number_to_string 2.5)", "2.5"),
+        ("(number_to_string 3.14159 :precision 2)", "3.14"),
+        ("(number_to_string 2.5 10 :precision 3)", "2.500"),
+        ("(number_to_string 2.5 :precision 0)", "2"),
+    ];
+
+    for (source, expected) in cases {
+        assert_eq!(
+            eval_str(source),
+            Ok(Value::String(expected.to_owned())),
+            "{}",
+            source
+        );
+    }
+
+    // the sign is written separately, even for the smallest int
+    #[cfg(not(feature = "bigint"))]
+    assert_eq!(
+        eval_str(&format!("(number_to_string {} 16)", IntType::MIN)),
+        Ok(Value::String(format!("-{:x}", IntType::MIN.unsigned_abs())))
+    );
+}
+
+#[test]
+fn string_to_number() {
+    let cases = [
+        ("(string_to_number \"42\")", Value::Int(42i16.into())),
+        ("(string_to_number \"-42\")", Value::Int((-42i16).into())),
+        ("(string_to_number \"+42\")", Value::Int(42i16.into())),
+        ("(string_to_number \"ff\" 16)", Value::Int(255i16.into())),
+        ("(string_to_number \"FF\" 16)", Value::Int(255i16.into())),
+        (
+            "(string_to_number \"-1010\" 2)",
+            Value::Int((-10i16).into()),
+        ),
+        ("(string_to_number \"2.5\")", Value::Float(2.5)),
+        ("(string_to_number \"1e2\")", Value::Float(100.0)),
+    ];
+
+    for (source, expected) in cases {
+        assert_eq!(eval_str(source), Ok(expected), "{}", source);
+    }
+
+    // anything that isn't a number is NIL
+    for garbage in [
+        "(string_to_number \"12\" 2)",
+        "(string_to_number \"2.5\" 16)",
+        "(string_to_number \"\")",
+        "(string_to_number \"-\")",
+        "(string_to_number \"abc\")",
+        "(string_to_number \"1 2\")",
+        "(string_to_number \" 1\")",
+        "(string_to_number \"1.2.3\")",
+        "(string_to_number \"inf\")",
+        "(string_to_number \"NaN\")",
+    ] {
+        assert_eq!(eval_str(garbage), Ok(Value::NIL), "{}", garbage);
+    }
+
+    // what's written in a radix reads back the same
+    assert_eq!(
+        eval_str("(string_to_number (number_to_string -1234 2) 2)"),
+        Ok(Value::Int((-1234i16).into()))
+    );
+}
+
+#[test]
+fn number_conversion_errors() {
+    let cases = [
+        (
+            "(number_to_string 1.5 16)",
+            "\"number_to_string\" can only write integers in radix 16; got 1.5",
+        ),
+        (
+            "(number_to_string 10 3)",
+            "\"number_to_string\" requires a radix of 2, 8, 10 or 16; got 3",
+        ),
+        (
+            "(number_to_string 10 :precision 2)",
+            "\"number_to_string\" can only take :precision for floats; got 10",
+        ),
+        (
+            "(number_to_string 1.5 :precision -1)",
+            "\"number_to_string\" requires :precision to be a non-negative integer; got -1",
+        ),
+        (
+            "(number_to_string \"1\")",
+            "\"number_to_string\" requires argument 1 to be a number; got \"1\"",
+        ),
+        (
+            "(string_to_number \"10\" 36)",
+            "\"string_to_number\" requires a radix of 2, 8, 10 or 16; got 36",
+        ),
+    ];
+
+    for (source, msg) in cases {
+        assert_eq!(
+            eval_str(source),
+            Err(RuntimeError::new(msg.to_owned())),
+            "{}",
+            source
+        );
+    }
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn string_to_number_overflow() {
+    // a number too big to be an int isn't garbage, so it's an error
+    assert_eq!(
+        eval_str(&format!("(string_to_number \"{}0\")", IntType::MAX)),
+        Err(RuntimeError::new(
+            "Integer overflow in \"string_to_number\"".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn floats_round_trip() {
+    let corpus: [FloatType; 14] = [
+        0.1,
+        1.0 / 3.0,
+        -2.5,
+        123456.79,
+        1e-7,
+        -2.5e-10,
+        1e16,
+        1e30,
+        FloatType::MAX,
+        FloatType::MIN_POSITIVE,
+        // subnormals, down to the smallest
+        FloatType::MIN_POSITIVE / 4.0,
+        FloatType::from_bits(1),
+        0.0,
+        -0.0,
+    ];
+
+    for float in corpus {
+        let written = Value::Float(float).to_string();
+        let env = Rc::new(RefCell::new(default_env()));
+        env.borrow_mut()
+            .define(Symbol::from("x"), Value::Float(float));
+
+        // compared by bits, so that -0.0 has to keep its sign
+        for source in [
+            "(string_to_number (number_to_string x))",
+            "(string_to_number (number_format x))",
+            written.as_str(),
+        ] {
+            let result = eval_block(
+                env.clone(),
+                parse(source).map(|expr| expr.expect("parse error in test source")),
+            );
+
+            match result {
+                Ok(Value::Float(read)) => assert_eq!(
+                    read.to_bits(),
+                    float.to_bits(),
+                    "{} read back from {}",
+                    written,
+                    source
+                ),
+                other => panic!("{} read back from {} as {:?}", written, source, other),
+            }
+        }
+    }
+}
+
+#[test]
+fn non_finite_floats() {
+    let cases = [
+        ("(* 1e30 1e300)", "+inf.0"),
+        ("(- -inf.0 1)", "-inf.0"),
+        ("(- +inf.0 +inf.0)", "+nan.0"),
+        ("(list +inf.0 -inf.0 -nan.0)", "(+inf.0 -inf.0 +nan.0)"),
+        ("(string_to_number \"-inf.0\")", "-inf.0"),
+        ("(number_to_string +nan.0)", "\"+nan.0\""),
+        ("(== +inf.0 (string_to_number \"+inf.0\"))", "T"),
+        // the same as any other symbol otherwise
+        ("'(+inf +nan.1 inf.0)", "(+inf +nan.1 inf.0)"),
+    ];
+
+    for (source, expected) in cases {
+        match eval_str(source) {
+            Ok(result) => assert_eq!(result.to_string(), expected, "{}", source),
+            Err(err) => panic!("{} failed: {}", source, err),
+        }
+    }
+}
+
+#[test]
+fn number_format() {
+    let cases = [
+        ("(number_format 42)", "42"),
+        ("(number_format 2.5)", "2.5"),
+        ("(number_format 3.14159 :places 2)", "3.14"),
+        ("(number_format 3 :places 2)", "3.00"),
+        ("(number_format 2.5 :places 0)", "2"),
+        ("(number_format 1234.5 :scientific T)", "1.2345e3"),
+        ("(number_format 1000 :scientific T)", "1.0e3"),
+        ("(number_format 1536.0 :scientific T :places 1)", "1.5e3"),
+        ("(number_format 0.0009765625 :scientific T)", "9.765625e-4"),
+        ("(number_format 42 :width 6)", "    42"),
+        ("(number_format -42 :width 6 :zero_pad T)", "-00042"),
+        ("(number_format 1.5 :width 2 :zero_pad T)", "1.5"),
+        ("(number_format 1234567 :separator \",\")", "1,234,567"),
+        ("(number_format -123456 :separator \"_\")", "-123_456"),
+        (
+            "(number_format 1234.5 :places 2 :separator \",\")",
+            "1,234.50",
+        ),
+        ("(number_format 255 :radix 16)", "ff"),
+        (
+            "(number_format 51966 :radix 16 :separator \" \" :width 8)",
+            "   c afe",
+        ),
+        (
+            "(number_format 5 :radix 2 :width 8 :zero_pad T)",
+            "00000101",
+        ),
+        ("(number_format -inf.0 :width 7 :zero_pad T)", " -inf.0"),
+    ];
+
+    for (source, expected) in cases {
+        assert_eq!(
+            eval_str(source),
+            Ok(Value::String(expected.to_owned())),
+            "{}",
+            source
+        );
+    }
+
+    let errors = [
+        (
+            "(number_format 1.5 :radix 16)",
+            "\"number_format\" can only write integers in radix 16; got 1.5",
+        ),
+        (
+            "(number_format 10 :radix 2 :places 1)",
+            "\"number_format\" can't take :places or :scientific with radix 2",
+        ),
+        (
+            "(number_format 10 :width -1)",
+            "\"number_format\" requires :width to be a non-negative integer; got -1",
+        ),
+        (
+            "(number_format 10 :separator 'comma)",
+            "\"number_format\" requires :separator to be a string; got comma",
+        ),
+        (
+            "(number_format \"10\")",
+            "\"number_format\" requires argument 1 to be a number; got \"10\"",
+        ),
+    ];
+
+    for (source, msg) in errors {
+        assert_eq!(
+            eval_str(source),
+            Err(RuntimeError::new(msg.to_owned())),
+            "{}",
+            source
+        );
+    }
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    eval_block(
+        env,
+        parse(source).map(|expr| expr.expect("parse error in test source")),
+    )
+}
@@ -1,7 +1,10 @@
 use rust_lisp::{
-    model::{IntType, Value},
-    parser::{parse, ParseError},
+    default_env, eval_str,
+    interpreter::eval,
+    model::{IntType, List, Symbol, Value},
+    parser::{parse, render_error, Brackets, ParseError, Parser},
 };
+use std::{any::Any, rc::Rc};
 
 #[macro_use]
 extern crate rust_lisp;
@@ -36,6 +39,36 @@ fn parse_nil() {
     assert_eq!(ast, Value::NIL);
 }
 
+#[test]
+fn parse_empty_string() {
+    let ast = parse("(list \"\" \"a\")").next().unwrap().unwrap();
+
+    assert_eq!(ast, lisp! { (list "" "a") });
+}
+
+#[test]
+fn parse_booleans() {
+    let ast = parse("(T F t f #t #f #T #tx)").next().unwrap().unwrap();
+
+    assert_eq!(
+        ast,
+        Value::List(
+            [
+                Value::True,
+                Value::False,
+                Value::True,
+                Value::False,
+                Value::True,
+                Value::False,
+                Value::True,
+                Value::Symbol(Symbol::from("#tx")),
+            ]
+            .iter()
+            .collect()
+        )
+    );
+}
+
 #[test]
 fn parse_atom() {
     let source = "12";
@@ -52,6 +85,127 @@ fn parse_negative_float() {
     assert_eq!(ast, vec![Ok(Value::from(-1.2))]);
 }
 
+#[test]
+fn parse_chars() {
+    let ast = parse("(#\\a #\\space #\\( #\\) #\\é #\\東 #\\newline #\\;)")
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        ast,
+        Value::List(
+            ['a', ' ', '(', ')', 'é', '東', '\n', ';']
+                .into_iter()
+                .map(Value::Char)
+                .collect()
+        )
+    );
+
+    // the names are used when printing, so it reads back the same
+    assert_eq!(
+        format!("{}", ast),
+        "(#\\a #\\space #\\( #\\) #\\é #\\東 #\\newline #\\;)"
+    );
+
+    assert_eq!(
+        parse("(#\\ab)").next(),
+        Some(Err(ParseError {
+            msg: "Unknown char name #\\ab at index 1".to_owned(),
+            span: 1..5,
+        }))
+    );
+    assert_eq!(
+        parse("#\\").next(),
+        Some(Err(ParseError {
+            msg: "Expected a char after #\\ at index 2".to_owned(),
+            span: 0..2,
+        }))
+    );
+}
+
+#[test]
+fn parse_bytes() {
+    let ast = parse("(#u8(1 2 255) #u8() #U8( 0 ;; zero\n 10))")
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        ast,
+        Value::List(
+            [vec![1, 2, 255], vec![], vec![0, 10]]
+                .into_iter()
+                .map(Value::from)
+                .collect()
+        )
+    );
+    assert_eq!(format!("{}", ast), "(#u8(1 2 255) #u8() #u8(0 10))");
+
+    assert_eq!(
+        parse("#u8(1 256)").next(),
+        Some(Err(ParseError {
+            msg: "Expected a byte (0 to 255) in bytes literal, got 256 at index 6".to_owned(),
+            span: 6..9,
+        }))
+    );
+    assert_eq!(
+        parse("#u8(1 (2))").next(),
+        Some(Err(ParseError {
+            msg: "Expected a byte (0 to 255) in bytes literal, got ( at index 6".to_owned(),
+            span: 6..7,
+        }))
+    );
+    assert_eq!(
+        parse("#u8(1 2").next(),
+        Some(Err(ParseError {
+            msg: "Unclosed bytes literal at index 0".to_owned(),
+            span: 0..7,
+        }))
+    );
+}
+
+#[test]
+fn parse_vectors() {
+    let ast = parse("(#(1 a \"b\") #() #(1 #(2 3) (4)))")
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        ast,
+        lisp_list![
+            Value::from(vec![lisp! { 1 }, lisp! { a }, lisp! { "b" }]),
+            Value::from(Vec::<Value>::new()),
+            Value::from(vec![
+                lisp! { 1 },
+                Value::from(vec![lisp! { 2 }, lisp! { 3 }]),
+                lisp! { (4) },
+            ])
+        ]
+    );
+    assert_eq!(format!("{}", ast), "(#(1 a \"b\") #() #(1 #(2 3) (4)))");
+
+    assert_eq!(
+        parse("#(1 #(2 3)").next(),
+        Some(Err(ParseError {
+            msg: "Unclosed vector at index 10".to_owned(),
+            span: 0..11,
+        }))
+    );
+
+    // with square brackets, a vector can't be closed by one
+    let mut parser = Parser::new();
+    parser.set_brackets(Brackets::Grouping);
+    assert_eq!(
+        parser.parse("#(1 2]").next(),
+        Some(Err(ParseError {
+            msg: "Mismatched ] at index 5: expected ) to close the #( at index 0".to_owned(),
+            span: 0..6,
+        }))
+    );
+}
+
 #[test]
 fn parse_multiple_lines() {
     let ast = parse(
@@ -82,7 +236,8 @@ fn parse_unclosed_string() {
     assert_eq!(
         res,
         Some(Err(ParseError {
-            msg: "Unclosed string at index 3".to_owned()
+            msg: "Unclosed string at index 3".to_owned(),
+            span: 0..4,
         }))
     )
 }
@@ -94,7 +249,8 @@ fn parse_unclosed_list() {
     assert_eq!(
         res,
         Some(Err(ParseError {
-            msg: "Unclosed list at index 7".to_owned()
+            msg: "Unclosed list at index 7".to_owned(),
+            span: 0..8,
         }))
     )
 }
@@ -106,14 +262,794 @@ fn parse_incomplete_float() {
     assert_eq!(
         res,
         Some(Err(ParseError {
-            msg: "Expected decimal value after '.' at index 1".to_owned()
+            msg: "Expected decimal value after '.' at index 1".to_owned(),
+            span: 1..2,
         }))
     )
 }
 
+#[test]
+fn parse_non_ascii() {
+    let res = parse("(\"東京\" café \"é\") ;; fin à la ligne é").collect::<Vec<_>>();
+
+    assert_eq!(res, vec![Ok(lisp! { ("東京" café "é") })]);
+}
+
+#[test]
+fn parse_stray_close() {
+    let res = parse("(a b)) (c)").collect::<Vec<_>>();
+
+    assert_eq!(
+        res,
+        vec![
+            Ok(lisp! { (a b) }),
+            Err(ParseError {
+                msg: "Unexpected ) at index 5".to_owned(),
+                span: 5..6,
+            })
+        ]
+    )
+}
+
 #[test]
 fn parse_nonsense() {
     let res = parse("122jkh").next();
 
     assert_eq!(res, None)
 }
+
+#[test]
+fn parse_error_ends_iteration() {
+    let res = parse("(1 2) \"foo (3 4)").collect::<Vec<_>>();
+
+    assert_eq!(
+        res,
+        vec![
+            Ok(lisp! { (1 2) }),
+            Err(ParseError {
+                msg: "Unclosed string at index 15".to_owned(),
+                span: 6..16,
+            })
+        ]
+    )
+}
+
+#[test]
+fn parse_quotes() {
+    let res = parse("'(a ,b) ''c (g @xs @(h))").collect::<Vec<_>>();
+
+    assert_eq!(
+        res,
+        vec![
+            Ok(lisp! { (quote (a (comma b))) }),
+            Ok(lisp! { (quote (quote c)) }),
+            Ok(lisp! { (g (splice xs) (splice (h))) }),
+        ]
+    );
+
+    assert_eq!(
+        parse("(a ')").next(),
+        Some(Err(ParseError {
+            msg: "Unclosed list at index 3".to_owned(),
+            span: 0..4,
+        }))
+    );
+}
+
+#[test]
+fn display_quotes() {
+    // quotes are displayed the way they're written, so they read back the same
+    for source in [
+        "'x",
+        "'(a ,b)",
+        "''c",
+        "(list 'a ,(g x))",
+        "',x",
+        "'NIL",
+        "(g @xs)",
+    ] {
+        let parsed = parse(source).next().unwrap().unwrap();
+        let displayed = parsed.to_string();
+
+        assert_eq!(displayed, source);
+        assert_eq!(parse(&displayed).next(), Some(Ok(parsed)));
+    }
+
+    // but only with exactly one argument
+    for source in ["(quote a b)", "(quote)", "(comma a b)"] {
+        let parsed = parse(source).next().unwrap().unwrap();
+
+        assert_eq!(parsed.to_string(), source);
+    }
+}
+
+#[test]
+fn parse_deeply_nested() {
+    const DEPTH: usize = 100_000;
+    let source = "(".repeat(DEPTH) + &")".repeat(DEPTH);
+
+    let mut ast = parse(&source).next().unwrap().unwrap();
+    let mut depth = 0;
+
+    while let Value::List(list) = &ast {
+        match list.car() {
+            Ok(inner) => {
+                depth += 1;
+                ast = inner;
+            }
+            Err(_) => break,
+        }
+    }
+
+    // the innermost () is NIL, which has no car
+    assert_eq!(depth, DEPTH - 1);
+}
+
+#[test]
+fn parse_deeply_nested_unclosed() {
+    let source = "(".repeat(100_000);
+
+    assert_eq!(
+        parse(&source).next(),
+        Some(Err(ParseError {
+            msg: "Unclosed list at index 100000".to_owned(),
+            span: 99999..100001,
+        }))
+    );
+}
+
+#[test]
+fn parse_many_quotes() {
+    let source = "'".repeat(100_000) + "x";
+    let ast = parse(&source).next().unwrap();
+
+    assert!(ast.is_ok());
+}
+
+#[test]
+fn parse_bracket_soup() {
+    // a small deterministic pseudo-random generator, so failures reproduce
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = move |max: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % max
+    };
+
+    let alphabet = ['(', ')', '\'', ',', ' ', 'x', '1', '"', ';'];
+
+    for round in 0..2000 {
+        // every other round only uses brackets
+        let chars = if round % 2 == 0 {
+            2
+        } else {
+            alphabet.len() as u64
+        };
+        let soup = (0..random(200))
+            .map(|_| alphabet[random(chars) as usize])
+            .collect::<String>();
+
+        // the iterator must finish, without panicking
+        let results = parse(&soup).collect::<Vec<_>>();
+
+        // and so must showing where any error was
+        if let Some(Err(err)) = results.last() {
+            render_error(&soup, err.span.clone(), err);
+        }
+
+        // pure brackets with too many opens must be reported
+        let mut depth = 0;
+        let no_stray_closes = soup.chars().all(|ch| {
+            depth += if ch == '(' { 1 } else { -1 };
+            depth >= 0
+        });
+
+        if chars == 2 && no_stray_closes && depth > 0 {
+            assert!(
+                matches!(results.last(), Some(Err(_))),
+                "expected an error for {:?}",
+                soup
+            );
+        }
+    }
+}
+
+#[test]
+fn render_parse_error() {
+    let source = "(define x 1)\n(print \"hello)\n(print x)";
+    let err = parse(source).nth(1).unwrap().unwrap_err();
+
+    assert_eq!(
+        render_error(source, err.span.clone(), &err),
+        "\
+Parse error: Unclosed string at index 36
+  |
+2 | (print \"hello)
+  |        ^^^^^^^
+"
+    );
+}
+
+#[test]
+fn render_error_columns() {
+    // columns count characters, not bytes
+    let source = "(list \"héllo\" 1.)";
+    let err = parse(source).next().unwrap().unwrap_err();
+
+    assert_eq!(
+        render_error(source, err.span.clone(), &err),
+        "\
+Parse error: Expected decimal value after '.' at index 16
+  |
+1 | (list \"héllo\" 1.)
+  |                ^
+"
+    );
+
+    // an unclosed list is shown where it starts
+    let source = "(a\n  (b c)";
+    let err = parse(source).next().unwrap().unwrap_err();
+
+    assert_eq!(
+        render_error(source, err.span.clone(), &err),
+        "\
+Parse error: Unclosed list at index 10
+  |
+1 | (a
+  | ^^
+"
+    );
+}
+
+#[test]
+fn render_unclosed_string() {
+    let source = "(define greeting \"hello)\n(print greeting)";
+    let err = parse(source).next().unwrap().unwrap_err();
+
+    assert_eq!(
+        err.render(source, "greet.lisp"),
+        "\
+Parse error: Unclosed string at index 40
+ --> greet.lisp:1:18
+  |
+1 | (define greeting \"hello)
+  |                  ^^^^^^^
+  |
+  = hint: add a `\"` where the string ends (strings can't contain `\"`)
+"
+    );
+}
+
+#[test]
+fn render_unclosed_list() {
+    // the error is at the end, but it's the `(` that needs closing
+    let source = "(define x 1)\n\n(defun f (y)\n  (+ x y)\n\n(print (f 2))\n";
+    let err = parse(source).nth(1).unwrap().unwrap_err();
+
+    assert_eq!(
+        err.render(source, "main.lisp"),
+        "\
+Parse error: Unclosed list at index 52
+ --> main.lisp:3:1
+  |
+3 | (defun f (y)
+  | ^^^^^^^^^^^^
+  |
+  = hint: this `(` is never closed; add a `)` where the list ends
+"
+    );
+}
+
+#[test]
+fn render_stray_close() {
+    let lines = (1..=10)
+        .map(|n| format!("(print {})", n))
+        .collect::<Vec<_>>();
+    let source = lines.join("\n") + ")";
+    let err = parse(&source).last().unwrap().unwrap_err();
+
+    // the gutter is as wide as the line number
+    assert_eq!(
+        err.render(&source, "count.lisp"),
+        "\
+Parse error: Unexpected ) at index 100
+  --> count.lisp:10:11
+   |
+10 | (print 10))
+   |           ^
+   |
+   = hint: this `)` has no matching `(`; remove it, or add a `(` where the list starts
+"
+    );
+}
+
+/// A UUID-ish value for the reader extension tests, written `#u"..."`: 32 hex
+/// digits, in groups of 8-4-4-4-12
+#[derive(Debug, PartialEq)]
+struct Uuid(u128);
+
+impl Uuid {
+    fn write(&self) -> String {
+        let hex = format!("{:032x}", self.0);
+
+        format!(
+            "#u\"{}-{}-{}-{}-{}\"",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+fn uuid_parser() -> Parser {
+    let mut parser = Parser::new();
+
+    parser.register_dispatch('u', |reader| {
+        let Value::String(text) = reader.read()? else {
+            return Err(reader.error("Expected a string after #u"));
+        };
+
+        let groups = text.split('-').map(str::len).collect::<Vec<_>>();
+        let digits = text.replace('-', "");
+
+        match u128::from_str_radix(&digits, 16) {
+            Ok(uuid) if groups == [8, 4, 4, 4, 12] && !digits.starts_with('+') => {
+                Ok(Value::Foreign(Rc::new(Uuid(uuid))))
+            }
+            _ => Err(reader.error(format!("Invalid UUID {:?}", text))),
+        }
+    });
+
+    parser
+}
+
+#[test]
+fn dispatch_macros() {
+    let parser = uuid_parser();
+    let source = "(find #u\"123e4567-e89b-12d3-a456-426614174000\" users)";
+    let ast = parser.parse(source).next().unwrap().unwrap();
+
+    let Value::List(list) = &ast else {
+        panic!("{}", ast)
+    };
+    let uuid = list.get(1).unwrap();
+    let uuid = <&Rc<dyn Any>>::try_from(&uuid)
+        .unwrap()
+        .downcast_ref::<Uuid>();
+
+    assert_eq!(uuid, Some(&Uuid(0x123e4567_e89b_12d3_a456_426614174000)));
+    assert_eq!(list.get(2), Some(Value::Symbol(Symbol::from("users"))));
+
+    // the value can be written back out, and reads the same
+    let written = uuid.unwrap().write();
+    assert_eq!(written, "#u\"123e4567-e89b-12d3-a456-426614174000\"");
+
+    let reread = parser.parse(&written).next().unwrap().unwrap();
+    let reread = <&Rc<dyn Any>>::try_from(&reread)
+        .unwrap()
+        .downcast_ref::<Uuid>();
+    assert_eq!(reread, uuid);
+
+    // without the extension, the same source reads as it always has (as a
+    // symbol)
+    let symbol = Value::Symbol(Symbol::from(&source[6..46]));
+    assert_eq!(
+        parse(source).next(),
+        Some(Ok(lisp! { (find {symbol} users) }))
+    );
+    assert_eq!(
+        parser.parse("(#t #f #\\u #tx)").collect::<Vec<_>>(),
+        parse("(#t #f #\\u #tx)").collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn dispatch_macro_errors() {
+    let parser = uuid_parser();
+
+    // errors from the extension are located at it
+    assert_eq!(
+        parser.parse("(a #u\"1234\" b)").collect::<Vec<_>>(),
+        vec![Err(ParseError {
+            msg: "Invalid UUID \"1234\" at index 3".to_owned(),
+            span: 3..11,
+        })]
+    );
+    assert_eq!(
+        parser.parse("(a #u 12)").next(),
+        Some(Err(ParseError {
+            msg: "Expected a string after #u at index 3".to_owned(),
+            span: 3..8,
+        }))
+    );
+
+    // as are errors from reading inside it
+    assert_eq!(
+        parser.parse("#u\"123e4567").next(),
+        Some(Err(ParseError {
+            msg: "Unclosed string at index 10".to_owned(),
+            span: 2..11,
+        }))
+    );
+    assert_eq!(
+        parser.parse("(#u)").next(),
+        Some(Err(ParseError {
+            msg: "Expected an expression at index 3".to_owned(),
+            span: 3..4,
+        }))
+    );
+
+    let source = "(define id\n  #u\"not-a-uuid\")";
+    let err = parser.parse(source).next().unwrap().unwrap_err();
+    assert_eq!(
+        err.render(source, "ids.lisp"),
+        "\
+Parse error: Invalid UUID \"not-a-uuid\" at index 13
+ --> ids.lisp:2:3
+  |
+2 |   #u\"not-a-uuid\")
+  |   ^^^^^^^^^^^^^^
+"
+    );
+}
+
+#[test]
+fn char_macros() {
+    let mut parser = Parser::new();
+
+    // [1 2] reads as (vector 1 2), and a stray ] is an error
+    parser.register_char_macro('[', |reader| {
+        let items = reader.read_until(']')?;
+        Ok(Value::List(
+            List::from_vec(items).cons(Value::Symbol(Symbol::from("vector"))),
+        ))
+    });
+    parser.register_char_macro(']', |reader| Err(reader.error("Unexpected ]")));
+
+    assert_eq!(
+        parser
+            .parse("[1 [a 'b] \"c\"][] (x[y])")
+            .collect::<Vec<_>>(),
+        vec![
+            Ok(lisp! { (vector 1 (vector a (quote b)) "c") }),
+            Ok(lisp! { (vector) }),
+            Ok(lisp! { (x (vector y)) }),
+        ]
+    );
+    assert_eq!(
+        parser.parse("(a])").collect::<Vec<_>>(),
+        vec![Err(ParseError {
+            msg: "Unexpected ] at index 2".to_owned(),
+            span: 2..3,
+        })]
+    );
+    assert_eq!(
+        parser.parse("[1 2)").next(),
+        Some(Err(ParseError {
+            msg: "Expected ] at index 4".to_owned(),
+            span: 0..5,
+        }))
+    );
+
+    // a raw token ends at whitespace, or at any char that ends tokens
+    let mut parser = Parser::new();
+    parser.register_dispatch('d', |reader| {
+        Ok(Value::String(reader.read_token().to_owned()))
+    });
+
+    assert_eq!(
+        parser.parse("(#d2024-01-01 #d)").next(),
+        Some(Ok(lisp! { ("2024-01-01" "") }))
+    );
+}
+
+#[test]
+fn brackets() {
+    // by default, they're part of symbols
+    assert_eq!(
+        parse("([a] {b})").next(),
+        Some(Ok(symbols(&["[a]", "{b}"])))
+    );
+
+    let mut parser = Parser::new();
+    parser.set_brackets(Brackets::Grouping);
+
+    assert_eq!(
+        parser
+            .parse("[+ 1 2] {let ([x 1]) x} (a[b]c . [d])")
+            .collect::<Vec<_>>(),
+        vec![
+            Ok(lisp! { (+ 1 2) }),
+            Ok(lisp! { (let ((x 1)) x) }),
+            Ok(lisp! { (a (b) c d) }),
+        ]
+    );
+
+    let env = default_env().into_shared();
+    let expr = parser.parse("[* 2 {+ 1 2}]").next().unwrap().unwrap();
+    assert_eq!(eval(env.clone(), &expr), Ok(lisp! { 6 }));
+
+    parser.set_brackets(Brackets::Collections);
+
+    assert_eq!(
+        parser.parse("[+ 1 2] {a [1 2]} []").collect::<Vec<_>>(),
+        vec![
+            Ok(lisp! { (list + 1 2) }),
+            Ok(lisp! { (hash a (list 1 2)) }),
+            Ok(lisp! { (list) }),
+        ]
+    );
+
+    let expr = parser
+        .parse("(hash_get {\"a\" [1 2]} \"a\")")
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(eval(env, &expr), Ok(lisp! { (1 2) }));
+}
+
+#[test]
+fn mismatched_brackets() {
+    for brackets in [Brackets::Grouping, Brackets::Collections] {
+        let mut parser = Parser::new();
+        parser.set_brackets(brackets);
+
+        let err = parser.parse("(a [b)]").next().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                msg: "Mismatched ) at index 5: expected ] to close the [ at index 3".to_owned(),
+                span: 3..6,
+            }
+        );
+        assert!(err.render("(a [b)]", "main.lisp").contains(
+            "= hint: a list has to be closed by the same kind of bracket that opened it"
+        ));
+
+        assert_eq!(
+            parser.parse("{a . (b)]").next(),
+            Some(Err(ParseError {
+                msg: "Expected } after the rest of a dotted list at index 8".to_owned(),
+                span: 8..9,
+            }))
+        );
+        assert_eq!(
+            parser.parse("(a) }").collect::<Vec<_>>(),
+            vec![
+                Ok(lisp! { (a) }),
+                Err(ParseError {
+                    msg: "Unexpected } at index 4".to_owned(),
+                    span: 4..5,
+                }),
+            ]
+        );
+    }
+}
+
+#[test]
+fn dotted_lists() {
+    assert_eq!(
+        parse("(1 . (2 3)) (1 2 . NIL) (. a) (a .b)").collect::<Vec<_>>(),
+        vec![
+            Ok(lisp! { (1 2 3) }),
+            Ok(lisp! { (1 2) }),
+            Ok(symbols(&[".", "a"])),
+            Ok(symbols(&["a", ".b"])),
+        ]
+    );
+
+    // a list that ends in something else is a dotted list, whose values are
+    // the ones before the dot
+    let dotted = parse("(1 2 . 3)").next().unwrap().unwrap();
+    assert_eq!(dotted.to_string(), "(1 2 . 3)");
+    let Value::List(list) = &dotted else {
+        panic!("{} isn't a list", dotted)
+    };
+    assert_eq!(list.dotted_tail(), Some(lisp! { 3 }));
+    assert_eq!(
+        list.into_iter().collect::<Vec<_>>(),
+        vec![lisp! { 1 }, lisp! { 2 }]
+    );
+    assert_ne!(dotted, lisp! { (1 2 3) });
+
+    assert_eq!(
+        parse("(1 .)").next(),
+        Some(Err(ParseError {
+            msg: "Expected a value after the . at index 3".to_owned(),
+            span: 3..5,
+        }))
+    );
+    assert_eq!(
+        parse("(1 . (2) 3)").next(),
+        Some(Err(ParseError {
+            msg: "Expected ) after the rest of a dotted list at index 9".to_owned(),
+            span: 9..10,
+        }))
+    );
+}
+
+#[test]
+fn shared_structure_round_trip() {
+    let shared = lisp! { (1 2 3) };
+    let value = Value::List([shared.clone(), lisp! { 4 }, shared].into_iter().collect());
+
+    let written = value.labelled().to_string();
+    assert_eq!(written, "(#0=(1 2 3) 4 #0#)");
+
+    let read = parse(&written).next().unwrap().unwrap();
+    assert_eq!(read, value);
+
+    let lists = lists_in(&read);
+    assert!(lists[0].ptr_eq(&lists[1]));
+
+    // a shared tail is written as the rest of a dotted list
+    let env = default_env().into_shared();
+    let value = eval_str(
+        env.clone(),
+        "(define tail (list 2 3)) (list (cons 1 tail) ''tail tail)",
+    )
+    .unwrap();
+
+    let written = value.labelled().to_string();
+    assert_eq!(written, "((1 . #0=(2 3)) 'tail #0#)");
+
+    let read = parse(&written).next().unwrap().unwrap();
+    assert_eq!(read, value);
+
+    let lists = lists_in(&read);
+    assert!(lists[0].cdr().ptr_eq(&lists[2]));
+
+    // `write` (and `Context::write()`) label it too
+    assert_eq!(
+        env.borrow().context().write(&value).to_string(),
+        "((1 . #0=(2 3)) 'tail #0#)"
+    );
+
+    // which isn't needed without anything shared
+    assert_eq!(
+        lisp! { (1 (2 3) "a") }.labelled().to_string(),
+        "(1 (2 3) \"a\")"
+    );
+}
+
+#[test]
+fn circular_structure_round_trip() {
+    let env = default_env().into_shared();
+    let value = eval_str(
+        env,
+        "
+    (define circle (list 1 2))
+    (set_cdr (cdr circle) circle)
+    (define nested (list 'a 'b))
+    (set_car (cdr nested) nested)
+    (list circle nested)",
+    )
+    .unwrap();
+
+    let written = value.labelled().to_string();
+    assert_eq!(written, "(#0=(1 2 . #0#) #1=(a #1#))");
+
+    let read = parse(&written).next().unwrap().unwrap();
+    assert_eq!(read, value);
+
+    let lists = lists_in(&read);
+    assert!(lists[0].cdr().cdr().ptr_eq(&lists[0]));
+    assert!(matches!(lists[1].get(1), Some(Value::List(nested)) if nested.ptr_eq(&lists[1])));
+
+    // and it's written the same way again
+    assert_eq!(read.labelled().to_string(), written);
+}
+
+#[test]
+fn labelled_literals_evaluate() {
+    let env = default_env().into_shared();
+
+    // a quoted literal keeps the structure it shares
+    let result = eval_str(
+        env.clone(),
+        "(define x (quote (#1=(q) #1#))) (memq (car x) (cdr x))",
+    );
+    assert_eq!(result, Ok(lisp! { ((q)) }));
+
+    // and one that contains itself evaluates to a copy which does too
+    let value = eval_str(env.clone(), "(quote #0=(a #0#))").unwrap();
+    let Value::List(list) = &value else {
+        panic!("expected a list, got {}", value);
+    };
+    assert!(matches!(list.cdr().car(), Ok(Value::List(item)) if item.ptr_eq(list)));
+
+    // even with something to fill in, or an automatic gensym
+    let value = eval_str(env.clone(), "(let ((v 1)) '#0=(,v #0# . #0#))").unwrap();
+    let Value::List(list) = &value else {
+        panic!("expected a list, got {}", value);
+    };
+    assert_eq!(list.car(), Ok(lisp! { 1 }));
+    assert!(matches!(list.cdr().car(), Ok(Value::List(item)) if item.ptr_eq(list)));
+    assert!(list.cdr().cdr().ptr_eq(list));
+
+    let value = eval_str(env.clone(), "(defmacro cycle () ''#0=(x# #0#)) (cycle)").unwrap();
+    let Value::List(list) = &value else {
+        panic!("expected a list, got {}", value);
+    };
+    assert_ne!(list.car(), Ok(Value::Symbol("x#".into())));
+    assert!(matches!(list.cdr().car(), Ok(Value::List(item)) if item.ptr_eq(list)));
+
+    // one can be passed to a macro
+    let value = eval_str(env.clone(), "(defmacro same (x) x) (same '#0=(b #0#))").unwrap();
+    let Value::List(list) = &value else {
+        panic!("expected a list, got {}", value);
+    };
+    assert_eq!(list.car(), Ok(lisp! { b }));
+
+    // changing the value of a literal doesn't change the code it's in
+    let result = eval_str(
+        env.clone(),
+        "(define h (lambda () (quote (1 2)))) (set_car (h) 9) (h)",
+    );
+    assert_eq!(result, Ok(lisp! { (1 2) }));
+
+    let result = eval_str(
+        env,
+        "(define g (lambda () (quote #0=(1 #0#)))) (set_car (g) 9) (car (g))",
+    );
+    assert_eq!(result, Ok(lisp! { 1 }));
+}
+
+#[test]
+fn label_errors() {
+    assert_eq!(
+        parse("(#0# #0=(1))").next(),
+        Some(Err(ParseError {
+            msg: "Undefined label #0# at index 1".to_owned(),
+            span: 1..4,
+        }))
+    );
+    assert_eq!(
+        parse("(#0=(1) #0=(2))").next(),
+        Some(Err(ParseError {
+            msg: "Label #0= is defined twice at index 8".to_owned(),
+            span: 8..11,
+        }))
+    );
+    assert_eq!(
+        parse("#0=#0#").next(),
+        Some(Err(ParseError {
+            msg: "Label #0= refers to nothing but itself at index 0".to_owned(),
+            span: 0..1,
+        }))
+    );
+
+    // labels belong to the form they're in
+    assert_eq!(
+        parse("#0=(1) #0#").nth(1),
+        Some(Err(ParseError {
+            msg: "Undefined label #0# at index 7".to_owned(),
+            span: 7..10,
+        }))
+    );
+}
+
+fn lists_in(value: &Value) -> Vec<List> {
+    let Value::List(list) = value else {
+        panic!("expected a list, got {}", value);
+    };
+
+    list.into_iter()
+        .filter_map(|item| match item {
+            Value::List(item) => Some(item),
+            _ => None,
+        })
+        .collect()
+}
+
+fn symbols(names: &[&str]) -> Value {
+    Value::List(
+        names
+            .iter()
+            .map(|name| Value::Symbol((*name).into()))
+            .collect(),
+    )
+}
@@ -0,0 +1,125 @@
+#![cfg(feature = "process")]
+
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp, lisp_list,
+    model::{Env, RuntimeError, Symbol, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn environment_variables() {
+    let result = eval_str(
+        "
+    (list
+      (getenv \"RUST_LISP_TEST_UNSET\")
+      (setenv \"RUST_LISP_TEST_VAR\" \"some value\")
+      (getenv \"RUST_LISP_TEST_VAR\"))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (NIL NIL "some value") }));
+
+    assert_eq!(
+        eval_str("(setenv \"A=B\" \"c\")"),
+        Err(RuntimeError::new(
+            "\"setenv\" can't set the environment variable \"A=B\" to \"c\"".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn exit() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    let result = eval_str_in(env.clone(), "(print 1) (exit 3) (print 2)");
+
+    assert_eq!(
+        result,
+        Err(RuntimeError::new(
+            "\"exit\" was called with status 3".to_owned()
+        ))
+    );
+    assert_eq!(env.borrow().context().take_exit_status(), Some(3));
+
+    // it's only reported once
+    assert_eq!(env.borrow().context().take_exit_status(), None);
+
+    // other errors aren't an exit
+    assert!(eval_str_in(env.clone(), "(car ())").is_err());
+    assert_eq!(env.borrow().context().take_exit_status(), None);
+
+    assert!(eval_str_in(env.clone(), "(exit)").is_err());
+    assert_eq!(env.borrow().context().take_exit_status(), Some(0));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_process() {
+    assert_eq!(
+        eval_str("(run_process \"printf\" (list \"%s|\" \"hello\" \"$HOME; exit 1\"))"),
+        Ok(lisp! { ((status 0) (stdout "hello|$HOME; exit 1|") (stderr "")) })
+    );
+
+    assert_eq!(
+        eval_str("(run_process \"sh\" (list \"-c\" \"printf oops >&2; exit 2\"))"),
+        Ok(lisp! { ((status 2) (stdout "") (stderr "oops")) })
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn shell() {
+    assert_eq!(
+        eval_str("(shell \"printf hello | tr a-z A-Z\")"),
+        Ok(lisp! { ((status 0) (stdout "HELLO") (stderr "")) })
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn shell() {
+    assert_eq!(
+        eval_str("(nth 1 (shell \"echo hello\"))"),
+        Ok(lisp_list![Symbol::from("stdout"), "hello\r\n"])
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_output() {
+    assert_eq!(
+        eval_str("(nth 1 (shell \"printf 'caf\\351'\"))"),
+        Ok(lisp_list![Symbol::from("stdout"), "caf\u{fffd}"])
+    );
+
+    assert_eq!(
+        eval_str("(nth 1 (shell \"printf 'caf\\351'\" :bytes T))"),
+        Ok(lisp! { (stdout (99 97 102 233)) })
+    );
+}
+
+#[test]
+fn missing_program() {
+    let result = eval_str("(run_process \"rust_lisp_no_such_program\")");
+
+    assert!(
+        matches!(
+            &result,
+            Err(RuntimeError { msg }) if msg.starts_with("\"run_process\" failed to run \"rust_lisp_no_such_program\": ")
+        ),
+        "{:?}",
+        result
+    );
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    eval_str_in(Rc::new(RefCell::new(default_env())), source)
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
@@ -0,0 +1,95 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn delay_and_force() {
+    let result = eval_str(
+        "
+    (define p (delay (+ 1 2)))
+    (list (force p) (force p))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (3 3) }));
+}
+
+#[test]
+fn force_is_memoized() {
+    let result = eval_str(
+        "
+    (define count 0)
+    (define p (delay (begin (set count (+ count 1)) count)))
+
+    (list count (force p) (force p) count)",
+    );
+
+    assert_eq!(result, Ok(lisp! { (0 1 1 1) }));
+}
+
+#[test]
+fn force_non_promise() {
+    assert_eq!(eval_str("(force 12)"), Ok(lisp! { 12 }));
+}
+
+#[test]
+fn force_caches_errors() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    let result = eval_str_in(
+        env.clone(),
+        "
+    (define count 0)
+    (define p (delay (begin (set count (+ count 1)) (car (list)))))
+
+    (force p)",
+    );
+
+    let err = Err(RuntimeError::new(
+        "Attempted to apply car on nil".to_owned(),
+    ));
+
+    assert_eq!(result, err);
+    assert_eq!(eval_str_in(env.clone(), "(force p)"), err);
+    assert_eq!(eval_str_in(env, "count"), Ok(lisp! { 1 }));
+}
+
+#[test]
+fn infinite_stream() {
+    let result = eval_str(
+        "
+    (defun integers_from (n)
+      (list n (delay (integers_from (+ n 1)))))
+
+    (defun stream_head (s) (car s))
+    (defun stream_tail (s) (force (nth 1 s)))
+
+    (defun stream_take (s n)
+      (defun loop (s n acc)
+        (if (== n 0)
+          (reverse acc)
+          (loop (stream_tail s) (- n 1) (cons (stream_head s) acc))))
+      (loop s n (list)))
+
+    (== (stream_take (integers_from 0) 100) (range 0 100))",
+    );
+
+    assert_eq!(result, Ok(Value::True));
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    eval_str_in(Rc::new(RefCell::new(default_env())), source)
+}
+
+#[cfg(test)]
+fn eval_str_in(
+    env: Rc<RefCell<rust_lisp::model::Env>>,
+    source: &str,
+) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "regex")]
+
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn is_re_match() {
+    assert_eq!(
+        eval_str("(list (is_re_match \"^a+b$\" \"aaab\") (is_re_match \"^a+b$\" \"abc\"))"),
+        Ok(lisp! { (T F) })
+    );
+}
+
+#[test]
+fn re_find() {
+    assert_eq!(
+        eval_str(
+            "
+    (list
+      (re_find \"(\\w+)@(\\w+)\\.com\" \"mail bob@example.com today\")
+      (re_find \"\\d+\" \"abc 123 456\")
+      (re_find \"(a)|(b)\" \"b\")
+      (re_find \"\\d+\" \"none\"))"
+        ),
+        Ok(lisp! { (("bob@example.com" "bob" "example") ("123") ("b" NIL "b") NIL) })
+    );
+}
+
+#[test]
+fn re_find_all() {
+    assert_eq!(
+        eval_str("(re_find_all \"(\\w)(\\d)\" \"a1 b2 c\")"),
+        Ok(lisp! { (("a1" "a" "1") ("b2" "b" "2")) })
+    );
+
+    assert_eq!(eval_str("(re_find_all \"x\" \"abc\")"), Ok(lisp! { () }));
+}
+
+#[test]
+fn re_replace() {
+    assert_eq!(
+        eval_str("(re_replace \"(\\w+) (\\w+)\" \"hello world, good day\" \"$2 $1\")"),
+        Ok(lisp! { "world hello, day good" })
+    );
+}
+
+#[test]
+fn re_split() {
+    assert_eq!(
+        eval_str("(re_split \",\\s*\" \"a, b,c\")"),
+        Ok(lisp! { ("a" "b" "c") })
+    );
+}
+
+#[test]
+fn unicode() {
+    assert_eq!(
+        eval_str(
+            "
+    (list
+      (re_find_all \"\\w+\" \"naïve café, 東京\")
+      (re_split \"é\" \"désolé\")
+      (is_re_match \"^.{2}$\" \"日本\"))"
+        ),
+        Ok(lisp! { ((("naïve") ("café") ("東京")) ("d" "sol" "") T) })
+    );
+}
+
+#[test]
+fn invalid_pattern() {
+    let result = eval_str("(re_find \"(unclosed\" \"text\")");
+
+    assert!(
+        matches!(
+            &result,
+            Err(RuntimeError { msg }) if msg.starts_with("\"re_find\" was given an invalid regex: ")
+                && msg.contains("unclosed group")
+        ),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn patterns_are_cached() {
+    // the same pattern used many times in a loop
+    assert_eq!(
+        eval_str(
+            "
+    (defun count (n total)
+      (if (== n 0)
+        total
+        (count (- n 1) (if (is_re_match \"^[0-9]+$\" \"12345\") (+ total 1) total))))
+
+    (count 1000 0)"
+        ),
+        Ok(Value::from(Into::<rust_lisp::model::IntType>::into(1000)))
+    );
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
@@ -0,0 +1,146 @@
+use rust_lisp::{
+    default_env, eval_str, lisp,
+    model::{Env, ErrorKind, Limit, ResourceLimits, RuntimeError},
+};
+use std::{cell::RefCell, rc::Rc};
+
+const PROGRAMS: &str = "
+(defun build (n lst) (if (== n 0) lst (build (- n 1) (cons n lst))))
+(defun double (n s) (if (== n 0) s (double (- n 1) (string_append s s))))";
+
+#[test]
+fn cons_limit() {
+    let env = env_with(ResourceLimits {
+        max_conses: Some(1000),
+        ..ResourceLimits::UNLIMITED
+    });
+
+    let err = eval_str(env.clone(), "(length (build 5000 (list)))").unwrap_err();
+    assert_eq!(
+        err.msg,
+        "Resource limit exceeded: more than 1000 cons cells were allocated"
+    );
+    assert_eq!(err.kind(), ErrorKind::ResourceLimit(Limit::MaxConses));
+
+    // builtins that make whole lists are counted too, before the list is made
+    // if they can be
+    for source in ["(range 0 100000000)", "(map (lambda (x) x) (range 0 600))"] {
+        assert_eq!(
+            eval_str(env.clone(), source).map_err(|err| err.kind()),
+            Err(ErrorKind::ResourceLimit(Limit::MaxConses)),
+            "{}",
+            source
+        );
+    }
+
+    // and the count starts again for each evaluation
+    assert_eq!(
+        eval_str(env, "(length (build 900 (list)))"),
+        Ok(lisp! { 900 })
+    );
+}
+
+#[test]
+fn string_limit() {
+    let env = env_with(ResourceLimits {
+        max_string_bytes: Some(1 << 16),
+        ..ResourceLimits::UNLIMITED
+    });
+
+    assert_eq!(
+        eval_str(env.clone(), "(double 100 \"ab\")"),
+        Err(RuntimeError::with_kind(
+            ErrorKind::ResourceLimit(Limit::MaxStringBytes),
+            "Resource limit exceeded: more than 65536 bytes of strings were created"
+        ))
+    );
+
+    // a string builder only counts what's appended to it
+    let result = eval_str(
+        env,
+        "
+    (define sb (string_builder))
+    (defun fill (n) (if (== n 0) sb (begin (sb_append sb \"abcd\") (fill (- n 1)))))
+    (fill 100000)",
+    );
+    assert_eq!(
+        result.map_err(|err| err.kind()),
+        Err(ErrorKind::ResourceLimit(Limit::MaxStringBytes))
+    );
+}
+
+#[test]
+fn collection_length_limit() {
+    let env = env_with(ResourceLimits {
+        max_collection_len: Some(100),
+        ..ResourceLimits::UNLIMITED
+    });
+
+    for (source, len) in [
+        ("(range 0 101)", 101),
+        ("(list_to_vector (build 150 (list)))", 150),
+        (
+            "(let ((h (hash))) (for_each (lambda (n) (hash_set h n n)) (build 100 (list))) (hash_set h 0 0))",
+            101,
+        ),
+    ] {
+        assert_eq!(
+            eval_str(env.clone(), source),
+            Err(RuntimeError::with_kind(ErrorKind::ResourceLimit(Limit::MaxCollectionLen), format!(
+                    "Resource limit exceeded: a collection of {} items is longer than the limit of 100",
+                    len
+                ))),
+            "{}",
+            source
+        );
+    }
+
+    // a list that's too long goes over this limit even when conses are
+    // limited too
+    let both = env_with(ResourceLimits {
+        max_conses: Some(1000),
+        max_collection_len: Some(100),
+        ..ResourceLimits::UNLIMITED
+    });
+    let err = eval_str(both, "(range 0 200)").unwrap_err();
+    assert_eq!(
+        err.kind(),
+        ErrorKind::ResourceLimit(Limit::MaxCollectionLen)
+    );
+    assert_eq!(Limit::MaxCollectionLen.name(), "max_collection_len");
+
+    // lots of short lists are fine
+    assert_eq!(
+        eval_str(env, "(length (map (lambda (n) (range 0 n)) (range 0 100)))"),
+        Ok(lisp! { 100 })
+    );
+}
+
+#[test]
+fn within_the_limits() {
+    let env = env_with(ResourceLimits {
+        max_conses: Some(100_000),
+        max_string_bytes: Some(100_000),
+        max_collection_len: Some(1000),
+    });
+
+    let result = eval_str(
+        env,
+        "
+    (define squares (map (lambda (n) (* n n)) (range 0 1000)))
+    (define names (map (lambda (n) (format \"item ~a\" n)) (range 0 100)))
+    (list (length (build 1000 (list))) (length squares) (car (reverse names)))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (1000 1000 "item 99") }));
+}
+
+fn env_with(resource_limits: ResourceLimits) -> Rc<RefCell<Env>> {
+    let env = default_env();
+    env.context().resource_limits.set(resource_limits);
+
+    let env = env.into_shared();
+    eval_str(env.clone(), PROGRAMS).unwrap();
+
+    env
+}
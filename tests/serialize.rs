@@ -0,0 +1,264 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{Env, RuntimeError, Symbol, Value},
+    parser::parse,
+    serialize::{restore_session, serialize_env, serialize_session},
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn save_and_restore_a_session() {
+    let env = new_env();
+
+    eval_str_in(
+        env.clone(),
+        "
+    (define rate 3)
+    (defun scale (x) (* x rate))",
+    )
+    .unwrap();
+
+    let source = serialize_env(&env.borrow()).unwrap();
+
+    assert_eq!(
+        source,
+        "\
+(define rate 3)
+(define scale (lambda (x) (* x rate)))
+"
+    );
+
+    let restored = new_env();
+    eval_str_in(restored.clone(), &source).unwrap();
+
+    assert_eq!(eval_str_in(restored, "(scale 5)"), Ok(lisp! { 15 }));
+}
+
+#[test]
+fn data_round_trips() {
+    let env = new_env();
+
+    eval_str_in(
+        env.clone(),
+        "
+    (define whole 2.0)
+    (define name \"café\")
+    (define sym 'hello)
+    (define nested '(1 (a \"b\") () (quote c) T F))
+    (define pairs (list '(a . 1) (list_star 'b 2.5)))
+    (define table (hash 'k (list 1 2)))
+    (define tables (list 1 (hash \"x\" 2)))
+    (define members (make_set 1 \"a\" 'b 2.5))
+    (define sets (list (make_set 'x) (make_set)))
+    (define hex \"0123456789abcdef\")
+    (define buffers (list #u8(0 255) (string_to_utf8 (string_append hex hex hex hex hex))))
+    (define vectors (list #(1 a (b)) (vector \"c\" #())))",
+    )
+    .unwrap();
+
+    let restored = new_env();
+    eval_str_in(restored.clone(), &serialize_env(&env.borrow()).unwrap()).unwrap();
+
+    assert_eq!(
+        eval_str_in(
+            restored,
+            "(list whole name sym nested (hash_get table 'k) (hash_get (nth 1 tables) \"x\") buffers vectors members sets pairs)"
+        ),
+        eval_str_in(
+            env,
+            "(list whole name sym nested (hash_get table 'k) (hash_get (nth 1 tables) \"x\") buffers vectors members sets pairs)"
+        )
+    );
+}
+
+#[test]
+fn functions_and_macros_round_trip() {
+    let env = new_env();
+
+    eval_str_in(
+        env.clone(),
+        "
+    (defun greet (name &key (greeting \"hi\") loud)
+      (list greeting name loud))
+
+    (defmacro twice (form) (list 'begin form form))
+
+    (define counter 0)",
+    )
+    .unwrap();
+
+    let restored = new_env();
+    eval_str_in(restored.clone(), &serialize_env(&env.borrow()).unwrap()).unwrap();
+
+    assert_eq!(
+        eval_str_in(
+            restored,
+            "
+    (twice (set counter (+ counter 1)))
+
+    (list (greet \"bob\") (greet \"amy\" :greeting \"hello\" :loud T) counter)"
+        ),
+        Ok(lisp! { (("hi" "bob" NIL) ("hello" "amy" T) 2) })
+    );
+}
+
+#[test]
+fn builtins_are_skipped() {
+    let env = new_env();
+
+    assert_eq!(serialize_env(&env.borrow()), Ok(String::new()));
+
+    // a redefined builtin is the user's own
+    eval_str_in(env.clone(), "(define print 1)").unwrap();
+    assert_eq!(
+        serialize_env(&env.borrow()),
+        Ok("(define print 1)\n".to_owned())
+    );
+}
+
+#[test]
+fn closures_are_an_error() {
+    let env = new_env();
+
+    eval_str_in(
+        env.clone(),
+        "
+    (defun make_adder (n) (lambda (x) (+ x n)))
+    (define add_two (make_adder 2))",
+    )
+    .unwrap();
+
+    assert_eq!(
+        serialize_env(&env.borrow()),
+        Err(RuntimeError::new(
+            "Can't serialize \"add_two\": it closes over local variables".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn runtime_values_are_an_error() {
+    for (source, reason) in [
+        (
+            "(define gen (generator (lambda () (yield 1))))",
+            "the generator <generator> can't be written as source",
+        ),
+        (
+            "(define gen (list 1 (delay 2)))",
+            "the promise <promise> can't be written as source",
+        ),
+        (
+            "(define gen (list 1 2)) (set_cdr (cdr gen) gen)",
+            "it contains a cyclic list",
+        ),
+        (
+            "(define gen (string_builder))",
+            "the string builder #<string_builder 0 chars> can't be written as source",
+        ),
+        (
+            "(define gen (list 'a (list 'comma 'b)))",
+            "the comma form ,b can't be quoted",
+        ),
+    ] {
+        let env = new_env();
+        eval_str_in(env.clone(), source).unwrap();
+
+        assert_eq!(
+            serialize_env(&env.borrow()),
+            Err(RuntimeError::new(format!(
+                "Can't serialize \"gen\": {}",
+                reason
+            ))),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn sessions_skip_what_they_cant_save() {
+    let env = new_env();
+
+    eval_str_in(
+        env.clone(),
+        "
+    (define items (list 1 \"two\" 'three))
+    (defun count_down (n) (if (== n 0) (list) (cons n (count_down (- n 1)))))
+    (defun make_adder (n) (lambda (x) (+ x n)))
+    (define add_two (make_adder 2))
+    (define first car)
+    (define gen (generator (lambda () (yield 1))))",
+    )
+    .unwrap();
+
+    let (source, warnings) = serialize_session(&env.borrow());
+
+    assert_eq!(
+        warnings,
+        vec![
+            "\"add_two\" was saved, but it closes over local variables, which weren't",
+            "Skipped \"first\": it's a function written in Rust",
+            "Skipped \"gen\": the generator <generator> can't be written as source",
+        ]
+    );
+
+    let restored = new_env();
+    assert_eq!(
+        restore_session(restored.clone(), &source, true),
+        Ok(["add_two", "count_down", "items", "make_adder"]
+            .into_iter()
+            .map(Symbol::from)
+            .collect())
+    );
+
+    assert_eq!(
+        eval_str_in(restored.clone(), "(list items (count_down 3))"),
+        Ok(lisp! { ((1 "two" three) (3 2 1)) })
+    );
+
+    // the function is restored, but not what it closed over
+    assert_eq!(
+        eval_str_in(restored, "(add_two 1)"),
+        Err(RuntimeError::new("\"n\" is not defined".to_owned()))
+    );
+}
+
+#[test]
+fn restoring_merges() {
+    let env = new_env();
+    eval_str_in(env.clone(), "(define a 1) (define b 2)").unwrap();
+    let (source, _) = serialize_session(&env.borrow());
+
+    let restored = new_env();
+    eval_str_in(restored.clone(), "(define b 20) (define c 30)").unwrap();
+
+    assert_eq!(
+        restore_session(restored.clone(), &source, false),
+        Ok(vec![Symbol::from("a")])
+    );
+    assert_eq!(
+        eval_str_in(restored.clone(), "(list a b c)"),
+        Ok(lisp! { (1 20 30) })
+    );
+
+    assert_eq!(
+        restore_session(restored.clone(), &source, true),
+        Ok(vec![Symbol::from("a"), Symbol::from("b")])
+    );
+    assert_eq!(
+        eval_str_in(restored, "(list a b c)"),
+        Ok(lisp! { (1 2 30) })
+    );
+}
+
+#[cfg(test)]
+fn new_env() -> Rc<RefCell<Env>> {
+    Rc::new(RefCell::new(default_env()))
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
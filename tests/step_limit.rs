@@ -0,0 +1,103 @@
+use rust_lisp::{default_env, eval_str, lisp, model::RuntimeError};
+
+const SPIN: &str = "
+(define spins 0)
+(defun spin () (set spins (+ spins 1)) (spin))";
+
+#[test]
+fn nested_limits() {
+    let env = default_env().into_shared();
+    eval_str(env.clone(), SPIN).unwrap();
+
+    // the inner limit trips first, and only its own with_step_limit gives up
+    assert_eq!(
+        eval_str(
+            env.clone(),
+            "(with_step_limit 1000 (list (with_step_limit 50 (spin) 'inner) 'after) 'outer)"
+        ),
+        Ok(lisp! { (inner after) })
+    );
+
+    // the steps inside the inner one count towards the outer one too
+    assert_eq!(
+        eval_str(
+            env.clone(),
+            "(with_step_limit 50 (with_step_limit 1000 (spin) 'inner) 'outer)"
+        ),
+        Ok(lisp! { outer })
+    );
+
+    // which isn't disturbed by an inner one giving up
+    assert_eq!(
+        eval_str(
+            env.clone(),
+            "(with_step_limit 100 (list (with_step_limit 10 (spin) 'inner) (spin)) 'outer)"
+        ),
+        Ok(lisp! { outer })
+    );
+
+    // an expression that stays within its limit isn't affected
+    assert_eq!(
+        eval_str(env.clone(), "(with_step_limit 100 (+ 1 2) 'gave_up)"),
+        Ok(lisp! { 3 })
+    );
+
+    // without a fallback, it's an error
+    assert_eq!(
+        eval_str(env.clone(), "(with_step_limit 50 (spin))"),
+        Err(RuntimeError::new(
+            "\"with_step_limit\" went over its limit of 50 steps".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval_str(env, "(with_step_limit (- 1) 1)"),
+        Err(RuntimeError::new(
+            "\"with_step_limit\" requires argument 1 to be a non-negative integer; got -1"
+                .to_owned()
+        ))
+    );
+}
+
+#[test]
+fn exact_limits() {
+    let env = default_env().into_shared();
+
+    // `(+ 1 (+ 2 3))` takes two steps, one for each call
+    assert_eq!(
+        eval_str(
+            env,
+            "(list
+               (with_step_limit 2 (+ 1 (+ 2 3)) 'gave_up)
+               (with_step_limit 1 (+ 1 (+ 2 3)) 'gave_up))"
+        ),
+        Ok(lisp! { (6 gave_up) })
+    );
+}
+
+#[test]
+fn instruction_count() {
+    let env = default_env().into_shared();
+
+    // the count only goes up, through calls and between them
+    let result = eval_str(
+        env.clone(),
+        "
+    (defun measure (thunk)
+      (define start (instruction_count))
+      (thunk)
+      (- (instruction_count) start))
+
+    (define counts (list (instruction_count) (instruction_count)))
+    (list
+      (< (car counts) (cadr counts))
+      (< (cadr counts) (instruction_count))
+      (measure (lambda () 1))
+      (measure (lambda () (+ 1 (+ 2 3)))))",
+    );
+
+    // and the cost of the same code is always the same
+    assert_eq!(result, Ok(lisp! { (T T 3 5) }));
+
+    // it counts from the start of each top-level evaluation
+    assert_eq!(eval_str(env, "(instruction_count)"), Ok(lisp! { 1 }));
+}
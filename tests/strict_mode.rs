@@ -0,0 +1,240 @@
+use rust_lisp::{
+    compiler::compile,
+    default_env, eval_str, lisp,
+    model::{Check, Env, RuntimeError, StrictMode},
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn lenient_by_default() {
+    let env = default_env().into_shared();
+
+    let result = eval_str(
+        env.clone(),
+        "
+    (define car (lambda (lst) 1))
+    (define x 1)
+    (define x 2)
+    (defun g (y) (defun h () (define y 4) y) (define x 3) (list x (h)))
+    (define lenght 5)
+    (set lenght 6)
+    (list (car (list 5)) x (g 1) lenght)",
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 2 (3 4) 6) }));
+    assert!(env.borrow().context().take_warnings().is_empty());
+}
+
+#[test]
+fn redefining_builtins() {
+    let env = env_with(StrictMode::STRICT);
+
+    assert_eq!(
+        eval_str(env.clone(), "(define car (lambda (lst) 1))"),
+        Err(RuntimeError::new(
+            "\"define\" redefines the builtin \"car\"".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval_str(env.clone(), "(defun g () (defun cadr (lst) 1)) (g)"),
+        Err(RuntimeError::new(
+            "\"defun\" redefines the builtin \"cadr\"".to_owned()
+        ))
+    );
+
+    // compiled code is checked too
+    let compiled = compile(&lisp! { (define list 1) });
+    assert_eq!(
+        compiled.eval(env.clone()),
+        Err(RuntimeError::new(
+            "\"define\" redefines the builtin \"list\"".to_owned()
+        ))
+    );
+
+    // and nothing was defined
+    assert_eq!(eval_str(env, "(car (list 5))"), Ok(lisp! { 5 }));
+}
+
+#[test]
+fn globals() {
+    let env = env_with(StrictMode {
+        redefine_global: Check::Error,
+        shadow_global: Check::Warn,
+        ..StrictMode::LENIENT
+    });
+
+    assert_eq!(
+        eval_str(env.clone(), "(define x 1) (define x 2)"),
+        Err(RuntimeError::new(
+            "\"define\" redefines the global \"x\"".to_owned()
+        ))
+    );
+
+    // shadowing is only a warning
+    let result = eval_str(
+        env.clone(),
+        "
+    (defun g ()
+      (define x 3)
+      (define y 4)
+      (+ x y))
+    (list (g) x (car (list 5)))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (7 1 5) }));
+    assert_eq!(
+        env.borrow().context().take_warnings(),
+        vec!["\"define\" shadows the global \"x\"".to_owned()]
+    );
+
+    // and `set` isn't a definition
+    assert_eq!(eval_str(env.clone(), "(set x 2) x"), Ok(lisp! { 2 }));
+    assert!(env.borrow().context().take_warnings().is_empty());
+}
+
+#[test]
+fn locals() {
+    let env = env_with(StrictMode {
+        shadow_local: Check::Error,
+        ..StrictMode::LENIENT
+    });
+
+    assert_eq!(
+        eval_str(
+            env.clone(),
+            "
+    (defun outer (x)
+      (defun inner ()
+        (define x 2)
+        x)
+      (inner))
+    (outer 1)"
+        ),
+        Err(RuntimeError::new(
+            "\"define\" shadows the local \"x\"".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval_str(env.clone(), "(let ((y 1)) (let ((z 2)) (define y 3) y))"),
+        Err(RuntimeError::new(
+            "\"define\" shadows the local \"y\"".to_owned()
+        ))
+    );
+
+    // defining a name again in the same scope isn't shadowing it, and neither
+    // are parameters
+    assert_eq!(
+        eval_str(
+            env,
+            "
+    (defun g (x)
+      (define y 1)
+      (define y 2)
+      ((lambda (x) (+ x y)) x))
+    (g 5)"
+        ),
+        Ok(lisp! { 7 })
+    );
+}
+
+#[test]
+fn similar_names() {
+    let env = env_with(StrictMode {
+        similar_name: Check::Error,
+        ..StrictMode::LENIENT
+    });
+
+    assert_eq!(
+        eval_str(env.clone(), "(define lenght 3)"),
+        Err(RuntimeError::new(
+            "\"define\" defines \"lenght\"; did you mean \"length\"?".to_owned()
+        ))
+    );
+    assert_eq!(
+        eval_str(
+            env.clone(),
+            "(define total 0) (defun add (n) (set totl n)) (add 1)"
+        ),
+        Err(RuntimeError::new(
+            "\"set\" sets \"totl\", which isn't defined; did you mean \"total\"?".to_owned()
+        ))
+    );
+
+    // compiled code is checked too
+    let compiled = compile(&lisp! { (set totals 1) });
+    assert_eq!(
+        compiled.eval(env.clone()),
+        Err(RuntimeError::new(
+            "\"set\" sets \"totals\", which isn't defined; did you mean \"total\"?".to_owned()
+        ))
+    );
+
+    // names that are defined, short, or not close to anything are fine
+    assert_eq!(
+        eval_str(
+            env,
+            "
+    (define total 1)
+    (set total 2)
+    (define tot 3)
+    (define zebra 4)
+    (define x 5)
+    (define y 6)
+    (list total tot x y)"
+        ),
+        Ok(lisp! { (2 3 5 6) })
+    );
+}
+
+#[test]
+fn warning_handler() {
+    let env = env_with(StrictMode {
+        redefine_builtin: Check::Warn,
+        similar_name: Check::Warn,
+        ..StrictMode::LENIENT
+    });
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let handled = warnings.clone();
+    env.borrow()
+        .context()
+        .set_warning_handler(move |msg| handled.borrow_mut().push(msg.to_owned()));
+
+    assert_eq!(
+        eval_str(
+            env.clone(),
+            "(define list 1) (define lenght 2) (warn \"done\") list"
+        ),
+        Ok(lisp! { 1 })
+    );
+    assert_eq!(
+        *warnings.borrow(),
+        vec![
+            "\"define\" redefines the builtin \"list\"".to_owned(),
+            "\"define\" defines \"lenght\"; did you mean \"length\"?".to_owned(),
+            "done".to_owned(),
+        ]
+    );
+
+    // which aren't recorded as well
+    assert!(env.borrow().context().take_warnings().is_empty());
+}
+
+#[test]
+fn special_form_parameters() {
+    // these are always errors, strict mode or not
+    for env in [env_with(StrictMode::LENIENT), env_with(StrictMode::STRICT)] {
+        assert_eq!(
+            eval_str(env, "(defun g (x if) x)"),
+            Err(RuntimeError::new(
+                "Special forms can't be used as parameter names: if".to_owned()
+            ))
+        );
+    }
+}
+
+fn env_with(strict_mode: StrictMode) -> Rc<RefCell<Env>> {
+    let env = default_env();
+    env.context().strict_mode.set(strict_mode);
+
+    env.into_shared()
+}
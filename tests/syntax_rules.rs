@@ -0,0 +1,178 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn simple_substitution() {
+    let result = eval_str(
+        "
+    (define_syntax unless
+      (syntax_rules ()
+        ((_ condition body) (if condition NIL body))))
+
+    (list (unless F 1) (unless T 2))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 NIL) }));
+}
+
+#[test]
+fn ellipsis() {
+    let result = eval_str(
+        "
+    (define_syntax my_let
+      (syntax_rules ()
+        ((_ ((name value) ...) body ...)
+         ((lambda (name ...) body ...) value ...))))
+
+    (list
+      (my_let ((a 1) (b 2)) (define c 3) (+ a b c))
+      (my_let () 4))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (6 4) }));
+}
+
+#[test]
+fn nested_ellipsis() {
+    let result = eval_str(
+        "
+    (define_syntax heads_and_tails
+      (syntax_rules ()
+        ((_ (head tail ...) ...)
+         (list (list head (list tail ...)) ...))))
+
+    (heads_and_tails (1 2 3) (4) (5 6))",
+    );
+
+    assert_eq!(result, Ok(lisp! { ((1 (2 3)) (4 ()) (5 (6))) }));
+}
+
+#[test]
+fn ellipsis_between_items() {
+    let result = eval_str(
+        "
+    (define_syntax ends
+      (syntax_rules ()
+        ((_ first middle ... last) (list first last))))
+
+    (list (ends 1 2 3 4) (ends 1 2))",
+    );
+
+    assert_eq!(result, Ok(lisp! { ((1 4) (1 2)) }));
+}
+
+#[test]
+fn literals() {
+    let result = eval_str(
+        "
+    (define_syntax pair
+      (syntax_rules (to)
+        ((_ a to b) (list a b))
+        ((_ a b) (list b a))))
+
+    (list (pair 1 to 2) (pair 1 2))",
+    );
+
+    assert_eq!(result, Ok(lisp! { ((1 2) (2 1)) }));
+}
+
+#[test]
+fn introduced_bindings_dont_capture() {
+    let result = eval_str(
+        "
+    (define_syntax swap
+      (syntax_rules ()
+        ((_ a b) (let ((tmp a)) (set a b) (set b tmp)))))
+
+    (define tmp 1)
+    (define other 2)
+    (swap tmp other)
+
+    (list tmp other)",
+    );
+
+    assert_eq!(result, Ok(lisp! { (2 1) }));
+}
+
+#[test]
+fn recursive_expansion() {
+    let result = eval_str(
+        "
+    (define_syntax my_or
+      (syntax_rules ()
+        ((_) F)
+        ((_ e) e)
+        ((_ e rest ...)
+         (let ((temp e))
+           (if temp temp (my_or rest ...))))))
+
+    (let ((temp 5))
+      (list (my_or) (my_or F temp) (my_or F F 3)))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (F 5 3) }));
+}
+
+#[test]
+fn no_matching_pattern() {
+    let result = eval_str(
+        "
+    (define_syntax pair
+      (syntax_rules ()
+        ((_ a b) (list a b))))
+
+    (pair 1 2 3)",
+    );
+
+    assert_eq!(
+        result,
+        Err(RuntimeError::new(
+            "No syntax_rules pattern of pair matched (pair 1 2 3)".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn malformed_templates() {
+    assert_eq!(
+        eval_str(
+            "
+    (define_syntax bad
+      (syntax_rules ()
+        ((_ a ...) (list a))))
+
+    (bad 1 2)"
+        ),
+        Err(RuntimeError::new("In syntax_rules for bad, a needs to be followed by ... in the template, like it is in the pattern".to_owned()))
+    );
+
+    assert_eq!(
+        eval_str(
+            "
+    (define_syntax bad
+      (syntax_rules ()
+        ((_ a) (list a ...))))
+
+    (bad 1)"
+        ),
+        Err(RuntimeError::new("In syntax_rules for bad, a is followed by ... in the template, but doesn't contain any variables that were followed by ... in the pattern".to_owned()))
+    );
+
+    assert_eq!(
+        eval_str("(define_syntax bad (lambda (x) x))"),
+        Err(RuntimeError::new("Expected (syntax_rules (literals...) (pattern template)...) for bad, found (lambda (x) x)".to_owned()))
+    );
+}
+
+#[cfg(test)]
+fn eval_str(source: &str) -> Result<Value, RuntimeError> {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "std")]
+
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{Env, RuntimeError, Symbol, Value},
+    parser::parse,
+};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+const SPIN: &str = "
+(define spins 0)
+(defun spin () (set spins (+ spins 1)) (spin))";
+
+#[test]
+fn inner_timeout() {
+    let env = new_env();
+    let start = Instant::now();
+
+    // the loop is given up on, and the rest of the program carries on
+    let result = eval_str_in(
+        env.clone(),
+        "(list (with_timeout 0.05 (spin) (quote gave_up)) (> spins 0) (+ 1 2))",
+    );
+
+    assert_eq!(result, Ok(lisp! { (gave_up T 3) }));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // and without a fallback, it's an error
+    assert_eq!(
+        eval_str_in(env.clone(), "(with_timeout 0.05 (spin))"),
+        Err(RuntimeError::new(
+            "\"with_timeout\" timed out after 0.05 seconds".to_owned()
+        ))
+    );
+
+    // an expression that finishes in time isn't affected
+    assert_eq!(
+        eval_str_in(env, "(with_timeout 10 (+ 1 2) (quote gave_up))"),
+        Ok(lisp! { 3 })
+    );
+}
+
+#[test]
+fn nested_timeouts() {
+    let env = new_env();
+
+    // the tightest deadline is the one that applies, and only its own
+    // with_timeout gives up
+    assert_eq!(
+        eval_str_in(
+            env.clone(),
+            "(with_timeout 0.05 (with_timeout 10 (spin) (quote inner)) (quote outer))"
+        ),
+        Ok(lisp! { outer })
+    );
+    assert_eq!(
+        eval_str_in(
+            env.clone(),
+            "(with_timeout 10 (list (with_timeout 0.05 (spin) (quote inner)) (quote after)) (quote outer))"
+        ),
+        Ok(lisp! { (inner after) })
+    );
+
+    assert_eq!(
+        eval_str_in(env, "(with_timeout (- 1) 1)"),
+        Err(RuntimeError::new(
+            "\"with_timeout\" requires argument 1 to be a non-negative number of seconds; got -1"
+                .to_owned()
+        ))
+    );
+}
+
+#[test]
+fn native_functions_can_check_the_deadline() {
+    let env = new_env();
+    let context = env.borrow().context().clone();
+
+    // a slow native function that gives up when it's out of time
+    env.borrow_mut().define(
+        Symbol::from("slow"),
+        Value::NativeFunc(|env, _args| {
+            while !env.borrow().context().deadline_exceeded() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            Ok(Value::from("stopped"))
+        }),
+    );
+
+    assert_eq!(
+        eval_str_in(env, "(with_timeout 0.05 (slow))"),
+        Ok(Value::from("stopped"))
+    );
+    assert!(!context.deadline_exceeded());
+}
+
+#[cfg(test)]
+fn new_env() -> Rc<RefCell<Env>> {
+    let env = default_env().into_shared();
+    eval_str_in(env.clone(), SPIN).unwrap();
+
+    env
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
@@ -0,0 +1,205 @@
+use rust_lisp::{
+    default_env,
+    interpreter::eval_block,
+    lisp,
+    model::{Env, RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+#[test]
+fn recursive_calls() {
+    let (env, output) = env_with_captured_output();
+
+    let result = eval_str_in(
+        env,
+        "
+    (defun fib (n)
+      (if (< n 2)
+        n
+        (+ (fib (- n 1)) (fib (- n 2)))))
+
+    (trace fib)
+    (fib 3)",
+    );
+
+    assert_eq!(result, Ok(lisp! { 2 }));
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+(fib 3)
+  (fib 2)
+    (fib 1)
+    fib returned 1
+    (fib 0)
+    fib returned 0
+  fib returned 1
+  (fib 1)
+  fib returned 1
+fib returned 2
+"
+    );
+}
+
+#[test]
+fn tail_calls_are_nested() {
+    let (env, output) = env_with_captured_output();
+
+    let result = eval_str_in(
+        env,
+        "
+    (defun count_down (n)
+      (if (== n 0)
+        (quote done)
+        (count_down (- n 1))))
+
+    (trace count_down)
+    (count_down 2)",
+    );
+
+    assert_eq!(result, Ok(lisp! { done }));
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+(count_down 2)
+  (count_down 1)
+    (count_down 0)
+    count_down returned done
+  count_down returned done
+count_down returned done
+"
+    );
+}
+
+#[test]
+fn native_functions() {
+    let (env, output) = env_with_captured_output();
+
+    let result = eval_str_in(
+        env,
+        "
+    (trace + car)
+    (+ 1 (+ 2 3))
+    (car (list))",
+    );
+
+    assert_eq!(
+        result,
+        Err(RuntimeError::new(
+            "Attempted to apply car on nil".to_owned()
+        ))
+    );
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+(+ 2 3)
++ returned 5
+(+ 1 5)
++ returned 6
+(car NIL)
+car failed: Attempted to apply car on nil
+"
+    );
+}
+
+#[test]
+fn survives_redefinition() {
+    let (env, output) = env_with_captured_output();
+
+    eval_str_in(
+        env,
+        "
+    (defun greet (name) name)
+    (trace greet)
+    (defun greet (name) (list \"hello\" name))
+    (greet \"bob\")",
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+(greet \"bob\")
+greet returned (\"hello\" \"bob\")
+"
+    );
+}
+
+#[test]
+fn untrace() {
+    let (env, output) = env_with_captured_output();
+
+    let result = eval_str_in(
+        env,
+        "
+    (defun double (n) (* n 2))
+    (defun triple (n) (* n 3))
+
+    (list
+      (trace double triple)
+      (double 1)
+      (untrace double)
+      (double 2)
+      (triple 3)
+      (untrace)
+      (triple 4))",
+    );
+
+    assert_eq!(result, Ok(lisp! { ((double triple) 2 (triple) 4 9 () 12) }));
+    assert_eq!(
+        output.borrow().as_str(),
+        "\
+(double 1)
+double returned 2
+(triple 3)
+triple returned 9
+"
+    );
+}
+
+#[test]
+fn requires_symbols() {
+    let (env, _) = env_with_captured_output();
+
+    assert_eq!(
+        eval_str_in(env, "(trace 1)"),
+        Err(RuntimeError::new(
+            "\"trace\" requires argument 1 to be a symbol; got 1".to_owned()
+        ))
+    );
+}
+
+#[test]
+#[cfg(feature = "std")] // uses `print`
+fn print_uses_output() {
+    let (env, output) = env_with_captured_output();
+
+    eval_str_in(env, "(print (list 1 \"two\"))").unwrap();
+
+    assert_eq!(output.borrow().as_str(), "(1 \"two\")\n");
+}
+
+#[cfg(test)]
+struct Capture(Rc<RefCell<String>>);
+
+#[cfg(test)]
+impl Write for Capture {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0.borrow_mut().push_str(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn env_with_captured_output() -> (Rc<RefCell<Env>>, Rc<RefCell<String>>) {
+    let env = default_env();
+    let output = Rc::new(RefCell::new(String::new()));
+    env.context().set_output(Capture(output.clone()));
+
+    (Rc::new(RefCell::new(env)), output)
+}
+
+#[cfg(test)]
+fn eval_str_in(env: Rc<RefCell<Env>>, source: &str) -> Result<Value, RuntimeError> {
+    eval_block(env, parse(source).map(|expr| expr.unwrap()))
+}
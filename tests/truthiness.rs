@@ -0,0 +1,163 @@
+use rust_lisp::{
+    compiler::compile,
+    default_env,
+    interpreter::eval,
+    lisp,
+    model::{Env, Truthiness, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// Each of these is `T` if `V` is truthy, and `F` otherwise
+const CONDITIONALS: [&str; 7] = [
+    "(if V T F)",
+    "(cond (V T) (T F))",
+    "(== (and V T) T)",
+    "(!= (or V F) F)",
+    "(not (not V))",
+    "(== (length (filter (lambda (x) V) (list 1))) 1)",
+    "(== (length (for ((x (list 1))) (when V) x)) 1)",
+];
+
+const VALUES: [&str; 5] = ["0", "\"\"", "()", "nil", "#f"];
+
+#[test]
+fn lisp_truthiness() {
+    assert_truthiness(Truthiness::Lisp, [true, true, false, false, false]);
+}
+
+#[test]
+fn scheme_truthiness() {
+    assert_truthiness(Truthiness::Scheme, [true, true, true, true, false]);
+}
+
+#[test]
+fn loose_truthiness() {
+    assert_truthiness(Truthiness::Loose, [false, false, false, false, false]);
+}
+
+#[test]
+fn custom_truthiness() {
+    // zero is false but the empty list isn't, which none of the presets do
+    assert_truthiness(
+        Truthiness::Custom {
+            empty_list_is_false: false,
+            zero_is_false: true,
+            empty_string_is_false: false,
+        },
+        [false, true, true, true, false],
+    );
+
+    // and the presets are just particular choices
+    assert_truthiness(
+        Truthiness::Custom {
+            empty_list_is_false: true,
+            zero_is_false: false,
+            empty_string_is_false: false,
+        },
+        [true, true, false, false, false],
+    );
+}
+
+#[test]
+fn not_truth_table() {
+    let values = ["nil", "#f", "0", "()", "\"\"", "\"a\""];
+
+    for (truthiness, expected) in [
+        (Truthiness::Lisp, "(T T F T F F)"),
+        (Truthiness::Scheme, "(F T F F F F)"),
+        (Truthiness::Loose, "(T T T T T F)"),
+    ] {
+        let env = env_with(truthiness);
+        let source = format!(
+            "(list {})",
+            values
+                .iter()
+                .map(|value| format!("(not {})", value))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        let expr = parse(&source).next().unwrap().unwrap();
+
+        // always exactly T or F, never the operand itself
+        assert_eq!(
+            eval(env, &expr).map(|result| result.to_string()),
+            Ok(expected.to_owned()),
+            "{:?}",
+            truthiness
+        );
+    }
+}
+
+#[test]
+fn default_truthiness() {
+    assert_eq!(Truthiness::default(), Truthiness::Lisp);
+    assert_eq!(default_env().context().truthiness.get(), Truthiness::Lisp);
+}
+
+#[test]
+fn loose_truthiness_of_other_values() {
+    let env = env_with(Truthiness::Loose);
+
+    for (source, expected) in [
+        ("0.0", false),
+        ("1", true),
+        ("-1.5", true),
+        ("\"a\"", true),
+        ("(list 0)", true),
+    ] {
+        let expr = parse(&format!("(not (not {}))", source))
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            eval(env.clone(), &expr),
+            Ok(Value::from(expected)),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn boolean_literals() {
+    let env = env_with(Truthiness::Scheme);
+    let expr = parse("(list #t #f (if #t 1 2) (if #f 1 2))")
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(eval(env, &expr), Ok(lisp! { (T F 1 2) }));
+}
+
+#[cfg(test)]
+fn assert_truthiness(truthiness: Truthiness, expected: [bool; 5]) {
+    let env = env_with(truthiness);
+
+    for (value, expected) in VALUES.iter().zip(expected) {
+        for conditional in CONDITIONALS {
+            let source = conditional.replace('V', value);
+            let expr = parse(&source).next().unwrap().unwrap();
+
+            let in_generator = format!(
+                "(car (list (next (generator (lambda () (yield {}))))))",
+                source
+            );
+            let in_generator = parse(&in_generator).next().unwrap().unwrap();
+
+            let expected = Ok(Value::from(expected));
+            assert_eq!(eval(env.clone(), &expr), expected, "{}", source);
+            assert_eq!(compile(&expr).eval(env.clone()), expected, "{}", source);
+            assert_eq!(eval(env.clone(), &in_generator), expected, "{}", source);
+        }
+    }
+}
+
+#[cfg(test)]
+fn env_with(truthiness: Truthiness) -> Rc<RefCell<Env>> {
+    let env = default_env();
+    env.context().truthiness.set(truthiness);
+
+    Rc::new(RefCell::new(env))
+}
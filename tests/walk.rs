@@ -0,0 +1,196 @@
+use rust_lisp::{
+    collections::HashSet,
+    lisp,
+    model::{List, Symbol, Value},
+    parser::parse,
+};
+use std::cell::RefCell;
+
+#[test]
+fn walk_is_pre_order() {
+    let program = lisp! {
+        (defun add (a b) (+ a (quote (b c))))
+    };
+
+    let mut visited = Vec::new();
+    program.walk(&mut |value| visited.push(value.clone()));
+
+    assert_eq!(
+        visited,
+        vec![
+            program.clone(),
+            lisp! { defun },
+            lisp! { add },
+            lisp! { (a b) },
+            lisp! { a },
+            lisp! { b },
+            lisp! { (+ a (quote (b c))) },
+            lisp! { + },
+            lisp! { a },
+            lisp! { (quote (b c)) },
+            lisp! { quote },
+            lisp! { (b c) },
+            lisp! { b },
+            lisp! { c },
+        ]
+    );
+}
+
+#[test]
+fn map_tree_rewrites_bottom_up() {
+    let program = lisp! {
+        (list (double 1) (lambda (x) (double x)))
+    };
+
+    // (double x) => (* 2 x)
+    let rewritten = program.map_tree(|value| match value {
+        Value::List(list) if list.car() == Ok(lisp! { double }) => {
+            Some(lisp_cons(lisp! { * }, list.cdr().cons(lisp! { 2 })))
+        }
+        _ => None,
+    });
+
+    assert_eq!(rewritten, lisp! { (list (* 2 1) (lambda (x) (* 2 x))) });
+
+    // each value is rewritten after its items, so the replacements are seen
+    let seen = RefCell::new(Vec::new());
+    lisp! { (a (b)) }.map_tree(|value| {
+        seen.borrow_mut().push(value.clone());
+        None
+    });
+
+    assert_eq!(
+        seen.into_inner(),
+        vec![lisp! { a }, lisp! { b }, lisp! { (b) }, lisp! { (a (b)) }]
+    );
+}
+
+#[test]
+fn map_tree_shares_untouched_parts() {
+    let program = parse("(if (ready) (go x) (wait 1 2))")
+        .next()
+        .unwrap()
+        .unwrap();
+    let items = match &program {
+        Value::List(list) => list.into_iter().collect::<Vec<_>>(),
+        _ => unreachable!(),
+    };
+
+    let rewritten = program.map_tree(|value| match value {
+        Value::Symbol(Symbol(name)) if &**name == "x" => Some(lisp! { y }),
+        _ => None,
+    });
+
+    assert_eq!(rewritten, lisp! { (if (ready) (go y) (wait 1 2)) });
+
+    let (original, rewritten) = match (&program, &rewritten) {
+        (Value::List(original), Value::List(rewritten)) => (original, rewritten),
+        _ => unreachable!(),
+    };
+
+    // the untouched `(ready)` is the same list, and so is the tail after the
+    // rewritten `(go y)`
+    assert!(same_list(&rewritten.get(1).unwrap(), &items[1]));
+    assert!(rewritten
+        .cdr()
+        .cdr()
+        .cdr()
+        .ptr_eq(&original.cdr().cdr().cdr()));
+    assert!(!rewritten.ptr_eq(original));
+
+    // with nothing rewritten, the result is the original
+    let unchanged = program.map_tree(|_| None);
+    assert!(same_list(&unchanged, &program));
+}
+
+#[test]
+fn referenced_symbols() {
+    let program = parse(
+        "
+    (begin
+      (define limit 10)
+      (defun clamp (x &key (low (- limit)))
+        (let ((high limit)
+              (scale (lambda (n) (* n factor))))
+          (if (> x high) high (max low (scale x)))))
+      (print '(quoted symbols ,shown :kw))
+      (for ((i items) (j (range 0 i))) (when (odd i)) (list i j))
+      (case_lambda ((a) a) ((a b) (+ a b c))))",
+    )
+    .next()
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+        program.referenced_symbols(),
+        symbols(&[
+            "-", "limit", "*", "factor", ">", "max", "print", "shown", "items", "range", "odd",
+            "list", "+", "c"
+        ])
+    );
+
+    // a parameter only shadows inside its own function
+    assert_eq!(
+        lisp! { (list (lambda (x) x) x) }.referenced_symbols(),
+        symbols(&["list", "x"])
+    );
+
+    // and a do loop's initial values are outside of the loop
+    assert_eq!(
+        lisp! { (do ((i 0 (+ i 1)) (acc i)) ((== i n) acc) (print acc)) }.referenced_symbols(),
+        symbols(&["+", "i", "==", "n", "print"])
+    );
+
+    // each let_values clause binds all of its names for the ones after it
+    assert_eq!(
+        lisp! { (let_values (((a b) (h a)) ((c) (g b))) (list a c d)) }.referenced_symbols(),
+        symbols(&["a", "d", "g", "h", "list"])
+    );
+
+    // quoted data can contain itself
+    let program = parse("(print '#0=(,shown #0# . #0#))")
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(program.referenced_symbols(), symbols(&["print", "shown"]));
+}
+
+#[test]
+fn deep_nesting() {
+    const DEPTH: usize = 100_000;
+    let source = "(h ".repeat(DEPTH) + "x" + &")".repeat(DEPTH);
+    let program = parse(&source).next().unwrap().unwrap();
+
+    let mut count = 0;
+    program.walk(&mut |_| count += 1);
+    assert_eq!(count, DEPTH * 2 + 1);
+
+    let rewritten = program.map_tree(|value| match value {
+        Value::Symbol(Symbol(name)) if &**name == "x" => Some(lisp! { y }),
+        _ => None,
+    });
+    let mut found_y = false;
+    rewritten.walk(&mut |value| found_y |= *value == lisp! { y });
+    assert!(found_y);
+
+    assert_eq!(program.referenced_symbols(), symbols(&["h", "x"]));
+
+    let lambdas = "(lambda (x) ".repeat(DEPTH) + "(g x)" + &")".repeat(DEPTH);
+    let program = parse(&lambdas).next().unwrap().unwrap();
+    assert_eq!(program.referenced_symbols(), symbols(&["g"]));
+}
+
+#[cfg(test)]
+fn symbols(names: &[&str]) -> HashSet<Symbol> {
+    names.iter().map(|name| Symbol::from(*name)).collect()
+}
+
+#[cfg(test)]
+fn lisp_cons(car: Value, cdr: List) -> Value {
+    Value::List(cdr.cons(car))
+}
+
+#[cfg(test)]
+fn same_list(a: &Value, b: &Value) -> bool {
+    matches!((a, b), (Value::List(a), Value::List(b)) if a.ptr_eq(b))
+}
@@ -0,0 +1,100 @@
+use rust_lisp::{
+    default_env,
+    interpreter::{eval, eval_with_warnings},
+    lisp,
+    model::{RuntimeError, Value},
+    parser::parse,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn warn_continues_evaluation() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    let (result, warnings) = eval_with_warnings(
+        env,
+        &parse_one(
+            "
+    (begin
+      (warn \"first\")
+      (warn (list 1 2))
+      (+ 1 2))",
+        ),
+    );
+
+    assert_eq!(result, Ok(lisp! { 3 }));
+    assert_eq!(warnings, vec!["first".to_owned(), "(1 2)".to_owned()]);
+}
+
+#[test]
+fn warnings_are_returned_with_errors() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    let (result, warnings) =
+        eval_with_warnings(env, &parse_one("(begin (warn \"careful\") (car (list)))"));
+
+    assert_eq!(
+        result,
+        Err(RuntimeError::new(
+            "Attempted to apply car on nil".to_owned()
+        ))
+    );
+    assert_eq!(warnings, vec!["careful".to_owned()]);
+}
+
+#[test]
+fn each_eval_gets_its_own_warnings() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    eval(env.clone(), &parse_one("(warn \"earlier\")")).unwrap();
+
+    let (result, warnings) = eval_with_warnings(env.clone(), &parse_one("(warn \"later\")"));
+
+    assert_eq!(result, Ok(Value::NIL));
+    assert_eq!(warnings, vec!["later".to_owned()]);
+
+    // warnings from outside eval_with_warnings() stay where they were
+    assert_eq!(
+        env.borrow().context().take_warnings(),
+        vec!["earlier".to_owned()]
+    );
+}
+
+#[test]
+fn native_functions_can_warn() {
+    let mut env = default_env();
+    env.define(
+        "lossy".into(),
+        Value::NativeFunc(|env, args| {
+            env.borrow().context().warn("lost precision");
+            Ok(args[0].clone())
+        }),
+    );
+
+    let (result, warnings) = eval_with_warnings(
+        Rc::new(RefCell::new(env)),
+        &parse_one("(list (lossy 1) (lossy 2))"),
+    );
+
+    assert_eq!(result, Ok(lisp! { (1 2) }));
+    assert_eq!(warnings, vec!["lost precision"; 2]);
+}
+
+#[test]
+fn deprecated_rest_parameter() {
+    let env = Rc::new(RefCell::new(default_env()));
+
+    let (result, warnings) =
+        eval_with_warnings(env, &parse_one("((lambda (a ...) (list a ...)) 1 2 3)"));
+
+    assert_eq!(result, Ok(lisp! { (1 (2 3)) }));
+    assert_eq!(
+        warnings,
+        vec!["The unnamed ... rest parameter is deprecated; use &rest and a name instead"]
+    );
+}
+
+#[cfg(test)]
+fn parse_one(source: &str) -> Value {
+    parse(source).next().unwrap().unwrap()
+}